@@ -0,0 +1,185 @@
+//! Voxelization of glTF animation clips. All frames of a clip share one lattice and one set of
+//! bounds (covering the animated scene over the whole clip), so they can be compared
+//! voxel-for-voxel.
+
+use std::path::Path;
+
+use miette::{miette, Result};
+
+use crate::{
+    mesh::{GltfDocument, GltfPose, MeshModel},
+    processing::csg::{combine_grids, CsgOperation},
+    voxelizer::{
+        aabb::Aabb,
+        compute_voxelization_bounds_for_models,
+        empty_grid_with_bounds,
+        grid::VoxelGrid,
+        voxelize_models_on_shared_grid,
+        MeshVoxelizationOptions,
+    },
+};
+
+
+pub struct AnimationClipOptions {
+    /// Frames per second.
+    pub frame_rate: f32,
+
+    /// Start of the clip in seconds. Defaults to the first keyframe of the animation.
+    pub start_time: Option<f32>,
+
+    /// End of the clip in seconds (inclusive). Defaults to the last keyframe of the animation.
+    pub end_time: Option<f32>,
+}
+
+
+pub struct VoxelizedFrame {
+    /// Animation time of the frame in seconds.
+    pub time: f32,
+
+    /// All models of the scene at this frame, merged into one grid.
+    pub grid: VoxelGrid,
+}
+
+
+/// Computes the frame times of a clip, starting at `start_time` and spaced `1 / frame_rate` apart.
+/// The last frame is at or before `end_time`.
+fn compute_frame_times(start_time: f32, end_time: f32, frame_rate: f32) -> Vec<f32> {
+    // The small tolerance avoids losing the last frame to floating-point error.
+    let frame_count = ((end_time - start_time) * frame_rate + 1e-4).floor() as usize + 1;
+
+    (0..frame_count)
+        .map(|frame_index| start_time + frame_index as f32 / frame_rate)
+        .collect()
+}
+
+
+/// Voxelizes the animation selected in `pose` at a fixed frame rate. Morph weight overrides
+/// from `pose` apply to every frame, while its time is ignored.
+pub fn voxelize_animation_clip(
+    gltf_file_path: &Path,
+    pose: &GltfPose,
+    max_voxelization_bounds: Aabb,
+    voxel_size: f32,
+    mesh_options: &MeshVoxelizationOptions,
+    options: &AnimationClipOptions,
+) -> Result<Vec<VoxelizedFrame>> {
+    let Some(animation_selector) = &pose.animation else {
+        return Err(miette!(
+            "An animation must be selected to voxelize a clip."
+        ));
+    };
+
+    if !(options.frame_rate.is_finite() && options.frame_rate > 0.0) {
+        return Err(miette!("Frame rate must be a positive number."));
+    }
+
+
+    let document = GltfDocument::load(gltf_file_path)?;
+
+    let (animation_start_time, animation_end_time) =
+        document.animation_time_range(animation_selector)?;
+
+    let start_time = options.start_time.unwrap_or(animation_start_time);
+    let end_time = options.end_time.unwrap_or(animation_end_time);
+
+    if !(start_time.is_finite() && end_time.is_finite()) || end_time < start_time {
+        return Err(miette!(
+            "Invalid clip time range {} to {} seconds.",
+            start_time,
+            end_time
+        ));
+    }
+
+    let frame_times = compute_frame_times(start_time, end_time, options.frame_rate);
+
+    // Instanced models are expanded, as stamping them would not keep the shared lattice.
+    let scene_at_time = |time: f32| -> Result<Vec<MeshModel>> {
+        Ok(document
+            .scene_at_pose(&GltfPose {
+                time,
+                ..pose.clone()
+            })?
+            .into_world_space_models())
+    };
+
+
+    // The first pass only determines the shared bounds, so that every frame
+    // can be voxelized directly on the final lattice (the voxelized frames
+    // themselves are all kept in memory until they are exported).
+    let mut clip_bounds: Option<Aabb> = None;
+
+    for time in &frame_times {
+        let frame_bounds = compute_voxelization_bounds_for_models(
+            &scene_at_time(*time)?,
+            &max_voxelization_bounds,
+            voxel_size,
+        );
+
+        clip_bounds = match (clip_bounds, frame_bounds) {
+            (Some(clip_bounds), Some(frame_bounds)) => {
+                Some(clip_bounds.compute_union(&frame_bounds))
+            }
+            (clip_bounds, frame_bounds) => clip_bounds.or(frame_bounds),
+        };
+    }
+
+    let clip_bounds = clip_bounds.ok_or_else(|| miette!("Animated scene contains no triangles."))?;
+
+
+    println!(
+        "Voxelizing {} frames from {} to {} seconds at {} frames per second.",
+        frame_times.len(),
+        start_time,
+        end_time,
+        options.frame_rate
+    );
+
+    frame_times
+        .into_iter()
+        .map(|time| {
+            let frame_models = scene_at_time(time)?;
+
+            // All models share the lattice, so the union is exact.
+            let grid = voxelize_models_on_shared_grid(
+                &frame_models,
+                clip_bounds,
+                voxel_size,
+                mesh_options,
+            )
+            .into_iter()
+            .map(|model| model.grid)
+            .reduce(|merged_grid, model_grid| {
+                combine_grids(&merged_grid, &model_grid, CsgOperation::Union)
+            })
+            .unwrap_or_else(|| empty_grid_with_bounds(clip_bounds, voxel_size));
+
+            Ok(VoxelizedFrame { time, grid })
+        })
+        .collect()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_times_include_the_end_time() {
+        assert_eq!(
+            compute_frame_times(0.0, 1.0, 4.0),
+            [0.0, 0.25, 0.5, 0.75, 1.0]
+        );
+
+        // (31 / 7) * 7 is slightly below 31 in floating point, but the last frame is still kept.
+        assert_eq!(
+            compute_frame_times(0.0, 31.0 / 7.0, 7.0).len(),
+            32
+        );
+
+        // An end time between frames is not rounded up to the next frame.
+        assert_eq!(compute_frame_times(1.0, 1.6, 2.0), [1.0, 1.5]);
+
+        assert_eq!(compute_frame_times(2.0, 2.0, 24.0), [2.0]);
+    }
+}
@@ -1,10 +1,39 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use clap::{Args, Parser, Subcommand};
-use glam::Vec3;
+use glam::{Affine3A, Mat4, Vec3};
 use miette::{miette, Context, IntoDiagnostic, Result};
 
-use crate::{exporter::VoxelExportType, voxelizer::aabb::Aabb};
+use crate::{
+    animation::AnimationClipOptions,
+    exporter::{
+        nrrd::NrrdOptions,
+        schematic::{BlockPalette, SchematicOptions},
+        slices::{Colormap, SliceAxis, SliceOptions},
+        surface::{SurfaceColorMode, SurfaceMeshFormat},
+        vtk::VtkOptions,
+        VoxelExportType,
+        VoxelOutputFormat,
+    },
+    importer::{RawVoxelGridFiles, RawVoxelGridLayout},
+    mesh::{AnimationSelector, GltfPose, MorphWeightOverride},
+    processing::{
+        csg::CsgOperation,
+        neighbourhood::Connectivity,
+        palette::{Palette, PaletteMethod, PaletteOptions, MAX_PALETTE_COLORS},
+        pipeline::{PostProcessingStep, POST_PROCESSING_STEP_FORMAT_HELP},
+        resample::{AttributeFilter, OccupancyRule, ResamplingOptions},
+    },
+    surface_extraction::{
+        marching_cubes::MarchingCubesField,
+        SurfaceExtractionMethod,
+        SurfaceExtractionOptions,
+    },
+    voxelizer::{aabb::Aabb, MeshVoxelizationOptions, PointCloudVoxelizationOptions},
+};
 
 
 #[cfg(feature = "visualization")]
@@ -26,6 +55,7 @@ pub struct VisualizationArgs {
     pub initial_camera_position: Option<String>,
 }
 
+#[cfg(feature = "visualization")]
 impl VisualizationArgs {
     pub fn initial_camera_position(&self) -> Result<Option<Vec3>> {
         let Some(initial_camera_position) = &self.initial_camera_position else {
@@ -45,29 +75,769 @@ pub struct ExportArgs {
     #[arg(long = "output-file-path")]
     pub output_file_path: PathBuf,
 
+    #[arg(
+        long = "output-format",
+        help = "One of: raw (headerless voxel data with a .meta.txt metadata file), \
+                nvox (self-describing voxel container, which can hold several export types), \
+                nrrd (NRRD file, see --detached-header and --gzip), mhd (MetaImage header with a .raw data file), \
+                nifti (NIfTI-1 .nii file), vti (XML VTK image data, which can hold several export types), \
+                vtk (legacy VTK structured points, which can hold several export types), \
+                vox (MagicaVoxel, requires --export-type palette-index_u8), \
+                schem (Minecraft Sponge schematic, requires --export-type palette-index_u8), \
+                png-slices (one PNG image per slice, see --slice-axis). Defaults to raw."
+    )]
+    pub output_format: Option<String>,
+
+    #[arg(
+        long = "detached-header",
+        help = "Writes the NRRD header into a separate .nhdr file next to the voxel data (.raw) \
+                instead of a single .nrrd file."
+    )]
+    pub detached_header: bool,
+
+    #[arg(
+        long = "gzip",
+        help = "Compresses the voxel data of NRRD exports with gzip, MetaImage exports with zlib \
+                (written to a .zraw data file) and NIfTI exports with gzip (written to a .nii.gz file)."
+    )]
+    pub gzip: bool,
+
+    #[arg(
+        long = "vtk-base64",
+        help = "Base64-encodes the appended data of VTI exports instead of storing it raw."
+    )]
+    pub vtk_base64: bool,
+
+    #[arg(
+        long = "vtk-point-data",
+        help = "Stores the voxels of VTI and VTK exports as point data (one point per voxel center) \
+                instead of cell data (one cell per voxel)."
+    )]
+    pub vtk_point_data: bool,
+
+    #[arg(
+        long = "vox-include-interior",
+        help = "Exports voxels inside meshes (in gray) in addition to the surface voxels in MagicaVoxel exports."
+    )]
+    pub vox_include_interior: bool,
+
+    #[arg(
+        long = "block-palette-file-path",
+        help = "Path to the blocks that voxel colors are mapped to (by nearest color) in schematic exports: \
+                a text file with one block per line, a block ID followed by its sRGB color, \
+                e.g. \"minecraft:red_wool #a12722\". Defaults to the sixteen concrete blocks."
+    )]
+    pub block_palette_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "schem-interior-block",
+        help = "Block to fill voxels inside meshes with in schematic exports, e.g. \"minecraft:stone\". \
+                If not set, they are left empty (air)."
+    )]
+    pub schem_interior_block: Option<String>,
+
+    #[arg(
+        long = "slice-axis",
+        help = "Axis that PNG slices are taken along. One of: x (side view), y (top-down view, \
+                as the grid is Y-up), z (front view). Defaults to y."
+    )]
+    pub slice_axis: Option<String>,
+
+    #[arg(
+        long = "slice-colormap",
+        help = "Colormap that metallic, roughness, intensity and surface distance values are drawn with in PNG slices. \
+                One of: grayscale, viridis, inferno. Defaults to grayscale."
+    )]
+    pub slice_colormap: Option<String>,
+
+    #[arg(
+        long = "slice-alpha",
+        help = "Adds an alpha channel to PNG slices, with empty voxels fully transparent."
+    )]
+    pub slice_alpha: bool,
+
+    #[arg(
+        long = "slice-contact-sheet",
+        help = "Additionally tiles all PNG slices into a single contact sheet image (<OUTPUT_FILE_PATH>.<axis>-sheet.png)."
+    )]
+    pub slice_contact_sheet: bool,
+
+    #[arg(
+        long = "slice-contact-sheet-columns",
+        help = "Number of slices per row of the contact sheet. Defaults to a roughly square sheet."
+    )]
+    pub slice_contact_sheet_columns: Option<u32>,
+
     #[arg(
         long = "export-type",
-        help = "One of: binary-edge_u1, binary-fill_u1, linear-rgb8-color_u8, metallic-value_u8, roughness-value_u8."
+        required = true,
+        help = "One of: binary-edge_u1, binary-fill_u1, linear-rgb8-color_u8, srgb-rgb8-color_u8, linear-rgb16-color_u16, \
+                linear-rgb-color_f32, metallic-value_u8, roughness-value_u8, intensity-value_u8 (point clouds only), \
+                surface-distance_f32 (distance of occupied voxels to the nearest empty voxel, in voxels), \
+                component-label_u32, palette-index_u8 (see --palette-method). \
+                Can be specified multiple times with --output-format nvox, vti or vtk, \
+                storing each export type as a separate channel or array."
+    )]
+    pub export_formats: Vec<String>,
+
+    #[arg(
+        long = "component-connectivity",
+        help = "Voxel connectivity (6, 18 or 26) used when labeling connected components \
+                for the component-label_u32 export type. Defaults to 26."
+    )]
+    pub component_connectivity: Option<String>,
+
+    #[arg(
+        long = "palette-method",
+        help = "Color quantization method for the palette-index_u8 export type. \
+                One of: median-cut, k-means (median cut refined with k-means). Defaults to median-cut."
+    )]
+    pub palette_method: Option<String>,
+
+    #[arg(
+        long = "palette-size",
+        help = "Maximum number of palette colors (1 to 255) for the palette-index_u8 export type. \
+                Defaults to 255."
+    )]
+    pub palette_size: Option<usize>,
+
+    #[arg(
+        long = "palette-file-path",
+        help = "Path to a palette to use for the palette-index_u8 export type instead of quantizing the voxel colors: \
+                a text file with one sRGB color per line, either \"#rrggbb\" or \"r g b\" (0-255)."
+    )]
+    pub palette_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "palette-dithering",
+        help = "Diffuses the color quantization error to neighbouring voxels \
+                for the palette-index_u8 export type."
     )]
-    pub export_format: String,
+    pub palette_dithering: bool,
 }
 
 impl ExportArgs {
+    pub fn output_format(&self) -> Result<VoxelOutputFormat> {
+        let Some(output_format) = &self.output_format else {
+            return Ok(VoxelOutputFormat::Raw);
+        };
+
+        match output_format.to_ascii_lowercase().as_str() {
+            "raw" => Ok(VoxelOutputFormat::Raw),
+            "nvox" => Ok(VoxelOutputFormat::NativeContainer),
+            "nrrd" => Ok(VoxelOutputFormat::Nrrd),
+            "mhd" => Ok(VoxelOutputFormat::MetaImage),
+            "nifti" => Ok(VoxelOutputFormat::Nifti),
+            "vti" => Ok(VoxelOutputFormat::VtkImageData),
+            "vtk" => Ok(VoxelOutputFormat::VtkLegacy),
+            "vox" => Ok(VoxelOutputFormat::MagicaVoxel),
+            "schem" => Ok(VoxelOutputFormat::SpongeSchematic),
+            "png-slices" => Ok(VoxelOutputFormat::PngSlices),
+            _ => Err(miette!(
+                "Invalid output format, must be one of: \
+                raw, nvox, nrrd, mhd, nifti, vti, vtk, vox, schem, png-slices."
+            )),
+        }
+    }
+
+    pub fn nrrd_options(&self) -> NrrdOptions {
+        NrrdOptions {
+            detached_header: self.detached_header,
+            gzip: self.gzip,
+        }
+    }
+
+    pub fn vtk_options(&self) -> Result<VtkOptions> {
+        Ok(VtkOptions {
+            legacy: self.output_format()? == VoxelOutputFormat::VtkLegacy,
+            base64: self.vtk_base64,
+            point_data: self.vtk_point_data,
+        })
+    }
+
+    pub fn schematic_options(&self) -> Result<SchematicOptions> {
+        let block_palette = match &self.block_palette_file_path {
+            Some(block_palette_file_path) => {
+                BlockPalette::load_from_file(block_palette_file_path)
+                    .wrap_err("Invalid --block-palette-file-path option.")?
+            }
+            None => BlockPalette::default(),
+        };
+
+        Ok(SchematicOptions {
+            block_palette,
+            interior_block_id: self.schem_interior_block.clone(),
+            dithering: self.palette_dithering,
+        })
+    }
+
+    pub fn slice_options(&self) -> Result<SliceOptions> {
+        let axis = self
+            .slice_axis
+            .as_deref()
+            .map(SliceAxis::from_str)
+            .transpose()?
+            .unwrap_or(SliceAxis::Y);
+
+        let colormap = self
+            .slice_colormap
+            .as_deref()
+            .map(Colormap::from_str)
+            .transpose()?
+            .unwrap_or(Colormap::Grayscale);
+
+        if self.slice_contact_sheet_columns == Some(0) {
+            return Err(miette!(
+                "--slice-contact-sheet-columns must be at least 1."
+            ));
+        }
+
+        Ok(SliceOptions {
+            axis,
+            colormap,
+            alpha: self.slice_alpha,
+            contact_sheet: self.slice_contact_sheet,
+            contact_sheet_columns: self.slice_contact_sheet_columns,
+        })
+    }
+
+    /// Returns the only export type, for output formats that hold a single one.
     pub fn export_format(&self) -> Result<VoxelExportType> {
-        let export_format = self.export_format.to_ascii_lowercase();
+        let [export_format] = self.export_formats.as_slice() else {
+            return Err(miette!(
+                "Multiple --export-type options are only supported with --output-format nvox, vti or vtk."
+            ));
+        };
+
+        self.parse_export_format(export_format)
+    }
+
+    pub fn export_formats(&self) -> Result<Vec<VoxelExportType>> {
+        self.export_formats
+            .iter()
+            .map(|export_format| self.parse_export_format(export_format))
+            .collect()
+    }
+
+    fn parse_export_format(&self, export_format: &str) -> Result<VoxelExportType> {
+        let export_format = export_format.to_ascii_lowercase();
 
         match export_format.as_str() {
             "binary-edge_u1" => Ok(VoxelExportType::BinaryEdgeStateU1),
             "binary-fill_u1" => Ok(VoxelExportType::BinaryFillStateU1),
             "linear-rgb8-color_u8" => Ok(VoxelExportType::LinearRgb8ColorU8),
+            "srgb-rgb8-color_u8" => Ok(VoxelExportType::SrgbRgb8ColorU8),
+            "linear-rgb16-color_u16" => Ok(VoxelExportType::LinearRgb16ColorU16),
+            "linear-rgb-color_f32" => Ok(VoxelExportType::LinearRgbColorF32),
             "metallic-value_u8" => Ok(VoxelExportType::MetallicValueU8),
             "roughness-value_u8" => Ok(VoxelExportType::RoughnessValueU8),
+            "intensity-value_u8" => Ok(VoxelExportType::IntensityValueU8),
+            "surface-distance_f32" => Ok(VoxelExportType::SurfaceDistanceF32),
+            "component-label_u32" => Ok(VoxelExportType::ComponentLabelU32 {
+                connectivity: self.component_connectivity()?,
+            }),
+            "palette-index_u8" => Ok(VoxelExportType::PaletteIndexU8 {
+                palette_options: self.palette_options()?,
+            }),
             _ => Err(miette!(
                 "Invalid export type, must be one of: binary-edge_u1, binary-fill_u1, \
-                linear-rgb8-color_u8, metallic-value_u8, roughness-value_u8."
+                linear-rgb8-color_u8, srgb-rgb8-color_u8, linear-rgb16-color_u16, linear-rgb-color_f32, \
+                metallic-value_u8, roughness-value_u8, intensity-value_u8, surface-distance_f32, \
+                component-label_u32, palette-index_u8."
             )),
         }
     }
+
+    pub fn component_connectivity(&self) -> Result<Connectivity> {
+        let Some(component_connectivity) = &self.component_connectivity else {
+            return Ok(Connectivity::TwentySix);
+        };
+
+        Connectivity::from_str(component_connectivity)
+            .wrap_err("Invalid --component-connectivity option.")
+    }
+
+    pub fn palette_options(&self) -> Result<PaletteOptions> {
+        let method = match &self.palette_method {
+            Some(method) => {
+                PaletteMethod::from_str(method).wrap_err("Invalid --palette-method option.")?
+            }
+            None => PaletteMethod::MedianCut,
+        };
+
+        let max_colors = self.palette_size.unwrap_or(MAX_PALETTE_COLORS);
+        if !(1..=MAX_PALETTE_COLORS).contains(&max_colors) {
+            return Err(miette!(
+                "Invalid --palette-size option: expected a value between 1 and {}.",
+                MAX_PALETTE_COLORS
+            ));
+        }
+
+        let user_palette = self
+            .palette_file_path
+            .as_ref()
+            .map(Palette::load_from_file)
+            .transpose()
+            .wrap_err("Invalid --palette-file-path option.")?;
+
+        if user_palette.is_some() && (self.palette_method.is_some() || self.palette_size.is_some()) {
+            println!(
+                "Warning: --palette-method and --palette-size are ignored when --palette-file-path is set."
+            );
+        }
+
+        Ok(PaletteOptions {
+            method,
+            max_colors,
+            user_palette,
+            dithering: self.palette_dithering,
+        })
+    }
+}
+
+
+
+#[derive(Args)]
+pub struct AnimationExportArgs {
+    #[command(flatten)]
+    pub export: ExportArgs,
+
+    #[arg(
+        long = "frame-rate",
+        help = "Number of frames to voxelize per second of animation. Defaults to 24."
+    )]
+    pub frame_rate: Option<f32>,
+
+    #[arg(
+        long = "start-time",
+        help = "Start of the clip in seconds. Defaults to the first keyframe of the animation."
+    )]
+    pub start_time: Option<f32>,
+
+    #[arg(
+        long = "end-time",
+        help = "End of the clip in seconds (inclusive). Defaults to the last keyframe of the animation."
+    )]
+    pub end_time: Option<f32>,
+
+    #[arg(
+        long = "single-file",
+        help = "Writes all frames into a single 4D file (frames stored one after another, \
+                each starting at a byte boundary) instead of one file per frame."
+    )]
+    pub single_file: bool,
+}
+
+impl AnimationExportArgs {
+    pub fn clip_options(&self) -> AnimationClipOptions {
+        AnimationClipOptions {
+            frame_rate: self.frame_rate.unwrap_or(24.0),
+            start_time: self.start_time,
+            end_time: self.end_time,
+        }
+    }
+}
+
+
+
+#[derive(Args)]
+pub struct MeshExportArgs {
+    #[arg(long = "output-file-path")]
+    pub output_file_path: PathBuf,
+
+    #[arg(
+        long = "mesh-format",
+        help = "One of: gltf (.gltf with a .bin buffer), obj (Wavefront OBJ), stl (binary STL, without colors). \
+                Defaults to gltf."
+    )]
+    pub mesh_format: Option<String>,
+
+    #[arg(
+        long = "surface-extraction",
+        help = "Method used to extract the surface from the voxel grid. One of: greedy \
+                (voxel faces, with coplanar faces of equal color merged), marching-cubes \
+                (smooth, watertight and manifold surface, see --marching-cubes-field), dual-contouring \
+                (places vertices on the original mesh surface using the triangles that touched each voxel, \
+                preserving sharp edges and corners). Defaults to greedy."
+    )]
+    pub surface_extraction_method: Option<String>,
+
+    #[arg(
+        long = "marching-cubes-field",
+        help = "Scalar field to extract the surface from with marching cubes. One of: occupancy \
+                (surface halfway between occupied and empty voxel centers), signed-distance \
+                (smoothed signed distance to the occupied voxels, follows the overall shape more closely). \
+                Defaults to signed-distance."
+    )]
+    pub marching_cubes_field: Option<String>,
+
+    #[arg(
+        long = "mesh-smoothing-iterations",
+        help = "Number of Taubin smoothing iterations to apply to the extracted mesh (which preserve its volume). \
+                Requires --surface-extraction marching-cubes or dual-contouring. Defaults to 0."
+    )]
+    pub mesh_smoothing_iterations: Option<u32>,
+
+    #[arg(
+        long = "mesh-decimation-ratio",
+        help = "If set, decimates the extracted mesh to roughly this fraction of its triangles (e.g. 0.25), \
+                keeping it watertight and manifold. Requires --surface-extraction marching-cubes or dual-contouring."
+    )]
+    pub mesh_decimation_ratio: Option<f32>,
+
+    #[arg(
+        long = "mesh-colors",
+        help = "How colors are stored in the mesh. One of: vertex (per-vertex colors), \
+                palette-texture (a small .palette.png texture with one texel per distinct color, \
+                requires --surface-extraction greedy). Defaults to vertex."
+    )]
+    pub mesh_colors: Option<String>,
+}
+
+impl MeshExportArgs {
+    pub fn mesh_format(&self) -> Result<SurfaceMeshFormat> {
+        self.mesh_format
+            .as_deref()
+            .map(SurfaceMeshFormat::from_str)
+            .transpose()
+            .map(|format| format.unwrap_or(SurfaceMeshFormat::Gltf))
+    }
+
+    fn surface_extraction_method(&self) -> Result<SurfaceExtractionMethod> {
+        self.surface_extraction_method
+            .as_deref()
+            .map(SurfaceExtractionMethod::from_str)
+            .transpose()
+            .map(|method| method.unwrap_or(SurfaceExtractionMethod::Greedy))
+    }
+
+    pub fn surface_extraction_options(&self) -> Result<SurfaceExtractionOptions> {
+        let method = self.surface_extraction_method()?;
+
+        let marching_cubes_field = self
+            .marching_cubes_field
+            .as_deref()
+            .map(MarchingCubesField::from_str)
+            .transpose()?
+            .unwrap_or(MarchingCubesField::SignedDistance);
+
+        let smoothing_iterations = self.mesh_smoothing_iterations.unwrap_or(0);
+
+        if let Some(decimation_ratio) = self.mesh_decimation_ratio {
+            if !(decimation_ratio > 0.0 && decimation_ratio <= 1.0) {
+                return Err(miette!(
+                    "--mesh-decimation-ratio must be in (0, 1]."
+                ));
+            }
+        }
+
+        if (smoothing_iterations > 0 || self.mesh_decimation_ratio.is_some())
+            && !method.produces_welded_mesh()
+        {
+            return Err(miette!(
+                "--mesh-smoothing-iterations and --mesh-decimation-ratio are not supported \
+                with the {} surface extraction method.",
+                method
+            ));
+        }
+
+        Ok(SurfaceExtractionOptions {
+            method,
+            marching_cubes_field,
+            smoothing_iterations,
+            decimation_ratio: self.mesh_decimation_ratio,
+        })
+    }
+
+    pub fn mesh_color_mode(&self) -> Result<SurfaceColorMode> {
+        let color_mode = self
+            .mesh_colors
+            .as_deref()
+            .map(SurfaceColorMode::from_str)
+            .transpose()?
+            .unwrap_or(SurfaceColorMode::VertexColors);
+
+        // Only greedy quads are flat-colored; the texture coordinates of vertices with different
+        // colors would interpolate across unrelated palette texels.
+        let method = self.surface_extraction_method()?;
+        if color_mode == SurfaceColorMode::PaletteTexture
+            && method != SurfaceExtractionMethod::Greedy
+        {
+            return Err(miette!(
+                "--mesh-colors palette-texture is not supported with the {} surface extraction method, \
+                which interpolates colors between vertices.",
+                method
+            ));
+        }
+
+        Ok(color_mode)
+    }
+}
+
+
+
+#[derive(Args)]
+pub struct CompareArgs {
+    #[arg(
+        long = "reference-raw-occupancy",
+        help = "Path to a previously exported binary-edge_u1 file to use as the reference grid \
+                instead of the main input files. See --raw-dimensions for its layout."
+    )]
+    pub reference_raw_occupancy_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "reference-raw-fill",
+        help = "Path to a previously exported binary-fill_u1 file belonging to --reference-raw-occupancy."
+    )]
+    pub reference_raw_fill_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "reference-raw-color",
+        help = "Path to a previously exported linear-rgb8-color_u8 file belonging to --reference-raw-occupancy."
+    )]
+    pub reference_raw_color_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "reference-raw-metallic",
+        help = "Path to a previously exported metallic-value_u8 file belonging to --reference-raw-occupancy."
+    )]
+    pub reference_raw_metallic_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "reference-raw-roughness",
+        help = "Path to a previously exported roughness-value_u8 file belonging to --reference-raw-occupancy."
+    )]
+    pub reference_raw_roughness_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-input-file-path",
+        alias = "against-gltf-file-path",
+        help = "Path to a mesh or point cloud file to voxelize (or a voxel container to load) and compare \
+                against the reference grid. Uses --voxel-size and --voxelization-bounds, unless \
+                --against-voxel-size is set. Mutually exclusive with --against-raw-occupancy."
+    )]
+    pub against_input_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-voxel-size",
+        help = "Voxel size (full box width) to voxelize --against-input-file-path with. Defaults to --voxel-size."
+    )]
+    pub against_voxel_size: Option<f32>,
+
+    #[arg(
+        long = "against-voxel-file-path",
+        help = "Path to a previously exported voxel container (.nvox) file to compare against the reference grid. \
+                Mutually exclusive with --against-input-file-path and --against-raw-occupancy."
+    )]
+    pub against_voxel_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-raw-occupancy",
+        help = "Path to a previously exported binary-edge_u1 file to compare against the reference grid. \
+                See --raw-dimensions for its layout."
+    )]
+    pub against_raw_occupancy_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-raw-fill",
+        help = "Path to a previously exported binary-fill_u1 file belonging to --against-raw-occupancy."
+    )]
+    pub against_raw_fill_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-raw-color",
+        help = "Path to a previously exported linear-rgb8-color_u8 file belonging to --against-raw-occupancy."
+    )]
+    pub against_raw_color_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-raw-metallic",
+        help = "Path to a previously exported metallic-value_u8 file belonging to --against-raw-occupancy."
+    )]
+    pub against_raw_metallic_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "against-raw-roughness",
+        help = "Path to a previously exported roughness-value_u8 file belonging to --against-raw-occupancy."
+    )]
+    pub against_raw_roughness_file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "raw-dimensions",
+        help = "Grid dimensions (in voxels) of the raw files. The format is \"(x, y, z)\". \
+                Together with --raw-origin and --raw-voxel-size, this overrides the layout of all raw grids, \
+                which is otherwise read from the .meta.txt file next to each occupancy file."
+    )]
+    pub raw_dimensions: Option<String>,
+
+    #[arg(
+        long = "raw-origin",
+        help = "World-space position of the minimum corner of the raw grids. The format is \"(x, y, z)\"."
+    )]
+    pub raw_origin: Option<String>,
+
+    #[arg(
+        long = "raw-voxel-size",
+        help = "Voxel size (full box width) of the raw grids."
+    )]
+    pub raw_voxel_size: Option<f32>,
+
+    #[arg(
+        long = "diff-output-file-path",
+        help = "If set, exports a diff label volume (u8 per voxel: 0 = empty in both, 1 = occupied in both, \
+                2 = removed, i.e. occupied only in the reference, 3 = added, i.e. occupied only in the compared grid) \
+                on the lattice of the reference grid."
+    )]
+    pub diff_output_file_path: Option<PathBuf>,
+}
+
+
+/// Where one of the two compared grids comes from.
+pub enum ComparedGridSource<'a> {
+    /// The main input files (`--input-file-path`), voxelized as for the other commands.
+    InputFiles,
+
+    /// A single mesh, point cloud or voxel container file.
+    InputFile {
+        file_path: &'a Path,
+        voxel_size: Option<f32>,
+    },
+
+    VoxelContainer(&'a Path),
+
+    Raw(RawVoxelGridFiles<'a>, RawVoxelGridLayout),
+}
+
+impl CompareArgs {
+    pub fn reference_source(&self, has_input_files: bool) -> Result<ComparedGridSource<'_>> {
+        let Some(occupancy_file_path) = &self.reference_raw_occupancy_file_path else {
+            if self.reference_raw_fill_file_path.is_some()
+                || self.reference_raw_color_file_path.is_some()
+                || self.reference_raw_metallic_file_path.is_some()
+                || self.reference_raw_roughness_file_path.is_some()
+            {
+                return Err(miette!(
+                    "The --reference-raw-* options require --reference-raw-occupancy."
+                ));
+            }
+
+            return Ok(ComparedGridSource::InputFiles);
+        };
+
+        if has_input_files {
+            return Err(miette!(
+                "--reference-raw-occupancy can not be combined with --input-file-path."
+            ));
+        }
+
+        self.raw_source(RawVoxelGridFiles {
+            occupancy_file_path,
+            fill_file_path: self.reference_raw_fill_file_path.as_deref(),
+            color_file_path: self.reference_raw_color_file_path.as_deref(),
+            metallic_file_path: self.reference_raw_metallic_file_path.as_deref(),
+            roughness_file_path: self.reference_raw_roughness_file_path.as_deref(),
+        })
+    }
+
+    pub fn against_source(&self, main_voxel_size: Option<f32>) -> Result<ComparedGridSource<'_>> {
+        let source_count = [
+            self.against_input_file_path.is_some(),
+            self.against_raw_occupancy_file_path.is_some(),
+            self.against_voxel_file_path.is_some(),
+        ]
+        .into_iter()
+        .filter(|is_set| *is_set)
+        .count();
+
+        if source_count != 1 {
+            return Err(miette!(
+                "Exactly one of --against-input-file-path, --against-raw-occupancy \
+                or --against-voxel-file-path is required."
+            ));
+        }
+
+        if let Some(file_path) = &self.against_input_file_path {
+            return Ok(ComparedGridSource::InputFile {
+                file_path,
+                voxel_size: self.against_voxel_size.or(main_voxel_size),
+            });
+        }
+
+        if let Some(file_path) = &self.against_voxel_file_path {
+            return Ok(ComparedGridSource::VoxelContainer(file_path));
+        }
+
+        let occupancy_file_path = self
+            .against_raw_occupancy_file_path
+            .as_ref()
+            .expect("expected one of the compared grid sources to be set");
+
+        self.raw_source(RawVoxelGridFiles {
+            occupancy_file_path,
+            fill_file_path: self.against_raw_fill_file_path.as_deref(),
+            color_file_path: self.against_raw_color_file_path.as_deref(),
+            metallic_file_path: self.against_raw_metallic_file_path.as_deref(),
+            roughness_file_path: self.against_raw_roughness_file_path.as_deref(),
+        })
+    }
+
+    /// Uses the layout given by the `--raw-*` options if any are set, or reads it
+    /// from the metadata file that was exported next to the occupancy file.
+    fn raw_source<'a>(&self, files: RawVoxelGridFiles<'a>) -> Result<ComparedGridSource<'a>> {
+        if self.raw_dimensions.is_none()
+            && self.raw_origin.is_none()
+            && self.raw_voxel_size.is_none()
+        {
+            let metadata_file_path = files.occupancy_file_path.with_extension("meta.txt");
+
+            let layout = RawVoxelGridLayout::read_from_metadata_file(&metadata_file_path)
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to read the layout of {}, pass --raw-dimensions, --raw-origin \
+                        and --raw-voxel-size instead.",
+                        files.occupancy_file_path.display()
+                    )
+                })?;
+
+            return Ok(ComparedGridSource::Raw(files, layout));
+        }
+
+        let (Some(raw_dimensions), Some(raw_origin), Some(raw_voxel_size)) = (
+            &self.raw_dimensions,
+            &self.raw_origin,
+            self.raw_voxel_size,
+        ) else {
+            return Err(miette!(
+                "--raw-dimensions, --raw-origin and --raw-voxel-size must be set together."
+            ));
+        };
+
+        let dimensions = parse_xyz_components_from_str(raw_dimensions)
+            .wrap_err("Invalid --raw-dimensions option.")?;
+
+        if dimensions.cmplt(Vec3::ONE).any() || dimensions.fract() != Vec3::ZERO {
+            return Err(miette!(
+                "--raw-dimensions must be positive whole numbers."
+            ));
+        }
+
+        let starting_point =
+            parse_xyz_components_from_str(raw_origin).wrap_err("Invalid --raw-origin option.")?;
+
+        if raw_voxel_size <= 0.0 {
+            return Err(miette!("--raw-voxel-size must be positive."));
+        }
+
+
+        Ok(ComparedGridSource::Raw(
+            files,
+            RawVoxelGridLayout {
+                dimensions: dimensions.as_u64vec3(),
+                starting_point,
+                voxel_size: raw_voxel_size,
+            },
+        ))
+    }
 }
 
 
@@ -80,6 +850,18 @@ pub enum CliCommand {
 
     #[command(name = "export")]
     Export(ExportArgs),
+
+    /// Voxelizes a glTF animation clip (selected with --animation) at a fixed frame rate,
+    /// with all frames sharing one lattice.
+    #[command(name = "export-animation")]
+    ExportAnimation(AnimationExportArgs),
+
+    /// Extracts the surface of the voxelized scene as a polygon mesh.
+    #[command(name = "export-mesh")]
+    ExportMesh(MeshExportArgs),
+
+    #[command(name = "compare")]
+    Compare(CompareArgs),
 }
 
 
@@ -99,29 +881,130 @@ pub struct CliArgs {
 
     #[arg(
         short = 'i',
-        long = "gltf-file-path",
-        help = "Path to the GLTF file containing the scene to voxelize."
+        long = "input-file-path",
+        alias = "gltf-file-path",
+        help = "Path to the file containing the scene to voxelize (GLTF, OBJ, STL or PLY, chosen by file extension). \
+                Previously exported voxel containers (.nvox) are loaded as they are instead of being voxelized, \
+                e.g. to resample or post-process them. \
+                Can be specified multiple times to voxelize several files in one invocation \
+                (see --csg-operation). Required, except for compare with --reference-raw-occupancy."
     )]
-    pub gltf_file_path: PathBuf,
+    pub input_file_paths: Vec<PathBuf>,
 
     #[arg(
         short = 's',
         long = "voxel-size",
         help = "Voxel size (full box width). The smaller the voxel, the higher the grid resolution, \
-                but at some point you will likely run out of memory."
+                but at some point you will likely run out of memory. \
+                Required unless all input files are voxel containers."
     )]
-    pub voxel_size: f32,
+    pub voxel_size: Option<f32>,
 
     #[arg(
         short = 'b',
         long = "voxelization-bounds",
         help = "Maximum voxelization bounds as an AABB (axis-aligned bounding box) in world space. \
-                The actual voxel grid will be adapted to fit each GLTF model individually, but will not exceed this bound. \
+                The actual voxel grid will be adapted to fit each model individually, but will not exceed this bound. \
                 The format is as follows: \"(min_x, min_y, min_z) / (max_x, max_y, max_z)\". \
                 Example: \"(-3, -2.5, -1) / (1, 1, 4.2)\""
     )]
     pub voxelization_bounds: Option<String>,
 
+    #[arg(
+        long = "animation",
+        help = "Name or index of the glTF animation to pose the scene with before voxelizing. \
+                Skinned meshes and morph targets are evaluated at the chosen pose."
+    )]
+    pub animation: Option<String>,
+
+    #[arg(
+        long = "animation-time",
+        requires = "animation",
+        help = "Time (in seconds) at which to sample the animation chosen with --animation. \
+                Clamped to the animation's time range. Defaults to 0."
+    )]
+    pub animation_time: Option<f32>,
+
+    #[arg(
+        long = "morph-weights",
+        help = "Morph target weights to use instead of the default or animated ones, \
+                as comma-separated values, optionally prefixed with a mesh name. \
+                Example: \"0.5,1\" (all meshes) or \"Face=0,0.8\". Can be repeated for different meshes."
+    )]
+    pub morph_weights: Vec<String>,
+
+    #[arg(
+        long = "texture-mipmaps",
+        help = "Samples textures at the mip level matching the voxel size, \
+                instead of always at full resolution. Reduces aliasing of detailed textures on large voxels."
+    )]
+    pub texture_mipmaps: bool,
+
+    #[arg(
+        long = "point-cloud-min-points",
+        help = "Minimum number of points a voxel must contain to be occupied, when voxelizing point clouds \
+                (XYZ, CSV, TXT, LAS, or PLY files without faces). Defaults to 1."
+    )]
+    pub point_cloud_minimum_points_per_voxel: Option<u32>,
+
+    #[arg(
+        long = "point-cloud-surface-only",
+        help = "When voxelizing point clouds, only occupy voxels that contain points, \
+                instead of also filling in the enclosed volume."
+    )]
+    pub point_cloud_surface_only: bool,
+
+    #[arg(
+        long = "csg-operation",
+        help = "Combines the voxelized input files (each with all of its models merged) \
+                with the given boolean operation, in the order they were provided. \
+                One of: union, intersection, difference, xor. Requires at least two --input-file-path options."
+    )]
+    pub csg_operation: Option<String>,
+
+    #[arg(
+        long = "resample-voxel-size",
+        help = "Resamples each voxelized (or loaded) model to a new voxel size (full box width) before post-processing."
+    )]
+    pub resample_voxel_size: Option<f32>,
+
+    #[arg(
+        long = "resample-origin",
+        help = "Resamples each voxelized model onto a lattice that has a voxel corner at the given \
+                world-space point. The format is \"(x, y, z)\"."
+    )]
+    pub resample_origin: Option<String>,
+
+    #[arg(
+        long = "resample-transform",
+        help = "Resamples each voxelized model into a new coordinate frame, given by an affine transform \
+                from the current world space. The format is 12 (3x4) or 16 (4x4) comma-separated \
+                matrix elements in row-major order."
+    )]
+    pub resample_transform: Option<String>,
+
+    #[arg(
+        long = "resample-occupancy",
+        help = "Rule for resampling voxel occupancy. One of: nearest, majority. Defaults to nearest."
+    )]
+    pub resample_occupancy_rule: Option<String>,
+
+    #[arg(
+        long = "resample-attribute-filter",
+        help = "Filter for resampling color, metallic and roughness values. \
+                One of: trilinear, average. Defaults to trilinear."
+    )]
+    pub resample_attribute_filter: Option<String>,
+
+    #[arg(
+        long = "post-process",
+        help = "Post-processing step to apply to each voxelized model before exporting or visualizing it. \
+                Can be specified multiple times; the steps are applied in the order provided. \
+                Example: \"close:element=radius-2\". See --help for the full format.",
+        long_help = POST_PROCESSING_STEP_FORMAT_HELP
+    )]
+    pub post_processing_steps: Vec<String>,
+
     #[command(subcommand)]
     pub command: CliCommand,
 }
@@ -172,6 +1055,45 @@ fn parse_xyz_components_from_str(xyz_string_in_parentheses: &str) -> Result<Vec3
 }
 
 
+fn parse_affine_transform_from_str(matrix_string: &str) -> Result<Affine3A> {
+    let elements = matrix_string
+        .split(',')
+        .map(|element| {
+            element
+                .trim()
+                .parse::<f32>()
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to parse {}", element))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if elements.len() != 12 && elements.len() != 16 {
+        return Err(miette!(
+            "Expected 12 (3x4) or 16 (4x4) matrix elements, got {}.",
+            elements.len()
+        ));
+    }
+
+    if elements.len() == 16 && elements[12..16] != [0.0, 0.0, 0.0, 1.0] {
+        return Err(miette!(
+            "The last row of a 4x4 affine transform must be \"0, 0, 0, 1\"."
+        ));
+    }
+
+    let mut row_major_elements = [0.0; 16];
+    row_major_elements[..12].copy_from_slice(&elements[..12]);
+    row_major_elements[15] = 1.0;
+
+    let transform = Affine3A::from_mat4(Mat4::from_cols_array(&row_major_elements).transpose());
+
+    if transform.matrix3.determinant().abs() <= f32::EPSILON {
+        return Err(miette!("The affine transform is not invertible."));
+    }
+
+    Ok(transform)
+}
+
+
 impl CliArgs {
     pub fn voxelization_bounds(&self) -> Result<Option<Aabb>> {
         let Some(voxelization_bounds_str) = self.voxelization_bounds.as_ref() else {
@@ -192,4 +1114,157 @@ impl CliArgs {
             maximum_bounds,
         )))
     }
+
+    pub fn voxel_size(&self) -> Result<f32> {
+        self.voxel_size
+            .ok_or_else(|| miette!("--voxel-size is required to voxelize meshes and point clouds."))
+    }
+
+    /// The pose at which glTF scenes are evaluated.
+    pub fn gltf_pose(&self) -> Result<GltfPose> {
+        let animation = self
+            .animation
+            .as_deref()
+            .map(AnimationSelector::from_str)
+            .transpose()?;
+
+        let time = self.animation_time.unwrap_or(0.0);
+        if !time.is_finite() {
+            return Err(miette!(
+                "--animation-time must be a finite number."
+            ));
+        }
+
+        let morph_weight_overrides = self
+            .morph_weights
+            .iter()
+            .map(|weights| {
+                MorphWeightOverride::from_str(weights)
+                    .wrap_err_with(|| miette!("Invalid --morph-weights value \"{}\".", weights))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(GltfPose {
+            animation,
+            time,
+            morph_weight_overrides,
+        })
+    }
+
+    pub fn mesh_voxelization_options(&self) -> MeshVoxelizationOptions {
+        // Hermite data is only needed (and worth its memory) when the surface is later
+        // extracted with dual contouring.
+        let collect_hermite_data = match &self.command {
+            CliCommand::ExportMesh(mesh_export_args) => mesh_export_args
+                .surface_extraction_options()
+                .is_ok_and(|options| options.method == SurfaceExtractionMethod::DualContouring),
+            _ => false,
+        };
+
+        MeshVoxelizationOptions {
+            select_texture_mip_levels: self.texture_mipmaps,
+            collect_hermite_data,
+        }
+    }
+
+    pub fn point_cloud_voxelization_options(&self) -> Result<PointCloudVoxelizationOptions> {
+        let minimum_points_per_voxel = self.point_cloud_minimum_points_per_voxel.unwrap_or(1);
+        if minimum_points_per_voxel == 0 {
+            return Err(miette!(
+                "--point-cloud-min-points must be at least 1."
+            ));
+        }
+
+        Ok(PointCloudVoxelizationOptions {
+            minimum_points_per_voxel,
+            surface_only: self.point_cloud_surface_only,
+        })
+    }
+
+    pub fn csg_operation(&self) -> Result<Option<CsgOperation>> {
+        let Some(csg_operation) = &self.csg_operation else {
+            return Ok(None);
+        };
+
+        if self.input_file_paths.len() < 2 {
+            return Err(miette!(
+                "--csg-operation requires at least two --input-file-path options."
+            ));
+        }
+
+        Ok(Some(CsgOperation::from_str(csg_operation)?))
+    }
+
+    /// Returns `None` if no resampling has been requested (i.e. if none of the voxel size,
+    /// origin or transform options are set).
+    pub fn resampling_options(&self) -> Result<Option<ResamplingOptions>> {
+        if self.resample_voxel_size.is_none()
+            && self.resample_origin.is_none()
+            && self.resample_transform.is_none()
+        {
+            if self.resample_occupancy_rule.is_some() || self.resample_attribute_filter.is_some() {
+                return Err(miette!(
+                    "--resample-occupancy and --resample-attribute-filter require at least one of \
+                    --resample-voxel-size, --resample-origin or --resample-transform."
+                ));
+            }
+
+            return Ok(None);
+        }
+
+
+        if let Some(voxel_size) = self.resample_voxel_size {
+            if voxel_size <= 0.0 {
+                return Err(miette!("--resample-voxel-size must be positive."));
+            }
+        }
+
+        let lattice_origin = self
+            .resample_origin
+            .as_ref()
+            .map(|origin| parse_xyz_components_from_str(origin))
+            .transpose()
+            .wrap_err("Invalid --resample-origin option.")?;
+
+        let transform = self
+            .resample_transform
+            .as_ref()
+            .map(|transform| parse_affine_transform_from_str(transform))
+            .transpose()
+            .wrap_err("Invalid --resample-transform option.")?
+            .unwrap_or(Affine3A::IDENTITY);
+
+        let occupancy_rule = self
+            .resample_occupancy_rule
+            .as_ref()
+            .map(|rule| OccupancyRule::from_str(rule))
+            .transpose()?
+            .unwrap_or(OccupancyRule::Nearest);
+
+        let attribute_filter = self
+            .resample_attribute_filter
+            .as_ref()
+            .map(|filter| AttributeFilter::from_str(filter))
+            .transpose()?
+            .unwrap_or(AttributeFilter::Trilinear);
+
+
+        Ok(Some(ResamplingOptions {
+            voxel_size: self.resample_voxel_size,
+            lattice_origin,
+            transform,
+            occupancy_rule,
+            attribute_filter,
+        }))
+    }
+
+    pub fn post_processing_steps(&self) -> Result<Vec<PostProcessingStep>> {
+        self.post_processing_steps
+            .iter()
+            .map(|step_str| {
+                PostProcessingStep::from_str(step_str)
+                    .wrap_err_with(|| miette!("Invalid post-processing step: \"{}\".", step_str))
+            })
+            .collect()
+    }
 }
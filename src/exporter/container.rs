@@ -0,0 +1,259 @@
+//! Native self-describing voxel container format (`.nvox`).
+//!
+//! All values are little-endian. A file consists of a header, followed by the payloads
+//! of all channels (in the order they are listed in the header, without padding):
+//!
+//! | Size    | Field                                                                      |
+//! |---------|----------------------------------------------------------------------------|
+//! | 8       | magic bytes `NRGVOXEL`                                                     |
+//! | 2       | format version (`u16`, currently 1)                                        |
+//! | 3 × 8   | grid dimensions in voxels (`u64` x, y, z)                                  |
+//! | 3 × 4   | world-space origin, i.e. the minimum corner of the grid (`f32` x, y, z)    |
+//! | 4       | voxel size, i.e. the full box width (`f32`)                                |
+//! | 3       | axis order as ASCII, fastest-varying axis first (always `xyz`)             |
+//! | 32      | SHA-256 hash of the source file(s), or zeroes if unknown                   |
+//! | 2       | channel count (`u16`)                                                      |
+//! | ...     | channel descriptions                                                       |
+//!
+//! Each channel description contains:
+//!
+//! | Size    | Field                                                                      |
+//! |---------|----------------------------------------------------------------------------|
+//! | 1 + N   | name length (`u8`) and name (UTF-8), e.g. `binary-edge_u1`                 |
+//! | 1       | component type (0 = `u1`, 1 = `u8`, 2 = `u16`, 3 = `u32`, 4 = `f32`)       |
+//! | 1       | components per element (`u8`)                                             |
+//! | 1       | color space (0 = none, 1 = linear, 2 = sRGB)                               |
+//! | 8       | element count (`u64`): the voxel count, or the color count for palettes   |
+//! | 8       | payload length in bytes (`u64`)                                            |
+//!
+//! Voxel channels are named after the export types they contain and store their values in
+//! the same layout as raw exports. The `palette-index_u8` channel is followed by a `palette`
+//! channel holding the sRGB color of each index, starting with index 1.
+
+use std::{
+    fs::File,
+    io::{self, Cursor, Write},
+    path::Path,
+};
+
+use glam::{U64Vec3, Vec3};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use sha2::{Digest, Sha256};
+
+use super::{
+    create_buffered_output_file,
+    finish_buffered_output_file,
+    write_voxel_grid_as_raw,
+    ColorSpace,
+    ComponentType,
+    VoxelExportType,
+};
+use crate::{processing::palette::build_palette, voxelizer::grid::VoxelGrid};
+
+
+pub const CONTAINER_MAGIC: [u8; 8] = *b"NRGVOXEL";
+
+pub const CONTAINER_FORMAT_VERSION: u16 = 1;
+
+pub const CONTAINER_FILE_EXTENSION: &str = "nvox";
+
+/// Axis order of all voxel channels (x varies fastest, then y, then z).
+pub const CONTAINER_AXIS_ORDER: [u8; 3] = *b"xyz";
+
+/// Name of the channel holding the palette of a `palette-index_u8` channel.
+pub const PALETTE_CHANNEL_NAME: &str = "palette";
+
+
+/// Describes a single channel of a container file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ContainerChannel {
+    pub name: String,
+    pub component_type: ComponentType,
+    pub components_per_element: u8,
+    pub color_space: Option<ColorSpace>,
+    pub element_count: u64,
+    pub payload_length: u64,
+}
+
+
+/// Everything stored in a container file before the channel payloads.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ContainerHeader {
+    pub version: u16,
+    pub dimensions: U64Vec3,
+    pub origin: Vec3,
+    pub voxel_size: f32,
+    pub axis_order: [u8; 3],
+    pub source_hash: [u8; 32],
+    pub channels: Vec<ContainerChannel>,
+}
+
+impl ContainerHeader {
+    fn write_to<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&CONTAINER_MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+
+        for dimension in self.dimensions.to_array() {
+            writer.write_all(&dimension.to_le_bytes())?;
+        }
+
+        for coordinate in self.origin.to_array() {
+            writer.write_all(&coordinate.to_le_bytes())?;
+        }
+
+        writer.write_all(&self.voxel_size.to_le_bytes())?;
+        writer.write_all(&self.axis_order)?;
+        writer.write_all(&self.source_hash)?;
+
+        writer.write_all(&(self.channels.len() as u16).to_le_bytes())?;
+
+        for channel in &self.channels {
+            writer.write_all(&[channel.name.len() as u8])?;
+            writer.write_all(channel.name.as_bytes())?;
+            writer.write_all(&[
+                component_type_code(channel.component_type),
+                channel.components_per_element,
+                color_space_code(channel.color_space),
+            ])?;
+            writer.write_all(&channel.element_count.to_le_bytes())?;
+            writer.write_all(&channel.payload_length.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+
+pub(crate) fn component_type_code(component_type: ComponentType) -> u8 {
+    match component_type {
+        ComponentType::U1 => 0,
+        ComponentType::U8 => 1,
+        ComponentType::U16 => 2,
+        ComponentType::U32 => 3,
+        ComponentType::F32 => 4,
+    }
+}
+
+pub(crate) fn color_space_code(color_space: Option<ColorSpace>) -> u8 {
+    match color_space {
+        None => 0,
+        Some(ColorSpace::Linear) => 1,
+        Some(ColorSpace::Srgb) => 2,
+    }
+}
+
+
+
+/// Computes the SHA-256 hash of the contents of the given files, in order.
+pub fn hash_source_files<P>(file_paths: &[P]) -> Result<[u8; 32]>
+where
+    P: AsRef<Path>,
+{
+    let mut hasher = Sha256::new();
+
+    for file_path in file_paths {
+        let file_path = file_path.as_ref();
+
+        let mut file = File::open(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to open {}.", file_path.display()))?;
+
+        io::copy(&mut file, &mut hasher)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read {}.", file_path.display()))?;
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+
+/// Writes a grid into a container file, with one channel per export type.
+pub fn export_voxel_grid_as_container<P>(
+    output_file_path: P,
+    grid: &VoxelGrid,
+    voxel_export_types: &[VoxelExportType],
+    source_hash: [u8; 32],
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let voxel_count = grid.voxels().len() as u64;
+
+    let mut channels = Vec::with_capacity(voxel_export_types.len());
+    let mut payloads = Vec::with_capacity(voxel_export_types.len());
+
+    for voxel_export_type in voxel_export_types {
+        let palette = match voxel_export_type {
+            VoxelExportType::PaletteIndexU8 { palette_options } => {
+                Some(build_palette([grid], palette_options))
+            }
+            _ => None,
+        };
+
+        // Writing into a cursor (instead of directly into the vector) makes `io::copy` read
+        // with its usual stack buffer, which the raw writers expect to fit whole voxels.
+        let mut payload = Cursor::new(Vec::new());
+        write_voxel_grid_as_raw(
+            &mut payload,
+            grid,
+            voxel_export_type,
+            palette.as_ref(),
+        )?;
+        let payload = payload.into_inner();
+
+        channels.push(ContainerChannel {
+            name: voxel_export_type.name().to_string(),
+            component_type: voxel_export_type.component_type(),
+            components_per_element: voxel_export_type.components_per_voxel() as u8,
+            color_space: voxel_export_type.color_space(),
+            element_count: voxel_count,
+            payload_length: payload.len() as u64,
+        });
+        payloads.push(payload);
+
+
+        if let Some(palette) = palette {
+            let palette_payload = palette.srgb8_colors().concat();
+
+            channels.push(ContainerChannel {
+                name: PALETTE_CHANNEL_NAME.to_string(),
+                component_type: ComponentType::U8,
+                components_per_element: 3,
+                color_space: Some(ColorSpace::Srgb),
+                element_count: palette.colors().len() as u64,
+                payload_length: palette_payload.len() as u64,
+            });
+            payloads.push(palette_payload);
+        }
+    }
+
+    let header = ContainerHeader {
+        version: CONTAINER_FORMAT_VERSION,
+        dimensions: grid.dimensions(),
+        origin: grid.starting_point,
+        voxel_size: grid.voxel_size(),
+        axis_order: CONTAINER_AXIS_ORDER,
+        source_hash,
+        channels,
+    };
+
+
+    let mut buffered_file = create_buffered_output_file(output_file_path)?;
+
+    header
+        .write_to(&mut buffered_file)
+        .into_diagnostic()
+        .wrap_err("Failed to write container header.")?;
+
+    for payload in payloads {
+        buffered_file
+            .write_all(&payload)
+            .into_diagnostic()
+            .wrap_err("Failed to write channel data.")?;
+    }
+
+    finish_buffered_output_file(buffered_file)
+}
@@ -0,0 +1,161 @@
+//! MetaImage export (`.mhd` header with a `.raw` data file, as read by ITK and 3D Slicer).
+//!
+//! The voxels are stored in the same order as raw exports, with color components interleaved
+//! (`ElementNumberOfChannels = 3`). Binary export types are stored as one `MET_UCHAR` (0 or 1)
+//! per voxel. `Offset` is the center of the first voxel and the axes map directly
+//! to the scene axes (identity `TransformMatrix`).
+
+use std::{
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::ZlibEncoder, Compression};
+use miette::{Context, IntoDiagnostic, Result};
+
+use super::{
+    create_buffered_output_file,
+    finish_buffered_output_file,
+    prepare_palette,
+    write_voxel_grid_unpacked,
+    ComponentType,
+    VoxelExportType,
+};
+use crate::voxelizer::grid::VoxelGrid;
+
+
+pub const METAIMAGE_HEADER_FILE_EXTENSION: &str = "mhd";
+
+
+fn metaimage_element_type(component_type: ComponentType) -> &'static str {
+    match component_type {
+        ComponentType::U1 | ComponentType::U8 => "MET_UCHAR",
+        ComponentType::U16 => "MET_USHORT",
+        ComponentType::U32 => "MET_UINT",
+        ComponentType::F32 => "MET_FLOAT",
+    }
+}
+
+
+/// Path of the MetaImage header file, e.g. `model.m-0.mhd` for `model.m-0`.
+pub fn metaimage_file_path(output_base_path: &Path) -> PathBuf {
+    let mut file_path = output_base_path.as_os_str().to_owned();
+    file_path.push(format!(".{}", METAIMAGE_HEADER_FILE_EXTENSION));
+
+    PathBuf::from(file_path)
+}
+
+
+/// Writes a grid into a MetaImage header (see [`metaimage_file_path`]) and a data file next to it:
+/// `.raw`, or `.zraw` with zlib-compressed data if `compress` is set.
+pub fn export_voxel_grid_as_metaimage(
+    output_base_path: &Path,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    compress: bool,
+) -> Result<()> {
+    let header_file_path = metaimage_file_path(output_base_path);
+    let data_file_path = header_file_path.with_extension(if compress { "zraw" } else { "raw" });
+
+    let palette = prepare_palette(&header_file_path, [grid], voxel_export_type)?;
+
+
+    let mut voxel_data = Cursor::new(Vec::new());
+    write_voxel_grid_unpacked(
+        &mut voxel_data,
+        grid,
+        voxel_export_type,
+        palette.as_ref(),
+    )?;
+    let mut voxel_data = voxel_data.into_inner();
+
+    if compress {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+        encoder
+            .write_all(&voxel_data)
+            .into_diagnostic()
+            .wrap_err("Failed to compress voxel data.")?;
+
+        voxel_data = encoder
+            .finish()
+            .into_diagnostic()
+            .wrap_err("Failed to compress voxel data.")?;
+    }
+
+
+    let dimensions = grid.dimensions();
+    let voxel_size = grid.voxel_size();
+    let first_voxel_center = grid.starting_point + grid.voxel_half_extent;
+
+    let mut header = format!(
+        "ObjectType = Image\n\
+        NDims = 3\n\
+        BinaryData = True\n\
+        BinaryDataByteOrderMSB = False\n\
+        CompressedData = {}\n",
+        if compress { "True" } else { "False" }
+    );
+
+    if compress {
+        header.push_str(&format!(
+            "CompressedDataSize = {}\n",
+            voxel_data.len()
+        ));
+    }
+
+    header.push_str(&format!(
+        "TransformMatrix = 1 0 0 0 1 0 0 0 1\n\
+        Offset = {} {} {}\n\
+        CenterOfRotation = 0 0 0\n\
+        ElementSpacing = {} {} {}\n\
+        DimSize = {} {} {}\n",
+        first_voxel_center.x,
+        first_voxel_center.y,
+        first_voxel_center.z,
+        voxel_size,
+        voxel_size,
+        voxel_size,
+        dimensions.x,
+        dimensions.y,
+        dimensions.z,
+    ));
+
+    if voxel_export_type.components_per_voxel() > 1 {
+        header.push_str(&format!(
+            "ElementNumberOfChannels = {}\n",
+            voxel_export_type.components_per_voxel()
+        ));
+    }
+
+    // `ElementDataFile` must be the last field of the header.
+    header.push_str(&format!(
+        "ElementType = {}\n\
+        ElementDataFile = {}\n",
+        metaimage_element_type(voxel_export_type.unpacked_component_type()),
+        data_file_path
+            .file_name()
+            .expect("expected the data file path to have a file name")
+            .to_string_lossy(),
+    ));
+
+
+    let mut buffered_header_file = create_buffered_output_file(&header_file_path)?;
+
+    buffered_header_file
+        .write_all(header.as_bytes())
+        .into_diagnostic()
+        .wrap_err("Failed to write MetaImage header.")?;
+
+    finish_buffered_output_file(buffered_header_file)?;
+
+
+    let mut buffered_data_file = create_buffered_output_file(&data_file_path)?;
+
+    buffered_data_file
+        .write_all(&voxel_data)
+        .into_diagnostic()
+        .wrap_err("Failed to write voxel data.")?;
+
+    finish_buffered_output_file(buffered_data_file)
+}
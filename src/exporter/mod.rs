@@ -5,19 +5,212 @@ use std::{
         prelude::{Read, Write},
         BufWriter,
     },
-    path::Path,
+    path::{Path, PathBuf},
 };
 
+use glam::Vec3;
 use miette::{Context, IntoDiagnostic, Result};
 
-use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+use crate::{
+    mesh::linear_component_to_srgb,
+    processing::{
+        compare::DiffLabel,
+        components::label_connected_components,
+        distance::squared_distance_to_nearest_non_target_voxel,
+        neighbourhood::Connectivity,
+        palette::{build_palette, map_grid_to_palette, Palette, PaletteOptions},
+    },
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+pub mod container;
+pub mod metaimage;
+pub mod nifti;
+pub mod nrrd;
+pub mod schematic;
+pub mod slices;
+pub mod surface;
+pub mod vox;
+pub mod vtk;
+
+/// File format that voxel data is exported in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VoxelOutputFormat {
+    /// Headerless voxel data of a single export type, with a plain-text metadata file.
+    Raw,
+    /// Self-describing container with one channel per export type (see [`container`]).
+    NativeContainer,
+    /// NRRD file with an attached or detached header (see [`nrrd`]).
+    Nrrd,
+    /// MetaImage header with a separate data file (see [`metaimage`]).
+    MetaImage,
+    /// NIfTI-1 file (see [`nifti`]).
+    Nifti,
+    /// XML VTK image data file with one array per export type (see [`vtk`]).
+    VtkImageData,
+    /// Legacy VTK structured points file with one array per export type (see [`vtk`]).
+    VtkLegacy,
+    /// MagicaVoxel file with palette-indexed voxels (see [`vox`]).
+    MagicaVoxel,
+    /// Minecraft schematic in the Sponge format (see [`schematic`]).
+    SpongeSchematic,
+    /// One PNG image per slice along an axis, optionally with a contact sheet (see [`slices`]).
+    PngSlices,
+}
+
 
+#[derive(Clone)]
 pub enum VoxelExportType {
     BinaryEdgeStateU1,
     BinaryFillStateU1,
     LinearRgb8ColorU8,
+    /// sRGB-encoded 8-bit color, which preserves dark tones much better than linear 8-bit color.
+    SrgbRgb8ColorU8,
+    /// Linear 16-bit color (little-endian).
+    LinearRgb16ColorU16,
+    /// Linear 32-bit floating-point color (little-endian).
+    LinearRgbColorF32,
     MetallicValueU8,
     RoughnessValueU8,
+    /// Average point intensity (only meaningful for voxelized point clouds).
+    IntensityValueU8,
+    /// Euclidean distance (in voxels) from the center of each occupied voxel to the center
+    /// of the nearest empty voxel, i.e. its depth below the surface (0 for empty voxels).
+    SurfaceDistanceF32,
+    /// Connected component label of each voxel (0 for empty voxels,
+    /// 1 for the largest component, 2 for the second largest, ...).
+    ComponentLabelU32 {
+        connectivity: Connectivity,
+    },
+    /// Palette index of each voxel (0 for voxels without a color); the palette is written
+    /// to a separate `.palette.txt` file next to the voxel data.
+    PaletteIndexU8 {
+        palette_options: PaletteOptions,
+    },
+}
+
+impl VoxelExportType {
+    /// Name of the export type, as accepted by `--export-type`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            VoxelExportType::BinaryEdgeStateU1 => "binary-edge_u1",
+            VoxelExportType::BinaryFillStateU1 => "binary-fill_u1",
+            VoxelExportType::LinearRgb8ColorU8 => "linear-rgb8-color_u8",
+            VoxelExportType::SrgbRgb8ColorU8 => "srgb-rgb8-color_u8",
+            VoxelExportType::LinearRgb16ColorU16 => "linear-rgb16-color_u16",
+            VoxelExportType::LinearRgbColorF32 => "linear-rgb-color_f32",
+            VoxelExportType::MetallicValueU8 => "metallic-value_u8",
+            VoxelExportType::RoughnessValueU8 => "roughness-value_u8",
+            VoxelExportType::IntensityValueU8 => "intensity-value_u8",
+            VoxelExportType::SurfaceDistanceF32 => "surface-distance_f32",
+            VoxelExportType::ComponentLabelU32 { .. } => "component-label_u32",
+            VoxelExportType::PaletteIndexU8 { .. } => "palette-index_u8",
+        }
+    }
+
+    /// Type of each stored component.
+    pub fn component_type(&self) -> ComponentType {
+        match self {
+            VoxelExportType::BinaryEdgeStateU1 | VoxelExportType::BinaryFillStateU1 => {
+                ComponentType::U1
+            }
+            VoxelExportType::LinearRgb8ColorU8
+            | VoxelExportType::SrgbRgb8ColorU8
+            | VoxelExportType::MetallicValueU8
+            | VoxelExportType::RoughnessValueU8
+            | VoxelExportType::IntensityValueU8
+            | VoxelExportType::PaletteIndexU8 { .. } => ComponentType::U8,
+            VoxelExportType::LinearRgb16ColorU16 => ComponentType::U16,
+            VoxelExportType::LinearRgbColorF32 | VoxelExportType::SurfaceDistanceF32 => {
+                ComponentType::F32
+            }
+            VoxelExportType::ComponentLabelU32 { .. } => ComponentType::U32,
+        }
+    }
+
+    /// Type of each stored component in formats without a bit type,
+    /// where bit-packed booleans are widened to one byte (0 or 1) per voxel.
+    pub fn unpacked_component_type(&self) -> ComponentType {
+        match self.component_type() {
+            ComponentType::U1 => ComponentType::U8,
+            component_type => component_type,
+        }
+    }
+
+    /// Number of components stored per voxel.
+    pub fn components_per_voxel(&self) -> usize {
+        match self {
+            VoxelExportType::LinearRgb8ColorU8
+            | VoxelExportType::SrgbRgb8ColorU8
+            | VoxelExportType::LinearRgb16ColorU16
+            | VoxelExportType::LinearRgbColorF32 => 3,
+            _ => 1,
+        }
+    }
+
+    /// Color space of the exported values, if they are colors.
+    pub fn color_space(&self) -> Option<ColorSpace> {
+        match self {
+            VoxelExportType::LinearRgb8ColorU8
+            | VoxelExportType::LinearRgb16ColorU16
+            | VoxelExportType::LinearRgbColorF32 => Some(ColorSpace::Linear),
+            VoxelExportType::SrgbRgb8ColorU8 => Some(ColorSpace::Srgb),
+            _ => None,
+        }
+    }
+}
+
+
+/// Type of the stored values. All multi-byte values are little-endian.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ComponentType {
+    /// Bit-packed booleans, eight voxels per byte (the first voxel in the most significant bit).
+    U1,
+    U8,
+    U16,
+    U32,
+    F32,
+}
+
+impl ComponentType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComponentType::U1 => "u1",
+            ComponentType::U8 => "u8",
+            ComponentType::U16 => "u16",
+            ComponentType::U32 => "u32",
+            ComponentType::F32 => "f32",
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+impl ColorSpace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorSpace::Linear => "linear",
+            ColorSpace::Srgb => "srgb",
+        }
+    }
+}
+
+
+/// Quantizes a value in `[0, 1]` to the full range of a `u8`, rounding to the nearest value.
+#[inline]
+fn unit_value_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+}
+
+/// Quantizes a value in `[0, 1]` to the full range of a `u16`, rounding to the nearest value.
+#[inline]
+fn unit_value_to_u16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
 }
 
 
@@ -216,9 +409,9 @@ impl<'g> Read for LinearRgb8ColorU8RawWriter<'g> {
         };
 
 
-        let red_value = (color.x * (u8::MAX as f32)) as u8;
-        let green_value = (color.y * (u8::MAX as f32)) as u8;
-        let blue_value = (color.z * (u8::MAX as f32)) as u8;
+        let red_value = unit_value_to_u8(color.x);
+        let green_value = unit_value_to_u8(color.y);
+        let blue_value = unit_value_to_u8(color.z);
 
         buf[0] = red_value;
         buf[1] = green_value;
@@ -232,6 +425,150 @@ impl<'g> Read for LinearRgb8ColorU8RawWriter<'g> {
 
 
 
+pub struct SrgbRgb8ColorU8RawWriter<'g> {
+    grid_voxels: &'g [VoxelData],
+    next_index: usize,
+}
+
+impl<'g> SrgbRgb8ColorU8RawWriter<'g> {
+    pub fn from_grid(grid: &'g VoxelGrid) -> Self {
+        Self {
+            grid_voxels: grid.voxels(),
+            next_index: 0,
+        }
+    }
+}
+
+
+impl<'g> Read for SrgbRgb8ColorU8RawWriter<'g> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.next_index >= self.grid_voxels.len() {
+            return Ok(0);
+        }
+
+        if buf.len() < 3 {
+            panic!("expected a buffer of size at least 3");
+        }
+
+
+        let color = edge_voxel_color(&self.grid_voxels[self.next_index]);
+
+        buf[0] = unit_value_to_u8(linear_component_to_srgb(color.x));
+        buf[1] = unit_value_to_u8(linear_component_to_srgb(color.y));
+        buf[2] = unit_value_to_u8(linear_component_to_srgb(color.z));
+
+        self.next_index += 1;
+
+        Ok(3)
+    }
+}
+
+
+
+pub struct LinearRgb16ColorU16RawWriter<'g> {
+    grid_voxels: &'g [VoxelData],
+    next_index: usize,
+}
+
+impl<'g> LinearRgb16ColorU16RawWriter<'g> {
+    pub fn from_grid(grid: &'g VoxelGrid) -> Self {
+        Self {
+            grid_voxels: grid.voxels(),
+            next_index: 0,
+        }
+    }
+}
+
+
+impl<'g> Read for LinearRgb16ColorU16RawWriter<'g> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.next_index >= self.grid_voxels.len() {
+            return Ok(0);
+        }
+
+        if buf.len() < 6 {
+            panic!("expected a buffer of size at least 6");
+        }
+
+
+        let color = edge_voxel_color(&self.grid_voxels[self.next_index]);
+
+        buf[0..2].copy_from_slice(&unit_value_to_u16(color.x).to_le_bytes());
+        buf[2..4].copy_from_slice(&unit_value_to_u16(color.y).to_le_bytes());
+        buf[4..6].copy_from_slice(&unit_value_to_u16(color.z).to_le_bytes());
+
+        self.next_index += 1;
+
+        Ok(6)
+    }
+}
+
+
+
+pub struct LinearRgbColorF32RawWriter<'g> {
+    grid_voxels: &'g [VoxelData],
+    next_index: usize,
+}
+
+impl<'g> LinearRgbColorF32RawWriter<'g> {
+    pub fn from_grid(grid: &'g VoxelGrid) -> Self {
+        Self {
+            grid_voxels: grid.voxels(),
+            next_index: 0,
+        }
+    }
+}
+
+
+impl<'g> Read for LinearRgbColorF32RawWriter<'g> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.next_index >= self.grid_voxels.len() {
+            return Ok(0);
+        }
+
+        if buf.len() < 12 {
+            panic!("expected a buffer of size at least 12");
+        }
+
+
+        let color = edge_voxel_color(&self.grid_voxels[self.next_index]);
+
+        buf[0..4].copy_from_slice(&color.x.to_le_bytes());
+        buf[4..8].copy_from_slice(&color.y.to_le_bytes());
+        buf[8..12].copy_from_slice(&color.z.to_le_bytes());
+
+        self.next_index += 1;
+
+        Ok(12)
+    }
+}
+
+
+/// Computes the distance (in voxels) from each occupied voxel to the nearest empty voxel
+/// (see [`VoxelExportType::SurfaceDistanceF32`]). Voxels outside of the grid count as empty.
+pub fn compute_surface_distances(grid: &VoxelGrid) -> Vec<f32> {
+    let voxels = grid.voxels();
+
+    squared_distance_to_nearest_non_target_voxel(grid, |flat_index| {
+        voxels[flat_index].is_occupied()
+    })
+    .into_iter()
+    .map(f32::sqrt)
+    .collect()
+}
+
+
+/// Returns the linear color of an edge voxel, or black for other voxels.
+#[inline]
+fn edge_voxel_color(voxel: &VoxelData) -> Vec3 {
+    match voxel {
+        VoxelData::Edge { color, .. } => *color,
+        _ => Vec3::ZERO,
+    }
+}
+
+
+
 pub struct MetallicValueU8RawWriter<'g> {
     grid_voxels: &'g [VoxelData],
     next_index: usize,
@@ -269,12 +606,12 @@ impl<'g> Read for MetallicValueU8RawWriter<'g> {
         };
 
 
-        let metallic_value_u8 = (metallic_value * (u8::MAX as f32)) as u8;
+        let metallic_value_u8 = unit_value_to_u8(*metallic_value);
         buf[0] = metallic_value_u8;
 
         self.next_index += 1;
 
-        Ok(3)
+        Ok(1)
     }
 }
 
@@ -317,71 +654,291 @@ impl<'g> Read for RoughnessValueU8RawWriter<'g> {
         };
 
 
-        let roughness_value_u8 = (rougness_value * (u8::MAX as f32)) as u8;
+        let roughness_value_u8 = unit_value_to_u8(*rougness_value);
         buf[0] = roughness_value_u8;
 
         self.next_index += 1;
 
-        Ok(3)
+        Ok(1)
     }
 }
 
 
 
-pub fn export_voxel_grid_as_raw<P>(
-    output_file_path: P,
+pub struct IntensityValueU8RawWriter<'g> {
+    grid_voxels: &'g [VoxelData],
+    next_index: usize,
+}
+
+impl<'g> IntensityValueU8RawWriter<'g> {
+    pub fn from_grid(grid: &'g VoxelGrid) -> Self {
+        Self {
+            grid_voxels: grid.voxels(),
+            next_index: 0,
+        }
+    }
+}
+
+
+impl<'g> Read for IntensityValueU8RawWriter<'g> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.next_index >= self.grid_voxels.len() {
+            return Ok(0);
+        }
+
+        if buf.is_empty() {
+            panic!("expected a buffer of size at least 1");
+        }
+
+
+        let voxel = &self.grid_voxels[self.next_index];
+
+        let VoxelData::Edge { intensity, .. } = voxel else {
+            buf[0] = 0;
+
+            self.next_index += 1;
+
+            return Ok(1);
+        };
+
+
+        let intensity_value_u8 = unit_value_to_u8(*intensity);
+        buf[0] = intensity_value_u8;
+
+        self.next_index += 1;
+
+        Ok(1)
+    }
+}
+
+
+
+pub struct PaletteIndexU8RawWriter {
+    indices: Vec<u8>,
+    next_index: usize,
+}
+
+impl PaletteIndexU8RawWriter {
+    pub fn from_grid(grid: &VoxelGrid, palette: &Palette, dithering: bool) -> Self {
+        Self {
+            indices: map_grid_to_palette(grid, palette, dithering),
+            next_index: 0,
+        }
+    }
+}
+
+
+impl Read for PaletteIndexU8RawWriter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining_indices = &self.indices[self.next_index..];
+        let byte_count = remaining_indices.len().min(buf.len());
+
+        buf[..byte_count].copy_from_slice(&remaining_indices[..byte_count]);
+
+        self.next_index += byte_count;
+
+        Ok(byte_count)
+    }
+}
+
+
+
+pub struct ComponentLabelU32RawWriter {
+    labels: Vec<u32>,
+    next_index: usize,
+}
+
+impl ComponentLabelU32RawWriter {
+    pub fn from_grid(grid: &VoxelGrid, connectivity: Connectivity) -> Self {
+        Self {
+            labels: label_connected_components(grid, connectivity).labels,
+            next_index: 0,
+        }
+    }
+}
+
+
+impl Read for ComponentLabelU32RawWriter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.next_index >= self.labels.len() {
+            return Ok(0);
+        }
+
+        if buf.len() < 4 {
+            panic!("expected a buffer of size at least 4");
+        }
+
+
+        let label = self.labels[self.next_index];
+        buf[0..4].copy_from_slice(&label.to_le_bytes());
+
+        self.next_index += 1;
+
+        Ok(4)
+    }
+}
+
+
+
+/// Writes the voxel data of a grid in the given raw format.
+///
+/// `palette` must be provided for [`VoxelExportType::PaletteIndexU8`].
+fn write_voxel_grid_as_raw<W>(
+    writer: &mut W,
     grid: &VoxelGrid,
-    voxel_export_type: VoxelExportType,
+    voxel_export_type: &VoxelExportType,
+    palette: Option<&Palette>,
 ) -> Result<()>
 where
-    P: AsRef<Path>,
+    W: Write,
 {
-    let file = File::create(output_file_path)
-        .into_diagnostic()
-        .wrap_err("Failed to open file.")?;
-
-    let mut buffered_file = BufWriter::new(file);
-
-
     match voxel_export_type {
         VoxelExportType::BinaryEdgeStateU1 => {
             let mut file_data_producer = BinaryEdgeStateU1RawWriter::from_grid(grid);
 
-            io::copy(&mut file_data_producer, &mut buffered_file)
+            io::copy(&mut file_data_producer, writer)
                 .into_diagnostic()
-                .wrap_err("Failed to write to file.")?;
+                .wrap_err("Failed to write voxel data.")?;
         }
         VoxelExportType::BinaryFillStateU1 => {
             let mut file_data_producer = BinaryFillStateU1RawWriter::from_grid(grid);
 
-            io::copy(&mut file_data_producer, &mut buffered_file)
+            io::copy(&mut file_data_producer, writer)
                 .into_diagnostic()
-                .wrap_err("Failed to write to file.")?;
+                .wrap_err("Failed to write voxel data.")?;
         }
         VoxelExportType::LinearRgb8ColorU8 => {
             let mut file_data_producer = LinearRgb8ColorU8RawWriter::from_grid(grid);
 
-            io::copy(&mut file_data_producer, &mut buffered_file)
+            io::copy(&mut file_data_producer, writer)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::SrgbRgb8ColorU8 => {
+            let mut file_data_producer = SrgbRgb8ColorU8RawWriter::from_grid(grid);
+
+            io::copy(&mut file_data_producer, writer)
                 .into_diagnostic()
-                .wrap_err("Failed to write to file.")?;
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::LinearRgb16ColorU16 => {
+            let mut file_data_producer = LinearRgb16ColorU16RawWriter::from_grid(grid);
+
+            io::copy(&mut file_data_producer, writer)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::LinearRgbColorF32 => {
+            let mut file_data_producer = LinearRgbColorF32RawWriter::from_grid(grid);
+
+            io::copy(&mut file_data_producer, writer)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
         }
         VoxelExportType::MetallicValueU8 => {
             let mut file_data_producer = MetallicValueU8RawWriter::from_grid(grid);
 
-            io::copy(&mut file_data_producer, &mut buffered_file)
+            io::copy(&mut file_data_producer, writer)
                 .into_diagnostic()
-                .wrap_err("Failed to write to file.")?;
+                .wrap_err("Failed to write voxel data.")?;
         }
         VoxelExportType::RoughnessValueU8 => {
             let mut file_data_producer = RoughnessValueU8RawWriter::from_grid(grid);
 
-            io::copy(&mut file_data_producer, &mut buffered_file)
+            io::copy(&mut file_data_producer, writer)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::IntensityValueU8 => {
+            let mut file_data_producer = IntensityValueU8RawWriter::from_grid(grid);
+
+            io::copy(&mut file_data_producer, writer)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::SurfaceDistanceF32 => {
+            let voxel_bytes = compute_surface_distances(grid)
+                .into_iter()
+                .flat_map(f32::to_le_bytes)
+                .collect::<Vec<_>>();
+
+            writer
+                .write_all(&voxel_bytes)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::ComponentLabelU32 { connectivity } => {
+            let mut file_data_producer = ComponentLabelU32RawWriter::from_grid(grid, *connectivity);
+
+            io::copy(&mut file_data_producer, writer)
                 .into_diagnostic()
-                .wrap_err("Failed to write to file.")?;
+                .wrap_err("Failed to write voxel data.")?;
+        }
+        VoxelExportType::PaletteIndexU8 { palette_options } => {
+            let palette = palette.expect("expected a palette for palette index exports");
+
+            let mut file_data_producer =
+                PaletteIndexU8RawWriter::from_grid(grid, palette, palette_options.dithering);
+
+            io::copy(&mut file_data_producer, writer)
+                .into_diagnostic()
+                .wrap_err("Failed to write voxel data.")?;
         }
     }
 
 
+    Ok(())
+}
+
+
+/// Writes the voxel data of a grid like [`write_voxel_grid_as_raw`], but with one byte per voxel
+/// for binary export types (see [`VoxelExportType::unpacked_component_type`]).
+fn write_voxel_grid_unpacked<W>(
+    writer: &mut W,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    palette: Option<&Palette>,
+) -> Result<()>
+where
+    W: Write,
+{
+    let is_voxel_set: fn(&VoxelData) -> bool = match voxel_export_type {
+        VoxelExportType::BinaryEdgeStateU1 => |voxel| {
+            matches!(
+                voxel,
+                VoxelData::Edge { .. } | VoxelData::InsideMesh
+            )
+        },
+        VoxelExportType::BinaryFillStateU1 => |voxel| matches!(voxel, VoxelData::InsideMesh),
+        _ => return write_voxel_grid_as_raw(writer, grid, voxel_export_type, palette),
+    };
+
+    let voxel_bytes = grid
+        .voxels()
+        .iter()
+        .map(|voxel| is_voxel_set(voxel) as u8)
+        .collect::<Vec<_>>();
+
+    writer
+        .write_all(&voxel_bytes)
+        .into_diagnostic()
+        .wrap_err("Failed to write voxel data.")
+}
+
+
+fn create_buffered_output_file<P>(output_file_path: P) -> Result<BufWriter<File>>
+where
+    P: AsRef<Path>,
+{
+    let file = File::create(output_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to open file.")?;
+
+    Ok(BufWriter::new(file))
+}
+
+
+fn finish_buffered_output_file(buffered_file: BufWriter<File>) -> Result<()> {
     let mut file = buffered_file
         .into_inner()
         .into_diagnostic()
@@ -391,7 +948,197 @@ where
         .into_diagnostic()
         .wrap_err("Failed to flush unbuffered file.")?;
 
-    drop(file);
+    Ok(())
+}
+
+
+/// Builds the palette for palette index exports and writes it next to the voxel data
+/// (see [`palette_file_path`]). Returns `None` for other export types.
+fn prepare_palette<'g, I>(
+    output_file_path: &Path,
+    grids: I,
+    voxel_export_type: &VoxelExportType,
+) -> Result<Option<Palette>>
+where
+    I: IntoIterator<Item = &'g VoxelGrid>,
+{
+    let VoxelExportType::PaletteIndexU8 { palette_options } = voxel_export_type else {
+        return Ok(None);
+    };
+
+    let palette = build_palette(grids, palette_options);
+
+    println!(
+        "Writing palette with {} colors to {}.",
+        palette.colors().len(),
+        palette_file_path(output_file_path).display()
+    );
+
+    let mut buffered_file = create_buffered_output_file(palette_file_path(output_file_path))?;
+
+    buffered_file
+        .write_all(palette.to_text().as_bytes())
+        .into_diagnostic()
+        .wrap_err("Failed to write palette.")?;
+
+    finish_buffered_output_file(buffered_file)?;
+
+    Ok(Some(palette))
+}
+
+
+/// Path of the palette file written alongside a palette index export,
+/// e.g. `model.m-0.palette.txt` for `model.m-0.bin`.
+pub fn palette_file_path(output_file_path: &Path) -> PathBuf {
+    output_file_path.with_extension("palette.txt")
+}
+
+
+pub fn export_voxel_grid_as_raw<P>(
+    output_file_path: P,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let palette = prepare_palette(
+        output_file_path.as_ref(),
+        [grid],
+        voxel_export_type,
+    )?;
+
+    let mut buffered_file = create_buffered_output_file(output_file_path)?;
+
+    write_voxel_grid_as_raw(
+        &mut buffered_file,
+        grid,
+        voxel_export_type,
+        palette.as_ref(),
+    )?;
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+/// Writes several grids with identical dimensions (e.g. animation frames) into a single file,
+/// one after another, forming a 4D volume. Each grid starts at a byte boundary.
+/// Palette index exports share a single palette across all grids.
+pub fn export_voxel_grids_as_raw_frames<'g, P, I>(
+    output_file_path: P,
+    grids: I,
+    voxel_export_type: &VoxelExportType,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'g VoxelGrid> + Clone,
+{
+    let palette = prepare_palette(
+        output_file_path.as_ref(),
+        grids.clone(),
+        voxel_export_type,
+    )?;
+
+    let mut buffered_file = create_buffered_output_file(output_file_path)?;
+
+    for grid in grids {
+        write_voxel_grid_as_raw(
+            &mut buffered_file,
+            grid,
+            voxel_export_type,
+            palette.as_ref(),
+        )?;
+    }
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+/// Writes a plain-text metadata file describing a raw export: the grid layout, the voxel format
+/// and, for color exports, the color space. `frame_count` is set for multi-frame files.
+pub fn export_raw_metadata<P>(
+    output_file_path: P,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    frame_count: Option<usize>,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let mut buffered_file = create_buffered_output_file(output_file_path)?;
+
+    let dimensions = grid.dimensions();
+    let starting_point = grid.starting_point;
+
+    let mut metadata = format!(
+        "export type: {}\n\
+        dimensions: {} {} {}\n\
+        origin: {} {} {}\n\
+        voxel size: {}\n\
+        voxel order: x fastest, then y, then z\n\
+        component type: {}\n\
+        components per voxel: {}\n\
+        byte order: little-endian\n",
+        voxel_export_type.name(),
+        dimensions.x,
+        dimensions.y,
+        dimensions.z,
+        starting_point.x,
+        starting_point.y,
+        starting_point.z,
+        grid.voxel_size(),
+        voxel_export_type.component_type().name(),
+        voxel_export_type.components_per_voxel(),
+    );
+
+    if let Some(color_space) = voxel_export_type.color_space() {
+        metadata.push_str(&format!("color space: {}\n", color_space.name()));
+    }
+
+    if let VoxelExportType::PaletteIndexU8 { .. } = voxel_export_type {
+        metadata.push_str(
+            "palette: index 0 is empty, index N is the sRGB color on line N of the .palette.txt file\n",
+        );
+    }
+
+    if let Some(frame_count) = frame_count {
+        metadata.push_str(&format!("frames: {}\n", frame_count));
+    }
+
+    buffered_file
+        .write_all(metadata.as_bytes())
+        .into_diagnostic()
+        .wrap_err("Failed to write metadata.")?;
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+/// Writes a [`DiffLabel`] volume (one byte per voxel, in the same order as the raw exports).
+pub fn export_diff_labels_as_raw<P>(output_file_path: P, diff_labels: &[DiffLabel]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let file = File::create(output_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to open file.")?;
+
+    let mut buffered_file = BufWriter::new(file);
+
+    let label_bytes = diff_labels
+        .iter()
+        .map(|label| *label as u8)
+        .collect::<Vec<_>>();
+
+    buffered_file
+        .write_all(&label_bytes)
+        .into_diagnostic()
+        .wrap_err("Failed to write to file.")?;
+
+    buffered_file
+        .flush()
+        .into_diagnostic()
+        .wrap_err("Failed to flush buffered writer.")?;
 
 
     Ok(())
@@ -0,0 +1,394 @@
+//! NIfTI-1 export (single-file `.nii`, or gzip-compressed `.nii.gz`).
+//!
+//! The file contains the 348-byte NIfTI-1 header, four empty extension bytes and the voxel data
+//! (starting at byte 352) in the same order as raw exports. Binary export types are stored as one
+//! `uint8` (0 or 1) per voxel. 8-bit colors use the interleaved `RGB24` data type, while 16-bit
+//! and floating-point colors are stored as a vector-valued image (`dim[5] = 3`), which NIfTI
+//! lays out component by component (all red values, then all green values, then all blue values).
+//!
+//! Both the qform and the sform map voxel indices to scene coordinates with the first voxel
+//! centered at the grid origin plus half a voxel and the axes matching the scene axes.
+//! Spatial units are left unspecified, as they depend on the source file.
+
+use std::{
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::{
+    create_buffered_output_file,
+    finish_buffered_output_file,
+    prepare_palette,
+    write_voxel_grid_unpacked,
+    ComponentType,
+    VoxelExportType,
+};
+use crate::voxelizer::grid::VoxelGrid;
+
+
+pub const NIFTI_FILE_EXTENSION: &str = "nii";
+
+const NIFTI_HEADER_SIZE: usize = 348;
+
+/// Offset of the voxel data: the header, followed by four (zeroed) extension flag bytes.
+const NIFTI_VOXEL_DATA_OFFSET: usize = NIFTI_HEADER_SIZE + 4;
+
+const NIFTI_DATA_TYPE_UINT8: i16 = 2;
+const NIFTI_DATA_TYPE_FLOAT32: i16 = 16;
+const NIFTI_DATA_TYPE_RGB24: i16 = 128;
+const NIFTI_DATA_TYPE_UINT16: i16 = 512;
+const NIFTI_DATA_TYPE_UINT32: i16 = 768;
+
+const NIFTI_INTENT_VECTOR: i16 = 1007;
+
+const NIFTI_XFORM_SCANNER_ANAT: i16 = 1;
+
+
+/// Data type code and bits per element (of a single component, except for `RGB24`).
+fn nifti_data_type(voxel_export_type: &VoxelExportType) -> (i16, i16) {
+    let component_type = voxel_export_type.unpacked_component_type();
+
+    if voxel_export_type.components_per_voxel() == 3 && component_type == ComponentType::U8 {
+        return (NIFTI_DATA_TYPE_RGB24, 24);
+    }
+
+    match component_type {
+        ComponentType::U1 | ComponentType::U8 => (NIFTI_DATA_TYPE_UINT8, 8),
+        ComponentType::U16 => (NIFTI_DATA_TYPE_UINT16, 16),
+        ComponentType::U32 => (NIFTI_DATA_TYPE_UINT32, 32),
+        ComponentType::F32 => (NIFTI_DATA_TYPE_FLOAT32, 32),
+    }
+}
+
+
+/// Little-endian NIfTI-1 header builder over a zeroed buffer of the full header size.
+struct NiftiHeader {
+    bytes: [u8; NIFTI_HEADER_SIZE],
+}
+
+impl NiftiHeader {
+    fn new() -> Self {
+        Self {
+            bytes: [0; NIFTI_HEADER_SIZE],
+        }
+    }
+
+    fn set_i16(&mut self, offset: usize, value: i16) {
+        self.bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_i32(&mut self, offset: usize, value: i32) {
+        self.bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_f32(&mut self, offset: usize, value: f32) {
+        self.bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Copies a string into a fixed-size, zero-padded field (truncating it if needed).
+    fn set_str(&mut self, offset: usize, field_length: usize, value: &str) {
+        let length = value.len().min(field_length - 1);
+        self.bytes[offset..offset + length].copy_from_slice(&value.as_bytes()[..length]);
+    }
+}
+
+
+fn nifti_header(grid: &VoxelGrid, voxel_export_type: &VoxelExportType) -> NiftiHeader {
+    let dimensions = grid.dimensions();
+    let voxel_size = grid.voxel_size();
+    let first_voxel_center = grid.starting_point + grid.voxel_half_extent;
+
+    let (data_type, bits_per_element) = nifti_data_type(voxel_export_type);
+    let is_vector =
+        data_type != NIFTI_DATA_TYPE_RGB24 && voxel_export_type.components_per_voxel() > 1;
+
+    let mut header = NiftiHeader::new();
+
+    // sizeof_hdr, regular
+    header.set_i32(0, NIFTI_HEADER_SIZE as i32);
+    header.bytes[38] = b'r';
+
+    // dim[8]
+    let dim: [i16; 8] = if is_vector {
+        [
+            5,
+            dimensions.x as i16,
+            dimensions.y as i16,
+            dimensions.z as i16,
+            1,
+            voxel_export_type.components_per_voxel() as i16,
+            1,
+            1,
+        ]
+    } else {
+        [
+            3,
+            dimensions.x as i16,
+            dimensions.y as i16,
+            dimensions.z as i16,
+            1,
+            1,
+            1,
+            1,
+        ]
+    };
+
+    for (index, value) in dim.into_iter().enumerate() {
+        header.set_i16(40 + index * 2, value);
+    }
+
+    // intent_code, datatype, bitpix
+    if is_vector {
+        header.set_i16(68, NIFTI_INTENT_VECTOR);
+    }
+    header.set_i16(70, data_type);
+    header.set_i16(72, bits_per_element);
+
+    // pixdim[8] (pixdim[0] is qfac), vox_offset, scl_slope
+    let pixdim: [f32; 8] = [1.0, voxel_size, voxel_size, voxel_size, 1.0, 1.0, 1.0, 1.0];
+    for (index, value) in pixdim.into_iter().enumerate() {
+        header.set_f32(76 + index * 4, value);
+    }
+
+    header.set_f32(108, NIFTI_VOXEL_DATA_OFFSET as f32);
+    header.set_f32(112, 1.0);
+
+    // descrip
+    let mut description = voxel_export_type.name().to_string();
+    if let Some(color_space) = voxel_export_type.color_space() {
+        description.push_str(&format!(" ({})", color_space.name()));
+    }
+    header.set_str(148, 80, &description);
+
+    // qform_code, sform_code
+    header.set_i16(252, NIFTI_XFORM_SCANNER_ANAT);
+    header.set_i16(254, NIFTI_XFORM_SCANNER_ANAT);
+
+    // quatern_b, quatern_c, quatern_d stay zero (identity rotation); qoffset_x, qoffset_y, qoffset_z
+    header.set_f32(268, first_voxel_center.x);
+    header.set_f32(272, first_voxel_center.y);
+    header.set_f32(276, first_voxel_center.z);
+
+    // srow_x, srow_y, srow_z
+    let affine_rows: [[f32; 4]; 3] = [
+        [voxel_size, 0.0, 0.0, first_voxel_center.x],
+        [0.0, voxel_size, 0.0, first_voxel_center.y],
+        [0.0, 0.0, voxel_size, first_voxel_center.z],
+    ];
+
+    for (row_index, row) in affine_rows.into_iter().enumerate() {
+        for (column_index, value) in row.into_iter().enumerate() {
+            header.set_f32(280 + row_index * 16 + column_index * 4, value);
+        }
+    }
+
+    // magic
+    header.set_str(344, 4, "n+1");
+
+    header
+}
+
+
+/// Converts interleaved voxel data (`component_size` bytes per component) into the planar
+/// layout of vector-valued NIfTI images.
+fn deinterleave_components(
+    voxel_data: &[u8],
+    components_per_voxel: usize,
+    component_size: usize,
+) -> Vec<u8> {
+    let voxel_stride = components_per_voxel * component_size;
+
+    let mut planar_data = Vec::with_capacity(voxel_data.len());
+
+    for component_index in 0..components_per_voxel {
+        let component_offset = component_index * component_size;
+
+        for voxel in voxel_data.chunks_exact(voxel_stride) {
+            planar_data
+                .extend_from_slice(&voxel[component_offset..component_offset + component_size]);
+        }
+    }
+
+    planar_data
+}
+
+
+/// Path of the NIfTI file, e.g. `model.m-0.nii` (or `model.m-0.nii.gz`) for `model.m-0`.
+pub fn nifti_file_path(output_base_path: &Path, gzip: bool) -> PathBuf {
+    let mut file_path = output_base_path.as_os_str().to_owned();
+    file_path.push(format!(".{}", NIFTI_FILE_EXTENSION));
+
+    if gzip {
+        file_path.push(".gz");
+    }
+
+    PathBuf::from(file_path)
+}
+
+
+/// Writes a grid into a NIfTI-1 file (see [`nifti_file_path`]).
+pub fn export_voxel_grid_as_nifti(
+    output_base_path: &Path,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    gzip: bool,
+) -> Result<()> {
+    if grid.dimensions().max_element() > i16::MAX as u64 {
+        return Err(miette!(
+            "NIfTI-1 supports at most {} voxels along each axis, but the grid has dimensions {}.",
+            i16::MAX,
+            grid.dimensions()
+        ));
+    }
+
+    let palette = prepare_palette(
+        &nifti_file_path(output_base_path, false),
+        [grid],
+        voxel_export_type,
+    )?;
+
+    let header = nifti_header(grid, voxel_export_type);
+
+
+    let mut voxel_data = Cursor::new(Vec::new());
+    write_voxel_grid_unpacked(
+        &mut voxel_data,
+        grid,
+        voxel_export_type,
+        palette.as_ref(),
+    )?;
+    let mut voxel_data = voxel_data.into_inner();
+
+    let (data_type, bits_per_element) = nifti_data_type(voxel_export_type);
+    if data_type != NIFTI_DATA_TYPE_RGB24 && voxel_export_type.components_per_voxel() > 1 {
+        voxel_data = deinterleave_components(
+            &voxel_data,
+            voxel_export_type.components_per_voxel(),
+            bits_per_element as usize / 8,
+        );
+    }
+
+
+    let mut file_contents = Vec::with_capacity(NIFTI_VOXEL_DATA_OFFSET + voxel_data.len());
+    file_contents.extend_from_slice(&header.bytes);
+    file_contents.extend_from_slice(&[0; NIFTI_VOXEL_DATA_OFFSET - NIFTI_HEADER_SIZE]);
+    file_contents.extend_from_slice(&voxel_data);
+
+    let buffered_file = create_buffered_output_file(nifti_file_path(output_base_path, gzip))?;
+
+    if gzip {
+        let mut encoder = GzEncoder::new(buffered_file, Compression::default());
+
+        encoder
+            .write_all(&file_contents)
+            .into_diagnostic()
+            .wrap_err("Failed to write NIfTI file.")?;
+
+        let buffered_file = encoder
+            .finish()
+            .into_diagnostic()
+            .wrap_err("Failed to finish gzip stream.")?;
+
+        finish_buffered_output_file(buffered_file)
+    } else {
+        let mut buffered_file = buffered_file;
+
+        buffered_file
+            .write_all(&file_contents)
+            .into_diagnostic()
+            .wrap_err("Failed to write NIfTI file.")?;
+
+        finish_buffered_output_file(buffered_file)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::voxelizer::voxel::VoxelData;
+
+    fn read_i16(header: &NiftiHeader, offset: usize) -> i16 {
+        i16::from_le_bytes(header.bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn read_f32(header: &NiftiHeader, offset: usize) -> f32 {
+        f32::from_le_bytes(header.bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_fields_are_at_their_offsets() {
+        let grid = VoxelGrid::from_voxels(
+            Vec3::new(-1.0, 0.0, 2.0),
+            0.25,
+            3,
+            2,
+            1,
+            vec![VoxelData::Empty; 6],
+        );
+
+        let header = nifti_header(&grid, &VoxelExportType::BinaryFillStateU1);
+
+        assert_eq!(
+            i32::from_le_bytes(header.bytes[0..4].try_into().unwrap()),
+            348
+        );
+        assert_eq!(
+            (0..8)
+                .map(|index| read_i16(&header, 40 + index * 2))
+                .collect::<Vec<_>>(),
+            [3, 3, 2, 1, 1, 1, 1, 1]
+        );
+        assert_eq!(read_i16(&header, 70), NIFTI_DATA_TYPE_UINT8);
+        assert_eq!(read_i16(&header, 72), 8);
+        assert_eq!(read_f32(&header, 80), 0.5);
+        assert_eq!(read_f32(&header, 108), 352.0);
+        assert_eq!(&header.bytes[148..162], b"binary-fill_u1");
+
+        // The first voxel center is mapped to the origin of the affine.
+        assert_eq!(read_f32(&header, 268), -0.75);
+        assert_eq!(read_f32(&header, 280), 0.5);
+        assert_eq!(read_f32(&header, 292), -0.75);
+        assert_eq!(read_f32(&header, 324), 2.25);
+
+        assert_eq!(&header.bytes[344..348], b"n+1\0");
+    }
+
+    #[test]
+    fn colors_are_rgb24_or_vector_valued() {
+        let grid = VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            2,
+            1,
+            1,
+            vec![VoxelData::Empty; 2],
+        );
+
+        let rgb8_header = nifti_header(&grid, &VoxelExportType::SrgbRgb8ColorU8);
+        assert_eq!(read_i16(&rgb8_header, 40), 3);
+        assert_eq!(read_i16(&rgb8_header, 70), NIFTI_DATA_TYPE_RGB24);
+        assert_eq!(read_i16(&rgb8_header, 72), 24);
+
+        let float_header = nifti_header(&grid, &VoxelExportType::LinearRgbColorF32);
+        assert_eq!(read_i16(&float_header, 40), 5);
+        assert_eq!(read_i16(&float_header, 50), 3);
+        assert_eq!(read_i16(&float_header, 68), NIFTI_INTENT_VECTOR);
+        assert_eq!(
+            read_i16(&float_header, 70),
+            NIFTI_DATA_TYPE_FLOAT32
+        );
+        assert_eq!(read_i16(&float_header, 72), 32);
+
+        assert_eq!(
+            deinterleave_components(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], 3, 2),
+            [1, 2, 7, 8, 3, 4, 9, 10, 5, 6, 11, 12]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! NRRD export (see <https://teem.sourceforge.net/nrrd/format.html>).
+//!
+//! Each export type is written as a 3D volume (or a 4D volume with a leading `RGB-color` axis
+//! for color exports) with the voxels in the same order as raw exports. Binary export types are
+//! stored as one `uint8` (0 or 1) per voxel, as NRRD has no bit type. The world-space layout
+//! is described by `space origin` (the center of the first voxel) and `space directions`
+//! (one voxel step along each axis).
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use miette::{Context, IntoDiagnostic, Result};
+
+use super::{
+    create_buffered_output_file,
+    finish_buffered_output_file,
+    prepare_palette,
+    write_voxel_grid_unpacked,
+    ComponentType,
+    VoxelExportType,
+};
+use crate::{processing::palette::Palette, voxelizer::grid::VoxelGrid};
+
+
+pub const NRRD_FILE_EXTENSION: &str = "nrrd";
+
+pub const NRRD_DETACHED_HEADER_FILE_EXTENSION: &str = "nhdr";
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NrrdOptions {
+    /// Writes the header into a separate `.nhdr` file next to the voxel data
+    /// instead of in front of it.
+    pub detached_header: bool,
+
+    /// Compresses the voxel data with gzip.
+    pub gzip: bool,
+}
+
+
+fn nrrd_type_name(component_type: ComponentType) -> &'static str {
+    match component_type {
+        ComponentType::U1 | ComponentType::U8 => "uint8",
+        ComponentType::U16 => "uint16",
+        ComponentType::U32 => "uint32",
+        ComponentType::F32 => "float",
+    }
+}
+
+
+/// Builds the NRRD header (including the empty line that terminates it).
+/// `data_file_name` is set for detached headers.
+fn nrrd_header(
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    options: &NrrdOptions,
+    data_file_name: Option<&str>,
+) -> String {
+    let dimensions = grid.dimensions();
+    let voxel_size = grid.voxel_size();
+    let first_voxel_center = grid.starting_point + grid.voxel_half_extent;
+
+    let is_multi_component = voxel_export_type.components_per_voxel() > 1;
+
+    let (dimension, sizes, space_directions, kinds) = if is_multi_component {
+        (
+            4,
+            format!(
+                "{} {} {} {}",
+                voxel_export_type.components_per_voxel(),
+                dimensions.x,
+                dimensions.y,
+                dimensions.z
+            ),
+            format!(
+                "none ({},0,0) (0,{},0) (0,0,{})",
+                voxel_size, voxel_size, voxel_size
+            ),
+            "RGB-color domain domain domain",
+        )
+    } else {
+        (
+            3,
+            format!(
+                "{} {} {}",
+                dimensions.x, dimensions.y, dimensions.z
+            ),
+            format!(
+                "({},0,0) (0,{},0) (0,0,{})",
+                voxel_size, voxel_size, voxel_size
+            ),
+            "domain domain domain",
+        )
+    };
+
+    let mut header = format!(
+        "NRRD0004\n\
+        # Complete NRRD file format specification at:\n\
+        # http://teem.sourceforge.net/nrrd/format.html\n\
+        content: {}\n\
+        type: {}\n\
+        dimension: {}\n\
+        space dimension: 3\n\
+        sizes: {}\n\
+        space directions: {}\n\
+        kinds: {}\n\
+        endian: little\n\
+        encoding: {}\n\
+        space origin: ({},{},{})\n",
+        voxel_export_type.name(),
+        nrrd_type_name(voxel_export_type.unpacked_component_type()),
+        dimension,
+        sizes,
+        space_directions,
+        kinds,
+        if options.gzip { "gzip" } else { "raw" },
+        first_voxel_center.x,
+        first_voxel_center.y,
+        first_voxel_center.z,
+    );
+
+    if let Some(color_space) = voxel_export_type.color_space() {
+        header.push_str(&format!("color space:={}\n", color_space.name()));
+    }
+
+    if let Some(data_file_name) = data_file_name {
+        header.push_str(&format!("data file: {}\n", data_file_name));
+    }
+
+    header.push('\n');
+
+    header
+}
+
+
+/// Writes the (optionally compressed) voxel data and returns the underlying writer.
+fn write_nrrd_data<W>(
+    writer: W,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    palette: Option<&Palette>,
+    gzip: bool,
+) -> Result<W>
+where
+    W: Write,
+{
+    if gzip {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+
+        write_voxel_grid_unpacked(&mut encoder, grid, voxel_export_type, palette)?;
+
+        encoder
+            .finish()
+            .into_diagnostic()
+            .wrap_err("Failed to finish gzip stream.")
+    } else {
+        let mut writer = writer;
+
+        write_voxel_grid_unpacked(&mut writer, grid, voxel_export_type, palette)?;
+
+        Ok(writer)
+    }
+}
+
+
+/// Path of the file holding the NRRD header, e.g. `model.m-0.nrrd` for `model.m-0`,
+/// or `model.m-0.nhdr` with a detached header.
+pub fn nrrd_file_path(output_base_path: &Path, options: &NrrdOptions) -> PathBuf {
+    let extension = if options.detached_header {
+        NRRD_DETACHED_HEADER_FILE_EXTENSION
+    } else {
+        NRRD_FILE_EXTENSION
+    };
+
+    let mut file_path = output_base_path.as_os_str().to_owned();
+    file_path.push(format!(".{}", extension));
+
+    PathBuf::from(file_path)
+}
+
+
+/// Writes a grid into an NRRD file (see [`nrrd_file_path`]). With a detached header,
+/// the voxel data is written to a `.raw` (or `.raw.gz`) file next to the header.
+pub fn export_voxel_grid_as_nrrd(
+    output_base_path: &Path,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    options: &NrrdOptions,
+) -> Result<()> {
+    let header_file_path = nrrd_file_path(output_base_path, options);
+
+    let palette = prepare_palette(&header_file_path, [grid], voxel_export_type)?;
+
+
+    if options.detached_header {
+        let data_file_path =
+            header_file_path.with_extension(if options.gzip { "raw.gz" } else { "raw" });
+
+        let data_file_name = data_file_path
+            .file_name()
+            .expect("expected the data file path to have a file name")
+            .to_string_lossy();
+
+        let mut buffered_header_file = create_buffered_output_file(&header_file_path)?;
+
+        buffered_header_file
+            .write_all(
+                nrrd_header(
+                    grid,
+                    voxel_export_type,
+                    options,
+                    Some(&data_file_name),
+                )
+                .as_bytes(),
+            )
+            .into_diagnostic()
+            .wrap_err("Failed to write NRRD header.")?;
+
+        finish_buffered_output_file(buffered_header_file)?;
+
+
+        let buffered_data_file = create_buffered_output_file(&data_file_path)?;
+
+        let buffered_data_file = write_nrrd_data(
+            buffered_data_file,
+            grid,
+            voxel_export_type,
+            palette.as_ref(),
+            options.gzip,
+        )?;
+
+        finish_buffered_output_file(buffered_data_file)
+    } else {
+        let mut buffered_file = create_buffered_output_file(&header_file_path)?;
+
+        buffered_file
+            .write_all(nrrd_header(grid, voxel_export_type, options, None).as_bytes())
+            .into_diagnostic()
+            .wrap_err("Failed to write NRRD header.")?;
+
+        let buffered_file = write_nrrd_data(
+            buffered_file,
+            grid,
+            voxel_export_type,
+            palette.as_ref(),
+            options.gzip,
+        )?;
+
+        finish_buffered_output_file(buffered_file)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::voxelizer::voxel::VoxelData;
+
+    #[test]
+    fn header_describes_the_grid_layout() {
+        let grid = VoxelGrid::from_voxels(
+            Vec3::new(-1.0, 0.0, 2.0),
+            0.25,
+            3,
+            2,
+            1,
+            vec![VoxelData::Empty; 6],
+        );
+
+        let attached_header = nrrd_header(
+            &grid,
+            &VoxelExportType::BinaryFillStateU1,
+            &NrrdOptions {
+                detached_header: false,
+                gzip: false,
+            },
+            None,
+        );
+
+        assert!(attached_header.starts_with("NRRD0004\n"));
+        assert!(attached_header.ends_with("\n\n"));
+        for line in [
+            "type: uint8",
+            "dimension: 3",
+            "sizes: 3 2 1",
+            "space directions: (0.5,0,0) (0,0.5,0) (0,0,0.5)",
+            "kinds: domain domain domain",
+            "encoding: raw",
+            "space origin: (-0.75,0.25,2.25)",
+        ] {
+            assert!(
+                attached_header
+                    .lines()
+                    .any(|header_line| header_line == line),
+                "missing header line \"{}\"",
+                line
+            );
+        }
+        assert!(!attached_header.contains("data file:"));
+
+
+        let detached_header = nrrd_header(
+            &grid,
+            &VoxelExportType::LinearRgbColorF32,
+            &NrrdOptions {
+                detached_header: true,
+                gzip: true,
+            },
+            Some("model.m-0.raw.gz"),
+        );
+
+        for line in [
+            "type: float",
+            "dimension: 4",
+            "sizes: 3 3 2 1",
+            "space directions: none (0.5,0,0) (0,0.5,0) (0,0,0.5)",
+            "kinds: RGB-color domain domain domain",
+            "encoding: gzip",
+            "data file: model.m-0.raw.gz",
+        ] {
+            assert!(
+                detached_header
+                    .lines()
+                    .any(|header_line| header_line == line),
+                "missing header line \"{}\"",
+                line
+            );
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Minecraft schematic export in the Sponge Schematic format, version 2
+//! (see <https://github.com/SpongePowered/Schematic-Specification>).
+//!
+//! The schematic is a gzip-compressed NBT file. Each `Edge` voxel becomes the block from
+//! the block palette whose color is nearest to the voxel color (compared in Oklab),
+//! optionally with `InsideMesh` voxels filled with an interior block, and all other voxels
+//! become air. Blocks are stored in `BlockData` as varint-encoded indices into the schematic's
+//! own `Palette`, ordered with x varying fastest, then z, then y. Both the grid and Minecraft
+//! are Y-up, so the axes are kept as they are.
+
+use std::{collections::HashMap, fs, io::Write, path::Path};
+
+use flate2::{write::GzEncoder, Compression};
+use glam::Vec3;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::{create_buffered_output_file, finish_buffered_output_file};
+use crate::{
+    processing::palette::{
+        map_grid_to_palette,
+        parse_srgb_color,
+        srgb8_to_linear_color,
+        Palette,
+        EMPTY_PALETTE_INDEX,
+        MAX_PALETTE_COLORS,
+    },
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+
+pub const SCHEMATIC_FILE_EXTENSION: &str = "schem";
+
+const SPONGE_SCHEMATIC_VERSION: i32 = 2;
+
+/// Minecraft data version of the block IDs in the default block palette (Minecraft 1.16.5).
+const MINECRAFT_DATA_VERSION: i32 = 2586;
+
+const AIR_BLOCK_ID: &str = "minecraft:air";
+
+/// The sixteen concrete blocks and their (approximate) sRGB colors.
+const DEFAULT_BLOCK_PALETTE: [(&str, [u8; 3]); 16] = [
+    ("minecraft:white_concrete", [207, 213, 214]),
+    ("minecraft:orange_concrete", [224, 97, 1]),
+    ("minecraft:magenta_concrete", [169, 48, 159]),
+    ("minecraft:light_blue_concrete", [36, 137, 199]),
+    ("minecraft:yellow_concrete", [241, 175, 21]),
+    ("minecraft:lime_concrete", [94, 169, 24]),
+    ("minecraft:pink_concrete", [213, 101, 143]),
+    ("minecraft:gray_concrete", [55, 58, 62]),
+    ("minecraft:light_gray_concrete", [125, 125, 115]),
+    ("minecraft:cyan_concrete", [21, 119, 136]),
+    ("minecraft:purple_concrete", [100, 32, 156]),
+    ("minecraft:blue_concrete", [45, 47, 143]),
+    ("minecraft:brown_concrete", [96, 60, 32]),
+    ("minecraft:green_concrete", [73, 91, 36]),
+    ("minecraft:red_concrete", [142, 33, 33]),
+    ("minecraft:black_concrete", [8, 10, 15]),
+];
+
+
+/// Prefixes block IDs without a namespace with `minecraft:`.
+fn namespaced_block_id(block_id: &str) -> String {
+    if block_id.contains(':') {
+        block_id.to_string()
+    } else {
+        format!("minecraft:{}", block_id)
+    }
+}
+
+
+/// Blocks that voxel colors are mapped to, each with a representative color.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockPalette {
+    block_ids: Vec<String>,
+    /// Linear RGB color of each block.
+    colors: Vec<Vec3>,
+}
+
+impl BlockPalette {
+    /// Loads a block palette from a text file containing one block per line: a block ID
+    /// (e.g. `minecraft:red_wool`), followed by its sRGB color, either as hex (`#rrggbb`)
+    /// or as three 0-255 components. Empty lines and lines starting with `//` are ignored.
+    pub fn load_from_file<P>(file_path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file_path = file_path.as_ref();
+
+        let contents = fs::read_to_string(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to read block palette file {}.",
+                    file_path.display()
+                )
+            })?;
+
+        let mut block_ids = Vec::new();
+        let mut colors = Vec::new();
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let (block_id, color) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| miette!("Expected a block ID followed by a color."))
+                .and_then(|(block_id, color)| Ok((block_id, parse_srgb_color(color.trim())?)))
+                .wrap_err_with(|| {
+                    miette!(
+                        "Invalid block on line {} of block palette file {}.",
+                        line_index + 1,
+                        file_path.display()
+                    )
+                })?;
+
+            block_ids.push(namespaced_block_id(block_id));
+            colors.push(color);
+        }
+
+        if colors.is_empty() {
+            return Err(miette!(
+                "Block palette file {} contains no blocks.",
+                file_path.display()
+            ));
+        }
+
+        if colors.len() > MAX_PALETTE_COLORS {
+            return Err(miette!(
+                "Block palette file {} contains {} blocks, but at most {} are supported.",
+                file_path.display(),
+                colors.len(),
+                MAX_PALETTE_COLORS
+            ));
+        }
+
+        Ok(Self { block_ids, colors })
+    }
+}
+
+impl Default for BlockPalette {
+    /// The sixteen colors of concrete.
+    fn default() -> Self {
+        Self {
+            block_ids: DEFAULT_BLOCK_PALETTE
+                .iter()
+                .map(|(block_id, _)| block_id.to_string())
+                .collect(),
+            colors: DEFAULT_BLOCK_PALETTE
+                .iter()
+                .map(|(_, srgb_color)| srgb8_to_linear_color(*srgb_color))
+                .collect(),
+        }
+    }
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SchematicOptions {
+    pub block_palette: BlockPalette,
+
+    /// If set, `InsideMesh` voxels are filled with this block (instead of air).
+    pub interior_block_id: Option<String>,
+
+    /// Whether to diffuse the color error of each block to neighbouring voxels.
+    pub dithering: bool,
+}
+
+
+/// Minimal big-endian NBT writer for named tags.
+struct NbtWriter {
+    bytes: Vec<u8>,
+}
+
+impl NbtWriter {
+    const TAG_END: u8 = 0;
+    const TAG_SHORT: u8 = 2;
+    const TAG_INT: u8 = 3;
+    const TAG_BYTE_ARRAY: u8 = 7;
+    const TAG_LIST: u8 = 9;
+    const TAG_COMPOUND: u8 = 10;
+    const TAG_INT_ARRAY: u8 = 11;
+
+    fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    fn tag_header(&mut self, tag_type: u8, name: &str) {
+        self.bytes.push(tag_type);
+        self.bytes
+            .extend_from_slice(&(name.len() as u16).to_be_bytes());
+        self.bytes.extend_from_slice(name.as_bytes());
+    }
+
+    fn begin_compound(&mut self, name: &str) {
+        self.tag_header(Self::TAG_COMPOUND, name);
+    }
+
+    fn end_compound(&mut self) {
+        self.bytes.push(Self::TAG_END);
+    }
+
+    fn short(&mut self, name: &str, value: i16) {
+        self.tag_header(Self::TAG_SHORT, name);
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn int(&mut self, name: &str, value: i32) {
+        self.tag_header(Self::TAG_INT, name);
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn byte_array(&mut self, name: &str, values: &[u8]) {
+        self.tag_header(Self::TAG_BYTE_ARRAY, name);
+        self.bytes
+            .extend_from_slice(&(values.len() as i32).to_be_bytes());
+        self.bytes.extend_from_slice(values);
+    }
+
+    fn int_array(&mut self, name: &str, values: &[i32]) {
+        self.tag_header(Self::TAG_INT_ARRAY, name);
+        self.bytes
+            .extend_from_slice(&(values.len() as i32).to_be_bytes());
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn empty_compound_list(&mut self, name: &str) {
+        self.tag_header(Self::TAG_LIST, name);
+        self.bytes.push(Self::TAG_COMPOUND);
+        self.bytes.extend_from_slice(&0i32.to_be_bytes());
+    }
+}
+
+
+/// Appends an unsigned LEB128 varint (seven bits per byte, least significant group first).
+fn write_varint(buffer: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buffer.push(byte);
+            return;
+        }
+
+        buffer.push(byte | 0x80);
+    }
+}
+
+
+/// Writes a grid into a gzip-compressed Sponge schematic (`.schem`).
+pub fn export_voxel_grid_as_schematic<P>(
+    output_file_path: P,
+    grid: &VoxelGrid,
+    options: &SchematicOptions,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let dimensions = grid.dimensions();
+    if dimensions.max_element() > u16::MAX as u64 {
+        return Err(miette!(
+            "Schematics support at most {} blocks along each axis, but the grid has dimensions {}.",
+            u16::MAX,
+            dimensions
+        ));
+    }
+
+    let color_palette = Palette::from_colors(options.block_palette.colors.clone());
+    let palette_indices = map_grid_to_palette(grid, &color_palette, options.dithering);
+
+    let interior_block_id = options
+        .interior_block_id
+        .as_deref()
+        .map(namespaced_block_id);
+
+
+    // Schematic palette indices are assigned in order of first use, starting with air.
+    let mut schematic_palette: HashMap<&str, u32> = HashMap::new();
+    schematic_palette.insert(AIR_BLOCK_ID, 0);
+
+    let mut block_data = Vec::with_capacity(grid.voxels().len());
+
+    for y in 0..grid.y_length() {
+        for z in 0..grid.z_length() {
+            for x in 0..grid.x_length() {
+                let flat_index = grid.flat_index(x, y, z);
+
+                let block_id = match &grid.voxels()[flat_index] {
+                    VoxelData::Edge { .. } if palette_indices[flat_index] != EMPTY_PALETTE_INDEX => {
+                        options.block_palette.block_ids[palette_indices[flat_index] as usize - 1]
+                            .as_str()
+                    }
+                    VoxelData::InsideMesh if interior_block_id.is_some() => {
+                        interior_block_id.as_deref().unwrap()
+                    }
+                    _ => AIR_BLOCK_ID,
+                };
+
+                let next_index = schematic_palette.len() as u32;
+                let schematic_index = *schematic_palette.entry(block_id).or_insert(next_index);
+
+                write_varint(&mut block_data, schematic_index);
+            }
+        }
+    }
+
+
+    let mut sorted_schematic_palette = schematic_palette.iter().collect::<Vec<_>>();
+    sorted_schematic_palette.sort_by_key(|(_, index)| **index);
+
+    let mut nbt = NbtWriter::new();
+
+    nbt.begin_compound("Schematic");
+    nbt.int("Version", SPONGE_SCHEMATIC_VERSION);
+    nbt.int("DataVersion", MINECRAFT_DATA_VERSION);
+    // Sizes are unsigned shorts, stored in (signed) short tags.
+    nbt.short("Width", dimensions.x as u16 as i16);
+    nbt.short("Height", dimensions.y as u16 as i16);
+    nbt.short("Length", dimensions.z as u16 as i16);
+    nbt.int_array("Offset", &[0, 0, 0]);
+    nbt.int("PaletteMax", schematic_palette.len() as i32);
+
+    nbt.begin_compound("Palette");
+    for (block_id, index) in sorted_schematic_palette {
+        nbt.int(block_id, *index as i32);
+    }
+    nbt.end_compound();
+
+    nbt.byte_array("BlockData", &block_data);
+    nbt.empty_compound_list("BlockEntities");
+    nbt.end_compound();
+
+
+    let buffered_file = create_buffered_output_file(output_file_path)?;
+    let mut encoder = GzEncoder::new(buffered_file, Compression::default());
+
+    encoder
+        .write_all(&nbt.bytes)
+        .into_diagnostic()
+        .wrap_err("Failed to write schematic.")?;
+
+    let buffered_file = encoder
+        .finish()
+        .into_diagnostic()
+        .wrap_err("Failed to finish gzip stream.")?;
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varints_use_seven_bits_per_byte() {
+        let encode = |value: u32| {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value);
+            buffer
+        };
+
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(1), [0x01]);
+        assert_eq!(encode(127), [0x7f]);
+        assert_eq!(encode(128), [0x80, 0x01]);
+        assert_eq!(encode(300), [0xac, 0x02]);
+        assert_eq!(encode(16_384), [0x80, 0x80, 0x01]);
+        assert_eq!(encode(u32::MAX), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+}
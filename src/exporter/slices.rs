@@ -0,0 +1,526 @@
+//! Export of a grid as a stack of PNG images, one per slice along the chosen axis
+//! (`<base>.<axis>-<slice>.png`, with the slice numbers zero-padded to equal width),
+//! optionally accompanied by a contact sheet that tiles all slices into a single image.
+//!
+//! Each image shows the values of a single export type: binary export types are drawn
+//! in grayscale (white for set voxels), 8-bit color export types as 8-bit RGB and
+//! `linear-rgb16-color_u16` as 16-bit RGB, storing the same values as the raw export.
+//! Scalar export types (metallic, roughness, intensity and surface distance) are drawn
+//! with a colormap; surface distances are divided by the largest distance in the grid first.
+//! Empty voxels are black, or transparent if an alpha channel is requested.
+//!
+//! The grid is Y-up (like glTF), so slices along y are viewed from above (+x to the right,
+//! -z up), slices along z from the front (+x to the right, +y up) and slices along x
+//! from the side (+z to the right, +y up).
+
+use std::{fmt::Display, io::Cursor, path::Path, str::FromStr};
+
+use image::{ColorType, DynamicImage, GenericImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+use miette::{miette, Context, IntoDiagnostic, Report, Result};
+
+use super::{write_voxel_grid_unpacked, VoxelExportType};
+use crate::voxelizer::grid::VoxelGrid;
+
+
+pub const PNG_FILE_EXTENSION: &str = "png";
+
+
+/// Grid axis that the slices are taken along.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SliceAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl FromStr for SliceAxis {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "x" => Ok(Self::X),
+            "y" => Ok(Self::Y),
+            "z" => Ok(Self::Z),
+            _ => Err(miette!(
+                "Invalid slice axis \"{}\", must be one of: x, y, z.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SliceAxis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SliceAxis::X => write!(f, "x"),
+            SliceAxis::Y => write!(f, "y"),
+            SliceAxis::Z => write!(f, "z"),
+        }
+    }
+}
+
+impl SliceAxis {
+    /// Index of the slice axis, followed by the grid axes along the image columns and rows.
+    fn axes(&self) -> [usize; 3] {
+        match self {
+            SliceAxis::X => [0, 2, 1],
+            SliceAxis::Y => [1, 0, 2],
+            SliceAxis::Z => [2, 0, 1],
+        }
+    }
+
+    /// Whether the row axis points up in the image (it points down, towards the viewer,
+    /// for the top-down view of slices along y).
+    fn row_axis_points_up(&self) -> bool {
+        !matches!(self, SliceAxis::Y)
+    }
+}
+
+
+/// Colormap that scalar export types are drawn with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Colormap {
+    Grayscale,
+    Viridis,
+    Inferno,
+}
+
+impl FromStr for Colormap {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "grayscale" => Ok(Self::Grayscale),
+            "viridis" => Ok(Self::Viridis),
+            "inferno" => Ok(Self::Inferno),
+            _ => Err(miette!(
+                "Invalid colormap \"{}\", must be one of: grayscale, viridis, inferno.",
+                s
+            )),
+        }
+    }
+}
+
+/// Ten evenly spaced samples of matplotlib's viridis colormap.
+const VIRIDIS_STOPS: [[u8; 3]; 10] = [
+    [0x44, 0x01, 0x54],
+    [0x48, 0x28, 0x78],
+    [0x3e, 0x4a, 0x89],
+    [0x31, 0x68, 0x8e],
+    [0x26, 0x82, 0x8e],
+    [0x1f, 0x9e, 0x89],
+    [0x35, 0xb7, 0x79],
+    [0x6e, 0xce, 0x58],
+    [0xb5, 0xde, 0x2b],
+    [0xfd, 0xe7, 0x25],
+];
+
+/// Ten evenly spaced samples of matplotlib's inferno colormap.
+const INFERNO_STOPS: [[u8; 3]; 10] = [
+    [0x00, 0x00, 0x04],
+    [0x1b, 0x0c, 0x41],
+    [0x4a, 0x0c, 0x6b],
+    [0x78, 0x1c, 0x6d],
+    [0xa5, 0x2c, 0x60],
+    [0xcf, 0x44, 0x46],
+    [0xed, 0x69, 0x25],
+    [0xfb, 0x9b, 0x06],
+    [0xf7, 0xd1, 0x3d],
+    [0xfc, 0xff, 0xa4],
+];
+
+impl Colormap {
+    /// Maps a value in `[0, 1]` to an sRGB color.
+    fn map(&self, value: f32) -> [u8; 3] {
+        let value = value.clamp(0.0, 1.0);
+
+        let stops = match self {
+            Colormap::Grayscale => {
+                let gray = (value * u8::MAX as f32).round() as u8;
+                return [gray; 3];
+            }
+            Colormap::Viridis => &VIRIDIS_STOPS,
+            Colormap::Inferno => &INFERNO_STOPS,
+        };
+
+        let position = value * (stops.len() - 1) as f32;
+        let lower_stop = (position.floor() as usize).min(stops.len() - 2);
+        let t = position - lower_stop as f32;
+
+        [0, 1, 2].map(|component| {
+            let lower = stops[lower_stop][component] as f32;
+            let upper = stops[lower_stop + 1][component] as f32;
+
+            (lower + (upper - lower) * t).round() as u8
+        })
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SliceOptions {
+    pub axis: SliceAxis,
+
+    /// Colormap for scalar export types.
+    pub colormap: Colormap,
+
+    /// Adds an alpha channel, with empty voxels fully transparent.
+    pub alpha: bool,
+
+    /// If set, all slices are additionally tiled into a single contact sheet image.
+    pub contact_sheet: bool,
+
+    /// Number of slices per contact sheet row. Defaults to a roughly square sheet.
+    pub contact_sheet_columns: Option<u32>,
+}
+
+
+/// Per-voxel pixel values of a single export type, as RGBA with 16 bits per component
+/// (8-bit values are scaled by 257, so they convert back to 8 bits exactly).
+struct VoxelPixels {
+    color_type: ColorType,
+    pixels: Vec<[u16; 4]>,
+}
+
+impl VoxelPixels {
+    fn from_grid(
+        grid: &VoxelGrid,
+        voxel_export_type: &VoxelExportType,
+        options: &SliceOptions,
+    ) -> Result<Self> {
+        let mut voxel_data = Cursor::new(Vec::new());
+
+        match voxel_export_type {
+            VoxelExportType::BinaryEdgeStateU1
+            | VoxelExportType::BinaryFillStateU1
+            | VoxelExportType::LinearRgb8ColorU8
+            | VoxelExportType::SrgbRgb8ColorU8
+            | VoxelExportType::LinearRgb16ColorU16
+            | VoxelExportType::MetallicValueU8
+            | VoxelExportType::RoughnessValueU8
+            | VoxelExportType::IntensityValueU8
+            | VoxelExportType::SurfaceDistanceF32 => {
+                write_voxel_grid_unpacked(&mut voxel_data, grid, voxel_export_type, None)?;
+            }
+            VoxelExportType::LinearRgbColorF32
+            | VoxelExportType::ComponentLabelU32 { .. }
+            | VoxelExportType::PaletteIndexU8 { .. } => {
+                return Err(miette!(
+                    "The {} export type can not be exported as PNG slices.",
+                    voxel_export_type.name()
+                ));
+            }
+        }
+
+        let voxel_data = voxel_data.into_inner();
+
+        let from_u8 = |value: u8| value as u16 * 257;
+
+        let is_scalar = matches!(
+            voxel_export_type,
+            VoxelExportType::MetallicValueU8
+                | VoxelExportType::RoughnessValueU8
+                | VoxelExportType::IntensityValueU8
+                | VoxelExportType::SurfaceDistanceF32
+        );
+
+        let rgb_pixels: Vec<[u16; 3]> = match voxel_export_type {
+            VoxelExportType::BinaryEdgeStateU1 | VoxelExportType::BinaryFillStateU1 => voxel_data
+                .iter()
+                .map(|value| [from_u8(*value * u8::MAX); 3])
+                .collect(),
+            VoxelExportType::LinearRgb8ColorU8 | VoxelExportType::SrgbRgb8ColorU8 => voxel_data
+                .chunks_exact(3)
+                .map(|color| [from_u8(color[0]), from_u8(color[1]), from_u8(color[2])])
+                .collect(),
+            VoxelExportType::LinearRgb16ColorU16 => voxel_data
+                .chunks_exact(6)
+                .map(|color| {
+                    [0, 1, 2].map(|component| {
+                        u16::from_le_bytes([color[component * 2], color[component * 2 + 1]])
+                    })
+                })
+                .collect(),
+            VoxelExportType::SurfaceDistanceF32 => {
+                let distances = voxel_data
+                    .chunks_exact(4)
+                    .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                    .collect::<Vec<_>>();
+
+                let max_distance = distances.iter().copied().fold(0.0, f32::max);
+
+                distances
+                    .iter()
+                    .map(|distance| {
+                        let value = if max_distance > 0.0 {
+                            distance / max_distance
+                        } else {
+                            0.0
+                        };
+
+                        options.colormap.map(value).map(from_u8)
+                    })
+                    .collect()
+            }
+            _ => voxel_data
+                .iter()
+                .map(|value| {
+                    options
+                        .colormap
+                        .map(*value as f32 / u8::MAX as f32)
+                        .map(from_u8)
+                })
+                .collect(),
+        };
+
+        let pixels = rgb_pixels
+            .into_iter()
+            .zip(grid.voxels())
+            .map(|([red, green, blue], voxel)| {
+                if voxel.is_occupied() {
+                    [red, green, blue, u16::MAX]
+                } else {
+                    [0; 4]
+                }
+            })
+            .collect();
+
+        let is_gray = matches!(
+            voxel_export_type,
+            VoxelExportType::BinaryEdgeStateU1 | VoxelExportType::BinaryFillStateU1
+        ) || (is_scalar && options.colormap == Colormap::Grayscale);
+
+        let color_type = match (
+            is_gray,
+            matches!(
+                voxel_export_type,
+                VoxelExportType::LinearRgb16ColorU16
+            ),
+            options.alpha,
+        ) {
+            (true, _, false) => ColorType::L8,
+            (true, _, true) => ColorType::La8,
+            (false, false, false) => ColorType::Rgb8,
+            (false, false, true) => ColorType::Rgba8,
+            (false, true, false) => ColorType::Rgb16,
+            (false, true, true) => ColorType::Rgba16,
+        };
+
+        Ok(Self { color_type, pixels })
+    }
+
+    /// Renders a single slice (see the module documentation for its orientation).
+    fn render_slice(&self, grid: &VoxelGrid, axis: SliceAxis, slice: u64) -> DynamicImage {
+        let [slice_axis, column_axis, row_axis] = axis.axes();
+        let dimensions = grid.dimensions().to_array();
+
+        let width = dimensions[column_axis] as u32;
+        let height = dimensions[row_axis] as u32;
+
+        let pixel_at = |column: u32, row: u32| {
+            let mut grid_index = [0u64; 3];
+            grid_index[slice_axis] = slice;
+            grid_index[column_axis] = column as u64;
+            grid_index[row_axis] = if axis.row_axis_points_up() {
+                (height - 1 - row) as u64
+            } else {
+                row as u64
+            };
+
+            self.pixels[grid.flat_index(grid_index[0], grid_index[1], grid_index[2])]
+        };
+
+        let to_u8 = |value: u16| (value >> 8) as u8;
+
+        match self.color_type {
+            ColorType::L8 => DynamicImage::ImageLuma8(ImageBuffer::from_fn(
+                width,
+                height,
+                |column, row| Luma([to_u8(pixel_at(column, row)[0])]),
+            )),
+            ColorType::La8 => DynamicImage::ImageLumaA8(ImageBuffer::from_fn(
+                width,
+                height,
+                |column, row| {
+                    let [gray, _, _, alpha] = pixel_at(column, row);
+                    LumaA([to_u8(gray), to_u8(alpha)])
+                },
+            )),
+            ColorType::Rgb8 => DynamicImage::ImageRgb8(ImageBuffer::from_fn(
+                width,
+                height,
+                |column, row| {
+                    let [red, green, blue, _] = pixel_at(column, row);
+                    Rgb([to_u8(red), to_u8(green), to_u8(blue)])
+                },
+            )),
+            ColorType::Rgba8 => DynamicImage::ImageRgba8(ImageBuffer::from_fn(
+                width,
+                height,
+                |column, row| Rgba(pixel_at(column, row).map(to_u8)),
+            )),
+            ColorType::Rgb16 => DynamicImage::ImageRgb16(ImageBuffer::from_fn(
+                width,
+                height,
+                |column, row| {
+                    let [red, green, blue, _] = pixel_at(column, row);
+                    Rgb([red, green, blue])
+                },
+            )),
+            _ => DynamicImage::ImageRgba16(ImageBuffer::from_fn(
+                width,
+                height,
+                |column, row| Rgba(pixel_at(column, row)),
+            )),
+        }
+    }
+}
+
+
+fn save_png(image: &DynamicImage, file_path: &Path) -> Result<()> {
+    image
+        .save_with_format(file_path, image::ImageFormat::Png)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to save {}.", file_path.display()))
+}
+
+
+/// Writes one PNG per slice of the grid along [`SliceOptions::axis`], named
+/// `<output_base_path>.<axis>-<slice>.png`, and (if enabled) a contact sheet named
+/// `<output_base_path>.<axis>-sheet.png`.
+pub fn export_voxel_grid_as_png_slices(
+    output_base_path: &Path,
+    grid: &VoxelGrid,
+    voxel_export_type: &VoxelExportType,
+    options: &SliceOptions,
+) -> Result<()> {
+    let voxel_pixels = VoxelPixels::from_grid(grid, voxel_export_type, options)?;
+
+    let [slice_axis, _, _] = options.axis.axes();
+    let slice_count = grid.dimensions().to_array()[slice_axis];
+    let slice_number_width = slice_count.saturating_sub(1).max(1).ilog10() as usize + 1;
+
+    let output_base_name = output_base_path.as_os_str().to_string_lossy();
+
+
+    let mut contact_sheet: Option<DynamicImage> = None;
+
+    let contact_sheet_columns = options
+        .contact_sheet_columns
+        .unwrap_or_else(|| (slice_count as f64).sqrt().ceil() as u32)
+        .clamp(1, slice_count as u32);
+    let contact_sheet_rows = (slice_count as u32).div_ceil(contact_sheet_columns);
+
+    for slice in 0..slice_count {
+        let slice_image = voxel_pixels.render_slice(grid, options.axis, slice);
+
+        let slice_file_path = format!(
+            "{}.{}-{:0width$}.{}",
+            output_base_name,
+            options.axis,
+            slice,
+            PNG_FILE_EXTENSION,
+            width = slice_number_width
+        );
+
+        save_png(&slice_image, Path::new(&slice_file_path))?;
+
+
+        if options.contact_sheet {
+            let sheet = contact_sheet.get_or_insert_with(|| {
+                DynamicImage::new(
+                    slice_image.width() * contact_sheet_columns,
+                    slice_image.height() * contact_sheet_rows,
+                    voxel_pixels.color_type,
+                )
+            });
+
+            let tile_column = slice as u32 % contact_sheet_columns;
+            let tile_row = slice as u32 / contact_sheet_columns;
+
+            sheet
+                .copy_from(
+                    &slice_image,
+                    tile_column * slice_image.width(),
+                    tile_row * slice_image.height(),
+                )
+                .into_diagnostic()
+                .wrap_err("Failed to copy slice into the contact sheet.")?;
+        }
+    }
+
+
+    if let Some(contact_sheet) = contact_sheet {
+        let contact_sheet_file_path = format!(
+            "{}.{}-sheet.{}",
+            output_base_name, options.axis, PNG_FILE_EXTENSION
+        );
+
+        save_png(
+            &contact_sheet,
+            Path::new(&contact_sheet_file_path),
+        )?;
+    }
+
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::voxelizer::voxel::VoxelData;
+
+    /// Positions of the white pixels of a grayscale slice.
+    fn white_pixels(image: &DynamicImage) -> Vec<(u32, u32)> {
+        let image = image.as_luma8().unwrap();
+
+        image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel.0[0] == u8::MAX)
+            .map(|(column, row, _)| (column, row))
+            .collect()
+    }
+
+    #[test]
+    fn slices_are_oriented_for_the_y_up_grid() {
+        // A single occupied voxel at the lowest y and z of the +x column.
+        let mut voxels = vec![VoxelData::Empty; 2 * 3 * 4];
+        voxels[1] = VoxelData::new_edge_with_default_material();
+        let grid = VoxelGrid::from_voxels(Vec3::ZERO, 0.5, 2, 3, 4, voxels);
+
+        let options = SliceOptions {
+            axis: SliceAxis::Y,
+            colormap: Colormap::Grayscale,
+            alpha: false,
+            contact_sheet: false,
+            contact_sheet_columns: None,
+        };
+        let voxel_pixels = VoxelPixels::from_grid(
+            &grid,
+            &VoxelExportType::BinaryEdgeStateU1,
+            &options,
+        )
+        .unwrap();
+
+        // Viewed from above: +x to the right, -z up (so z = 0 is the top row).
+        let top_view = voxel_pixels.render_slice(&grid, SliceAxis::Y, 0);
+        assert_eq!((top_view.width(), top_view.height()), (2, 4));
+        assert_eq!(white_pixels(&top_view), [(1, 0)]);
+
+        // Viewed from the front: +x to the right, +y up.
+        let front_view = voxel_pixels.render_slice(&grid, SliceAxis::Z, 0);
+        assert_eq!((front_view.width(), front_view.height()), (2, 3));
+        assert_eq!(white_pixels(&front_view), [(1, 2)]);
+
+        // Viewed from the side: +z to the right, +y up.
+        let side_view = voxel_pixels.render_slice(&grid, SliceAxis::X, 1);
+        assert_eq!((side_view.width(), side_view.height()), (4, 3));
+        assert_eq!(white_pixels(&side_view), [(0, 2)]);
+
+        assert!(white_pixels(&voxel_pixels.render_slice(&grid, SliceAxis::X, 0)).is_empty());
+    }
+}
@@ -0,0 +1,659 @@
+//! Export of extracted surfaces ([`SurfaceMesh`]es) as glTF (`.gltf` with a `.bin` buffer),
+//! Wavefront OBJ (`.obj`) or binary STL (`.stl`) files.
+//!
+//! Colors are either stored per vertex (glTF `COLOR_0` in linear RGB, or the common OBJ
+//! `v x y z r g b` extension in sRGB, matching how OBJ vertex colors are imported) or baked
+//! into a small palette texture (`.palette.png`, one texel per distinct color) that each vertex
+//! samples at the center of its texel, which requires flat-colored faces (as greedy meshes have).
+//! The origin and voxel size of the source grid are recorded in the glTF `extras`, in an OBJ
+//! comment and in the STL header; the vertices themselves are in world space, so the mesh overlays
+//! the source scene.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::Write,
+    path::Path,
+    str::FromStr,
+};
+
+use glam::{Vec2, Vec3};
+use gltf::json::{self, extras::RawValue, mesh::Semantic, validation::Checked::Valid};
+use image::{Rgb, RgbImage};
+use miette::{miette, Context, IntoDiagnostic, Report, Result};
+
+use super::{create_buffered_output_file, finish_buffered_output_file};
+use crate::{
+    mesh::{linear_component_to_srgb, DEFAULT_METALLIC, DEFAULT_ROUGHNESS},
+    processing::palette::linear_color_to_srgb8,
+    surface_extraction::SurfaceMesh,
+};
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SurfaceMeshFormat {
+    Gltf,
+    Obj,
+    /// Binary STL, which has no colors.
+    Stl,
+}
+
+impl FromStr for SurfaceMeshFormat {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gltf" => Ok(Self::Gltf),
+            "obj" => Ok(Self::Obj),
+            "stl" => Ok(Self::Stl),
+            _ => Err(miette!(
+                "Invalid mesh format \"{}\", must be one of: gltf, obj, stl.",
+                s
+            )),
+        }
+    }
+}
+
+
+/// How vertex colors are stored in exported surface meshes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SurfaceColorMode {
+    VertexColors,
+    PaletteTexture,
+}
+
+impl FromStr for SurfaceColorMode {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vertex" => Ok(Self::VertexColors),
+            "palette-texture" => Ok(Self::PaletteTexture),
+            _ => Err(miette!(
+                "Invalid mesh color mode \"{}\", must be one of: vertex, palette-texture.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SurfaceColorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SurfaceColorMode::VertexColors => write!(f, "vertex"),
+            SurfaceColorMode::PaletteTexture => write!(f, "palette-texture"),
+        }
+    }
+}
+
+
+/// A texture with one texel per distinct (8-bit sRGB) vertex color, along with the texture
+/// coordinates of each vertex (`(0, 0)` being the top-left corner, as in glTF).
+struct PaletteTexture {
+    image: RgbImage,
+    tex_coords: Vec<Vec2>,
+}
+
+fn bake_palette_texture(mesh: &SurfaceMesh) -> PaletteTexture {
+    let mut texel_indices: HashMap<[u8; 3], u32> = HashMap::new();
+    let mut texel_colors = Vec::new();
+
+    let vertex_texel_indices = mesh
+        .colors
+        .iter()
+        .map(|color| {
+            let srgb_color = linear_color_to_srgb8(*color);
+
+            *texel_indices.entry(srgb_color).or_insert_with(|| {
+                texel_colors.push(srgb_color);
+                (texel_colors.len() - 1) as u32
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // A square texture keeps the size reasonable even with many distinct colors.
+    let side_length = (texel_colors.len() as f64).sqrt().ceil().max(1.0) as u32;
+
+    let mut image = RgbImage::new(side_length, side_length);
+    for (texel_index, srgb_color) in texel_colors.into_iter().enumerate() {
+        let texel_index = texel_index as u32;
+
+        image.put_pixel(
+            texel_index % side_length,
+            texel_index / side_length,
+            Rgb(srgb_color),
+        );
+    }
+
+    let tex_coords = vertex_texel_indices
+        .into_iter()
+        .map(|texel_index| {
+            Vec2::new(
+                (texel_index % side_length) as f32 + 0.5,
+                (texel_index / side_length) as f32 + 0.5,
+            ) / side_length as f32
+        })
+        .collect();
+
+    PaletteTexture { image, tex_coords }
+}
+
+
+fn file_path_with_suffix(output_base_path: &Path, suffix: &str) -> std::path::PathBuf {
+    let mut file_path = output_base_path.as_os_str().to_owned();
+    file_path.push(suffix);
+
+    file_path.into()
+}
+
+fn file_name_of(file_path: &Path) -> String {
+    file_path
+        .file_name()
+        .expect("expected the output file path to have a file name")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Percent-encodes a file name for use as a relative glTF URI (RFC 3986),
+/// leaving only unreserved characters as they are.
+fn percent_encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The origin and voxel size of the source grid, stored in the glTF `extras`.
+fn grid_extras(mesh: &SurfaceMesh) -> Result<json::Extras> {
+    let extras = json::Value::Object(
+        [
+            (
+                "gridOrigin".to_string(),
+                json::Value::from(mesh.grid_origin.to_array().to_vec()),
+            ),
+            (
+                "voxelSize".to_string(),
+                json::Value::from(mesh.voxel_size),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    json::serialize::to_string(&extras)
+        .and_then(RawValue::from_string)
+        .map(Some)
+        .into_diagnostic()
+        .wrap_err("Failed to serialize glTF extras.")
+}
+
+
+fn save_palette_texture(palette_texture: &PaletteTexture, file_path: &Path) -> Result<()> {
+    palette_texture
+        .image
+        .save(file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to save palette texture to {}.",
+                file_path.display()
+            )
+        })
+}
+
+
+fn write_file(file_path: &Path, contents: &[u8]) -> Result<()> {
+    let mut buffered_file = create_buffered_output_file(file_path)?;
+
+    buffered_file
+        .write_all(contents)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to write {}.", file_path.display()))?;
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+fn export_surface_mesh_as_gltf(
+    output_base_path: &Path,
+    mesh: &SurfaceMesh,
+    color_mode: SurfaceColorMode,
+) -> Result<()> {
+    let gltf_file_path = file_path_with_suffix(output_base_path, ".gltf");
+    let buffer_file_path = file_path_with_suffix(output_base_path, ".bin");
+    let texture_file_path = file_path_with_suffix(output_base_path, ".palette.png");
+
+    let palette_texture =
+        (color_mode == SurfaceColorMode::PaletteTexture).then(|| bake_palette_texture(mesh));
+
+    let mut root = json::Root {
+        asset: json::Asset {
+            generator: Some("nrg-mesh-to-volume-conversion".to_string()),
+            extras: grid_extras(mesh)?,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+
+    // Buffer layout: positions, normals, colors or texture coordinates, indices
+    // (all components are four bytes long, so every view stays aligned).
+    let mut buffer = Vec::new();
+
+    let mut push_accessor = |data: Vec<u8>,
+                             target: json::buffer::Target,
+                             component_type: json::accessor::ComponentType,
+                             type_: json::accessor::Type,
+                             count: usize,
+                             bounds: Option<(Vec3, Vec3)>| {
+        let view_index = json::Index::new(root.buffer_views.len() as u32);
+        root.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: data.len().into(),
+            byte_offset: Some(buffer.len().into()),
+            byte_stride: None,
+            name: None,
+            target: Some(Valid(target)),
+            extensions: None,
+            extras: None,
+        });
+        buffer.extend_from_slice(&data);
+
+        let accessor_index = json::Index::new(root.accessors.len() as u32);
+        root.accessors.push(json::Accessor {
+            buffer_view: Some(view_index),
+            byte_offset: None,
+            count: count.into(),
+            component_type: Valid(json::accessor::GenericComponentType(
+                component_type,
+            )),
+            extensions: None,
+            extras: None,
+            type_: Valid(type_),
+            min: bounds.map(|(min, _)| json::Value::from(min.to_array().to_vec())),
+            max: bounds.map(|(_, max)| json::Value::from(max.to_array().to_vec())),
+            name: None,
+            normalized: false,
+            sparse: None,
+        });
+
+        accessor_index
+    };
+
+    let vec3_bytes = |values: &[Vec3]| -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_array())
+            .flat_map(f32::to_le_bytes)
+            .collect()
+    };
+
+    let position_min = mesh
+        .positions
+        .iter()
+        .fold(Vec3::INFINITY, |min, position| min.min(*position));
+    let position_max = mesh
+        .positions
+        .iter()
+        .fold(Vec3::NEG_INFINITY, |max, position| {
+            max.max(*position)
+        });
+
+    let vertex_count = mesh.vertex_count();
+
+    let mut attributes = BTreeMap::new();
+
+    attributes.insert(
+        Valid(Semantic::Positions),
+        push_accessor(
+            vec3_bytes(&mesh.positions),
+            json::buffer::Target::ArrayBuffer,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec3,
+            vertex_count,
+            Some((position_min, position_max)),
+        ),
+    );
+    attributes.insert(
+        Valid(Semantic::Normals),
+        push_accessor(
+            vec3_bytes(&mesh.normals),
+            json::buffer::Target::ArrayBuffer,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec3,
+            vertex_count,
+            None,
+        ),
+    );
+
+    match &palette_texture {
+        Some(palette_texture) => attributes.insert(
+            Valid(Semantic::TexCoords(0)),
+            push_accessor(
+                palette_texture
+                    .tex_coords
+                    .iter()
+                    .flat_map(|tex_coord| tex_coord.to_array())
+                    .flat_map(f32::to_le_bytes)
+                    .collect(),
+                json::buffer::Target::ArrayBuffer,
+                json::accessor::ComponentType::F32,
+                json::accessor::Type::Vec2,
+                vertex_count,
+                None,
+            ),
+        ),
+        None => attributes.insert(
+            Valid(Semantic::Colors(0)),
+            push_accessor(
+                vec3_bytes(&mesh.colors),
+                json::buffer::Target::ArrayBuffer,
+                json::accessor::ComponentType::F32,
+                json::accessor::Type::Vec3,
+                vertex_count,
+                None,
+            ),
+        ),
+    };
+
+    let indices = push_accessor(
+        mesh.triangle_indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect(),
+        json::buffer::Target::ElementArrayBuffer,
+        json::accessor::ComponentType::U32,
+        json::accessor::Type::Scalar,
+        mesh.triangle_indices.len(),
+        None,
+    );
+
+    root.buffers.push(json::Buffer {
+        byte_length: buffer.len().into(),
+        name: None,
+        uri: Some(percent_encode_uri_component(&file_name_of(
+            &buffer_file_path,
+        ))),
+        extensions: None,
+        extras: None,
+    });
+
+
+    let base_color_texture = palette_texture.as_ref().map(|_| {
+        // Nearest filtering keeps each vertex on the exact color of its texel.
+        root.samplers.push(json::texture::Sampler {
+            mag_filter: Some(Valid(json::texture::MagFilter::Nearest)),
+            min_filter: Some(Valid(json::texture::MinFilter::Nearest)),
+            wrap_s: Valid(json::texture::WrappingMode::ClampToEdge),
+            wrap_t: Valid(json::texture::WrappingMode::ClampToEdge),
+            ..Default::default()
+        });
+        root.images.push(json::Image {
+            buffer_view: None,
+            mime_type: None,
+            name: None,
+            uri: Some(percent_encode_uri_component(&file_name_of(
+                &texture_file_path,
+            ))),
+            extensions: None,
+            extras: None,
+        });
+        root.textures.push(json::Texture {
+            name: None,
+            sampler: Some(json::Index::new(0)),
+            source: json::Index::new(0),
+            extensions: None,
+            extras: None,
+        });
+
+        json::texture::Info {
+            index: json::Index::new(0),
+            tex_coord: 0,
+            extensions: None,
+            extras: None,
+        }
+    });
+
+    root.materials.push(json::Material {
+        pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+            base_color_factor: json::material::PbrBaseColorFactor([1.0; 4]),
+            base_color_texture,
+            metallic_factor: json::material::StrengthFactor(DEFAULT_METALLIC),
+            roughness_factor: json::material::StrengthFactor(DEFAULT_ROUGHNESS),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    root.meshes.push(json::Mesh {
+        extensions: None,
+        extras: None,
+        name: None,
+        primitives: vec![json::mesh::Primitive {
+            attributes,
+            extensions: None,
+            extras: None,
+            indices: Some(indices),
+            material: Some(json::Index::new(0)),
+            mode: Valid(json::mesh::Mode::Triangles),
+            targets: None,
+        }],
+        weights: None,
+    });
+
+    root.nodes.push(json::Node {
+        camera: None,
+        children: None,
+        extensions: None,
+        extras: grid_extras(mesh)?,
+        matrix: None,
+        mesh: Some(json::Index::new(0)),
+        name: Some(file_name_of(output_base_path)),
+        rotation: None,
+        scale: None,
+        translation: None,
+        skin: None,
+        weights: None,
+    });
+
+    root.scenes.push(json::Scene {
+        extensions: None,
+        extras: None,
+        name: None,
+        nodes: vec![json::Index::new(0)],
+    });
+    root.scene = Some(json::Index::new(0));
+
+
+    let mut gltf_json = root
+        .to_vec()
+        .into_diagnostic()
+        .wrap_err("Failed to serialize glTF document.")?;
+    gltf_json.push(b'\n');
+
+    write_file(&buffer_file_path, &buffer)?;
+    write_file(&gltf_file_path, &gltf_json)?;
+
+    if let Some(palette_texture) = &palette_texture {
+        save_palette_texture(palette_texture, &texture_file_path)?;
+    }
+
+    Ok(())
+}
+
+
+fn export_surface_mesh_as_obj(
+    output_base_path: &Path,
+    mesh: &SurfaceMesh,
+    color_mode: SurfaceColorMode,
+) -> Result<()> {
+    let obj_file_path = file_path_with_suffix(output_base_path, ".obj");
+    let material_file_path = file_path_with_suffix(output_base_path, ".mtl");
+    let texture_file_path = file_path_with_suffix(output_base_path, ".palette.png");
+
+    let palette_texture =
+        (color_mode == SurfaceColorMode::PaletteTexture).then(|| bake_palette_texture(mesh));
+
+    let mut obj = format!(
+        "# Surface of a voxel grid, exported by nrg-mesh-to-volume-conversion.\n\
+        # grid origin: {} {} {}\n\
+        # voxel size: {}\n",
+        mesh.grid_origin.x, mesh.grid_origin.y, mesh.grid_origin.z, mesh.voxel_size
+    );
+
+    if palette_texture.is_some() {
+        obj.push_str(&format!(
+            "mtllib {}\n",
+            file_name_of(&material_file_path)
+        ));
+    }
+
+    obj.push_str(&format!("o {}\n", file_name_of(output_base_path)));
+
+
+    for (position, color) in mesh.positions.iter().zip(mesh.colors.iter()) {
+        if palette_texture.is_some() {
+            obj.push_str(&format!(
+                "v {} {} {}\n",
+                position.x, position.y, position.z
+            ));
+        } else {
+            let srgb_color = color
+                .to_array()
+                .map(|component| linear_component_to_srgb(component.clamp(0.0, 1.0)));
+
+            obj.push_str(&format!(
+                "v {} {} {} {} {} {}\n",
+                position.x, position.y, position.z, srgb_color[0], srgb_color[1], srgb_color[2]
+            ));
+        }
+    }
+
+    if let Some(palette_texture) = &palette_texture {
+        // OBJ texture coordinates start at the bottom-left corner of the texture.
+        for tex_coord in &palette_texture.tex_coords {
+            obj.push_str(&format!(
+                "vt {} {}\n",
+                tex_coord.x,
+                1.0 - tex_coord.y
+            ));
+        }
+
+        obj.push_str("usemtl palette\n");
+    }
+
+    for normal in &mesh.normals {
+        obj.push_str(&format!(
+            "vn {} {} {}\n",
+            normal.x, normal.y, normal.z
+        ));
+    }
+
+    for triangle in mesh.triangle_indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+
+        if palette_texture.is_some() {
+            obj.push_str(&format!(
+                "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}\n"
+            ));
+        } else {
+            obj.push_str(&format!("f {a}//{a} {b}//{b} {c}//{c}\n"));
+        }
+    }
+
+
+    write_file(&obj_file_path, obj.as_bytes())?;
+
+    if let Some(palette_texture) = &palette_texture {
+        let material = format!(
+            "newmtl palette\n\
+            Kd 1 1 1\n\
+            map_Kd {}\n",
+            file_name_of(&texture_file_path)
+        );
+
+        write_file(&material_file_path, material.as_bytes())?;
+        save_palette_texture(palette_texture, &texture_file_path)?;
+    }
+
+    Ok(())
+}
+
+
+fn export_surface_mesh_as_stl(output_base_path: &Path, mesh: &SurfaceMesh) -> Result<()> {
+    let stl_file_path = file_path_with_suffix(output_base_path, ".stl");
+
+    let mut stl = Vec::with_capacity(84 + mesh.triangle_count() * 50);
+
+    // The 80-byte header is free-form, but must not start with "solid" (as ASCII STL files do).
+    let header = format!(
+        "Voxel grid surface; grid origin: {} {} {}; voxel size: {}",
+        mesh.grid_origin.x, mesh.grid_origin.y, mesh.grid_origin.z, mesh.voxel_size
+    );
+
+    let mut header_bytes = [0u8; 80];
+    let header_length = header.len().min(header_bytes.len());
+    header_bytes[..header_length].copy_from_slice(&header.as_bytes()[..header_length]);
+
+    stl.extend_from_slice(&header_bytes);
+    stl.extend_from_slice(&(mesh.triangle_count() as u32).to_le_bytes());
+
+    for triangle in mesh.triangle_indices.chunks_exact(3) {
+        let [a, b, c] =
+            [triangle[0], triangle[1], triangle[2]].map(|index| mesh.positions[index as usize]);
+
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+
+        for vector in [normal, a, b, c] {
+            for component in vector.to_array() {
+                stl.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+
+        // Attribute byte count.
+        stl.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    write_file(&stl_file_path, &stl)
+}
+
+
+/// Writes a surface mesh to `<output_base_path>.gltf` (with a `.bin` buffer),
+/// `<output_base_path>.obj` or `<output_base_path>.stl`, plus a `.palette.png` texture
+/// (and an `.mtl` file for OBJ) if colors are baked into a palette texture.
+/// STL files carry no colors, so the color mode is ignored for them.
+pub fn export_surface_mesh(
+    output_base_path: &Path,
+    mesh: &SurfaceMesh,
+    format: SurfaceMeshFormat,
+    color_mode: SurfaceColorMode,
+) -> Result<()> {
+    if mesh.triangle_count() == 0 {
+        return Err(miette!(
+            "The extracted surface contains no triangles."
+        ));
+    }
+
+    // Non-finite values cannot be represented in JSON (and make no sense in any format).
+    if !mesh.grid_origin.is_finite()
+        || !mesh.voxel_size.is_finite()
+        || [&mesh.positions, &mesh.normals, &mesh.colors]
+            .iter()
+            .any(|values| values.iter().any(|value| !value.is_finite()))
+    {
+        return Err(miette!(
+            "The extracted surface contains non-finite values."
+        ));
+    }
+
+    match format {
+        SurfaceMeshFormat::Gltf => export_surface_mesh_as_gltf(output_base_path, mesh, color_mode),
+        SurfaceMeshFormat::Obj => export_surface_mesh_as_obj(output_base_path, mesh, color_mode),
+        SurfaceMeshFormat::Stl => export_surface_mesh_as_stl(output_base_path, mesh),
+    }
+}
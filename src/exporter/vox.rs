@@ -0,0 +1,342 @@
+//! MagicaVoxel `.vox` export (see <https://github.com/ephtracy/voxel-model>).
+//!
+//! Voxel colors are quantized to a palette of at most 255 colors (stored in the `RGBA` chunk)
+//! and each occupied voxel is stored as an `XYZI` entry with its palette index. As MagicaVoxel
+//! is Z-up, the (Y-up) grid is rotated on export: `x` stays `x`, `-z` becomes `y` and `y`
+//! becomes `z`. Grids larger than 256 voxels along any axis are split into several models
+//! of at most 256³ voxels, each placed in the scene with an `nTRN` transform node.
+
+use std::{io::Write, path::Path};
+
+use glam::U64Vec3;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::{create_buffered_output_file, finish_buffered_output_file};
+use crate::{
+    processing::palette::{
+        build_palette,
+        map_grid_to_palette,
+        PaletteOptions,
+        EMPTY_PALETTE_INDEX,
+        MAX_PALETTE_COLORS,
+    },
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+
+pub const VOX_FILE_EXTENSION: &str = "vox";
+
+const VOX_FORMAT_VERSION: i32 = 150;
+
+/// Maximum number of voxels along each axis of a single model.
+const VOX_MAX_MODEL_SIZE: u64 = 256;
+
+/// sRGB color of `InsideMesh` voxels, which have no color of their own.
+const VOX_INTERIOR_COLOR: [u8; 3] = [128, 128, 128];
+
+
+struct VoxModel {
+    /// Position of the model's first voxel in the whole (Z-up) volume.
+    offset: U64Vec3,
+    size: U64Vec3,
+    /// `x`, `y`, `z` and palette index of each voxel.
+    voxels: Vec<[u8; 4]>,
+}
+
+
+fn write_vox_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+fn write_vox_dict(buffer: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    buffer.extend_from_slice(&(entries.len() as i32).to_le_bytes());
+
+    for (key, value) in entries {
+        write_vox_string(buffer, key);
+        write_vox_string(buffer, value);
+    }
+}
+
+fn write_vox_chunk(buffer: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    buffer.extend_from_slice(id);
+    buffer.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    buffer.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    buffer.extend_from_slice(content);
+    buffer.extend_from_slice(children);
+}
+
+/// Writes an `nTRN` node with a single frame, translated by `translation` (if set).
+fn write_vox_transform_node(
+    buffer: &mut Vec<u8>,
+    node_id: i32,
+    child_node_id: i32,
+    layer_id: i32,
+    translation: Option<String>,
+) {
+    let mut content = Vec::new();
+
+    content.extend_from_slice(&node_id.to_le_bytes());
+    write_vox_dict(&mut content, &[]);
+    content.extend_from_slice(&child_node_id.to_le_bytes());
+    // Reserved ID, layer ID and frame count.
+    content.extend_from_slice(&(-1i32).to_le_bytes());
+    content.extend_from_slice(&layer_id.to_le_bytes());
+    content.extend_from_slice(&1i32.to_le_bytes());
+
+    match &translation {
+        Some(translation) => write_vox_dict(&mut content, &[("_t", translation)]),
+        None => write_vox_dict(&mut content, &[]),
+    }
+
+    write_vox_chunk(buffer, b"nTRN", &content, &[]);
+}
+
+
+/// Splits the occupied voxels of the grid into models of at most [`VOX_MAX_MODEL_SIZE`]³ voxels.
+/// `palette_indices` holds the palette index of each voxel (0 for voxels that are not exported).
+fn split_into_models(grid: &VoxelGrid, palette_indices: &[u8]) -> Vec<VoxModel> {
+    let volume_size = U64Vec3::new(grid.x_length(), grid.z_length(), grid.y_length());
+    let model_counts = (volume_size + VOX_MAX_MODEL_SIZE - 1) / VOX_MAX_MODEL_SIZE;
+
+    let mut models = Vec::new();
+
+    for model_z in 0..model_counts.z {
+        for model_y in 0..model_counts.y {
+            for model_x in 0..model_counts.x {
+                let offset = U64Vec3::new(model_x, model_y, model_z) * VOX_MAX_MODEL_SIZE;
+                let size = (volume_size - offset).min(U64Vec3::splat(VOX_MAX_MODEL_SIZE));
+
+                let mut voxels = Vec::new();
+
+                for local_z in 0..size.z {
+                    for local_y in 0..size.y {
+                        for local_x in 0..size.x {
+                            let vox_position = offset + U64Vec3::new(local_x, local_y, local_z);
+
+                            let palette_index = palette_indices[grid.flat_index(
+                                vox_position.x,
+                                vox_position.z,
+                                grid.z_length() - 1 - vox_position.y,
+                            )];
+
+                            if palette_index != EMPTY_PALETTE_INDEX {
+                                voxels.push([
+                                    local_x as u8,
+                                    local_y as u8,
+                                    local_z as u8,
+                                    palette_index,
+                                ]);
+                            }
+                        }
+                    }
+                }
+
+                if !voxels.is_empty() {
+                    models.push(VoxModel {
+                        offset,
+                        size,
+                        voxels,
+                    });
+                }
+            }
+        }
+    }
+
+    models
+}
+
+
+/// Writes a grid into a MagicaVoxel file (`.vox`). `Edge` voxels are colored with a palette
+/// built from their colors; with `include_interior`, `InsideMesh` voxels are exported as well
+/// (with a separate gray palette entry).
+pub fn export_voxel_grid_as_vox<P>(
+    output_file_path: P,
+    grid: &VoxelGrid,
+    palette_options: &PaletteOptions,
+    include_interior: bool,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let mut palette_options = palette_options.clone();
+    if include_interior {
+        // The last palette entry is reserved for interior voxels.
+        palette_options.max_colors = palette_options.max_colors.min(MAX_PALETTE_COLORS - 1);
+    }
+
+    let palette = build_palette([grid], &palette_options);
+    let mut palette_colors = palette.srgb8_colors();
+
+    let mut palette_indices = map_grid_to_palette(grid, &palette, palette_options.dithering);
+
+    if include_interior {
+        if palette_colors.len() >= MAX_PALETTE_COLORS {
+            return Err(miette!(
+                "Exporting interior voxels requires a palette with at most {} colors.",
+                MAX_PALETTE_COLORS - 1
+            ));
+        }
+
+        palette_colors.push(VOX_INTERIOR_COLOR);
+        let interior_palette_index = palette_colors.len() as u8;
+
+        for (voxel, palette_index) in grid.voxels().iter().zip(palette_indices.iter_mut()) {
+            if matches!(voxel, VoxelData::InsideMesh) {
+                *palette_index = interior_palette_index;
+            }
+        }
+    }
+
+
+    let models = split_into_models(grid, &palette_indices);
+    if models.is_empty() {
+        return Err(miette!("The grid has no voxels to export."));
+    }
+
+    let mut children = Vec::new();
+
+    for model in &models {
+        let mut size_content = Vec::with_capacity(12);
+        for size in model.size.to_array() {
+            size_content.extend_from_slice(&(size as i32).to_le_bytes());
+        }
+        write_vox_chunk(&mut children, b"SIZE", &size_content, &[]);
+
+        let mut voxel_content = Vec::with_capacity(4 + model.voxels.len() * 4);
+        voxel_content.extend_from_slice(&(model.voxels.len() as i32).to_le_bytes());
+        voxel_content.extend(model.voxels.iter().flatten());
+        write_vox_chunk(&mut children, b"XYZI", &voxel_content, &[]);
+    }
+
+
+    // Scene graph: a root transform node, a group node and a transform + shape node pair per model.
+    // MagicaVoxel places the center of each model at its translation.
+    let volume_center =
+        U64Vec3::new(grid.x_length(), grid.z_length(), grid.y_length()).as_i64vec3() / 2;
+
+    write_vox_transform_node(&mut children, 0, 1, -1, None);
+
+    let mut group_content = Vec::new();
+    group_content.extend_from_slice(&1i32.to_le_bytes());
+    write_vox_dict(&mut group_content, &[]);
+    group_content.extend_from_slice(&(models.len() as i32).to_le_bytes());
+    for model_index in 0..models.len() {
+        group_content.extend_from_slice(&(2 + model_index as i32 * 2).to_le_bytes());
+    }
+    write_vox_chunk(&mut children, b"nGRP", &group_content, &[]);
+
+    for (model_index, model) in models.iter().enumerate() {
+        let translation = (model.offset + model.size / 2).as_i64vec3() - volume_center;
+        let transform_node_id = 2 + model_index as i32 * 2;
+
+        write_vox_transform_node(
+            &mut children,
+            transform_node_id,
+            transform_node_id + 1,
+            0,
+            Some(format!(
+                "{} {} {}",
+                translation.x, translation.y, translation.z
+            )),
+        );
+
+        let mut shape_content = Vec::new();
+        shape_content.extend_from_slice(&(transform_node_id + 1).to_le_bytes());
+        write_vox_dict(&mut shape_content, &[]);
+        shape_content.extend_from_slice(&1i32.to_le_bytes());
+        shape_content.extend_from_slice(&(model_index as i32).to_le_bytes());
+        write_vox_dict(&mut shape_content, &[]);
+        write_vox_chunk(&mut children, b"nSHP", &shape_content, &[]);
+    }
+
+
+    // Palette entry N of the RGBA chunk holds the color of index N + 1.
+    let mut palette_content = Vec::with_capacity(256 * 4);
+    for entry_index in 0..256 {
+        let [r, g, b] = palette_colors
+            .get(entry_index)
+            .copied()
+            .unwrap_or([0, 0, 0]);
+
+        palette_content.extend_from_slice(&[r, g, b, 255]);
+    }
+    write_vox_chunk(&mut children, b"RGBA", &palette_content, &[]);
+
+
+    let mut file_contents = Vec::with_capacity(8 + 12 + children.len());
+    file_contents.extend_from_slice(b"VOX ");
+    file_contents.extend_from_slice(&VOX_FORMAT_VERSION.to_le_bytes());
+    write_vox_chunk(&mut file_contents, b"MAIN", &[], &children);
+
+
+    let mut buffered_file = create_buffered_output_file(output_file_path)?;
+
+    buffered_file
+        .write_all(&file_contents)
+        .into_diagnostic()
+        .wrap_err("Failed to write MagicaVoxel file.")?;
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn grids_larger_than_256_voxels_are_split() {
+        // 257 voxels along x and z (which becomes the vox y axis), a single layer along y.
+        let grid = VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            257,
+            1,
+            257,
+            vec![VoxelData::Empty; 257 * 257],
+        );
+
+        let mut palette_indices = vec![1; 257 * 257];
+        palette_indices[grid.flat_index(256, 0, 0)] = 7;
+        palette_indices[grid.flat_index(0, 0, 256)] = EMPTY_PALETTE_INDEX;
+
+        let models = split_into_models(&grid, &palette_indices);
+
+        assert_eq!(
+            models
+                .iter()
+                .map(|model| (model.offset, model.size, model.voxels.len()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    U64Vec3::ZERO,
+                    U64Vec3::new(256, 256, 1),
+                    256 * 256 - 1
+                ),
+                (
+                    U64Vec3::new(256, 0, 0),
+                    U64Vec3::new(1, 256, 1),
+                    256
+                ),
+                (
+                    U64Vec3::new(0, 256, 0),
+                    U64Vec3::new(256, 1, 1),
+                    256
+                ),
+                (
+                    U64Vec3::new(256, 256, 0),
+                    U64Vec3::new(1, 1, 1),
+                    1
+                ),
+            ]
+        );
+
+        // Grid z = 0 is the far end of the vox y axis.
+        assert_eq!(models[3].voxels, [[0, 0, 0, 7]]);
+        assert!(!models[0].voxels.contains(&[0, 0, 0, 1]));
+    }
+}
@@ -0,0 +1,417 @@
+//! VTK `ImageData` export, either as an XML `.vti` file or as a legacy `.vtk` file
+//! (`STRUCTURED_POINTS` dataset), as read by e.g. ParaView.
+//!
+//! Each export type becomes a separate data array named after it, so a single file can hold
+//! the occupancy, fill state, color, metallic and roughness of a grid. By default, the arrays are
+//! stored as cell data (one cell per voxel, with the image origin at the minimum corner of the grid);
+//! with [`VtkOptions::point_data`], they are stored as point data instead (one point per voxel,
+//! with the image origin at the center of the first voxel). Binary export types are stored as one
+//! byte (0 or 1) per voxel.
+//!
+//! XML files store their arrays in an appended data section (each array prefixed with its byte
+//! length as a `UInt64`), either raw or base64-encoded. Legacy files store them as big-endian
+//! binary field data.
+
+use std::{
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use miette::{Context, IntoDiagnostic, Result};
+
+use super::{
+    create_buffered_output_file,
+    finish_buffered_output_file,
+    prepare_palette,
+    write_voxel_grid_unpacked,
+    ComponentType,
+    VoxelExportType,
+};
+use crate::voxelizer::grid::VoxelGrid;
+
+
+pub const VTK_XML_IMAGE_DATA_FILE_EXTENSION: &str = "vti";
+
+pub const VTK_LEGACY_FILE_EXTENSION: &str = "vtk";
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct VtkOptions {
+    /// Writes a legacy `.vtk` file instead of an XML `.vti` file.
+    pub legacy: bool,
+
+    /// Base64-encodes the appended data of XML files (instead of storing it raw).
+    pub base64: bool,
+
+    /// Stores the voxels as point data instead of cell data.
+    pub point_data: bool,
+}
+
+
+/// A single named data array.
+struct VtkDataArray {
+    name: &'static str,
+    component_type: ComponentType,
+    components_per_tuple: usize,
+    /// Little-endian values, interleaved by component.
+    data: Vec<u8>,
+}
+
+
+fn xml_type_name(component_type: ComponentType) -> &'static str {
+    match component_type {
+        ComponentType::U1 | ComponentType::U8 => "UInt8",
+        ComponentType::U16 => "UInt16",
+        ComponentType::U32 => "UInt32",
+        ComponentType::F32 => "Float32",
+    }
+}
+
+fn legacy_type_name(component_type: ComponentType) -> &'static str {
+    match component_type {
+        ComponentType::U1 | ComponentType::U8 => "unsigned_char",
+        ComponentType::U16 => "unsigned_short",
+        ComponentType::U32 => "unsigned_int",
+        ComponentType::F32 => "float",
+    }
+}
+
+fn component_size(component_type: ComponentType) -> usize {
+    match component_type {
+        ComponentType::U1 | ComponentType::U8 => 1,
+        ComponentType::U16 => 2,
+        ComponentType::U32 | ComponentType::F32 => 4,
+    }
+}
+
+
+/// Path of the VTK file, e.g. `model.m-0.vti` (or `model.m-0.vtk` for legacy files) for `model.m-0`.
+pub fn vtk_file_path(output_base_path: &Path, options: &VtkOptions) -> PathBuf {
+    let extension = if options.legacy {
+        VTK_LEGACY_FILE_EXTENSION
+    } else {
+        VTK_XML_IMAGE_DATA_FILE_EXTENSION
+    };
+
+    let mut file_path = output_base_path.as_os_str().to_owned();
+    file_path.push(format!(".{}", extension));
+
+    PathBuf::from(file_path)
+}
+
+
+/// Returns the origin and the number of points along each axis of the image.
+fn image_origin_and_point_dimensions(
+    grid: &VoxelGrid,
+    options: &VtkOptions,
+) -> ([f32; 3], [u64; 3]) {
+    let dimensions = grid.dimensions();
+
+    if options.point_data {
+        (
+            (grid.starting_point + grid.voxel_half_extent).to_array(),
+            dimensions.to_array(),
+        )
+    } else {
+        (
+            grid.starting_point.to_array(),
+            (dimensions + 1).to_array(),
+        )
+    }
+}
+
+
+fn xml_image_data(grid: &VoxelGrid, data_arrays: &[VtkDataArray], options: &VtkOptions) -> Vec<u8> {
+    let (origin, point_dimensions) = image_origin_and_point_dimensions(grid, options);
+    let voxel_size = grid.voxel_size();
+
+    let extent = format!(
+        "0 {} 0 {} 0 {}",
+        point_dimensions[0] - 1,
+        point_dimensions[1] - 1,
+        point_dimensions[2] - 1
+    );
+
+    let data_section_name = if options.point_data {
+        "PointData"
+    } else {
+        "CellData"
+    };
+
+
+    let mut header = String::new();
+    header.push_str("<?xml version=\"1.0\"?>\n");
+    header.push_str(
+        "<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" header_type=\"UInt64\">\n",
+    );
+    header.push_str(&format!(
+        "  <ImageData WholeExtent=\"{}\" Origin=\"{} {} {}\" Spacing=\"{} {} {}\" \
+        Direction=\"1 0 0 0 1 0 0 0 1\">\n",
+        extent, origin[0], origin[1], origin[2], voxel_size, voxel_size, voxel_size,
+    ));
+    header.push_str(&format!("    <Piece Extent=\"{}\">\n", extent));
+    header.push_str(&format!("      <{}>\n", data_section_name));
+
+
+    let mut appended_data = Vec::new();
+
+    for data_array in data_arrays {
+        header.push_str(&format!(
+            "        <DataArray type=\"{}\" Name=\"{}\" NumberOfComponents=\"{}\" \
+            format=\"appended\" offset=\"{}\"/>\n",
+            xml_type_name(data_array.component_type),
+            data_array.name,
+            data_array.components_per_tuple,
+            appended_data.len()
+        ));
+
+        let length_header = (data_array.data.len() as u64).to_le_bytes();
+
+        if options.base64 {
+            // The header and the data are encoded separately, as VTK does.
+            appended_data.extend_from_slice(BASE64_STANDARD.encode(length_header).as_bytes());
+            appended_data.extend_from_slice(BASE64_STANDARD.encode(&data_array.data).as_bytes());
+        } else {
+            appended_data.extend_from_slice(&length_header);
+            appended_data.extend_from_slice(&data_array.data);
+        }
+    }
+
+
+    header.push_str(&format!("      </{}>\n", data_section_name));
+    header.push_str("    </Piece>\n");
+    header.push_str("  </ImageData>\n");
+    header.push_str(&format!(
+        "  <AppendedData encoding=\"{}\">\n",
+        if options.base64 { "base64" } else { "raw" }
+    ));
+    // Appended data starts right after the underscore.
+    header.push_str("    _");
+
+    let mut file_contents = header.into_bytes();
+    file_contents.extend_from_slice(&appended_data);
+    file_contents.extend_from_slice(b"\n  </AppendedData>\n</VTKFile>\n");
+
+    file_contents
+}
+
+
+fn legacy_structured_points(
+    grid: &VoxelGrid,
+    data_arrays: &[VtkDataArray],
+    options: &VtkOptions,
+) -> Vec<u8> {
+    let (origin, point_dimensions) = image_origin_and_point_dimensions(grid, options);
+    let voxel_size = grid.voxel_size();
+
+    let mut file_contents = format!(
+        "# vtk DataFile Version 3.0\n\
+        {}\n\
+        BINARY\n\
+        DATASET STRUCTURED_POINTS\n\
+        DIMENSIONS {} {} {}\n\
+        ORIGIN {} {} {}\n\
+        SPACING {} {} {}\n\
+        {} {}\n\
+        FIELD FieldData {}\n",
+        data_arrays
+            .iter()
+            .map(|data_array| data_array.name)
+            .collect::<Vec<_>>()
+            .join(", "),
+        point_dimensions[0],
+        point_dimensions[1],
+        point_dimensions[2],
+        origin[0],
+        origin[1],
+        origin[2],
+        voxel_size,
+        voxel_size,
+        voxel_size,
+        if options.point_data {
+            "POINT_DATA"
+        } else {
+            "CELL_DATA"
+        },
+        grid.voxels().len(),
+        data_arrays.len(),
+    )
+    .into_bytes();
+
+    for data_array in data_arrays {
+        file_contents.extend_from_slice(
+            format!(
+                "{} {} {} {}\n",
+                data_array.name,
+                data_array.components_per_tuple,
+                grid.voxels().len(),
+                legacy_type_name(data_array.component_type)
+            )
+            .as_bytes(),
+        );
+
+        // Legacy binary files are big-endian.
+        let component_size = component_size(data_array.component_type);
+        for component in data_array.data.chunks_exact(component_size) {
+            file_contents.extend(component.iter().rev());
+        }
+
+        file_contents.push(b'\n');
+    }
+
+    file_contents
+}
+
+
+/// Writes a grid into a VTK file (see [`vtk_file_path`]), with one data array per export type.
+pub fn export_voxel_grid_as_vtk(
+    output_base_path: &Path,
+    grid: &VoxelGrid,
+    voxel_export_types: &[VoxelExportType],
+    options: &VtkOptions,
+) -> Result<()> {
+    let output_file_path = vtk_file_path(output_base_path, options);
+
+    let mut data_arrays = Vec::with_capacity(voxel_export_types.len());
+
+    for voxel_export_type in voxel_export_types {
+        let palette = prepare_palette(&output_file_path, [grid], voxel_export_type)?;
+
+        let mut data = Cursor::new(Vec::new());
+        write_voxel_grid_unpacked(
+            &mut data,
+            grid,
+            voxel_export_type,
+            palette.as_ref(),
+        )?;
+
+        data_arrays.push(VtkDataArray {
+            name: voxel_export_type.name(),
+            component_type: voxel_export_type.unpacked_component_type(),
+            components_per_tuple: voxel_export_type.components_per_voxel(),
+            data: data.into_inner(),
+        });
+    }
+
+    let file_contents = if options.legacy {
+        legacy_structured_points(grid, &data_arrays, options)
+    } else {
+        xml_image_data(grid, &data_arrays, options)
+    };
+
+
+    let mut buffered_file = create_buffered_output_file(output_file_path)?;
+
+    buffered_file
+        .write_all(&file_contents)
+        .into_diagnostic()
+        .wrap_err("Failed to write VTK file.")?;
+
+    finish_buffered_output_file(buffered_file)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::voxelizer::voxel::VoxelData;
+
+    fn test_grid_and_data_arrays() -> (VoxelGrid, Vec<VtkDataArray>) {
+        let grid = VoxelGrid::from_voxels(
+            Vec3::new(-1.0, 0.0, 2.0),
+            0.25,
+            2,
+            1,
+            1,
+            vec![VoxelData::Empty; 2],
+        );
+
+        let data_arrays = vec![VtkDataArray {
+            name: "component-label_u16",
+            component_type: ComponentType::U16,
+            components_per_tuple: 1,
+            data: vec![0x01, 0x02, 0x03, 0x04],
+        }];
+
+        (grid, data_arrays)
+    }
+
+    #[test]
+    fn xml_header_and_raw_appended_data() {
+        let (grid, data_arrays) = test_grid_and_data_arrays();
+
+        let file_contents = xml_image_data(
+            &grid,
+            &data_arrays,
+            &VtkOptions {
+                legacy: false,
+                base64: false,
+                point_data: false,
+            },
+        );
+
+        // The array name contains an underscore as well, so we look for the indented one.
+        let data_start = file_contents
+            .windows(5)
+            .position(|window| window == b"    _")
+            .unwrap()
+            + 5;
+        let header = String::from_utf8(file_contents[..data_start].to_vec()).unwrap();
+
+        assert!(header.contains(
+            "<ImageData WholeExtent=\"0 2 0 1 0 1\" Origin=\"-1 0 2\" Spacing=\"0.5 0.5 0.5\""
+        ));
+        assert!(header.contains("<CellData>"));
+        assert!(header.contains(
+            "<DataArray type=\"UInt16\" Name=\"component-label_u16\" NumberOfComponents=\"1\" \
+            format=\"appended\" offset=\"0\"/>"
+        ));
+        assert!(header.contains("<AppendedData encoding=\"raw\">"));
+
+        // Each array is prefixed with its byte length.
+        assert_eq!(
+            file_contents[data_start..data_start + 12],
+            [4, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x02, 0x03, 0x04]
+        );
+        assert!(file_contents.ends_with(b"\n  </AppendedData>\n</VTKFile>\n"));
+    }
+
+    #[test]
+    fn legacy_header_and_big_endian_point_data() {
+        let (grid, data_arrays) = test_grid_and_data_arrays();
+
+        let file_contents = legacy_structured_points(
+            &grid,
+            &data_arrays,
+            &VtkOptions {
+                legacy: true,
+                base64: false,
+                point_data: true,
+            },
+        );
+
+        let expected_header = "# vtk DataFile Version 3.0\n\
+            component-label_u16\n\
+            BINARY\n\
+            DATASET STRUCTURED_POINTS\n\
+            DIMENSIONS 2 1 1\n\
+            ORIGIN -0.75 0.25 2.25\n\
+            SPACING 0.5 0.5 0.5\n\
+            POINT_DATA 2\n\
+            FIELD FieldData 1\n\
+            component-label_u16 1 2 unsigned_short\n";
+
+        assert_eq!(
+            file_contents[..expected_header.len()],
+            *expected_header.as_bytes()
+        );
+        assert_eq!(
+            file_contents[expected_header.len()..],
+            [0x02, 0x01, 0x04, 0x03, b'\n']
+        );
+    }
+}
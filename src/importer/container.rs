@@ -0,0 +1,565 @@
+//! Reading of native voxel container files
+//! (see [`exporter::container`](crate::exporter::container) for the format).
+
+use std::{fs, path::Path};
+
+use glam::{U64Vec3, Vec3};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::u8_to_unit_f32;
+use crate::{
+    exporter::{
+        container::{
+            ContainerChannel,
+            ContainerHeader,
+            CONTAINER_AXIS_ORDER,
+            CONTAINER_FORMAT_VERSION,
+            CONTAINER_MAGIC,
+            PALETTE_CHANNEL_NAME,
+        },
+        ColorSpace,
+        ComponentType,
+    },
+    mesh::{srgb_component_to_linear, DEFAULT_METALLIC, DEFAULT_ROUGHNESS},
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+
+/// A container file, with its header and the payload of each channel.
+pub struct VoxelContainer {
+    pub header: ContainerHeader,
+
+    /// Payload of each channel, in the same order as [`ContainerHeader::channels`].
+    payloads: Vec<Vec<u8>>,
+}
+
+impl VoxelContainer {
+    pub fn read_from_file<P>(file_path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file_path = file_path.as_ref();
+
+        let data = fs::read(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read {}.", file_path.display()))?;
+
+        Self::parse(&data).wrap_err_with(|| {
+            miette!(
+                "Invalid voxel container file {}.",
+                file_path.display()
+            )
+        })
+    }
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader { data, offset: 0 };
+
+        if reader.read_array::<8>()? != CONTAINER_MAGIC {
+            return Err(miette!("Missing container magic bytes."));
+        }
+
+        let version = u16::from_le_bytes(reader.read_array()?);
+        if version == 0 || version > CONTAINER_FORMAT_VERSION {
+            return Err(miette!(
+                "Unsupported container format version {} (supported up to {}).",
+                version,
+                CONTAINER_FORMAT_VERSION
+            ));
+        }
+
+        let dimensions = U64Vec3::new(
+            u64::from_le_bytes(reader.read_array()?),
+            u64::from_le_bytes(reader.read_array()?),
+            u64::from_le_bytes(reader.read_array()?),
+        );
+
+        let origin = Vec3::new(
+            f32::from_le_bytes(reader.read_array()?),
+            f32::from_le_bytes(reader.read_array()?),
+            f32::from_le_bytes(reader.read_array()?),
+        );
+
+        let voxel_size = f32::from_le_bytes(reader.read_array()?);
+        let axis_order = reader.read_array::<3>()?;
+        let source_hash = reader.read_array::<32>()?;
+
+
+        let channel_count = u16::from_le_bytes(reader.read_array()?);
+        let mut channels = Vec::with_capacity(channel_count as usize);
+
+        for _ in 0..channel_count {
+            let [name_length] = reader.read_array::<1>()?;
+            let name = String::from_utf8(reader.read_bytes(name_length as usize)?.to_vec())
+                .into_diagnostic()
+                .wrap_err("Invalid channel name.")?;
+
+            let [component_type_code, components_per_element, color_space_code] =
+                reader.read_array::<3>()?;
+
+            channels.push(ContainerChannel {
+                name,
+                component_type: component_type_from_code(component_type_code)?,
+                components_per_element,
+                color_space: color_space_from_code(color_space_code)?,
+                element_count: u64::from_le_bytes(reader.read_array()?),
+                payload_length: u64::from_le_bytes(reader.read_array()?),
+            });
+        }
+
+
+        let payloads = channels
+            .iter()
+            .map(|channel| {
+                reader
+                    .read_bytes(channel.payload_length as usize)
+                    .map(|payload| payload.to_vec())
+                    .wrap_err_with(|| {
+                        miette!(
+                            "Truncated payload of channel \"{}\".",
+                            channel.name
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+
+        Ok(Self {
+            header: ContainerHeader {
+                version,
+                dimensions,
+                origin,
+                voxel_size,
+                axis_order,
+                source_hash,
+                channels,
+            },
+            payloads,
+        })
+    }
+
+    /// Returns the description and payload of the channel with the given name.
+    pub fn channel(&self, name: &str) -> Option<(&ContainerChannel, &[u8])> {
+        self.header
+            .channels
+            .iter()
+            .zip(&self.payloads)
+            .find(|(channel, _)| channel.name == name)
+            .map(|(channel, payload)| (channel, payload.as_slice()))
+    }
+
+    /// Reconstructs a voxel grid from the channels of the container.
+    ///
+    /// Occupancy is taken from the `binary-edge_u1` channel or, if missing, from a palette index
+    /// or component label channel. Colors are taken from any color or palette channel.
+    /// Missing attributes of edge voxels are set to the [default material](crate::mesh::MeshMaterial::default) values.
+    pub fn to_voxel_grid(&self) -> Result<VoxelGrid> {
+        if self.header.axis_order != CONTAINER_AXIS_ORDER {
+            return Err(miette!(
+                "Unsupported axis order \"{}\".",
+                String::from_utf8_lossy(&self.header.axis_order)
+            ));
+        }
+
+        let dimensions = self.header.dimensions;
+        let voxel_count = dimensions
+            .x
+            .checked_mul(dimensions.y)
+            .and_then(|count| count.checked_mul(dimensions.z))
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| {
+                miette!(
+                    "The grid dimensions {} x {} x {} are too large.",
+                    dimensions.x,
+                    dimensions.y,
+                    dimensions.z
+                )
+            })?;
+
+
+        let occupancy = if let Some(occupancy) = self.bit_channel("binary-edge_u1", voxel_count)? {
+            occupancy
+        } else if let Some(indices) = self.u8_channel("palette-index_u8", voxel_count)? {
+            indices.iter().map(|index| *index != 0).collect()
+        } else if let Some(labels) = self.u32_channel("component-label_u32", voxel_count)? {
+            labels.iter().map(|label| *label != 0).collect()
+        } else {
+            return Err(miette!(
+                "The container has no channel to derive occupancy from \
+                (binary-edge_u1, palette-index_u8 or component-label_u32)."
+            ));
+        };
+
+        let fill = self.bit_channel("binary-fill_u1", voxel_count)?;
+        let colors = self.colors(voxel_count)?;
+
+        let metallic_values = self.u8_channel("metallic-value_u8", voxel_count)?;
+        let roughness_values = self.u8_channel("roughness-value_u8", voxel_count)?;
+        let intensities = self.u8_channel("intensity-value_u8", voxel_count)?;
+
+
+        let voxels = (0..voxel_count)
+            .map(|index| {
+                if fill.as_ref().map(|fill| fill[index]).unwrap_or(false) {
+                    return VoxelData::InsideMesh;
+                }
+
+                if !occupancy[index] {
+                    return VoxelData::Empty;
+                }
+
+                VoxelData::Edge {
+                    color: colors
+                        .as_ref()
+                        .map(|colors| colors[index])
+                        .unwrap_or(Vec3::ONE),
+                    metallic_value: metallic_values
+                        .map(|values| u8_to_unit_f32(values[index]))
+                        .unwrap_or(DEFAULT_METALLIC),
+                    rougness_value: roughness_values
+                        .map(|values| u8_to_unit_f32(values[index]))
+                        .unwrap_or(DEFAULT_ROUGHNESS),
+                    intensity: intensities
+                        .map(|values| u8_to_unit_f32(values[index]))
+                        .unwrap_or(0.0),
+                }
+            })
+            .collect::<Vec<_>>();
+
+
+        Ok(VoxelGrid::from_voxels(
+            self.header.origin,
+            self.header.voxel_size / 2.0,
+            self.header.dimensions.x,
+            self.header.dimensions.y,
+            self.header.dimensions.z,
+            voxels,
+        ))
+    }
+
+
+    /// Decodes linear voxel colors from the first available color channel.
+    fn colors(&self, voxel_count: usize) -> Result<Option<Vec<Vec3>>> {
+        for name in ["linear-rgb8-color_u8", "srgb-rgb8-color_u8"] {
+            if let Some((channel, values)) =
+                self.typed_channel(name, voxel_count, 3, ComponentType::U8)?
+            {
+                return Ok(Some(
+                    values
+                        .chunks_exact(3)
+                        .map(|rgb| match channel.color_space {
+                            Some(ColorSpace::Srgb) => srgb8_to_linear([rgb[0], rgb[1], rgb[2]]),
+                            _ => Vec3::from_array([rgb[0], rgb[1], rgb[2]].map(u8_to_unit_f32)),
+                        })
+                        .collect(),
+                ));
+            }
+        }
+
+        if let Some((_, values)) = self.typed_channel(
+            "linear-rgb16-color_u16",
+            voxel_count,
+            3,
+            ComponentType::U16,
+        )? {
+            return Ok(Some(
+                values
+                    .chunks_exact(6)
+                    .map(|rgb| {
+                        Vec3::from_array([0, 2, 4].map(|offset| {
+                            u16::from_le_bytes([rgb[offset], rgb[offset + 1]]) as f32
+                                / u16::MAX as f32
+                        }))
+                    })
+                    .collect(),
+            ));
+        }
+
+        if let Some((_, values)) = self.typed_channel(
+            "linear-rgb-color_f32",
+            voxel_count,
+            3,
+            ComponentType::F32,
+        )? {
+            return Ok(Some(
+                values
+                    .chunks_exact(12)
+                    .map(|rgb| {
+                        Vec3::from_array([0, 4, 8].map(|offset| {
+                            f32::from_le_bytes([
+                                rgb[offset],
+                                rgb[offset + 1],
+                                rgb[offset + 2],
+                                rgb[offset + 3],
+                            ])
+                        }))
+                    })
+                    .collect(),
+            ));
+        }
+
+        if let (Some(indices), Some((_, palette))) = (
+            self.u8_channel("palette-index_u8", voxel_count)?,
+            self.channel(PALETTE_CHANNEL_NAME),
+        ) {
+            let palette_colors = palette
+                .chunks_exact(3)
+                .map(|rgb| srgb8_to_linear([rgb[0], rgb[1], rgb[2]]))
+                .collect::<Vec<_>>();
+
+            return Ok(Some(
+                indices
+                    .iter()
+                    .map(|index| {
+                        (*index as usize)
+                            .checked_sub(1)
+                            .and_then(|palette_entry| palette_colors.get(palette_entry))
+                            .copied()
+                            .unwrap_or(Vec3::ONE)
+                    })
+                    .collect(),
+            ));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the description and payload of a voxel channel, after checking its layout.
+    fn typed_channel(
+        &self,
+        name: &str,
+        voxel_count: usize,
+        components_per_element: usize,
+        component_type: ComponentType,
+    ) -> Result<Option<(&ContainerChannel, &[u8])>> {
+        let Some((channel, payload)) = self.channel(name) else {
+            return Ok(None);
+        };
+
+        let component_count = voxel_count
+            .checked_mul(components_per_element)
+            .ok_or_else(|| miette!("Channel \"{}\" is too large.", name))?;
+
+        let expected_length = match component_type {
+            ComponentType::U1 => Some(component_count.div_ceil(8)),
+            ComponentType::U8 => Some(component_count),
+            ComponentType::U16 => component_count.checked_mul(2),
+            ComponentType::U32 | ComponentType::F32 => component_count.checked_mul(4),
+        }
+        .ok_or_else(|| miette!("Channel \"{}\" is too large.", name))?;
+
+        if channel.component_type != component_type
+            || channel.components_per_element as usize != components_per_element
+            || channel.element_count != voxel_count as u64
+            || payload.len() != expected_length
+        {
+            return Err(miette!(
+                "Unexpected layout of channel \"{}\": expected {} voxels with {} {} components \
+                ({} bytes), got {} voxels with {} {} components ({} bytes).",
+                name,
+                voxel_count,
+                components_per_element,
+                component_type.name(),
+                expected_length,
+                channel.element_count,
+                channel.components_per_element,
+                channel.component_type.name(),
+                payload.len()
+            ));
+        }
+
+        Ok(Some((channel, payload)))
+    }
+
+    fn u8_channel(&self, name: &str, voxel_count: usize) -> Result<Option<&[u8]>> {
+        Ok(self
+            .typed_channel(name, voxel_count, 1, ComponentType::U8)?
+            .map(|(_, payload)| payload))
+    }
+
+    fn u32_channel(&self, name: &str, voxel_count: usize) -> Result<Option<Vec<u32>>> {
+        Ok(self
+            .typed_channel(name, voxel_count, 1, ComponentType::U32)?
+            .map(|(_, payload)| {
+                payload
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect()
+            }))
+    }
+
+    fn bit_channel(&self, name: &str, voxel_count: usize) -> Result<Option<Vec<bool>>> {
+        let Some((_, payload)) = self.typed_channel(name, voxel_count, 1, ComponentType::U1)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            (0..voxel_count)
+                .map(|index| (payload[index / 8] >> (7 - (index % 8))) & 1 == 1)
+                .collect(),
+        ))
+    }
+}
+
+
+
+/// Sequential reader over a byte slice that fails on truncated data.
+struct ByteReader<'d> {
+    data: &'d [u8],
+    offset: usize,
+}
+
+impl<'d> ByteReader<'d> {
+    fn read_bytes(&mut self, length: usize) -> Result<&'d [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset.saturating_add(length))
+            .ok_or_else(|| miette!("Unexpected end of file."))?;
+
+        self.offset += length;
+
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+
+        Ok(array)
+    }
+}
+
+
+fn component_type_from_code(code: u8) -> Result<ComponentType> {
+    match code {
+        0 => Ok(ComponentType::U1),
+        1 => Ok(ComponentType::U8),
+        2 => Ok(ComponentType::U16),
+        3 => Ok(ComponentType::U32),
+        4 => Ok(ComponentType::F32),
+        _ => Err(miette!("Unknown component type {}.", code)),
+    }
+}
+
+fn color_space_from_code(code: u8) -> Result<Option<ColorSpace>> {
+    match code {
+        0 => Ok(None),
+        1 => Ok(Some(ColorSpace::Linear)),
+        2 => Ok(Some(ColorSpace::Srgb)),
+        _ => Err(miette!("Unknown color space {}.", code)),
+    }
+}
+
+
+fn srgb8_to_linear(rgb: [u8; 3]) -> Vec3 {
+    Vec3::from_array(rgb.map(|component| srgb_component_to_linear(u8_to_unit_f32(component))))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use glam::Vec3;
+
+    use super::VoxelContainer;
+    use crate::{
+        exporter::{container::export_voxel_grid_as_container, VoxelExportType},
+        voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+    };
+
+    #[test]
+    fn container_round_trip() {
+        let [x_length, y_length, z_length] = [5, 4, 3];
+        let voxel_count = (x_length * y_length * z_length) as usize;
+
+        let voxels = (0..voxel_count)
+            .map(|flat_index| match flat_index % 3 {
+                0 => VoxelData::Empty,
+                1 => VoxelData::Edge {
+                    color: Vec3::new(flat_index as f32 / voxel_count as f32, 0.25, 1.0),
+                    metallic_value: flat_index as f32 / voxel_count as f32,
+                    rougness_value: 1.0 - flat_index as f32 / voxel_count as f32,
+                    intensity: 0.5,
+                },
+                _ => VoxelData::InsideMesh,
+            })
+            .collect::<Vec<_>>();
+
+        let grid = VoxelGrid::from_voxels(
+            Vec3::new(-1.0, 2.0, 0.5),
+            0.125,
+            x_length,
+            y_length,
+            z_length,
+            voxels,
+        );
+
+        let file_path = std::env::temp_dir().join(format!(
+            "nrg-container-round-trip-{}.nvox",
+            std::process::id()
+        ));
+
+        export_voxel_grid_as_container(
+            &file_path,
+            &grid,
+            &[
+                VoxelExportType::BinaryEdgeStateU1,
+                VoxelExportType::BinaryFillStateU1,
+                VoxelExportType::LinearRgbColorF32,
+                VoxelExportType::MetallicValueU8,
+                VoxelExportType::RoughnessValueU8,
+                VoxelExportType::IntensityValueU8,
+            ],
+            [7; 32],
+        )
+        .unwrap();
+
+        let container = VoxelContainer::read_from_file(&file_path);
+        fs::remove_file(&file_path).unwrap();
+
+        let container = container.unwrap();
+        assert_eq!(container.header.source_hash, [7; 32]);
+
+        let imported_grid = container.to_voxel_grid().unwrap();
+
+        assert_eq!(imported_grid.dimensions(), grid.dimensions());
+        assert_eq!(imported_grid.starting_point, grid.starting_point);
+        assert_eq!(imported_grid.voxel_size(), grid.voxel_size());
+
+        // Metallic, roughness and intensity values are quantized to eight bits.
+        let tolerance = 0.5 / u8::MAX as f32 + f32::EPSILON;
+
+        for (flat_index, (imported_voxel, voxel)) in
+            imported_grid.voxels().iter().zip(grid.voxels()).enumerate()
+        {
+            match (imported_voxel, voxel) {
+                (VoxelData::Empty, VoxelData::Empty)
+                | (VoxelData::InsideMesh, VoxelData::InsideMesh) => {}
+                (
+                    VoxelData::Edge {
+                        color: imported_color,
+                        metallic_value: imported_metallic_value,
+                        rougness_value: imported_roughness_value,
+                        intensity: imported_intensity,
+                    },
+                    VoxelData::Edge {
+                        color,
+                        metallic_value,
+                        rougness_value,
+                        intensity,
+                    },
+                ) => {
+                    assert_eq!(imported_color, color);
+                    assert!((imported_metallic_value - metallic_value).abs() <= tolerance);
+                    assert!((imported_roughness_value - rougness_value).abs() <= tolerance);
+                    assert!((imported_intensity - intensity).abs() <= tolerance);
+                }
+                _ => panic!(
+                    "voxel {flat_index} was imported as {imported_voxel:?} instead of {voxel:?}"
+                ),
+            }
+        }
+    }
+}
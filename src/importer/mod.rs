@@ -0,0 +1,281 @@
+//! Importing of previously exported voxel grids.
+
+use std::{fs, path::Path};
+
+use glam::{U64Vec3, Vec3};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use crate::{
+    mesh::{DEFAULT_METALLIC, DEFAULT_ROUGHNESS},
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+pub mod container;
+
+
+/// Paths to raw files produced by [`export_voxel_grid_as_raw`](crate::exporter::export_voxel_grid_as_raw),
+/// one per export type. Only the occupancy (`binary-edge_u1`) file is required.
+pub struct RawVoxelGridFiles<'p> {
+    /// File exported as `binary-edge_u1`.
+    pub occupancy_file_path: &'p Path,
+
+    /// File exported as `binary-fill_u1`. If missing, all occupied voxels are treated as edge voxels.
+    pub fill_file_path: Option<&'p Path>,
+
+    /// File exported as `linear-rgb8-color_u8`.
+    pub color_file_path: Option<&'p Path>,
+
+    /// File exported as `metallic-value_u8`.
+    pub metallic_file_path: Option<&'p Path>,
+
+    /// File exported as `roughness-value_u8`.
+    pub roughness_file_path: Option<&'p Path>,
+}
+
+
+/// Describes the grid that raw files were exported from, as raw files carry no such information.
+pub struct RawVoxelGridLayout {
+    pub dimensions: U64Vec3,
+
+    /// The bounding box edge of the lowest-x lowest-y lowest-z voxel.
+    pub starting_point: Vec3,
+
+    /// Full voxel size (box width).
+    pub voxel_size: f32,
+}
+
+impl RawVoxelGridLayout {
+    /// Reads the layout from a `.meta.txt` file written by
+    /// [`export_raw_metadata`](crate::exporter::export_raw_metadata).
+    pub fn read_from_metadata_file(metadata_file_path: &Path) -> Result<Self> {
+        let metadata = fs::read_to_string(metadata_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read {}.", metadata_file_path.display()))?;
+
+        let field = |name: &str| {
+            metadata
+                .lines()
+                .find_map(|line| {
+                    line.strip_prefix(name)
+                        .and_then(|rest| rest.strip_prefix(':'))
+                        .map(str::trim)
+                })
+                .ok_or_else(|| {
+                    miette!(
+                        "Missing \"{}\" in {}.",
+                        name,
+                        metadata_file_path.display()
+                    )
+                })
+        };
+
+        let parse_numbers = |name: &str| -> Result<Vec<f64>> {
+            field(name)?
+                .split_whitespace()
+                .map(|value| {
+                    value
+                        .parse::<f64>()
+                        .into_diagnostic()
+                        .wrap_err_with(|| miette!("Invalid \"{}\" value \"{}\".", name, value))
+                })
+                .collect()
+        };
+
+        if metadata.lines().any(|line| line.starts_with("frames:")) {
+            return Err(miette!(
+                "{} describes an animation with several frames, expected a single grid.",
+                metadata_file_path.display()
+            ));
+        }
+
+        let unexpected_value_count = || {
+            miette!(
+                "Unexpected number of values in {}.",
+                metadata_file_path.display()
+            )
+        };
+
+        let [dimension_x, dimension_y, dimension_z] = parse_numbers("dimensions")?[..] else {
+            return Err(unexpected_value_count());
+        };
+        let [origin_x, origin_y, origin_z] = parse_numbers("origin")?[..] else {
+            return Err(unexpected_value_count());
+        };
+        let [voxel_size] = parse_numbers("voxel size")?[..] else {
+            return Err(unexpected_value_count());
+        };
+
+        let dimensions = [dimension_x, dimension_y, dimension_z];
+        if dimensions
+            .iter()
+            .any(|length| *length < 1.0 || length.fract() != 0.0)
+        {
+            return Err(miette!(
+                "The dimensions in {} must be positive whole numbers.",
+                metadata_file_path.display()
+            ));
+        }
+
+        if voxel_size <= 0.0 {
+            return Err(miette!(
+                "The voxel size in {} must be positive.",
+                metadata_file_path.display()
+            ));
+        }
+
+        Ok(Self {
+            dimensions: U64Vec3::new(
+                dimension_x as u64,
+                dimension_y as u64,
+                dimension_z as u64,
+            ),
+            starting_point: Vec3::new(origin_x as f32, origin_y as f32, origin_z as f32),
+            voxel_size: voxel_size as f32,
+        })
+    }
+
+    fn voxel_count(&self) -> Result<usize> {
+        self.dimensions
+            .x
+            .checked_mul(self.dimensions.y)
+            .and_then(|count| count.checked_mul(self.dimensions.z))
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| {
+                miette!(
+                    "The grid dimensions {} x {} x {} are too large.",
+                    self.dimensions.x,
+                    self.dimensions.y,
+                    self.dimensions.z
+                )
+            })
+    }
+}
+
+
+
+/// Reconstructs a voxel grid from raw exported files.
+///
+/// Missing color, metallic and roughness values of edge voxels are set
+/// to the [default material](crate::mesh::MeshMaterial::default) values.
+pub fn import_voxel_grid_from_raw(
+    files: &RawVoxelGridFiles,
+    layout: &RawVoxelGridLayout,
+) -> Result<VoxelGrid> {
+    let voxel_count = layout.voxel_count()?;
+
+    let occupancy = read_bit_packed_file(files.occupancy_file_path, voxel_count)?;
+
+    let fill = files
+        .fill_file_path
+        .map(|path| read_bit_packed_file(path, voxel_count))
+        .transpose()?;
+
+    let colors = files
+        .color_file_path
+        .map(|path| {
+            let byte_count = voxel_count
+                .checked_mul(3)
+                .ok_or_else(|| miette!("The color file of the grid is too large."))?;
+
+            read_u8_file(path, byte_count)
+        })
+        .transpose()?;
+
+    let metallic_values = files
+        .metallic_file_path
+        .map(|path| read_u8_file(path, voxel_count))
+        .transpose()?;
+
+    let roughness_values = files
+        .roughness_file_path
+        .map(|path| read_u8_file(path, voxel_count))
+        .transpose()?;
+
+
+    let voxels = (0..voxel_count)
+        .map(|index| {
+            if !occupancy[index] {
+                return VoxelData::Empty;
+            }
+
+            if fill.as_ref().map(|fill| fill[index]).unwrap_or(false) {
+                return VoxelData::InsideMesh;
+            }
+
+            let color = colors
+                .as_ref()
+                .map(|colors| {
+                    Vec3::new(
+                        u8_to_unit_f32(colors[index * 3]),
+                        u8_to_unit_f32(colors[index * 3 + 1]),
+                        u8_to_unit_f32(colors[index * 3 + 2]),
+                    )
+                })
+                .unwrap_or(Vec3::ONE);
+
+            VoxelData::Edge {
+                color,
+                metallic_value: metallic_values
+                    .as_ref()
+                    .map(|values| u8_to_unit_f32(values[index]))
+                    .unwrap_or(DEFAULT_METALLIC),
+                rougness_value: roughness_values
+                    .as_ref()
+                    .map(|values| u8_to_unit_f32(values[index]))
+                    .unwrap_or(DEFAULT_ROUGHNESS),
+                intensity: 0.0,
+            }
+        })
+        .collect::<Vec<_>>();
+
+
+    Ok(VoxelGrid::from_voxels(
+        layout.starting_point,
+        layout.voxel_size / 2.0,
+        layout.dimensions.x,
+        layout.dimensions.y,
+        layout.dimensions.z,
+        voxels,
+    ))
+}
+
+
+
+#[inline]
+fn u8_to_unit_f32(value: u8) -> f32 {
+    value as f32 / u8::MAX as f32
+}
+
+
+fn read_file_with_expected_length(file_path: &Path, expected_length: usize) -> Result<Vec<u8>> {
+    let data = fs::read(file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to read {}.", file_path.display()))?;
+
+    if data.len() != expected_length {
+        return Err(miette!(
+            "Unexpected size of {}: expected {} bytes, got {}. \
+            Are the grid dimensions correct?",
+            file_path.display(),
+            expected_length,
+            data.len()
+        ));
+    }
+
+    Ok(data)
+}
+
+
+fn read_u8_file(file_path: &Path, value_count: usize) -> Result<Vec<u8>> {
+    read_file_with_expected_length(file_path, value_count)
+}
+
+
+/// Reads a file with one bit per voxel (the first voxel is in the most significant bit).
+fn read_bit_packed_file(file_path: &Path, voxel_count: usize) -> Result<Vec<bool>> {
+    let data = read_file_with_expected_length(file_path, voxel_count.div_ceil(8))?;
+
+    Ok((0..voxel_count)
+        .map(|index| (data[index / 8] >> (7 - (index % 8))) & 1 == 1)
+        .collect())
+}
@@ -0,0 +1,150 @@
+//! Loading and voxelization of input files, which are either mesh scenes or point clouds,
+//! or previously exported voxel containers.
+
+use std::path::Path;
+
+use miette::{miette, Context, Result};
+
+use crate::{
+    exporter::container::CONTAINER_FILE_EXTENSION,
+    importer::container::VoxelContainer,
+    mesh::{load_mesh_scene_from_file, GltfPose, MeshScene},
+    point_cloud::{is_point_cloud_file, load_point_cloud_from_file, PointCloud},
+    voxelizer::{
+        aabb::Aabb,
+        grid::{ContextualVoxelGrid, VoxelGrid},
+        voxelize_instanced_model,
+        voxelize_models,
+        voxelize_point_cloud,
+        MeshVoxelizationOptions,
+        PointCloudVoxelizationOptions,
+    },
+};
+
+
+/// An input file: either a scene that still has to be voxelized,
+/// or a previously exported voxel grid that is used as it is.
+pub enum InputFile {
+    Scene(InputScene),
+    VoxelGrid(VoxelGrid),
+}
+
+impl InputFile {
+    /// Loads a voxel container (`.nvox`) as a grid, or any other file as a scene
+    /// (see [`InputScene::load_from_file`]).
+    pub fn load_from_file(file_path: &Path, gltf_pose: &GltfPose) -> Result<Self> {
+        if !is_voxel_container_file(file_path) {
+            return Ok(Self::Scene(InputScene::load_from_file(
+                file_path, gltf_pose,
+            )?));
+        }
+
+        let container = VoxelContainer::read_from_file(file_path)?;
+
+        Ok(Self::VoxelGrid(
+            container
+                .to_voxel_grid()
+                .wrap_err("Failed to import voxel container.")?,
+        ))
+    }
+
+    /// Voxelizes a scene (see [`InputScene::voxelize`]), or returns a loaded grid as a single model.
+    /// The voxel size is only required (and the bounds only used) for scenes.
+    pub fn into_voxelized_models(
+        self,
+        voxelization_bounds: Aabb,
+        voxel_size: Option<f32>,
+        mesh_options: &MeshVoxelizationOptions,
+        point_cloud_options: &PointCloudVoxelizationOptions,
+    ) -> Result<Vec<ContextualVoxelGrid>> {
+        match self {
+            InputFile::Scene(input_scene) => {
+                let voxel_size = voxel_size.ok_or_else(|| {
+                    miette!("A voxel size (--voxel-size) is required to voxelize meshes and point clouds.")
+                })?;
+
+                Ok(input_scene.voxelize(
+                    voxelization_bounds,
+                    voxel_size,
+                    mesh_options,
+                    point_cloud_options,
+                ))
+            }
+            InputFile::VoxelGrid(grid) => Ok(vec![ContextualVoxelGrid {
+                gltf_model_primitive_index: 0,
+                grid,
+            }]),
+        }
+    }
+}
+
+
+fn is_voxel_container_file(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(CONTAINER_FILE_EXTENSION))
+}
+
+
+
+pub enum InputScene {
+    Mesh(MeshScene),
+    PointCloud(PointCloud),
+}
+
+impl InputScene {
+    /// Loads a mesh scene or a point cloud, depending on the file (see [`is_point_cloud_file`]).
+    /// glTF scenes are evaluated at the given pose.
+    pub fn load_from_file(file_path: &Path, gltf_pose: &GltfPose) -> Result<Self> {
+        if is_point_cloud_file(file_path)? {
+            Ok(Self::PointCloud(load_point_cloud_from_file(
+                file_path,
+            )?))
+        } else {
+            Ok(Self::Mesh(load_mesh_scene_from_file(
+                file_path, gltf_pose,
+            )?))
+        }
+    }
+
+    /// Voxelizes each model of a mesh scene into its own grid,
+    /// or the whole point cloud into a single grid.
+    pub fn voxelize(
+        &self,
+        voxelization_bounds: Aabb,
+        voxel_size: f32,
+        mesh_options: &MeshVoxelizationOptions,
+        point_cloud_options: &PointCloudVoxelizationOptions,
+    ) -> Vec<ContextualVoxelGrid> {
+        match self {
+            InputScene::Mesh(mesh_scene) => {
+                let mut voxelized_models = voxelize_models(
+                    &mesh_scene.models,
+                    voxelization_bounds,
+                    voxel_size,
+                    mesh_options,
+                );
+
+                voxelized_models.extend(
+                    mesh_scene.instanced_models.iter().map(|instanced_model| {
+                        voxelize_instanced_model(
+                            instanced_model,
+                            &voxelization_bounds,
+                            voxel_size,
+                            mesh_options,
+                        )
+                    }),
+                );
+
+                voxelized_models
+            }
+            InputScene::PointCloud(point_cloud) => vec![voxelize_point_cloud(
+                point_cloud,
+                voxelization_bounds,
+                voxel_size,
+                point_cloud_options,
+            )],
+        }
+    }
+}
@@ -1,24 +1,61 @@
 use std::{path::Path, str::FromStr, time::Instant};
 
 use clap::Parser;
-use cli::CliCommand;
-use easy_gltf::model::Triangle;
+use cli::{
+    AnimationExportArgs,
+    CliCommand,
+    CompareArgs,
+    ComparedGridSource,
+    ExportArgs,
+    MeshExportArgs,
+};
 use glam::Vec3 as GlamVec3;
 use miette::{miette, Context, IntoDiagnostic, Result};
 use tracing_subscriber::EnvFilter;
-use voxelizer::grid::ContextualVoxelGrid;
+use voxelizer::grid::{ContextualVoxelGrid, VoxelGrid};
 
 use crate::{
+    animation::voxelize_animation_clip,
     cli::CliArgs,
-    exporter::export_voxel_grid_as_raw,
+    exporter::{
+        container::{export_voxel_grid_as_container, hash_source_files, CONTAINER_FILE_EXTENSION},
+        export_diff_labels_as_raw,
+        export_raw_metadata,
+        export_voxel_grid_as_raw,
+        export_voxel_grids_as_raw_frames,
+        metaimage::export_voxel_grid_as_metaimage,
+        nifti::export_voxel_grid_as_nifti,
+        nrrd::export_voxel_grid_as_nrrd,
+        schematic::{export_voxel_grid_as_schematic, SCHEMATIC_FILE_EXTENSION},
+        slices::export_voxel_grid_as_png_slices,
+        surface::export_surface_mesh,
+        vox::{export_voxel_grid_as_vox, VOX_FILE_EXTENSION},
+        vtk::export_voxel_grid_as_vtk,
+        VoxelExportType,
+        VoxelOutputFormat,
+    },
+    importer::{container::VoxelContainer, import_voxel_grid_from_raw},
+    input::InputFile,
     logging::initialize_tracing,
-    voxelizer::voxelize_models,
+    processing::{
+        compare::compare_grids,
+        csg::{combine_grids, CsgOperation},
+        resample::resample,
+    },
+    surface_extraction::{extract_surface, SurfaceExtractionMethod},
 };
 
 
+mod animation;
 mod cli;
 mod exporter;
+mod importer;
+mod input;
 mod logging;
+mod mesh;
+mod point_cloud;
+mod processing;
+mod surface_extraction;
 
 #[cfg(feature = "visualization")]
 mod visualization;
@@ -28,72 +65,712 @@ use crate::visualization::run_visualization;
 mod voxelizer;
 
 
-fn load_gltf_scene_from_file<P>(gltf_file_path: P) -> Result<easy_gltf::Scene>
-where
-    P: AsRef<Path>,
-{
-    let gltf_scenes = easy_gltf::load(gltf_file_path)
-        .map_err(|error| miette!("Failed to load GLTF file: {error:?}"))?;
+/// Returns the voxelization bounds from the CLI arguments, or unlimited bounds if none were provided.
+fn voxelization_bounds_or_unlimited(cli_args: &CliArgs) -> Result<voxelizer::aabb::Aabb> {
+    Ok(cli_args
+        .voxelization_bounds()
+        .wrap_err("Invalid voxelization bounds.")?
+        .unwrap_or(voxelizer::aabb::Aabb {
+            min: GlamVec3::MIN,
+            max: GlamVec3::MAX,
+        }))
+}
+
+
+fn perform_voxelization(cli_args: &CliArgs) -> Result<Vec<ContextualVoxelGrid>> {
+    if cli_args.input_file_paths.is_empty() {
+        return Err(miette!(
+            "At least one --input-file-path is required."
+        ));
+    }
+
+    let voxelization_bounds = voxelization_bounds_or_unlimited(cli_args)?;
+
+    let csg_operation = cli_args
+        .csg_operation()
+        .wrap_err("Invalid CSG operation.")?;
+
+    let mesh_options = cli_args.mesh_voxelization_options();
+    let point_cloud_options = cli_args.point_cloud_voxelization_options()?;
+    let gltf_pose = cli_args.gltf_pose()?;
+
+
+
+    let input_files = cli_args
+        .input_file_paths
+        .iter()
+        .map(|input_file_path| {
+            InputFile::load_from_file(input_file_path, &gltf_pose).wrap_err_with(|| {
+                miette!(
+                    "Failed to load scene from {}.",
+                    input_file_path.display()
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+
+    let time_voxelization_start = Instant::now();
+
+    let voxelized_scenes = input_files
+        .into_iter()
+        .zip(cli_args.input_file_paths.iter())
+        .map(|(input_file, input_file_path)| {
+            input_file
+                .into_voxelized_models(
+                    voxelization_bounds,
+                    cli_args.voxel_size,
+                    &mesh_options,
+                    &point_cloud_options,
+                )
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to voxelize {}.",
+                        input_file_path.display()
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let time_voxelization_total = time_voxelization_start.elapsed();
+
+
+    println!(
+        "Voxelization complete in {:.1} seconds, starting visualization.",
+        time_voxelization_total.as_secs_f32()
+    );
+
 
-    let Some(first_scene) = gltf_scenes.into_iter().next() else {
-        return Err(miette!("Provided GLTF file contains no scenes."));
+    let Some(csg_operation) = csg_operation else {
+        return Ok(voxelized_scenes.into_iter().flatten().collect());
     };
 
-    Ok(first_scene)
+    let time_csg_start = Instant::now();
+
+    let mut scene_grids = voxelized_scenes
+        .into_iter()
+        .zip(cli_args.input_file_paths.iter())
+        .map(|(voxelized_models, input_file_path)| {
+            merge_voxelized_models(voxelized_models).ok_or_else(|| {
+                miette!(
+                    "Input file {} contains no models.",
+                    input_file_path.display()
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter();
+
+    let mut combined_grid = scene_grids
+        .next()
+        .expect("expected at least two voxelized scenes");
+
+    for scene_grid in scene_grids {
+        combined_grid = combine_grids(&combined_grid, &scene_grid, csg_operation);
+    }
+
+    let time_csg_total = time_csg_start.elapsed();
+
+
+    println!(
+        "CSG operation {} complete in {:.1} seconds.",
+        csg_operation,
+        time_csg_total.as_secs_f32()
+    );
+
+    Ok(vec![ContextualVoxelGrid {
+        gltf_model_primitive_index: 0,
+        grid: combined_grid,
+    }])
 }
 
 
-#[deprecated]
-#[allow(dead_code)]
-fn collect_mesh_triangles(gltf_scene: &easy_gltf::Scene) -> Result<Vec<Triangle>> {
-    let mut collected_triangles: Vec<Triangle> = Vec::new();
+/// Merges all voxelized models (e.g. of a single scene) into one grid with a union operation.
+/// Returns `None` if there are no models.
+fn merge_voxelized_models(voxelized_models: Vec<ContextualVoxelGrid>) -> Option<VoxelGrid> {
+    voxelized_models
+        .into_iter()
+        .map(|model| model.grid)
+        .reduce(|merged_grid, model_grid| {
+            combine_grids(&merged_grid, &model_grid, CsgOperation::Union)
+        })
+}
 
-    for model in &gltf_scene.models {
-        let model_triangles: Vec<Triangle> = model
-            .triangles()
-            .map_err(|error| miette!("Failed to get triangles for model. Reason: {error:?}"))?;
 
-        collected_triangles.extend(model_triangles);
-    }
+fn perform_resampling(
+    cli_args: &CliArgs,
+    voxelized_models: Vec<ContextualVoxelGrid>,
+) -> Result<Vec<ContextualVoxelGrid>> {
+    let Some(resampling_options) = cli_args
+        .resampling_options()
+        .wrap_err("Invalid resampling options.")?
+    else {
+        return Ok(voxelized_models);
+    };
 
-    Ok(collected_triangles)
+
+    let time_resampling_start = Instant::now();
+
+    let resampled_models = voxelized_models
+        .into_iter()
+        .enumerate()
+        .map(|(model_index, model)| {
+            println!(
+                "Resampling model {} ({}).",
+                model_index, resampling_options
+            );
+
+            ContextualVoxelGrid {
+                gltf_model_primitive_index: model.gltf_model_primitive_index,
+                grid: resample(&model.grid, &resampling_options),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let time_resampling_total = time_resampling_start.elapsed();
+
+
+    println!(
+        "Resampling complete in {:.1} seconds.",
+        time_resampling_total.as_secs_f32()
+    );
+
+    Ok(resampled_models)
 }
 
 
+fn perform_post_processing(
+    cli_args: &CliArgs,
+    voxelized_models: Vec<ContextualVoxelGrid>,
+) -> Result<Vec<ContextualVoxelGrid>> {
+    let post_processing_steps = cli_args
+        .post_processing_steps()
+        .wrap_err("Invalid post-processing steps.")?;
 
-fn perform_voxelization(cli_args: &CliArgs) -> Result<Vec<ContextualVoxelGrid>> {
-    let voxelization_bounds = cli_args
-        .voxelization_bounds()
-        .wrap_err("Invalid voxelization bounds.")?
-        .unwrap_or(voxelizer::aabb::Aabb {
-            min: GlamVec3::MIN,
-            max: GlamVec3::MAX,
-        });
+    if post_processing_steps.is_empty() {
+        return Ok(voxelized_models);
+    }
+
+
+    let time_post_processing_start = Instant::now();
 
+    let post_processed_models = voxelized_models
+        .into_iter()
+        .enumerate()
+        .map(|(model_index, model)| {
+            let mut grid = model.grid;
 
+            for step in &post_processing_steps {
+                println!(
+                    "Applying post-processing step {} to model {}.",
+                    step, model_index
+                );
 
+                grid = step.apply(grid);
+            }
 
-    let gltf_scene = load_gltf_scene_from_file(&cli_args.gltf_file_path)
-        .wrap_err("Failed to load GLTF scene.")?;
+            ContextualVoxelGrid {
+                gltf_model_primitive_index: model.gltf_model_primitive_index,
+                grid,
+            }
+        })
+        .collect::<Vec<_>>();
 
+    let time_post_processing_total = time_post_processing_start.elapsed();
 
-    let time_voxelization_start = Instant::now();
 
-    let voxelized_models = voxelize_models(
-        &gltf_scene.models,
-        voxelization_bounds,
-        cli_args.voxel_size,
+    println!(
+        "Post-processing complete in {:.1} seconds.",
+        time_post_processing_total.as_secs_f32()
     );
 
-    let time_voxelization_total = time_voxelization_start.elapsed();
+    Ok(post_processed_models)
+}
+
+
+fn perform_animation_export(
+    cli_args: &CliArgs,
+    animation_export_args: &AnimationExportArgs,
+) -> Result<()> {
+    let [input_file_path] = cli_args.input_file_paths.as_slice() else {
+        return Err(miette!(
+            "Exporting an animation requires exactly one input file."
+        ));
+    };
+
+    if !is_gltf_file_path(input_file_path) {
+        return Err(miette!(
+            "Exporting an animation requires a GLTF input file."
+        ));
+    }
+
+    let gltf_pose = cli_args.gltf_pose()?;
+    if gltf_pose.animation.is_none() {
+        return Err(miette!(
+            "Exporting an animation requires the --animation option."
+        ));
+    }
 
+    // Validates the export options before the (slow) voxelization.
+    animation_export_args.export.output_format()?;
+    animation_export_args.export.export_formats()?;
+
+
+    let time_voxelization_start = Instant::now();
+
+    let voxelized_frames = voxelize_animation_clip(
+        input_file_path,
+        &gltf_pose,
+        voxelization_bounds_or_unlimited(cli_args)?,
+        cli_args.voxel_size()?,
+        &cli_args.mesh_voxelization_options(),
+        &animation_export_args.clip_options(),
+    )
+    .wrap_err("Failed to voxelize animation clip.")?;
+
+    let time_voxelization_total = time_voxelization_start.elapsed();
 
     println!(
-        "Voxelization complete in {:.1} seconds, starting visualization.",
+        "Voxelization of {} frames complete in {:.1} seconds.",
+        voxelized_frames.len(),
         time_voxelization_total.as_secs_f32()
     );
 
-    Ok(voxelized_models)
+
+    // Resampling and post-processing don't depend on the grid contents,
+    // so the frames still share one lattice afterwards.
+    let (frame_times, frame_grids): (Vec<_>, Vec<_>) = voxelized_frames
+        .into_iter()
+        .map(|frame| {
+            (
+                frame.time,
+                ContextualVoxelGrid {
+                    gltf_model_primitive_index: 0,
+                    grid: frame.grid,
+                },
+            )
+        })
+        .unzip();
+
+    let frame_grids = perform_resampling(cli_args, frame_grids)
+        .wrap_err("Failed to resample animation frames.")?;
+    let frame_grids = perform_post_processing(cli_args, frame_grids)
+        .wrap_err("Failed to post-process animation frames.")?;
+
+    let frame_grids = frame_grids
+        .into_iter()
+        .map(|frame| frame.grid)
+        .collect::<Vec<_>>();
+
+
+    let output_file_path = &animation_export_args.export.output_file_path;
+    let output_file_name = output_file_path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| miette!("Invalid output file path."))?;
+
+    let first_grid = &frame_grids[0];
+
+    println!(
+        "Shared frame lattice: dimensions {}, origin {}, voxel size {}.",
+        first_grid.dimensions(),
+        first_grid.starting_point,
+        first_grid.voxel_size()
+    );
+
+    let output_format = animation_export_args.export.output_format()?;
+
+    if animation_export_args.single_file {
+        if output_format != VoxelOutputFormat::Raw {
+            return Err(miette!(
+                "--single-file is only supported with the raw output format."
+            ));
+        }
+
+        let voxel_export_type = animation_export_args.export.export_format()?;
+
+        export_raw_metadata(
+            output_file_path.with_file_name(format!("{}.meta.txt", output_file_name)),
+            first_grid,
+            &voxel_export_type,
+            Some(frame_grids.len()),
+        )?;
+
+        let frames_output_file_name = format!("{}.frames.bin", output_file_name);
+
+        println!(
+            "Exporting {} frames (at times {:?}) to {}...",
+            frame_grids.len(),
+            frame_times,
+            frames_output_file_name
+        );
+
+        export_voxel_grids_as_raw_frames(
+            output_file_path.with_file_name(frames_output_file_name),
+            &frame_grids,
+            &voxel_export_type,
+        )?;
+    } else {
+        let source_hash = hash_source_files(&cli_args.input_file_paths)?;
+
+        for (frame_index, (frame_time, frame_grid)) in
+            frame_times.iter().zip(&frame_grids).enumerate()
+        {
+            let frame_output_base_path = output_file_path.with_file_name(format!(
+                "{}.f-{:04}",
+                output_file_name, frame_index
+            ));
+
+            println!(
+                "Exporting frame {} (time {}) to {}...",
+                frame_index,
+                frame_time,
+                frame_output_base_path.display()
+            );
+
+            export_grid_in_output_format(
+                &animation_export_args.export,
+                &frame_output_base_path,
+                frame_grid,
+                source_hash,
+            )?;
+        }
+    }
+
+
+    Ok(())
+}
+
+
+/// Voxelizes the input files, then resamples and post-processes the resulting models.
+fn perform_scene_voxelization(cli_args: &CliArgs) -> Result<Vec<ContextualVoxelGrid>> {
+    let voxelized_scene = perform_voxelization(cli_args).wrap_err("Failed to voxelize.")?;
+    let voxelized_scene = perform_resampling(cli_args, voxelized_scene)
+        .wrap_err("Failed to resample voxelized scene.")?;
+
+    perform_post_processing(cli_args, voxelized_scene)
+        .wrap_err("Failed to post-process voxelized scene.")
+}
+
+
+fn perform_export(
+    cli_args: &CliArgs,
+    export_args: &ExportArgs,
+    voxelized_scene: Vec<ContextualVoxelGrid>,
+) -> Result<()> {
+    let output_file_name = export_args
+        .output_file_path
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+
+    let source_hash = hash_source_files(&cli_args.input_file_paths)?;
+
+    for (model_index, model) in voxelized_scene.into_iter().enumerate() {
+        let model_output_base_path = export_args
+            .output_file_path
+            .with_file_name(format!("{}.m-{}", output_file_name, model_index));
+
+        println!(
+            "Exporting model {} to {} (dimensions {}, origin {}, voxel size {})...",
+            model_index,
+            model_output_base_path.display(),
+            model.grid.dimensions(),
+            model.grid.starting_point,
+            model.grid.voxel_size()
+        );
+
+        export_grid_in_output_format(
+            export_args,
+            &model_output_base_path,
+            &model.grid,
+            source_hash,
+        )?;
+    }
+
+    Ok(())
+}
+
+
+/// Exports a grid in the selected output format. Output files are named by appending
+/// the format's extension to `output_base_path` (e.g. `model.m-0.nvox`).
+fn export_grid_in_output_format(
+    export_args: &ExportArgs,
+    output_base_path: &Path,
+    grid: &VoxelGrid,
+    source_hash: [u8; 32],
+) -> Result<()> {
+    let output_base_name = output_base_path.as_os_str().to_string_lossy();
+
+    match export_args.output_format()? {
+        VoxelOutputFormat::Raw => {
+            let voxel_export_type = export_args.export_format()?;
+
+            export_voxel_grid_as_raw(
+                format!("{}.bin", output_base_name),
+                grid,
+                &voxel_export_type,
+            )?;
+
+            export_raw_metadata(
+                format!("{}.meta.txt", output_base_name),
+                grid,
+                &voxel_export_type,
+                None,
+            )
+        }
+        VoxelOutputFormat::NativeContainer => export_voxel_grid_as_container(
+            format!(
+                "{}.{}",
+                output_base_name, CONTAINER_FILE_EXTENSION
+            ),
+            grid,
+            &export_args.export_formats()?,
+            source_hash,
+        ),
+        VoxelOutputFormat::Nrrd => export_voxel_grid_as_nrrd(
+            output_base_path,
+            grid,
+            &export_args.export_format()?,
+            &export_args.nrrd_options(),
+        ),
+        VoxelOutputFormat::MetaImage => export_voxel_grid_as_metaimage(
+            output_base_path,
+            grid,
+            &export_args.export_format()?,
+            export_args.gzip,
+        ),
+        VoxelOutputFormat::Nifti => export_voxel_grid_as_nifti(
+            output_base_path,
+            grid,
+            &export_args.export_format()?,
+            export_args.gzip,
+        ),
+        VoxelOutputFormat::VtkImageData | VoxelOutputFormat::VtkLegacy => export_voxel_grid_as_vtk(
+            output_base_path,
+            grid,
+            &export_args.export_formats()?,
+            &export_args.vtk_options()?,
+        ),
+        VoxelOutputFormat::MagicaVoxel => {
+            let VoxelExportType::PaletteIndexU8 { palette_options } = export_args.export_format()?
+            else {
+                return Err(miette!(
+                    "MagicaVoxel exports require --export-type palette-index_u8."
+                ));
+            };
+
+            export_voxel_grid_as_vox(
+                format!("{}.{}", output_base_name, VOX_FILE_EXTENSION),
+                grid,
+                &palette_options,
+                export_args.vox_include_interior,
+            )
+        }
+        VoxelOutputFormat::SpongeSchematic => {
+            let VoxelExportType::PaletteIndexU8 { .. } = export_args.export_format()? else {
+                return Err(miette!(
+                    "Schematic exports require --export-type palette-index_u8."
+                ));
+            };
+
+            export_voxel_grid_as_schematic(
+                format!(
+                    "{}.{}",
+                    output_base_name, SCHEMATIC_FILE_EXTENSION
+                ),
+                grid,
+                &export_args.schematic_options()?,
+            )
+        }
+        VoxelOutputFormat::PngSlices => export_voxel_grid_as_png_slices(
+            output_base_path,
+            grid,
+            &export_args.export_format()?,
+            &export_args.slice_options()?,
+        ),
+    }
+}
+
+
+/// Loads one of the two compared grids and applies the same resampling and post-processing
+/// steps as for the other one, so that neither side is biased. All models are merged into one grid.
+fn load_compared_grid(cli_args: &CliArgs, source: ComparedGridSource) -> Result<VoxelGrid> {
+    let models = match source {
+        ComparedGridSource::InputFiles => perform_voxelization(cli_args)?,
+        ComparedGridSource::InputFile {
+            file_path,
+            voxel_size,
+        } => InputFile::load_from_file(file_path, &cli_args.gltf_pose()?)
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to load scene from {}.",
+                    file_path.display()
+                )
+            })?
+            .into_voxelized_models(
+                voxelization_bounds_or_unlimited(cli_args)?,
+                voxel_size,
+                &cli_args.mesh_voxelization_options(),
+                &cli_args.point_cloud_voxelization_options()?,
+            )
+            .wrap_err_with(|| miette!("Failed to voxelize {}.", file_path.display()))?,
+        ComparedGridSource::VoxelContainer(file_path) => {
+            let container = VoxelContainer::read_from_file(file_path)?;
+
+            println!(
+                "Loaded voxel container {} (format version {}, dimensions {}, channels: {}, source hash {}).",
+                file_path.display(),
+                container.header.version,
+                container.header.dimensions,
+                container
+                    .header
+                    .channels
+                    .iter()
+                    .map(|channel| channel.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                container
+                    .header
+                    .source_hash
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>()
+            );
+
+            vec![ContextualVoxelGrid {
+                gltf_model_primitive_index: 0,
+                grid: container
+                    .to_voxel_grid()
+                    .wrap_err("Failed to import voxel container.")?,
+            }]
+        }
+        ComparedGridSource::Raw(raw_files, raw_layout) => vec![ContextualVoxelGrid {
+            gltf_model_primitive_index: 0,
+            grid: import_voxel_grid_from_raw(&raw_files, &raw_layout)
+                .wrap_err("Failed to import raw voxel grid.")?,
+        }],
+    };
+
+    let models = perform_resampling(cli_args, models)?;
+    let models = perform_post_processing(cli_args, models)?;
+
+    merge_voxelized_models(models).ok_or_else(|| miette!("The grid contains no models."))
+}
+
+
+fn perform_comparison(cli_args: &CliArgs, compare_args: &CompareArgs) -> Result<()> {
+    let reference_source = compare_args
+        .reference_source(!cli_args.input_file_paths.is_empty())
+        .wrap_err("Invalid comparison options.")?;
+    let against_source = compare_args
+        .against_source(cli_args.voxel_size)
+        .wrap_err("Invalid comparison options.")?;
+
+    let reference_grid = load_compared_grid(cli_args, reference_source)
+        .wrap_err("Failed to load the reference grid.")?;
+    let candidate_grid = load_compared_grid(cli_args, against_source)
+        .wrap_err("Failed to load the compared grid.")?;
+
+
+    let comparison = compare_grids(&reference_grid, &candidate_grid);
+    comparison.print_report();
+
+
+    if let Some(diff_output_file_path) = &compare_args.diff_output_file_path {
+        let diff_lattice = &comparison.diff_lattice;
+
+        println!(
+            "Exporting diff labels to {} (dimensions {}, origin {}, voxel size {})...",
+            diff_output_file_path.display(),
+            diff_lattice.dimensions(),
+            diff_lattice.starting_point,
+            diff_lattice.voxel_size()
+        );
+
+        export_diff_labels_as_raw(diff_output_file_path, &comparison.diff_labels)?;
+    }
+
+
+    Ok(())
+}
+
+
+/// Extracts the surface of each voxelized model and exports it as a mesh.
+fn perform_mesh_export(
+    mesh_export_args: &MeshExportArgs,
+    voxelized_scene: Vec<ContextualVoxelGrid>,
+) -> Result<()> {
+    let mesh_format = mesh_export_args.mesh_format()?;
+    let surface_extraction_options = mesh_export_args.surface_extraction_options()?;
+    let color_mode = mesh_export_args.mesh_color_mode()?;
+
+    let output_file_name = mesh_export_args
+        .output_file_path
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+
+    for (model_index, model) in voxelized_scene.into_iter().enumerate() {
+        let model_output_base_path = mesh_export_args
+            .output_file_path
+            .with_file_name(format!("{}.m-{}", output_file_name, model_index));
+
+        if surface_extraction_options.method == SurfaceExtractionMethod::DualContouring
+            && !model.grid.has_hermite_data()
+        {
+            println!(
+                "Model {} has no Hermite data (e.g. it is a point cloud, was loaded from a voxel container, \
+                or was resampled or post-processed), dual contouring vertices will not follow sharp features.",
+                model_index
+            );
+        }
+
+        let time_extraction_start = Instant::now();
+        let surface_mesh = extract_surface(&model.grid, &surface_extraction_options);
+
+        println!(
+            "Extracted surface of model {} ({}) in {:.4} seconds: {} vertices, {} triangles.",
+            model_index,
+            surface_extraction_options.method,
+            time_extraction_start.elapsed().as_secs_f64(),
+            surface_mesh.vertex_count(),
+            surface_mesh.triangle_count()
+        );
+
+        println!(
+            "Exporting model {} surface to {}...",
+            model_index,
+            model_output_base_path.display()
+        );
+
+        export_surface_mesh(
+            &model_output_base_path,
+            &surface_mesh,
+            mesh_format,
+            color_mode,
+        )
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to export surface of model {}.",
+                model_index
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+
+fn is_gltf_file_path(file_path: &std::path::Path) -> bool {
+    file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("gltf") || extension.eq_ignore_ascii_case("glb")
+        })
 }
 
 
@@ -118,51 +795,62 @@ fn main() -> Result<()> {
     );
 
 
-    let voxelized_scene = perform_voxelization(&cli_args).wrap_err("Failed to voxelize.")?;
-
-
-    match cli_args.command {
+    match &cli_args.command {
         #[cfg(feature = "visualization")]
         CliCommand::Visualize(visualization_args) => {
-            let visualization_voxel_size = cli_args.voxel_size
+            let visualization_voxel_size = cli_args.voxel_size()?
                 * visualization_args
                     .visualization_voxel_size_ratio
                     .unwrap_or(1.0);
 
             let initial_camera_position = visualization_args.initial_camera_position()?;
 
+            // The original scene is displayed alongside the voxels, which requires a GLTF file.
+            let original_scene_file_path = cli_args
+                .input_file_paths
+                .first()
+                .ok_or_else(|| miette!("At least one --input-file-path is required."))?;
+            if !is_gltf_file_path(original_scene_file_path) {
+                return Err(miette!(
+                    "Visualization requires the first input file to be a GLTF file."
+                ));
+            }
+
             run_visualization(
-                &cli_args.gltf_file_path,
-                voxelized_scene,
+                original_scene_file_path,
+                perform_scene_voxelization(&cli_args)?,
                 visualization_voxel_size,
                 initial_camera_position,
             );
         }
 
         CliCommand::Export(export_args) => {
-            let output_file_name = export_args
-                .output_file_path
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap();
+            perform_export(
+                &cli_args,
+                export_args,
+                perform_scene_voxelization(&cli_args)?,
+            )
+            .wrap_err("Failed to export voxelized scene.")?;
+        }
 
-            for (model_index, model) in voxelized_scene.into_iter().enumerate() {
-                let model_output_file_path = format!("{}.m-{}.bin", output_file_name, model_index);
+        // Animation clips are voxelized frame by frame, on a lattice shared by all frames.
+        CliCommand::ExportAnimation(animation_export_args) => {
+            perform_animation_export(&cli_args, animation_export_args)
+                .wrap_err("Failed to export animation.")?;
+        }
 
-                println!(
-                    "Exporting model {} to {}...",
-                    model_index, model_output_file_path
-                );
+        CliCommand::ExportMesh(mesh_export_args) => {
+            perform_mesh_export(
+                mesh_export_args,
+                perform_scene_voxelization(&cli_args)?,
+            )
+            .wrap_err("Failed to export surface meshes.")?;
+        }
 
-                export_voxel_grid_as_raw(
-                    &export_args
-                        .output_file_path
-                        .with_file_name(model_output_file_path),
-                    &model.grid,
-                    export_args.export_format()?,
-                )?;
-            }
+        // Both compared grids are loaded (and resampled and post-processed) separately.
+        CliCommand::Compare(compare_args) => {
+            perform_comparison(&cli_args, compare_args)
+                .wrap_err("Failed to compare voxel grids.")?;
         }
     };
 
@@ -0,0 +1,316 @@
+//! Sampling of glTF animations into node transforms and morph target weights.
+
+use ::gltf::animation::{util::ReadOutputs, Interpolation, Property};
+use glam::{Mat4, Quat, Vec3};
+use miette::{miette, Result};
+
+
+/// Local transforms and morph target weights of all nodes in a document,
+/// indexed by node index.
+pub struct NodePose {
+    translations: Vec<Vec3>,
+    rotations: Vec<Quat>,
+    scales: Vec<Vec3>,
+    /// Only set for nodes whose morph target weights are animated.
+    morph_weights: Vec<Option<Vec<f32>>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl NodePose {
+    pub fn from_rest_pose(document: &::gltf::Document) -> Self {
+        let node_count = document.nodes().count();
+
+        let mut translations = Vec::with_capacity(node_count);
+        let mut rotations = Vec::with_capacity(node_count);
+        let mut scales = Vec::with_capacity(node_count);
+        let mut children = Vec::with_capacity(node_count);
+
+        for node in document.nodes() {
+            let (translation, rotation, scale) = node.transform().decomposed();
+
+            translations.push(Vec3::from_array(translation));
+            rotations.push(Quat::from_array(rotation));
+            scales.push(Vec3::from_array(scale));
+            children.push(node.children().map(|child| child.index()).collect());
+        }
+
+        Self {
+            translations,
+            rotations,
+            scales,
+            morph_weights: vec![None; node_count],
+            children,
+        }
+    }
+
+    pub fn morph_weights(&self, node_index: usize) -> Option<&[f32]> {
+        self.morph_weights[node_index].as_deref()
+    }
+
+    /// Computes world transforms of all nodes reachable from the scene's root nodes.
+    /// Nodes that are not part of the scene have no world transform.
+    pub fn compute_world_transforms(&self, scene: &::gltf::Scene) -> Vec<Option<Mat4>> {
+        let mut world_transforms = vec![None; self.translations.len()];
+
+        let mut pending_nodes = scene
+            .nodes()
+            .map(|node| (node.index(), Mat4::IDENTITY))
+            .collect::<Vec<_>>();
+
+        while let Some((node_index, parent_transform)) = pending_nodes.pop() {
+            let world_transform = parent_transform
+                * Mat4::from_scale_rotation_translation(
+                    self.scales[node_index],
+                    self.rotations[node_index],
+                    self.translations[node_index],
+                );
+
+            world_transforms[node_index] = Some(world_transform);
+
+            pending_nodes.extend(
+                self.children[node_index]
+                    .iter()
+                    .map(|child_index| (*child_index, world_transform)),
+            );
+        }
+
+        world_transforms
+    }
+}
+
+
+
+/// Returns the first and last keyframe time across all channels of an animation.
+pub fn animation_time_range(
+    animation: &::gltf::Animation,
+    buffers: &[::gltf::buffer::Data],
+) -> Result<(f32, f32)> {
+    animation
+        .channels()
+        .filter_map(|channel| {
+            channel
+                .reader(|buffer| Some(&buffers[buffer.index()]))
+                .read_inputs()
+        })
+        .flatten()
+        .fold(
+            None,
+            |range: Option<(f32, f32)>, time| match range {
+                Some((start, end)) => Some((start.min(time), end.max(time))),
+                None => Some((time, time)),
+            },
+        )
+        .ok_or_else(|| miette!("Animation has no keyframes."))
+}
+
+
+/// Samples all channels of an animation at the given time (in seconds, clamped to the
+/// keyframe range of each channel) and applies them to the node pose.
+pub fn sample_animation(
+    animation: &::gltf::Animation,
+    buffers: &[::gltf::buffer::Data],
+    time: f32,
+    node_pose: &mut NodePose,
+) -> Result<()> {
+    for (channel_index, channel) in animation.channels().enumerate() {
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+        let node_index = channel.target().node().index();
+
+        let keyframe_times = reader
+            .read_inputs()
+            .ok_or_else(|| {
+                miette!(
+                    "Animation channel {} has no inputs.",
+                    channel_index
+                )
+            })?
+            .collect::<Vec<_>>();
+
+        let outputs = reader.read_outputs().ok_or_else(|| {
+            miette!(
+                "Animation channel {} has no outputs.",
+                channel_index
+            )
+        })?;
+
+        let values: Vec<f32> = match outputs {
+            ReadOutputs::Translations(translations) => translations.flatten().collect(),
+            ReadOutputs::Rotations(rotations) => rotations.into_f32().flatten().collect(),
+            ReadOutputs::Scales(scales) => scales.flatten().collect(),
+            ReadOutputs::MorphTargetWeights(weights) => weights.into_f32().collect(),
+        };
+
+        let sampler = KeyframeSampler {
+            keyframe_times: &keyframe_times,
+            values: &values,
+            interpolation: channel.sampler().interpolation(),
+        };
+
+
+        match channel.target().property() {
+            Property::Translation => {
+                node_pose.translations[node_index] = Vec3::from_slice(&sampler.sample(time, 3)?);
+            }
+            Property::Rotation => {
+                node_pose.rotations[node_index] = sampler.sample_rotation(time)?;
+            }
+            Property::Scale => {
+                node_pose.scales[node_index] = Vec3::from_slice(&sampler.sample(time, 3)?);
+            }
+            Property::MorphTargetWeights => {
+                let values_per_keyframe = match sampler.interpolation {
+                    Interpolation::CubicSpline => 3,
+                    _ => 1,
+                };
+
+                let weight_count =
+                    values.len() / (keyframe_times.len() * values_per_keyframe).max(1);
+
+                node_pose.morph_weights[node_index] = Some(sampler.sample(time, weight_count)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+
+struct KeyframeSampler<'a> {
+    keyframe_times: &'a [f32],
+    /// Flattened output values. For cubic spline interpolation, each keyframe
+    /// has an in-tangent, a value and an out-tangent, in that order.
+    values: &'a [f32],
+    interpolation: Interpolation,
+}
+
+impl KeyframeSampler<'_> {
+    /// Returns the keyframe value (or tangent) `element` of keyframe `keyframe_index`.
+    fn element(&self, keyframe_index: usize, element: usize, component_count: usize) -> &[f32] {
+        let elements_per_keyframe = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+
+        let start = (keyframe_index * elements_per_keyframe + element) * component_count;
+
+        &self.values[start..start + component_count]
+    }
+
+    fn value(&self, keyframe_index: usize, component_count: usize) -> &[f32] {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.element(keyframe_index, 1, component_count),
+            _ => self.element(keyframe_index, 0, component_count),
+        }
+    }
+
+    /// Finds the keyframe at or before `time` and the normalized
+    /// position between it and the next keyframe.
+    fn locate(&self, time: f32) -> Result<(usize, f32)> {
+        let Some(last_keyframe_index) = self.keyframe_times.len().checked_sub(1) else {
+            return Err(miette!("Animation channel has no keyframes."));
+        };
+
+        let next_keyframe_index = self
+            .keyframe_times
+            .partition_point(|keyframe_time| *keyframe_time <= time);
+
+        if next_keyframe_index == 0 {
+            return Ok((0, 0.0));
+        }
+        if next_keyframe_index > last_keyframe_index {
+            return Ok((last_keyframe_index, 0.0));
+        }
+
+        let keyframe_index = next_keyframe_index - 1;
+        let start_time = self.keyframe_times[keyframe_index];
+        let end_time = self.keyframe_times[next_keyframe_index];
+
+        Ok((
+            keyframe_index,
+            (time - start_time) / (end_time - start_time),
+        ))
+    }
+
+    fn check_value_count(&self, component_count: usize) -> Result<()> {
+        let elements_per_keyframe = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+
+        if self.values.len() < self.keyframe_times.len() * elements_per_keyframe * component_count {
+            return Err(miette!(
+                "Animation channel has fewer output values than keyframes."
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn sample(&self, time: f32, component_count: usize) -> Result<Vec<f32>> {
+        self.check_value_count(component_count)?;
+        let (keyframe_index, factor) = self.locate(time)?;
+
+        let start_value = self.value(keyframe_index, component_count);
+
+        if factor == 0.0 {
+            return Ok(start_value.to_vec());
+        }
+
+        let end_value = self.value(keyframe_index + 1, component_count);
+
+        Ok(match self.interpolation {
+            Interpolation::Step => start_value.to_vec(),
+            Interpolation::Linear => start_value
+                .iter()
+                .zip(end_value)
+                .map(|(start, end)| start + (end - start) * factor)
+                .collect(),
+            Interpolation::CubicSpline => {
+                let keyframe_duration =
+                    self.keyframe_times[keyframe_index + 1] - self.keyframe_times[keyframe_index];
+
+                let start_out_tangent = self.element(keyframe_index, 2, component_count);
+                let end_in_tangent = self.element(keyframe_index + 1, 0, component_count);
+
+                // Hermite spline basis functions.
+                let t = factor;
+                let t2 = t * t;
+                let t3 = t2 * t;
+
+                let start_value_weight = 2.0 * t3 - 3.0 * t2 + 1.0;
+                let start_tangent_weight = (t3 - 2.0 * t2 + t) * keyframe_duration;
+                let end_value_weight = -2.0 * t3 + 3.0 * t2;
+                let end_tangent_weight = (t3 - t2) * keyframe_duration;
+
+                (0..component_count)
+                    .map(|component| {
+                        start_value_weight * start_value[component]
+                            + start_tangent_weight * start_out_tangent[component]
+                            + end_value_weight * end_value[component]
+                            + end_tangent_weight * end_in_tangent[component]
+                    })
+                    .collect()
+            }
+        })
+    }
+
+    fn sample_rotation(&self, time: f32) -> Result<Quat> {
+        if self.interpolation != Interpolation::Linear {
+            return Ok(Quat::from_slice(&self.sample(time, 4)?).normalize());
+        }
+
+        self.check_value_count(4)?;
+        let (keyframe_index, factor) = self.locate(time)?;
+
+        let start_rotation = Quat::from_slice(self.value(keyframe_index, 4)).normalize();
+
+        if factor == 0.0 {
+            return Ok(start_rotation);
+        }
+
+        let end_rotation = Quat::from_slice(self.value(keyframe_index + 1, 4)).normalize();
+
+        Ok(start_rotation.slerp(end_rotation, factor))
+    }
+}
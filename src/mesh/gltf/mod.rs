@@ -0,0 +1,969 @@
+//! glTF loading, including evaluation of node hierarchies, animations,
+//! skinning and morph targets at a chosen pose.
+
+use std::{collections::HashMap, fmt::Display, path::Path, str::FromStr, sync::Arc};
+
+use glam::{Affine3A, Mat4, Quat, Vec2, Vec3, Vec4};
+use image::RgbaImage;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use self::animation::{animation_time_range, sample_animation, NodePose};
+use super::{
+    texture::{
+        FilterMode,
+        Texture,
+        TextureColorSpace,
+        TextureReference,
+        TextureSampler,
+        TextureTransform,
+        WrapMode,
+        TEX_COORD_SET_COUNT,
+    },
+    InstancedMeshModel,
+    MeshMaterial,
+    MeshModel,
+    MeshScene,
+    MeshTriangle,
+    MeshVertex,
+};
+
+mod animation;
+
+
+/// Selects an animation by its index or name.
+#[derive(Clone, PartialEq, Debug)]
+pub enum AnimationSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for AnimationSelector {
+    type Err = miette::Report;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        let selector = selector.trim();
+        if selector.is_empty() {
+            return Err(miette!(
+                "Animation name or index must not be empty."
+            ));
+        }
+
+        Ok(match selector.parse::<usize>() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(selector.to_string()),
+        })
+    }
+}
+
+impl Display for AnimationSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "{}", index),
+            Self::Name(name) => write!(f, "\"{}\"", name),
+        }
+    }
+}
+
+
+/// Morph target weights to use instead of the default or animated ones.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MorphWeightOverride {
+    /// If `None`, the weights apply to all meshes with morph targets.
+    pub mesh_name: Option<String>,
+
+    pub weights: Vec<f32>,
+}
+
+impl FromStr for MorphWeightOverride {
+    type Err = miette::Report;
+
+    /// Parses `[<mesh name>=]<weight>,<weight>,...`.
+    fn from_str(weights_str: &str) -> Result<Self, Self::Err> {
+        let (mesh_name, weights_str) = match weights_str.split_once('=') {
+            Some((mesh_name, weights_str)) => (Some(mesh_name.trim().to_string()), weights_str),
+            None => (None, weights_str),
+        };
+
+        let weights = weights_str
+            .split(',')
+            .map(|weight| {
+                weight
+                    .trim()
+                    .parse::<f32>()
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to parse morph target weight {}.", weight))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { mesh_name, weights })
+    }
+}
+
+
+/// The pose to evaluate a glTF scene at. The default is the rest pose
+/// (no animation, default morph target weights).
+#[derive(Clone, Default, Debug)]
+pub struct GltfPose {
+    pub animation: Option<AnimationSelector>,
+
+    /// Animation time in seconds (clamped to the animation's time range).
+    pub time: f32,
+
+    pub morph_weight_overrides: Vec<MorphWeightOverride>,
+}
+
+
+
+const GPU_INSTANCING_EXTENSION_NAME: &str = "EXT_mesh_gpu_instancing";
+
+
+/// A mesh placed at one or more transforms.
+struct MeshPlacement<'a> {
+    mesh: ::gltf::Mesh<'a>,
+
+    morph_weights: Vec<f32>,
+
+    /// Joint matrices of the node's skin. Skinned placements are never grouped,
+    /// as their vertices are already in world space.
+    joint_matrices: Option<Vec<Mat4>>,
+
+    instance_transforms: Vec<Mat4>,
+}
+
+
+/// A loaded glTF file, from which mesh scenes can be built at different poses.
+pub struct GltfDocument {
+    document: ::gltf::Document,
+    buffers: Vec<::gltf::buffer::Data>,
+
+    /// Converted materials, indexed by glTF material index.
+    materials: Vec<Arc<MeshMaterial>>,
+
+    /// Material for primitives that don't reference one (the glTF default material).
+    default_material: Arc<MeshMaterial>,
+}
+
+impl GltfDocument {
+    pub fn load(gltf_file_path: &Path) -> Result<Self> {
+        let (document, buffers, images) = ::gltf::import(gltf_file_path)
+            .into_diagnostic()
+            .wrap_err("Failed to load GLTF file.")?;
+
+        let mut texture_converter = TextureConverter::new(&images);
+
+        let materials = document
+            .materials()
+            .map(|material| Arc::new(texture_converter.convert_material(&material)))
+            .collect::<Vec<_>>();
+
+        // The glTF default material is fully metallic and rough (see the glTF specification,
+        // section 3.9.6), unlike the format-independent default.
+        let default_material = Arc::new(MeshMaterial {
+            metallic_factor: 1.0,
+            ..MeshMaterial::default()
+        });
+
+
+        Ok(Self {
+            document,
+            buffers,
+            materials,
+            default_material,
+        })
+    }
+
+    /// Returns the first and last keyframe time (in seconds) of the selected animation.
+    pub fn animation_time_range(&self, selector: &AnimationSelector) -> Result<(f32, f32)> {
+        animation_time_range(&self.find_animation(selector)?, &self.buffers)
+    }
+
+    /// Evaluates the default (or first) scene at the given pose, producing one model per
+    /// triangle primitive. Meshes placed more than once (by several nodes or through
+    /// `EXT_mesh_gpu_instancing`) become instanced models in local space, the rest
+    /// have vertices in world space.
+    pub fn scene_at_pose(&self, pose: &GltfPose) -> Result<MeshScene> {
+        let scene = self
+            .document
+            .default_scene()
+            .or_else(|| self.document.scenes().next())
+            .ok_or_else(|| miette!("Provided GLTF file contains no scenes."))?;
+
+        let mut node_pose = NodePose::from_rest_pose(&self.document);
+
+        if let Some(animation_selector) = &pose.animation {
+            let animation = self.find_animation(animation_selector)?;
+            sample_animation(
+                &animation,
+                &self.buffers,
+                pose.time,
+                &mut node_pose,
+            )?;
+        }
+
+        let world_transforms = node_pose.compute_world_transforms(&scene);
+
+
+        // Non-skinned placements of the same mesh with the same morph target weights are
+        // grouped, so that meshes placed several times are only voxelized once.
+        let mut mesh_placements: Vec<MeshPlacement> = Vec::new();
+
+        for node in self.document.nodes() {
+            let (Some(mesh), Some(world_transform)) = (node.mesh(), world_transforms[node.index()])
+            else {
+                continue;
+            };
+
+            let morph_weights = self.morph_weights(&node, &mesh, &node_pose, pose);
+
+            if let Some(skin) = node.skin() {
+                if node
+                    .extension_value(GPU_INSTANCING_EXTENSION_NAME)
+                    .is_some()
+                {
+                    println!(
+                        "Warning: ignoring {} on skinned node {}.",
+                        GPU_INSTANCING_EXTENSION_NAME,
+                        node.index()
+                    );
+                }
+
+                mesh_placements.push(MeshPlacement {
+                    mesh,
+                    morph_weights,
+                    joint_matrices: Some(self.joint_matrices(&skin, &world_transforms)?),
+                    instance_transforms: vec![Mat4::IDENTITY],
+                });
+
+                continue;
+            }
+
+            let instance_transforms = match self.gpu_instance_transforms(&node)? {
+                Some(local_instance_transforms) => local_instance_transforms
+                    .into_iter()
+                    .map(|local_instance_transform| world_transform * local_instance_transform)
+                    .collect(),
+                None => vec![world_transform],
+            };
+
+            let existing_placement = mesh_placements.iter_mut().find(|placement| {
+                placement.joint_matrices.is_none()
+                    && placement.mesh.index() == mesh.index()
+                    && placement.morph_weights == morph_weights
+            });
+
+            match existing_placement {
+                Some(placement) => placement.instance_transforms.extend(instance_transforms),
+                None => mesh_placements.push(MeshPlacement {
+                    mesh,
+                    morph_weights,
+                    joint_matrices: None,
+                    instance_transforms,
+                }),
+            }
+        }
+
+
+        let mut models: Vec<MeshModel> = Vec::new();
+        let mut instanced_models: Vec<InstancedMeshModel> = Vec::new();
+
+        for placement in mesh_placements {
+            if let [transform] = placement.instance_transforms.as_slice() {
+                models.extend(self.mesh_models(
+                    &placement.mesh,
+                    *transform,
+                    &placement.morph_weights,
+                    placement.joint_matrices.as_deref(),
+                )?);
+
+                continue;
+            }
+
+            let instance_transforms = placement
+                .instance_transforms
+                .iter()
+                .copied()
+                .map(Affine3A::from_mat4)
+                .collect::<Vec<_>>();
+
+            instanced_models.extend(
+                self.mesh_models(
+                    &placement.mesh,
+                    Mat4::IDENTITY,
+                    &placement.morph_weights,
+                    None,
+                )?
+                .into_iter()
+                .map(|model| InstancedMeshModel {
+                    model,
+                    instance_transforms: instance_transforms.clone(),
+                }),
+            );
+        }
+
+
+        Ok(MeshScene {
+            models,
+            instanced_models,
+        })
+    }
+
+
+    /// Builds one model per triangle primitive of the mesh.
+    fn mesh_models(
+        &self,
+        mesh: &::gltf::Mesh,
+        transform: Mat4,
+        morph_weights: &[f32],
+        joint_matrices: Option<&[Mat4]>,
+    ) -> Result<Vec<MeshModel>> {
+        let mut models: Vec<MeshModel> = Vec::new();
+
+        for primitive in mesh.primitives() {
+            let Some(triangles) = self.primitive_triangles(
+                &primitive,
+                transform,
+                morph_weights,
+                joint_matrices,
+            )?
+            else {
+                println!(
+                    "Warning: skipping primitive {} of mesh {} (mode {:?} is not supported).",
+                    primitive.index(),
+                    mesh.name().unwrap_or("(unnamed)"),
+                    primitive.mode()
+                );
+
+                continue;
+            };
+
+            let material = primitive
+                .material()
+                .index()
+                .and_then(|material_index| self.materials.get(material_index))
+                .unwrap_or(&self.default_material)
+                .clone();
+
+            models.push(MeshModel {
+                triangles,
+                material,
+                primitive_index: primitive.index(),
+            });
+        }
+
+        Ok(models)
+    }
+
+
+    /// Reads the node-local instance transforms of the `EXT_mesh_gpu_instancing` extension,
+    /// if the node uses it.
+    fn gpu_instance_transforms(&self, node: &::gltf::Node) -> Result<Option<Vec<Mat4>>> {
+        use ::gltf::{accessor::Iter as AccessorIter, animation::util::Rotations};
+
+        let Some(extension) = node.extension_value(GPU_INSTANCING_EXTENSION_NAME) else {
+            return Ok(None);
+        };
+
+        let buffer_data = |buffer: ::gltf::Buffer| Some(&self.buffers[buffer.index()][..]);
+
+        let attribute_accessor = |attribute_name: &str| {
+            extension
+                .get("attributes")
+                .and_then(|attributes| attributes.get(attribute_name))
+                .and_then(|accessor_index| accessor_index.as_u64())
+                .map(|accessor_index| {
+                    self.document
+                        .accessors()
+                        .nth(accessor_index as usize)
+                        .ok_or_else(|| {
+                            miette!(
+                                "Node {} references missing instance {} accessor {}.",
+                                node.index(),
+                                attribute_name,
+                                accessor_index
+                            )
+                        })
+                })
+                .transpose()
+        };
+
+        let invalid_accessor = |attribute_name: &str| {
+            miette!(
+                "Node {} has an invalid instance {} accessor.",
+                node.index(),
+                attribute_name
+            )
+        };
+
+
+        let translations = attribute_accessor("TRANSLATION")?
+            .map(|accessor| {
+                AccessorIter::<[f32; 3]>::new(accessor, buffer_data)
+                    .map(|translations| translations.map(Vec3::from_array).collect::<Vec<_>>())
+                    .ok_or_else(|| invalid_accessor("TRANSLATION"))
+            })
+            .transpose()?;
+
+        let rotations = attribute_accessor("ROTATION")?
+            .map(|accessor| {
+                use ::gltf::accessor::DataType;
+
+                let rotations = match accessor.data_type() {
+                    DataType::F32 => AccessorIter::new(accessor, buffer_data).map(Rotations::F32),
+                    DataType::I8 => AccessorIter::new(accessor, buffer_data).map(Rotations::I8),
+                    DataType::U8 => AccessorIter::new(accessor, buffer_data).map(Rotations::U8),
+                    DataType::I16 => AccessorIter::new(accessor, buffer_data).map(Rotations::I16),
+                    DataType::U16 => AccessorIter::new(accessor, buffer_data).map(Rotations::U16),
+                    DataType::U32 => None,
+                };
+
+                rotations
+                    .map(|rotations| {
+                        rotations
+                            .into_f32()
+                            .map(|rotation| Quat::from_array(rotation).normalize())
+                            .collect::<Vec<_>>()
+                    })
+                    .ok_or_else(|| invalid_accessor("ROTATION"))
+            })
+            .transpose()?;
+
+        let scales = attribute_accessor("SCALE")?
+            .map(|accessor| {
+                AccessorIter::<[f32; 3]>::new(accessor, buffer_data)
+                    .map(|scales| scales.map(Vec3::from_array).collect::<Vec<_>>())
+                    .ok_or_else(|| invalid_accessor("SCALE"))
+            })
+            .transpose()?;
+
+
+        let instance_counts = [
+            translations.as_ref().map(Vec::len),
+            rotations.as_ref().map(Vec::len),
+            scales.as_ref().map(Vec::len),
+        ];
+
+        let Some(instance_count) = instance_counts.into_iter().flatten().max() else {
+            return Ok(None);
+        };
+
+        if instance_counts
+            .into_iter()
+            .flatten()
+            .any(|count| count != instance_count)
+        {
+            return Err(miette!(
+                "Instance attributes of node {} have differing counts.",
+                node.index()
+            ));
+        }
+
+
+        Ok(Some(
+            (0..instance_count)
+                .map(|instance_index| {
+                    Mat4::from_scale_rotation_translation(
+                        scales
+                            .as_ref()
+                            .map_or(Vec3::ONE, |scales| scales[instance_index]),
+                        rotations.as_ref().map_or(Quat::IDENTITY, |rotations| {
+                            rotations[instance_index]
+                        }),
+                        translations.as_ref().map_or(Vec3::ZERO, |translations| {
+                            translations[instance_index]
+                        }),
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+
+    fn find_animation(&self, selector: &AnimationSelector) -> Result<::gltf::Animation<'_>> {
+        let animation = match selector {
+            AnimationSelector::Index(index) => self.document.animations().nth(*index),
+            AnimationSelector::Name(name) => self
+                .document
+                .animations()
+                .find(|animation| animation.name() == Some(name.as_str())),
+        };
+
+        animation.ok_or_else(|| {
+            let available_animations = self
+                .document
+                .animations()
+                .map(|animation| {
+                    format!(
+                        "{} ({})",
+                        animation.index(),
+                        animation.name().unwrap_or("unnamed")
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            miette!(
+                "Animation {} not found. Available animations: {}.",
+                selector,
+                if available_animations.is_empty() {
+                    "none".to_string()
+                } else {
+                    available_animations.join(", ")
+                }
+            )
+        })
+    }
+
+
+    /// Morph target weights, in order of priority: user overrides, animated weights,
+    /// node weights, mesh weights, and finally zeros.
+    fn morph_weights(
+        &self,
+        node: &::gltf::Node,
+        mesh: &::gltf::Mesh,
+        node_pose: &NodePose,
+        pose: &GltfPose,
+    ) -> Vec<f32> {
+        let user_override = pose
+            .morph_weight_overrides
+            .iter()
+            .rev()
+            .find(
+                |weight_override| match &weight_override.mesh_name {
+                    Some(mesh_name) => mesh.name() == Some(mesh_name.as_str()),
+                    None => true,
+                },
+            );
+
+        if let Some(user_override) = user_override {
+            return user_override.weights.clone();
+        }
+
+        node_pose
+            .morph_weights(node.index())
+            .map(<[f32]>::to_vec)
+            .or_else(|| node.weights().map(<[f32]>::to_vec))
+            .or_else(|| mesh.weights().map(<[f32]>::to_vec))
+            .unwrap_or_default()
+    }
+
+
+    fn joint_matrices(
+        &self,
+        skin: &::gltf::Skin,
+        world_transforms: &[Option<Mat4>],
+    ) -> Result<Vec<Mat4>> {
+        let inverse_bind_matrices = skin
+            .reader(|buffer| Some(&self.buffers[buffer.index()]))
+            .read_inverse_bind_matrices()
+            .map(|matrices| {
+                matrices
+                    .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![Mat4::IDENTITY; skin.joints().count()]);
+
+        skin.joints()
+            .zip(inverse_bind_matrices)
+            .map(|(joint, inverse_bind_matrix)| {
+                let joint_world_transform = world_transforms[joint.index()].ok_or_else(|| {
+                    miette!(
+                        "Skin joint node {} is not part of the scene.",
+                        joint.index()
+                    )
+                })?;
+
+                Ok(joint_world_transform * inverse_bind_matrix)
+            })
+            .collect()
+    }
+
+
+    /// Returns `None` if the primitive is not made of triangles.
+    fn primitive_triangles(
+        &self,
+        primitive: &::gltf::Primitive,
+        world_transform: Mat4,
+        morph_weights: &[f32],
+        joint_matrices: Option<&[Mat4]>,
+    ) -> Result<Option<Vec<MeshTriangle>>> {
+        use ::gltf::mesh::Mode;
+
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+        let mut positions = reader
+            .read_positions()
+            .ok_or_else(|| {
+                miette!(
+                    "Primitive {} has no positions.",
+                    primitive.index()
+                )
+            })?
+            .map(Vec3::from_array)
+            .collect::<Vec<_>>();
+
+        let mut tex_coords = vec![[Vec2::ZERO; TEX_COORD_SET_COUNT]; positions.len()];
+
+        for tex_coord_set in 0..TEX_COORD_SET_COUNT {
+            let Some(set_tex_coords) = reader.read_tex_coords(tex_coord_set as u32) else {
+                continue;
+            };
+
+            for (vertex_tex_coords, set_tex_coords) in
+                tex_coords.iter_mut().zip(set_tex_coords.into_f32())
+            {
+                vertex_tex_coords[tex_coord_set] = Vec2::from_array(set_tex_coords);
+            }
+        }
+
+
+        // Vertex colors are multiplied with the base color (see the glTF specification,
+        // section 3.9.2); normalized integer colors and RGB colors are converted to RGBA floats.
+        let colors = reader
+            .read_colors(0)
+            .map(|colors| {
+                colors
+                    .into_rgba_f32()
+                    .map(Vec4::from_array)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_else(|| vec![Vec4::ONE; positions.len()]);
+
+
+        // Morph targets are applied before skinning.
+        for (target_positions, weight) in reader
+            .read_morph_targets()
+            .map(|(target_positions, _, _)| target_positions)
+            .zip(morph_weights)
+        {
+            let Some(target_positions) = target_positions else {
+                continue;
+            };
+
+            if *weight == 0.0 {
+                continue;
+            }
+
+            for (position, displacement) in positions.iter_mut().zip(target_positions) {
+                *position += Vec3::from_array(displacement) * *weight;
+            }
+        }
+
+
+        // The transform of a skinned mesh node is ignored, as joint transforms already place
+        // the vertices into world space (see the glTF specification, section 3.7.3).
+        let skinning = match joint_matrices {
+            Some(joint_matrices) => match (reader.read_joints(0), reader.read_weights(0)) {
+                (Some(joints), Some(weights)) => Some((
+                    joint_matrices,
+                    joints.into_u16().collect::<Vec<_>>(),
+                    weights.into_f32().collect::<Vec<_>>(),
+                )),
+                _ => None,
+            },
+            None => None,
+        };
+
+        if let Some((joint_matrices, joints, weights)) = skinning {
+            for ((position, vertex_joints), vertex_weights) in
+                positions.iter_mut().zip(joints).zip(weights)
+            {
+                let mut skinning_matrix = Mat4::ZERO;
+
+                for (joint, weight) in vertex_joints.into_iter().zip(vertex_weights) {
+                    if weight == 0.0 {
+                        continue;
+                    }
+
+                    let joint_matrix = joint_matrices
+                        .get(joint as usize)
+                        .ok_or_else(|| miette!("Vertex references missing skin joint {}.", joint))?;
+
+                    skinning_matrix += *joint_matrix * weight;
+                }
+
+                *position = skinning_matrix.transform_point3(*position);
+            }
+        } else {
+            for position in &mut positions {
+                *position = world_transform.transform_point3(*position);
+            }
+        }
+
+
+        let vertices = positions
+            .into_iter()
+            .zip(tex_coords)
+            .zip(colors)
+            .map(|((position, tex_coords), color)| MeshVertex {
+                position,
+                tex_coords,
+                color,
+            })
+            .collect::<Vec<_>>();
+
+        let indices = reader
+            .read_indices()
+            .map(|indices| {
+                indices
+                    .into_u32()
+                    .map(|index| index as usize)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_else(|| (0..vertices.len()).collect());
+
+        let triangle_indices: Vec<[usize; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            Mode::TriangleStrip => indices
+                .windows(3)
+                .enumerate()
+                .map(|(triangle_index, triangle)| {
+                    // Every other triangle in a strip has reversed winding.
+                    if triangle_index % 2 == 0 {
+                        [triangle[0], triangle[1], triangle[2]]
+                    } else {
+                        [triangle[1], triangle[0], triangle[2]]
+                    }
+                })
+                .collect(),
+            Mode::TriangleFan => indices
+                .windows(2)
+                .skip(1)
+                .map(|edge| [indices[0], edge[0], edge[1]])
+                .collect(),
+            _ => return Ok(None),
+        };
+
+
+        triangle_indices
+            .into_iter()
+            .map(|triangle| {
+                let vertex_at = |index: usize| {
+                    vertices.get(index).copied().ok_or_else(|| {
+                        miette!(
+                            "Primitive {} references missing vertex {}.",
+                            primitive.index(),
+                            index
+                        )
+                    })
+                };
+
+                Ok([
+                    vertex_at(triangle[0])?,
+                    vertex_at(triangle[1])?,
+                    vertex_at(triangle[2])?,
+                ])
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+}
+
+
+pub fn load_gltf_scene(gltf_file_path: &Path, pose: &GltfPose) -> Result<MeshScene> {
+    GltfDocument::load(gltf_file_path)?.scene_at_pose(pose)
+}
+
+
+
+/// Converts glTF images into textures, reusing already converted ones.
+struct TextureConverter<'i> {
+    images: &'i [::gltf::image::Data],
+    textures: HashMap<(usize, TextureColorSpace), Arc<Texture>>,
+}
+
+impl<'i> TextureConverter<'i> {
+    fn new(images: &'i [::gltf::image::Data]) -> Self {
+        Self {
+            images,
+            textures: HashMap::new(),
+        }
+    }
+
+    fn convert_material(&mut self, material: &::gltf::Material) -> MeshMaterial {
+        let pbr = material.pbr_metallic_roughness();
+
+        // Metallic and roughness values share a texture (in the blue and green channels).
+        let metallic_roughness_texture = pbr
+            .metallic_roughness_texture()
+            .map(|texture_info| self.texture_reference(&texture_info, TextureColorSpace::Linear));
+
+        MeshMaterial {
+            base_color_factor: Vec4::from_array(pbr.base_color_factor()),
+            base_color_texture: pbr
+                .base_color_texture()
+                .map(|texture_info| self.texture_reference(&texture_info, TextureColorSpace::Srgb)),
+            metallic_factor: pbr.metallic_factor(),
+            metallic_texture: metallic_roughness_texture.clone(),
+            roughness_factor: pbr.roughness_factor(),
+            roughness_texture: metallic_roughness_texture,
+        }
+    }
+
+    fn texture_reference(
+        &mut self,
+        texture_info: &::gltf::texture::Info,
+        color_space: TextureColorSpace,
+    ) -> TextureReference {
+        let gltf_texture = texture_info.texture();
+        let image_index = gltf_texture.source().index();
+
+        let images = self.images;
+        let texture = self
+            .textures
+            .entry((image_index, color_space))
+            .or_insert_with(|| {
+                Arc::new(Texture::new(
+                    convert_image_to_rgba(&images[image_index]),
+                    color_space,
+                ))
+            })
+            .clone();
+
+
+        let texture_transform = texture_info.texture_transform();
+
+        // The extension can override the texture coordinate set.
+        let tex_coord_set = texture_transform
+            .as_ref()
+            .and_then(|texture_transform| texture_transform.tex_coord())
+            .unwrap_or(texture_info.tex_coord()) as usize;
+
+        let tex_coord_set = if tex_coord_set < TEX_COORD_SET_COUNT {
+            tex_coord_set
+        } else {
+            println!(
+                "Warning: texture {} uses texture coordinate set {}, \
+                but only the first {} are supported; using set 0.",
+                gltf_texture.index(),
+                tex_coord_set,
+                TEX_COORD_SET_COUNT
+            );
+
+            0
+        };
+
+
+        TextureReference {
+            texture,
+            sampler: convert_sampler(&gltf_texture.sampler()),
+            transform: texture_transform
+                .map(|texture_transform| TextureTransform {
+                    offset: Vec2::from_array(texture_transform.offset()),
+                    rotation: texture_transform.rotation(),
+                    scale: Vec2::from_array(texture_transform.scale()),
+                })
+                .unwrap_or_default(),
+            tex_coord_set,
+        }
+    }
+}
+
+
+fn convert_sampler(sampler: &::gltf::texture::Sampler) -> TextureSampler {
+    use ::gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let convert_wrap_mode = |wrapping_mode: WrappingMode| match wrapping_mode {
+        WrappingMode::ClampToEdge => WrapMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => WrapMode::MirroredRepeat,
+        WrappingMode::Repeat => WrapMode::Repeat,
+    };
+
+    let default_sampler = TextureSampler::default();
+
+    let (minification_filter, mipmap_filter) = match sampler.min_filter() {
+        None => (
+            default_sampler.minification_filter,
+            default_sampler.mipmap_filter,
+        ),
+        Some(MinFilter::Nearest) => (FilterMode::Nearest, None),
+        Some(MinFilter::Linear) => (FilterMode::Linear, None),
+        Some(MinFilter::NearestMipmapNearest) => (FilterMode::Nearest, Some(FilterMode::Nearest)),
+        Some(MinFilter::LinearMipmapNearest) => (FilterMode::Linear, Some(FilterMode::Nearest)),
+        Some(MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, Some(FilterMode::Linear)),
+        Some(MinFilter::LinearMipmapLinear) => (FilterMode::Linear, Some(FilterMode::Linear)),
+    };
+
+    TextureSampler {
+        wrap_s: convert_wrap_mode(sampler.wrap_s()),
+        wrap_t: convert_wrap_mode(sampler.wrap_t()),
+        magnification_filter: match sampler.mag_filter() {
+            None => default_sampler.magnification_filter,
+            Some(MagFilter::Nearest) => FilterMode::Nearest,
+            Some(MagFilter::Linear) => FilterMode::Linear,
+        },
+        minification_filter,
+        mipmap_filter,
+    }
+}
+
+
+/// Converts any glTF image format to 8-bit RGBA. Grayscale images are expanded to RGB,
+/// 16-bit channels are truncated to 8 bits and float channels are clamped to `[0, 1]`.
+fn convert_image_to_rgba(image_data: &::gltf::image::Data) -> RgbaImage {
+    use ::gltf::image::Format;
+
+    let (channel_count, bytes_per_channel) = match image_data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let read_channel = |pixel_bytes: &[u8], channel_index: usize| -> u8 {
+        let channel_bytes = &pixel_bytes[channel_index * bytes_per_channel..][..bytes_per_channel];
+
+        match bytes_per_channel {
+            1 => channel_bytes[0],
+            // Little-endian, so the second byte is the most significant one.
+            2 => channel_bytes[1],
+            _ => {
+                let value = f32::from_le_bytes([
+                    channel_bytes[0],
+                    channel_bytes[1],
+                    channel_bytes[2],
+                    channel_bytes[3],
+                ]);
+
+                (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+            }
+        }
+    };
+
+    let rgba_pixels = image_data
+        .pixels
+        .chunks_exact(channel_count * bytes_per_channel)
+        .flat_map(|pixel_bytes| match channel_count {
+            1 => {
+                let gray = read_channel(pixel_bytes, 0);
+                [gray, gray, gray, u8::MAX]
+            }
+            2 => {
+                let gray = read_channel(pixel_bytes, 0);
+                [gray, gray, gray, read_channel(pixel_bytes, 1)]
+            }
+            3 => [
+                read_channel(pixel_bytes, 0),
+                read_channel(pixel_bytes, 1),
+                read_channel(pixel_bytes, 2),
+                u8::MAX,
+            ],
+            _ => [
+                read_channel(pixel_bytes, 0),
+                read_channel(pixel_bytes, 1),
+                read_channel(pixel_bytes, 2),
+                read_channel(pixel_bytes, 3),
+            ],
+        })
+        .collect::<Vec<_>>();
+
+    RgbaImage::from_raw(image_data.width, image_data.height, rgba_pixels)
+        .expect("expected converted image data to match its dimensions")
+}
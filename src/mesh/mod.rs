@@ -0,0 +1,280 @@
+//! Format-independent triangle meshes and materials, along with loaders
+//! for the supported input formats (selected by file extension).
+
+use std::{path::Path, sync::Arc};
+
+use glam::{Affine3A, Vec2, Vec3, Vec4};
+use miette::{miette, Result};
+
+use self::texture::TextureReference;
+pub use self::{
+    gltf::{AnimationSelector, GltfDocument, GltfPose, MorphWeightOverride},
+    texture::{SurfacePoint, TEX_COORD_SET_COUNT},
+};
+
+mod gltf;
+mod obj;
+pub(crate) mod ply;
+mod stl;
+mod texture;
+
+
+/// A single mesh vertex, with attributes relevant for voxelization.
+#[derive(Clone, Copy, Debug)]
+pub struct MeshVertex {
+    pub position: Vec3,
+
+    /// Texture coordinate sets, with `(0, 0)` being the top-left corner of the texture
+    /// (the glTF convention). Missing sets are zero.
+    pub tex_coords: [Vec2; TEX_COORD_SET_COUNT],
+
+    /// Linear RGBA vertex color, multiplied with the material base color.
+    /// Vertices without a color are white.
+    pub color: Vec4,
+}
+
+impl MeshVertex {
+    pub fn from_position(position: Vec3) -> Self {
+        Self {
+            position,
+            tex_coords: [Vec2::ZERO; TEX_COORD_SET_COUNT],
+            color: Vec4::ONE,
+        }
+    }
+}
+
+
+pub type MeshTriangle = [MeshVertex; 3];
+
+
+/// Metallic value of the default material, also used for voxels without material information.
+pub const DEFAULT_METALLIC: f32 = 0.0;
+
+/// Roughness value of the default material, also used for voxels without material information.
+pub const DEFAULT_ROUGHNESS: f32 = 1.0;
+
+
+/// Metallic-roughness material (the glTF PBR model), which all input formats are mapped to.
+#[derive(Clone, Debug)]
+pub struct MeshMaterial {
+    /// Linear RGBA base color factor.
+    pub base_color_factor: Vec4,
+
+    /// Base color texture (usually sRGB-encoded).
+    pub base_color_texture: Option<TextureReference>,
+
+    pub metallic_factor: f32,
+    /// Texture with metallic values in its blue channel (the glTF convention).
+    pub metallic_texture: Option<TextureReference>,
+
+    pub roughness_factor: f32,
+    /// Texture with roughness values in its green channel (the glTF convention).
+    pub roughness_texture: Option<TextureReference>,
+}
+
+impl Default for MeshMaterial {
+    /// A white, non-metallic and fully rough material,
+    /// used for formats and models that have no material information.
+    fn default() -> Self {
+        Self {
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            metallic_factor: DEFAULT_METALLIC,
+            metallic_texture: None,
+            roughness_factor: DEFAULT_ROUGHNESS,
+            roughness_texture: None,
+        }
+    }
+}
+
+impl MeshMaterial {
+    /// Returns the linear RGBA base color at the given surface point.
+    pub fn base_color(&self, surface_point: &SurfacePoint) -> Vec4 {
+        match &self.base_color_texture {
+            Some(texture) => self.base_color_factor * texture.sample(surface_point),
+            None => self.base_color_factor,
+        }
+    }
+
+    pub fn metallic(&self, surface_point: &SurfacePoint) -> f32 {
+        match &self.metallic_texture {
+            Some(texture) => self.metallic_factor * texture.sample(surface_point).z,
+            None => self.metallic_factor,
+        }
+    }
+
+    pub fn roughness(&self, surface_point: &SurfacePoint) -> f32 {
+        match &self.roughness_texture {
+            Some(texture) => self.roughness_factor * texture.sample(surface_point).y,
+            None => self.roughness_factor,
+        }
+    }
+}
+
+
+/// Converts an sRGB-encoded color component in `[0, 1]` to linear space
+/// (the piecewise sRGB transfer function of IEC 61966-2-1).
+#[inline]
+pub(crate) fn srgb_component_to_linear(component: f32) -> f32 {
+    if component <= 0.04045 {
+        component / 12.92
+    } else {
+        ((component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Inverse of [`srgb_component_to_linear`].
+#[inline]
+pub(crate) fn linear_component_to_srgb(component: f32) -> f32 {
+    if component <= 0.0031308 {
+        component * 12.92
+    } else {
+        1.055 * component.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+
+
+/// A triangle mesh with a single material.
+pub struct MeshModel {
+    pub triangles: Vec<MeshTriangle>,
+
+    pub material: Arc<MeshMaterial>,
+
+    /// Index of the primitive (or object) this model was created from, within its mesh (or file).
+    pub primitive_index: usize,
+}
+
+
+impl MeshModel {
+    /// Returns a copy of the model with all vertices transformed.
+    pub fn transformed(&self, transform: &Affine3A) -> Self {
+        Self {
+            triangles: self
+                .triangles
+                .iter()
+                .map(|triangle| {
+                    triangle.map(|vertex| MeshVertex {
+                        position: transform.transform_point3(vertex.position),
+                        ..vertex
+                    })
+                })
+                .collect(),
+            material: self.material.clone(),
+            primitive_index: self.primitive_index,
+        }
+    }
+}
+
+
+/// A model placed into the scene several times. It is voxelized once in local space
+/// and then stamped into the world grid at each instance transform where possible
+/// (see [`voxelize_instanced_model`](crate::voxelizer::voxelize_instanced_model)).
+pub struct InstancedMeshModel {
+    /// The model, with vertices in local space.
+    pub model: MeshModel,
+
+    /// Local-to-world transforms of all instances.
+    pub instance_transforms: Vec<Affine3A>,
+}
+
+impl InstancedMeshModel {
+    /// Converts the instances into a single model with vertices in world space.
+    pub fn into_world_space_model(self) -> MeshModel {
+        let triangles = self
+            .instance_transforms
+            .iter()
+            .flat_map(|instance_transform| {
+                self.model.triangles.iter().map(|triangle| {
+                    triangle.map(|vertex| MeshVertex {
+                        position: instance_transform.transform_point3(vertex.position),
+                        ..vertex
+                    })
+                })
+            })
+            .collect();
+
+        MeshModel {
+            triangles,
+            ..self.model
+        }
+    }
+}
+
+
+pub struct MeshScene {
+    /// Models with vertices in world space.
+    pub models: Vec<MeshModel>,
+
+    /// Models that are instanced several times (only produced by the glTF loader).
+    pub instanced_models: Vec<InstancedMeshModel>,
+}
+
+impl MeshScene {
+    /// Returns all models with vertices in world space, expanding instanced models.
+    pub fn into_world_space_models(self) -> Vec<MeshModel> {
+        self.models
+            .into_iter()
+            .chain(
+                self.instanced_models
+                    .into_iter()
+                    .map(InstancedMeshModel::into_world_space_model),
+            )
+            .collect()
+    }
+}
+
+
+/// Supported extensions, listed in error messages.
+const SUPPORTED_MESH_FILE_EXTENSIONS: &str = "gltf, glb, obj, stl, ply";
+
+
+/// Loads a mesh scene, choosing the loader based on the file extension.
+///
+/// glTF scenes are evaluated at the given pose, which is ignored for other formats.
+pub fn load_mesh_scene_from_file(file_path: &Path, gltf_pose: &GltfPose) -> Result<MeshScene> {
+    let extension = file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "gltf" | "glb" => gltf::load_gltf_scene(file_path, gltf_pose),
+        "obj" => obj::load_obj_scene(file_path),
+        "stl" => stl::load_stl_scene(file_path),
+        "ply" => ply::load_ply_scene(file_path),
+        _ => Err(miette!(
+            "Unsupported input file extension \"{}\", expected one of: {}.",
+            extension,
+            SUPPORTED_MESH_FILE_EXTENSIONS
+        )),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_transfer_function_is_exact_and_round_trips() {
+        // Dark tones lie on the linear segment of the curve.
+        assert_eq!(
+            (linear_component_to_srgb(0.001) * 255.0).round(),
+            3.0
+        );
+        assert!((srgb_component_to_linear(0.04) - 0.04 / 12.92).abs() < 1e-7);
+
+        assert!((srgb_component_to_linear(0.5) - 0.214_041_14).abs() < 1e-6);
+        assert_eq!(srgb_component_to_linear(1.0), 1.0);
+
+        for value in 0..=u8::MAX {
+            let srgb_component = value as f32 / 255.0;
+            let round_tripped = linear_component_to_srgb(srgb_component_to_linear(srgb_component));
+
+            assert_eq!((round_tripped * 255.0).round() as u8, value);
+        }
+    }
+}
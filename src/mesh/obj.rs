@@ -0,0 +1,320 @@
+//! Wavefront OBJ loading (with MTL materials, if available).
+//!
+//! MTL materials are mapped to the metallic-roughness model as follows:
+//! the diffuse color (`Kd`) and texture (`map_Kd`) become the base color, the dissolve value (`d`)
+//! becomes its alpha, and the PBR extension values (`Pm`, `Pr`, `map_Pm`, `map_Pr`) are used
+//! for metallic and roughness values. Without `Pr`, roughness is approximated from the
+//! specular exponent (`Ns`).
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use glam::{Vec2, Vec3, Vec4};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::{
+    srgb_component_to_linear,
+    texture::{Texture, TextureColorSpace, TextureReference, TEX_COORD_SET_COUNT},
+    MeshMaterial,
+    MeshModel,
+    MeshScene,
+    MeshVertex,
+};
+
+
+pub fn load_obj_scene(obj_file_path: &Path) -> Result<MeshScene> {
+    let (obj_models, obj_materials) = tobj::load_obj(
+        obj_file_path,
+        &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+        },
+    )
+    .into_diagnostic()
+    .wrap_err("Failed to parse OBJ file.")?;
+
+    let obj_materials = obj_materials.unwrap_or_else(|error| {
+        println!(
+            "Warning: failed to load OBJ materials ({}), using default materials.",
+            error
+        );
+
+        Vec::new()
+    });
+
+
+    let material_directory_path = obj_file_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let mut texture_cache = TextureCache::new(material_directory_path);
+
+    let materials = obj_materials
+        .iter()
+        .map(|material| Arc::new(convert_material(material, &mut texture_cache)))
+        .collect::<Vec<_>>();
+
+    let default_material = Arc::new(MeshMaterial::default());
+
+
+    let models = obj_models
+        .iter()
+        .enumerate()
+        .map(|(model_index, model)| {
+            let material = model
+                .mesh
+                .material_id
+                .and_then(|material_id| materials.get(material_id))
+                .unwrap_or(&default_material)
+                .clone();
+
+            Ok(MeshModel {
+                triangles: collect_triangles(&model.mesh).wrap_err_with(|| {
+                    miette!(
+                        "Invalid geometry in OBJ object \"{}\".",
+                        model.name
+                    )
+                })?,
+                material,
+                primitive_index: model_index,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+
+    Ok(MeshScene {
+        models,
+        instanced_models: Vec::new(),
+    })
+}
+
+
+fn collect_triangles(mesh: &tobj::Mesh) -> Result<Vec<[MeshVertex; 3]>> {
+    let vertex_count = mesh.positions.len() / 3;
+
+    let has_tex_coords = mesh.texcoords.len() == vertex_count * 2;
+    let has_colors = mesh.vertex_color.len() == vertex_count * 3;
+
+    let vertex_at = |vertex_index: usize| -> Result<MeshVertex> {
+        if vertex_index >= vertex_count {
+            return Err(miette!(
+                "Face references missing vertex {}.",
+                vertex_index
+            ));
+        }
+
+        let position = Vec3::from_slice(&mesh.positions[vertex_index * 3..vertex_index * 3 + 3]);
+
+        // OBJ texture coordinates have their origin in the bottom-left corner.
+        // OBJ files only have a single texture coordinate set.
+        let mut tex_coords = [Vec2::ZERO; TEX_COORD_SET_COUNT];
+        if has_tex_coords {
+            let obj_tex_coords =
+                Vec2::from_slice(&mesh.texcoords[vertex_index * 2..vertex_index * 2 + 2]);
+            tex_coords[0] = Vec2::new(obj_tex_coords.x, 1.0 - obj_tex_coords.y);
+        }
+
+        let color = if has_colors {
+            let srgb_color =
+                Vec3::from_slice(&mesh.vertex_color[vertex_index * 3..vertex_index * 3 + 3]);
+
+            Vec3::from_array(srgb_color.to_array().map(srgb_component_to_linear)).extend(1.0)
+        } else {
+            Vec4::ONE
+        };
+
+        Ok(MeshVertex {
+            position,
+            tex_coords,
+            color,
+        })
+    };
+
+
+    mesh.indices
+        .chunks_exact(3)
+        .map(|triangle_indices| {
+            Ok([
+                vertex_at(triangle_indices[0] as usize)?,
+                vertex_at(triangle_indices[1] as usize)?,
+                vertex_at(triangle_indices[2] as usize)?,
+            ])
+        })
+        .collect()
+}
+
+
+fn convert_material(material: &tobj::Material, texture_cache: &mut TextureCache) -> MeshMaterial {
+    let unknown_parameter_value = |name: &str| -> Option<f32> {
+        material
+            .unknown_param
+            .get(name)
+            .and_then(|value| value.trim().parse::<f32>().ok())
+    };
+
+    let default_material = MeshMaterial::default();
+
+    let base_color_factor = material
+        .diffuse
+        .map(Vec3::from_array)
+        .unwrap_or(Vec3::ONE)
+        .extend(material.dissolve.unwrap_or(1.0));
+
+    let roughness_factor = unknown_parameter_value("Pr")
+        .or_else(|| {
+            material
+                .shininess
+                .map(|specular_exponent| (2.0 / (specular_exponent.max(0.0) + 2.0)).sqrt())
+        })
+        .unwrap_or(default_material.roughness_factor);
+
+
+    MeshMaterial {
+        base_color_factor,
+        base_color_texture: material
+            .diffuse_texture
+            .as_deref()
+            .and_then(|texture_path| texture_cache.color_texture(texture_path))
+            .map(TextureReference::new),
+        metallic_factor: unknown_parameter_value("Pm").unwrap_or(default_material.metallic_factor),
+        metallic_texture: material
+            .unknown_param
+            .get("map_Pm")
+            .and_then(|texture_path| texture_cache.grayscale_texture(texture_path))
+            .map(TextureReference::new),
+        roughness_factor,
+        roughness_texture: material
+            .unknown_param
+            .get("map_Pr")
+            .and_then(|texture_path| texture_cache.grayscale_texture(texture_path))
+            .map(TextureReference::new),
+    }
+}
+
+
+
+/// Loads each texture referenced by the MTL file only once.
+/// Textures that fail to load are reported and ignored.
+struct TextureCache {
+    material_directory_path: PathBuf,
+    color_textures: HashMap<String, Option<Arc<Texture>>>,
+    grayscale_textures: HashMap<String, Option<Arc<Texture>>>,
+}
+
+impl TextureCache {
+    fn new(material_directory_path: PathBuf) -> Self {
+        Self {
+            material_directory_path,
+            color_textures: HashMap::new(),
+            grayscale_textures: HashMap::new(),
+        }
+    }
+
+    /// Loads an sRGB-encoded color texture.
+    fn color_texture(&mut self, texture_path: &str) -> Option<Arc<Texture>> {
+        let material_directory_path = &self.material_directory_path;
+
+        self.color_textures
+            .entry(texture_path.to_string())
+            .or_insert_with(|| {
+                load_texture(material_directory_path, texture_path).map(|image| {
+                    Arc::new(Texture::new(
+                        image.into_rgba8(),
+                        TextureColorSpace::Srgb,
+                    ))
+                })
+            })
+            .clone()
+    }
+
+    /// Loads a linear grayscale texture (its value is stored in all color channels).
+    fn grayscale_texture(&mut self, texture_path: &str) -> Option<Arc<Texture>> {
+        let material_directory_path = &self.material_directory_path;
+
+        self.grayscale_textures
+            .entry(texture_path.to_string())
+            .or_insert_with(|| {
+                load_texture(material_directory_path, texture_path)
+                    .map(|image| Arc::new(Texture::from_grayscale(&image.into_luma8())))
+            })
+            .clone()
+    }
+}
+
+
+fn load_texture(material_directory_path: &Path, texture_path: &str) -> Option<image::DynamicImage> {
+    // Texture statements can contain options before the file name (e.g. "-bm 0.5 bump.png"),
+    // which we ignore.
+    let texture_file_name = texture_path.split_whitespace().last()?;
+    let texture_file_path = material_directory_path.join(texture_file_name);
+
+    match image::open(&texture_file_path) {
+        Ok(image) => Some(image),
+        Err(error) => {
+            println!(
+                "Warning: failed to load texture {} ({}), ignoring it.",
+                texture_file_path.display(),
+                error
+            );
+
+            None
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn quad_with_vertex_colors_and_tex_coords() {
+        let file_path =
+            std::env::temp_dir().join(format!("nrg-obj-quad-{}.obj", std::process::id()));
+
+        fs::write(
+            &file_path,
+            "o quad\n\
+            v 0 0 0 1 0 0\n\
+            v 1 0 0 0 1 0\n\
+            v 1 1 0 0 0 1\n\
+            v 0 1 0 1 1 1\n\
+            vt 0 0\n\
+            vt 1 0\n\
+            vt 1 1\n\
+            vt 0 1\n\
+            f 1/1 2/2 3/3 4/4\n",
+        )
+        .unwrap();
+
+        let scene = load_obj_scene(&file_path);
+        fs::remove_file(&file_path).unwrap();
+
+        let scene = scene.unwrap();
+        assert_eq!(scene.models.len(), 1);
+
+        let triangles = &scene.models[0].triangles;
+        assert_eq!(triangles.len(), 2);
+
+        let first_vertex = triangles[0][0];
+        assert_eq!(first_vertex.position, Vec3::ZERO);
+        assert_eq!(first_vertex.color, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        // The texture coordinate origin moves to the top-left corner.
+        assert_eq!(first_vertex.tex_coords[0], Vec2::new(0.0, 1.0));
+
+        // Models without materials use the default material.
+        assert_eq!(
+            scene.models[0].material.base_color_factor,
+            MeshMaterial::default().base_color_factor
+        );
+    }
+}
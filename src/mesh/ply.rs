@@ -0,0 +1,295 @@
+//! PLY (binary and ASCII) loading. Vertex colors (`red`, `green`, `blue` and optionally `alpha`
+//! properties, either as integers normalized by the maximum of their type or as floats in `[0, 1]`)
+//! are used if present.
+//! PLY files carry no material information, so the [default material](MeshMaterial::default) is used.
+
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+
+use glam::{Vec2, Vec3, Vec4};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use ply_rs::{
+    parser::Parser,
+    ply::{DefaultElement, Ply, Property},
+};
+
+use super::{
+    srgb_component_to_linear,
+    MeshMaterial,
+    MeshModel,
+    MeshScene,
+    MeshVertex,
+    TEX_COORD_SET_COUNT,
+};
+
+
+pub(crate) fn read_ply_file(ply_file_path: &Path) -> Result<Ply<DefaultElement>> {
+    let file = File::open(ply_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to open PLY file.")?;
+
+    Parser::<DefaultElement>::new()
+        .read_ply(&mut BufReader::new(file))
+        .into_diagnostic()
+        .wrap_err("Failed to parse PLY file.")
+}
+
+
+/// Returns `true` if the PLY file has no faces (i.e. if it is a point cloud).
+/// Only the header is read.
+pub(crate) fn is_ply_file_without_faces(ply_file_path: &Path) -> Result<bool> {
+    let file = File::open(ply_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to open PLY file.")?;
+
+    let header = Parser::<DefaultElement>::new()
+        .read_header(&mut BufReader::new(file))
+        .into_diagnostic()
+        .wrap_err("Failed to parse PLY header.")?;
+
+    Ok(header
+        .elements
+        .get("face")
+        .map(|face_element| face_element.count == 0)
+        .unwrap_or(true))
+}
+
+
+pub fn load_ply_scene(ply_file_path: &Path) -> Result<MeshScene> {
+    let ply = read_ply_file(ply_file_path)?;
+
+
+    let vertices = ply
+        .payload
+        .get("vertex")
+        .ok_or_else(|| miette!("PLY file contains no vertex element."))?
+        .iter()
+        .map(|vertex_element| {
+            Ok(MeshVertex {
+                position: ply_vertex_position(vertex_element)?,
+                tex_coords: [Vec2::ZERO; TEX_COORD_SET_COUNT],
+                color: ply_vertex_color(vertex_element).unwrap_or(Vec4::ONE),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let faces = ply
+        .payload
+        .get("face")
+        .ok_or_else(|| miette!("PLY file contains no face element."))?;
+
+
+    let mut triangles: Vec<[MeshVertex; 3]> = Vec::with_capacity(faces.len());
+
+    for face_element in faces {
+        let vertex_indices = face_element
+            .get("vertex_indices")
+            .or_else(|| face_element.get("vertex_index"))
+            .and_then(property_as_index_list)
+            .ok_or_else(|| miette!("PLY face has no vertex_indices property."))?;
+
+        let face_vertices = vertex_indices
+            .iter()
+            .map(|vertex_index| {
+                vertices.get(*vertex_index).copied().ok_or_else(|| {
+                    miette!(
+                        "PLY face references missing vertex {}.",
+                        vertex_index
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Polygons are triangulated as a fan around their first vertex.
+        for vertex_pair in face_vertices.windows(2).skip(1) {
+            triangles.push([face_vertices[0], vertex_pair[0], vertex_pair[1]]);
+        }
+    }
+
+
+    Ok(MeshScene {
+        models: vec![MeshModel {
+            triangles,
+            material: Arc::new(MeshMaterial::default()),
+            primitive_index: 0,
+        }],
+        instanced_models: Vec::new(),
+    })
+}
+
+
+pub(crate) fn ply_vertex_position(vertex_element: &DefaultElement) -> Result<Vec3> {
+    let component = |name: &str| {
+        vertex_element
+            .get(name)
+            .and_then(property_as_f32)
+            .ok_or_else(|| miette!("PLY vertex has no numeric {} property.", name))
+    };
+
+    Ok(Vec3::new(
+        component("x")?,
+        component("y")?,
+        component("z")?,
+    ))
+}
+
+
+/// Returns the linear RGBA color of the vertex, if it has one.
+///
+/// Integer color properties are normalized by the maximum of their type,
+/// float ones are expected to already be in the [0, 1] range (and are clamped to it).
+pub(crate) fn ply_vertex_color(vertex_element: &DefaultElement) -> Option<Vec4> {
+    let component = |name: &str| -> Option<f32> {
+        let value = match vertex_element.get(name)? {
+            Property::Char(value) => *value as f32 / i8::MAX as f32,
+            Property::UChar(value) => *value as f32 / u8::MAX as f32,
+            Property::Short(value) => *value as f32 / i16::MAX as f32,
+            Property::UShort(value) => *value as f32 / u16::MAX as f32,
+            Property::Int(value) => (*value as f64 / i32::MAX as f64) as f32,
+            Property::UInt(value) => (*value as f64 / u32::MAX as f64) as f32,
+            property => property_as_f32(property)?,
+        };
+
+        Some(value.clamp(0.0, 1.0))
+    };
+
+    let srgb_color = Vec3::new(
+        component("red")?,
+        component("green")?,
+        component("blue")?,
+    );
+
+    Some(
+        Vec3::from_array(srgb_color.to_array().map(srgb_component_to_linear))
+            .extend(component("alpha").unwrap_or(1.0)),
+    )
+}
+
+
+pub(crate) fn property_as_f32(property: &Property) -> Option<f32> {
+    match property {
+        Property::Char(value) => Some(*value as f32),
+        Property::UChar(value) => Some(*value as f32),
+        Property::Short(value) => Some(*value as f32),
+        Property::UShort(value) => Some(*value as f32),
+        Property::Int(value) => Some(*value as f32),
+        Property::UInt(value) => Some(*value as f32),
+        Property::Float(value) => Some(*value),
+        Property::Double(value) => Some(*value as f32),
+        _ => None,
+    }
+}
+
+
+fn property_as_index_list(property: &Property) -> Option<Vec<usize>> {
+    fn to_indices<T: Copy + TryInto<usize>>(values: &[T]) -> Option<Vec<usize>> {
+        values
+            .iter()
+            .map(|value| (*value).try_into().ok())
+            .collect()
+    }
+
+    match property {
+        Property::ListChar(values) => to_indices(values),
+        Property::ListUChar(values) => to_indices(values),
+        Property::ListShort(values) => to_indices(values),
+        Property::ListUShort(values) => to_indices(values),
+        Property::ListInt(values) => to_indices(values),
+        Property::ListUInt(values) => to_indices(values),
+        _ => None,
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn ascii_quad_with_16_bit_colors() {
+        let file_path =
+            std::env::temp_dir().join(format!("nrg-ply-quad-{}.ply", std::process::id()));
+
+        fs::write(
+            &file_path,
+            "ply\n\
+            format ascii 1.0\n\
+            element vertex 4\n\
+            property float x\n\
+            property float y\n\
+            property float z\n\
+            property ushort red\n\
+            property ushort green\n\
+            property ushort blue\n\
+            element face 1\n\
+            property list uchar int vertex_indices\n\
+            end_header\n\
+            0 0 0 65535 0 0\n\
+            1 0 0 0 65535 0\n\
+            1 1 0 0 0 65535\n\
+            0 1 0 65535 65535 65535\n\
+            4 0 1 2 3\n",
+        )
+        .unwrap();
+
+        let scene = load_ply_scene(&file_path);
+        fs::remove_file(&file_path).unwrap();
+
+        let scene = scene.unwrap();
+        assert_eq!(scene.models.len(), 1);
+
+        // The quad is triangulated as a fan around its first vertex.
+        let triangles = &scene.models[0].triangles;
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[1][2].position, Vec3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(
+            triangles[0][0].color,
+            Vec4::new(1.0, 0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            triangles[0][2].color,
+            Vec4::new(0.0, 0.0, 1.0, 1.0)
+        );
+        assert_eq!(triangles[1][2].color, Vec4::ONE);
+    }
+
+    #[test]
+    fn ascii_float_colors_are_clamped() {
+        let file_path = std::env::temp_dir().join(format!(
+            "nrg-ply-float-colors-{}.ply",
+            std::process::id()
+        ));
+
+        fs::write(
+            &file_path,
+            "ply\n\
+            format ascii 1.0\n\
+            element vertex 3\n\
+            property float x\n\
+            property float y\n\
+            property float z\n\
+            property float red\n\
+            property float green\n\
+            property float blue\n\
+            element face 1\n\
+            property list uchar int vertex_indices\n\
+            end_header\n\
+            0 0 0 2 -1 1\n\
+            1 0 0 0 0 0\n\
+            0 1 0 0 0 0\n\
+            3 0 1 2\n",
+        )
+        .unwrap();
+
+        let scene = load_ply_scene(&file_path);
+        fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(
+            scene.unwrap().models[0].triangles[0][0].color,
+            Vec4::new(1.0, 0.0, 1.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! STL (binary and ASCII) loading. STL files carry no material information,
+//! so the [default material](MeshMaterial::default) is used.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use glam::Vec3;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::{MeshMaterial, MeshModel, MeshScene, MeshVertex};
+
+
+pub fn load_stl_scene(stl_file_path: &Path) -> Result<MeshScene> {
+    let mut file = File::open(stl_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to open STL file.")?;
+
+    let mesh = stl_io::read_stl(&mut file)
+        .into_diagnostic()
+        .wrap_err("Failed to parse STL file.")?;
+
+    let vertex_positions = mesh
+        .vertices
+        .iter()
+        .map(|vertex| Vec3::new(vertex[0], vertex[1], vertex[2]))
+        .collect::<Vec<_>>();
+
+
+    let triangles = mesh
+        .faces
+        .iter()
+        .map(|face| {
+            let mut triangle = [MeshVertex::from_position(Vec3::ZERO); 3];
+
+            for (triangle_vertex, vertex_index) in triangle.iter_mut().zip(face.vertices) {
+                let position = vertex_positions.get(vertex_index).ok_or_else(|| {
+                    miette!(
+                        "STL face references missing vertex {}.",
+                        vertex_index
+                    )
+                })?;
+
+                *triangle_vertex = MeshVertex::from_position(*position);
+            }
+
+            Ok(triangle)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+
+    Ok(MeshScene {
+        models: vec![MeshModel {
+            triangles,
+            material: Arc::new(MeshMaterial::default()),
+            primitive_index: 0,
+        }],
+        instanced_models: Vec::new(),
+    })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use glam::Vec4;
+
+    use super::*;
+
+    #[test]
+    fn ascii_triangle() {
+        let file_path = std::env::temp_dir().join(format!(
+            "nrg-stl-triangle-{}.stl",
+            std::process::id()
+        ));
+
+        fs::write(
+            &file_path,
+            "solid triangle\n\
+            facet normal 0 0 1\n\
+            outer loop\n\
+            vertex 0 0 0\n\
+            vertex 1 0 0\n\
+            vertex 0 2 0.5\n\
+            endloop\n\
+            endfacet\n\
+            endsolid triangle\n",
+        )
+        .unwrap();
+
+        let scene = load_stl_scene(&file_path);
+        fs::remove_file(&file_path).unwrap();
+
+        let scene = scene.unwrap();
+        assert_eq!(scene.models.len(), 1);
+
+        let triangles = &scene.models[0].triangles;
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(
+            triangles[0].map(|vertex| vertex.position),
+            [Vec3::ZERO, Vec3::X, Vec3::new(0.0, 2.0, 0.5)]
+        );
+        assert_eq!(triangles[0][0].color, Vec4::ONE);
+    }
+}
@@ -0,0 +1,478 @@
+//! Texture sampling: wrap modes, bilinear filtering, mip selection by footprint
+//! and texture coordinate transforms (as in glTF and `KHR_texture_transform`).
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, OnceLock},
+};
+
+use glam::{Vec2, Vec4};
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+
+use super::{linear_component_to_srgb, srgb_component_to_linear};
+
+
+/// Number of texture coordinate sets stored per vertex.
+pub const TEX_COORD_SET_COUNT: usize = 2;
+
+
+/// How texture coordinates outside of `[0, 1]` are handled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+impl WrapMode {
+    /// Maps a (possibly out-of-range) texel index into `0..size`.
+    fn wrap(self, texel_index: i64, size: u32) -> u32 {
+        let size = size as i64;
+
+        let wrapped_index = match self {
+            WrapMode::Repeat => texel_index.rem_euclid(size),
+            WrapMode::MirroredRepeat => {
+                let mirrored_index = texel_index.rem_euclid(size * 2);
+
+                if mirrored_index >= size {
+                    size * 2 - 1 - mirrored_index
+                } else {
+                    mirrored_index
+                }
+            }
+            WrapMode::ClampToEdge => texel_index.clamp(0, size - 1),
+        };
+
+        wrapped_index as u32
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FilterMode {
+    Nearest,
+    Linear,
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextureSampler {
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+
+    /// Filter used when a texel is larger than the sampled footprint.
+    pub magnification_filter: FilterMode,
+
+    /// Filter used within a mip level when a texel is smaller than the sampled footprint.
+    pub minification_filter: FilterMode,
+
+    /// Filter used between mip levels. If `None`, only the full-resolution level is used.
+    pub mipmap_filter: Option<FilterMode>,
+}
+
+impl Default for TextureSampler {
+    /// Repeating, (tri)linearly filtered sampler, used when no sampler is specified.
+    fn default() -> Self {
+        Self {
+            wrap_s: WrapMode::Repeat,
+            wrap_t: WrapMode::Repeat,
+            magnification_filter: FilterMode::Linear,
+            minification_filter: FilterMode::Linear,
+            mipmap_filter: Some(FilterMode::Linear),
+        }
+    }
+}
+
+
+/// Texture coordinate transform (see the `KHR_texture_transform` glTF extension):
+/// texture coordinates are scaled, then rotated, then offset.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureTransform {
+    pub offset: Vec2,
+
+    /// Rotation in radians, counter-clockwise in texture space.
+    pub rotation: f32,
+
+    pub scale: Vec2,
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            rotation: 0.0,
+            scale: Vec2::ONE,
+        }
+    }
+}
+
+impl TextureTransform {
+    pub fn apply(&self, tex_coords: Vec2) -> Vec2 {
+        let scaled = tex_coords * self.scale;
+        let (sin, cos) = self.rotation.sin_cos();
+
+        self.offset
+            + Vec2::new(
+                cos * scaled.x + sin * scaled.y,
+                -sin * scaled.x + cos * scaled.y,
+            )
+    }
+
+    /// Factor by which the transform enlarges distances in texture space (at most).
+    fn footprint_scale(&self) -> f32 {
+        self.scale.abs().max_element()
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TextureColorSpace {
+    /// Color channels are sRGB-encoded (alpha is always linear).
+    Srgb,
+    Linear,
+}
+
+
+/// An RGBA texture with lazily generated mip levels.
+pub struct Texture {
+    image: RgbaImage,
+    color_space: TextureColorSpace,
+
+    /// Mip levels below the full-resolution image, each half the size of the previous one.
+    mip_levels: OnceLock<Vec<RgbaImage>>,
+}
+
+impl Texture {
+    pub fn new(image: RgbaImage, color_space: TextureColorSpace) -> Self {
+        Self {
+            image,
+            color_space,
+            mip_levels: OnceLock::new(),
+        }
+    }
+
+    /// Creates a linear texture with the grayscale value in the red, green and blue channels.
+    pub fn from_grayscale(image: &GrayImage) -> Self {
+        Self::new(
+            RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+                let Luma([value]) = *image.get_pixel(x, y);
+                Rgba([value, value, value, u8::MAX])
+            }),
+            TextureColorSpace::Linear,
+        )
+    }
+
+    fn mip_levels(&self) -> &[RgbaImage] {
+        self.mip_levels.get_or_init(|| {
+            let mut mip_levels: Vec<RgbaImage> = Vec::new();
+            let mut previous_level = &self.image;
+
+            while previous_level.width() > 1 || previous_level.height() > 1 {
+                let next_level = self.downsample(previous_level);
+                mip_levels.push(next_level);
+                previous_level = mip_levels.last().unwrap();
+            }
+
+            mip_levels
+        })
+    }
+
+    fn level(&self, level_index: usize) -> &RgbaImage {
+        match level_index {
+            0 => &self.image,
+            _ => &self.mip_levels()[level_index - 1],
+        }
+    }
+
+    fn level_count(&self) -> usize {
+        self.image.width().max(self.image.height()).ilog2() as usize + 1
+    }
+
+    /// Halves the image with a 2x2 box filter (in linear space).
+    fn downsample(&self, image: &RgbaImage) -> RgbaImage {
+        let width = (image.width() / 2).max(1);
+        let height = (image.height() / 2).max(1);
+
+        RgbaImage::from_fn(width, height, |x, y| {
+            let mut sum = Vec4::ZERO;
+
+            for (offset_x, offset_y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let source_x = (x * 2 + offset_x).min(image.width() - 1);
+                let source_y = (y * 2 + offset_y).min(image.height() - 1);
+
+                sum += self.decode(*image.get_pixel(source_x, source_y));
+            }
+
+            self.encode(sum / 4.0)
+        })
+    }
+
+    fn decode(&self, pixel: Rgba<u8>) -> Vec4 {
+        let color = Vec4::from_array(pixel.0.map(|channel| channel as f32 / 255.0));
+
+        match self.color_space {
+            TextureColorSpace::Srgb => Vec4::new(
+                srgb_component_to_linear(color.x),
+                srgb_component_to_linear(color.y),
+                srgb_component_to_linear(color.z),
+                color.w,
+            ),
+            TextureColorSpace::Linear => color,
+        }
+    }
+
+    fn encode(&self, color: Vec4) -> Rgba<u8> {
+        let color = match self.color_space {
+            TextureColorSpace::Srgb => Vec4::new(
+                linear_component_to_srgb(color.x),
+                linear_component_to_srgb(color.y),
+                linear_component_to_srgb(color.z),
+                color.w,
+            ),
+            TextureColorSpace::Linear => color,
+        };
+
+        Rgba(
+            color
+                .to_array()
+                .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8),
+        )
+    }
+
+    /// Returns the linear RGBA color at the given texture coordinates.
+    ///
+    /// `footprint` is the size of the sampled area in texture coordinate units;
+    /// it selects the mip level (a footprint of 0 always samples the full-resolution level).
+    pub fn sample(&self, tex_coords: Vec2, footprint: f32, sampler: &TextureSampler) -> Vec4 {
+        let footprint_in_texels = footprint * self.image.width().max(self.image.height()) as f32;
+        let level_of_detail = if footprint_in_texels > 1.0 {
+            footprint_in_texels.log2()
+        } else {
+            0.0
+        };
+
+        if level_of_detail <= 0.0 {
+            return self.sample_level(
+                0,
+                tex_coords,
+                sampler.magnification_filter,
+                sampler,
+            );
+        }
+
+        let maximum_level = (self.level_count() - 1) as f32;
+
+        match sampler.mipmap_filter {
+            None => self.sample_level(
+                0,
+                tex_coords,
+                sampler.minification_filter,
+                sampler,
+            ),
+            Some(FilterMode::Nearest) => self.sample_level(
+                level_of_detail.round().min(maximum_level) as usize,
+                tex_coords,
+                sampler.minification_filter,
+                sampler,
+            ),
+            Some(FilterMode::Linear) => {
+                let level_of_detail = level_of_detail.min(maximum_level);
+                let lower_level = level_of_detail.floor();
+
+                let lower_sample = self.sample_level(
+                    lower_level as usize,
+                    tex_coords,
+                    sampler.minification_filter,
+                    sampler,
+                );
+
+                if lower_level == level_of_detail {
+                    return lower_sample;
+                }
+
+                let upper_sample = self.sample_level(
+                    lower_level as usize + 1,
+                    tex_coords,
+                    sampler.minification_filter,
+                    sampler,
+                );
+
+                lower_sample.lerp(upper_sample, level_of_detail - lower_level)
+            }
+        }
+    }
+
+    fn sample_level(
+        &self,
+        level_index: usize,
+        tex_coords: Vec2,
+        filter: FilterMode,
+        sampler: &TextureSampler,
+    ) -> Vec4 {
+        let level = self.level(level_index);
+        let (width, height) = level.dimensions();
+
+        let texel_coordinates = tex_coords * Vec2::new(width as f32, height as f32);
+
+        let texel_at = |x: i64, y: i64| {
+            self.decode(*level.get_pixel(
+                sampler.wrap_s.wrap(x, width),
+                sampler.wrap_t.wrap(y, height),
+            ))
+        };
+
+        match filter {
+            FilterMode::Nearest => texel_at(
+                texel_coordinates.x.floor() as i64,
+                texel_coordinates.y.floor() as i64,
+            ),
+            FilterMode::Linear => {
+                // Texel centers are at half-integer coordinates.
+                let texel_coordinates = texel_coordinates - 0.5;
+                let texel_min = texel_coordinates.floor();
+                let fraction = texel_coordinates - texel_min;
+
+                let (x, y) = (texel_min.x as i64, texel_min.y as i64);
+
+                let top = texel_at(x, y).lerp(texel_at(x + 1, y), fraction.x);
+                let bottom = texel_at(x, y + 1).lerp(texel_at(x + 1, y + 1), fraction.x);
+
+                top.lerp(bottom, fraction.y)
+            }
+        }
+    }
+}
+
+impl Debug for Texture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Texture")
+            .field("width", &self.image.width())
+            .field("height", &self.image.height())
+            .field("color_space", &self.color_space)
+            .finish_non_exhaustive()
+    }
+}
+
+
+/// A point on a surface to sample textures at.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfacePoint {
+    /// Texture coordinates of each set.
+    pub tex_coords: [Vec2; TEX_COORD_SET_COUNT],
+
+    /// Size of the sampled area (e.g. a voxel) in texture coordinate units of each set,
+    /// or 0 to always sample the full-resolution texture.
+    pub tex_coord_footprints: [f32; TEX_COORD_SET_COUNT],
+}
+
+/// A texture as referenced by a material, along with how to sample it.
+#[derive(Clone, Debug)]
+pub struct TextureReference {
+    pub texture: Arc<Texture>,
+    pub sampler: TextureSampler,
+    pub transform: TextureTransform,
+
+    /// Index of the texture coordinate set to use (below [`TEX_COORD_SET_COUNT`]).
+    pub tex_coord_set: usize,
+}
+
+impl TextureReference {
+    /// References a texture with the default sampler, no transform and the first texture
+    /// coordinate set.
+    pub fn new(texture: Arc<Texture>) -> Self {
+        Self {
+            texture,
+            sampler: TextureSampler::default(),
+            transform: TextureTransform::default(),
+            tex_coord_set: 0,
+        }
+    }
+
+    /// Returns the linear RGBA color at the given surface point.
+    pub fn sample(&self, surface_point: &SurfacePoint) -> Vec4 {
+        let tex_coords = self
+            .transform
+            .apply(surface_point.tex_coords[self.tex_coord_set]);
+        let footprint = surface_point.tex_coord_footprints[self.tex_coord_set]
+            * self.transform.footprint_scale();
+
+        self.texture.sample(tex_coords, footprint, &self.sampler)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn texel_indices_are_wrapped() {
+        let wrapped_indices = |wrap_mode: WrapMode| {
+            [-5, -1, 0, 3, 4, 7, 9].map(|texel_index| wrap_mode.wrap(texel_index, 4))
+        };
+
+        assert_eq!(
+            wrapped_indices(WrapMode::Repeat),
+            [3, 3, 0, 3, 0, 3, 1]
+        );
+        assert_eq!(
+            wrapped_indices(WrapMode::MirroredRepeat),
+            [3, 0, 0, 3, 3, 0, 1]
+        );
+        assert_eq!(
+            wrapped_indices(WrapMode::ClampToEdge),
+            [0, 0, 0, 3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn texture_transform_scales_then_rotates_then_offsets() {
+        assert_eq!(
+            TextureTransform::default().apply(Vec2::new(0.25, 0.75)),
+            Vec2::new(0.25, 0.75)
+        );
+
+        let transform = TextureTransform {
+            offset: Vec2::new(0.0, 1.0),
+            rotation: FRAC_PI_2,
+            scale: Vec2::new(2.0, 0.5),
+        };
+
+        assert!(transform
+            .apply(Vec2::new(0.5, 0.0))
+            .abs_diff_eq(Vec2::new(0.0, 0.0), 1e-6));
+        assert!(transform
+            .apply(Vec2::new(0.0, 1.0))
+            .abs_diff_eq(Vec2::new(0.5, 1.0), 1e-6));
+    }
+
+    #[test]
+    fn srgb_texels_round_trip_through_sampling_and_mip_levels() {
+        for value in 0..=u8::MAX {
+            let texel = Rgba([value, value, value, u8::MAX]);
+            let texture = Texture::new(
+                RgbaImage::from_pixel(2, 2, texel),
+                TextureColorSpace::Srgb,
+            );
+
+            let color = texture.sample(Vec2::splat(0.5), 0.0, &TextureSampler::default());
+
+            // Dark texels lie on the linear segment of the sRGB curve.
+            if value <= 10 {
+                assert!((color.x - value as f32 / 255.0 / 12.92).abs() < 1e-6);
+            }
+
+            // The linear color re-encodes to the same texel, as it does in sRGB exports.
+            assert_eq!(
+                (linear_component_to_srgb(color.x) * 255.0).round() as u8,
+                value
+            );
+
+            // Averaging a uniform texture must not shift its color.
+            assert_eq!(*texture.level(1).get_pixel(0, 0), texel);
+        }
+    }
+}
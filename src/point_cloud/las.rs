@@ -0,0 +1,62 @@
+//! LAS point cloud loading (only available with the `las` feature).
+//! LAS colors and intensities are 16-bit values.
+//!
+//! LAS coordinates are often georeferenced (i.e. far away from the origin), so they are
+//! made relative to the minimum of the header bounds in double precision before
+//! being converted to single precision. The subtracted offset is printed so that
+//! the voxel grid can be placed back into the original coordinate system.
+
+use std::path::Path;
+
+use glam::Vec3;
+use miette::{Context, IntoDiagnostic, Result};
+
+use super::{PointCloud, PointCloudPoint};
+use crate::mesh::srgb_component_to_linear;
+
+
+pub fn load_las_point_cloud(las_file_path: &Path) -> Result<PointCloud> {
+    let mut reader = las::Reader::from_path(las_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to open LAS file.")?;
+
+    let bounds_minimum = reader.header().bounds().min;
+    println!(
+        "LAS point coordinates are offset by ({}, {}, {}) \
+        (subtract from the original coordinates to get the voxelized ones).",
+        bounds_minimum.x, bounds_minimum.y, bounds_minimum.z,
+    );
+
+    let point_data = reader
+        .read_all()
+        .into_diagnostic()
+        .wrap_err("Failed to read LAS points.")?;
+
+    let points = point_data
+        .points()
+        .map(|point| {
+            let point = point
+                .into_diagnostic()
+                .wrap_err("Failed to read LAS point.")?;
+
+            Ok(PointCloudPoint {
+                position: Vec3::new(
+                    (point.x - bounds_minimum.x) as f32,
+                    (point.y - bounds_minimum.y) as f32,
+                    (point.z - bounds_minimum.z) as f32,
+                ),
+                color: point.color.map(|color| {
+                    Vec3::new(
+                        srgb_component_to_linear(color.red as f32 / u16::MAX as f32),
+                        srgb_component_to_linear(color.green as f32 / u16::MAX as f32),
+                        srgb_component_to_linear(color.blue as f32 / u16::MAX as f32),
+                    )
+                }),
+                intensity: Some(point.intensity as f32 / u16::MAX as f32),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+
+    Ok(PointCloud { points })
+}
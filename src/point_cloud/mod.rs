@@ -0,0 +1,109 @@
+//! Point clouds (e.g. from LiDAR or photogrammetry) and their loaders,
+//! selected by file extension.
+
+use std::path::Path;
+
+use glam::Vec3;
+use miette::{miette, Result};
+
+use crate::mesh::ply::is_ply_file_without_faces;
+
+#[cfg(feature = "las")]
+mod las;
+mod ply;
+mod xyz;
+
+
+pub struct PointCloudPoint {
+    pub position: Vec3,
+
+    /// Linear RGB color.
+    pub color: Option<Vec3>,
+
+    /// Intensity, normalized to `[0, 1]`.
+    pub intensity: Option<f32>,
+}
+
+
+pub struct PointCloud {
+    pub points: Vec<PointCloudPoint>,
+}
+
+impl PointCloud {
+    /// Scales intensities into `[0, 1]`, if they aren't already. Intensities up to 255 are
+    /// assumed to be 8-bit, those up to 65535 to be 16-bit, and larger ones are divided
+    /// by the maximum intensity.
+    fn normalize_intensities(&mut self) {
+        let maximum_intensity = self
+            .points
+            .iter()
+            .filter_map(|point| point.intensity)
+            .fold(0.0, f32::max);
+
+        let intensity_scale = if maximum_intensity <= 1.0 {
+            return;
+        } else if maximum_intensity <= u8::MAX as f32 {
+            u8::MAX as f32
+        } else if maximum_intensity <= u16::MAX as f32 {
+            u16::MAX as f32
+        } else {
+            maximum_intensity
+        };
+
+        for point in &mut self.points {
+            if let Some(intensity) = &mut point.intensity {
+                *intensity = (*intensity / intensity_scale).max(0.0);
+            }
+        }
+    }
+}
+
+
+/// Returns `true` if the file should be loaded as a point cloud instead of a mesh:
+/// XYZ, CSV, TXT and LAS files always are, while PLY files are only if they contain no faces.
+pub fn is_point_cloud_file(file_path: &Path) -> Result<bool> {
+    match lowercase_file_extension(file_path).as_str() {
+        "xyz" | "csv" | "txt" | "las" => Ok(true),
+        "ply" => is_ply_file_without_faces(file_path),
+        _ => Ok(false),
+    }
+}
+
+
+pub fn load_point_cloud_from_file(file_path: &Path) -> Result<PointCloud> {
+    let extension = lowercase_file_extension(file_path);
+
+    let point_cloud = match extension.as_str() {
+        "xyz" | "csv" | "txt" => xyz::load_xyz_point_cloud(file_path)?,
+        "ply" => ply::load_ply_point_cloud(file_path)?,
+        #[cfg(feature = "las")]
+        "las" => las::load_las_point_cloud(file_path)?,
+        #[cfg(not(feature = "las"))]
+        "las" => {
+            return Err(miette!(
+                "LAS input is not available, compile with the \"las\" feature to enable it."
+            ))
+        }
+        _ => {
+            return Err(miette!(
+                "Unsupported point cloud file extension \"{}\", expected one of: xyz, csv, txt, ply, las.",
+                extension
+            ))
+        }
+    };
+
+    if point_cloud.points.is_empty() {
+        return Err(miette!("Point cloud contains no points."));
+    }
+
+    Ok(point_cloud)
+}
+
+
+fn lowercase_file_extension(file_path: &Path) -> String {
+    file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+        .unwrap_or_default()
+}
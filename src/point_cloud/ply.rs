@@ -0,0 +1,45 @@
+//! PLY point cloud loading (files without faces). Vertex colors and the `intensity`
+//! (or `scalar_intensity`) property are used if present.
+
+use std::path::Path;
+
+use miette::{miette, Result};
+use ply_rs::ply::DefaultElement;
+
+use super::{PointCloud, PointCloudPoint};
+use crate::mesh::ply::{ply_vertex_color, ply_vertex_position, property_as_f32, read_ply_file};
+
+
+pub fn load_ply_point_cloud(ply_file_path: &Path) -> Result<PointCloud> {
+    let ply = read_ply_file(ply_file_path)?;
+
+    let vertices = ply
+        .payload
+        .get("vertex")
+        .ok_or_else(|| miette!("PLY file contains no vertex element."))?;
+
+
+    let points = vertices
+        .iter()
+        .map(|vertex_element| {
+            Ok(PointCloudPoint {
+                position: ply_vertex_position(vertex_element)?,
+                color: ply_vertex_color(vertex_element).map(|color| color.truncate()),
+                intensity: ply_vertex_intensity(vertex_element),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut point_cloud = PointCloud { points };
+    point_cloud.normalize_intensities();
+
+    Ok(point_cloud)
+}
+
+
+fn ply_vertex_intensity(vertex_element: &DefaultElement) -> Option<f32> {
+    ["intensity", "scalar_intensity", "scalar_Intensity"]
+        .into_iter()
+        .find_map(|name| vertex_element.get(name))
+        .and_then(property_as_f32)
+}
@@ -0,0 +1,271 @@
+//! Loading of text point clouds (XYZ, CSV and similar), with one point per line.
+//!
+//! Values can be separated by commas, semicolons or whitespace. Lines starting with `#` or `//`
+//! are ignored. If the first line contains column names, the `x`, `y`, `z`, `r`/`red`, `g`/`green`,
+//! `b`/`blue` and `i`/`intensity` columns are used. Otherwise the columns are inferred from
+//! their count: `x y z`, `x y z intensity`, `x y z r g b` or `x y z r g b intensity`.
+//! Colors are sRGB, either in `[0, 1]` or, if any component is larger than 1, in `[0, 255]`.
+
+use std::{fs, path::Path};
+
+use glam::Vec3;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::{PointCloud, PointCloudPoint};
+use crate::mesh::srgb_component_to_linear;
+
+
+/// Column index of each attribute.
+struct ColumnLayout {
+    x: usize,
+    y: usize,
+    z: usize,
+    color: Option<[usize; 3]>,
+    intensity: Option<usize>,
+}
+
+impl ColumnLayout {
+    fn from_header(column_names: &[&str]) -> Result<Self> {
+        let column_index = |names: &[&str]| {
+            column_names.iter().position(|column_name| {
+                names
+                    .iter()
+                    .any(|name| column_name.trim_matches('"').eq_ignore_ascii_case(name))
+            })
+        };
+
+        let (Some(x), Some(y), Some(z)) = (
+            column_index(&["x"]),
+            column_index(&["y"]),
+            column_index(&["z"]),
+        ) else {
+            return Err(miette!(
+                "Point cloud header must contain x, y and z columns."
+            ));
+        };
+
+        let color = match (
+            column_index(&["r", "red"]),
+            column_index(&["g", "green"]),
+            column_index(&["b", "blue"]),
+        ) {
+            (Some(red), Some(green), Some(blue)) => Some([red, green, blue]),
+            _ => None,
+        };
+
+        Ok(Self {
+            x,
+            y,
+            z,
+            color,
+            intensity: column_index(&["i", "intensity", "scalar_intensity"]),
+        })
+    }
+
+    fn from_column_count(column_count: usize) -> Result<Self> {
+        let (color, intensity) = match column_count {
+            3 => (None, None),
+            4 => (None, Some(3)),
+            6 => (Some([3, 4, 5]), None),
+            7 => (Some([3, 4, 5]), Some(6)),
+            _ => {
+                return Err(miette!(
+                    "Can't infer point cloud columns from {} values per line, \
+                    add a header line with column names.",
+                    column_count
+                ))
+            }
+        };
+
+        Ok(Self {
+            x: 0,
+            y: 1,
+            z: 2,
+            color,
+            intensity,
+        })
+    }
+}
+
+
+pub fn load_xyz_point_cloud(xyz_file_path: &Path) -> Result<PointCloud> {
+    let contents = fs::read_to_string(xyz_file_path)
+        .into_diagnostic()
+        .wrap_err("Failed to read point cloud file.")?;
+
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(line_index, line)| (line_index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#') && !line.starts_with("//"))
+        .peekable();
+
+    let Some((_, first_line)) = lines.peek() else {
+        return Err(miette!("Point cloud file is empty."));
+    };
+
+    let first_line_values = split_line(first_line);
+    let is_first_line_header = first_line_values
+        .iter()
+        .any(|value| value.parse::<f32>().is_err());
+
+    let column_layout = if is_first_line_header {
+        let layout = ColumnLayout::from_header(&first_line_values)?;
+        lines.next();
+        layout
+    } else {
+        ColumnLayout::from_column_count(first_line_values.len())?
+    };
+
+
+    let mut points: Vec<PointCloudPoint> = Vec::new();
+    // Colors are kept in their original range until we know whether it is [0, 1] or [0, 255].
+    let mut maximum_color_component: f32 = 0.0;
+
+    for (line_number, line) in lines {
+        let values = split_line(line)
+            .into_iter()
+            .map(|value| value.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Invalid value on line {}.", line_number))?;
+
+        let value_at = |column_index: usize| {
+            values.get(column_index).copied().ok_or_else(|| {
+                miette!(
+                    "Missing value in column {} on line {}.",
+                    column_index + 1,
+                    line_number
+                )
+            })
+        };
+
+        let color = column_layout
+            .color
+            .map(|[red, green, blue]| -> Result<Vec3> {
+                Ok(Vec3::new(
+                    value_at(red)?,
+                    value_at(green)?,
+                    value_at(blue)?,
+                ))
+            })
+            .transpose()?;
+
+        if let Some(color) = color {
+            maximum_color_component = maximum_color_component.max(color.max_element());
+        }
+
+        points.push(PointCloudPoint {
+            position: Vec3::new(
+                value_at(column_layout.x)?,
+                value_at(column_layout.y)?,
+                value_at(column_layout.z)?,
+            ),
+            color,
+            intensity: column_layout.intensity.map(value_at).transpose()?,
+        });
+    }
+
+
+    let color_scale = if maximum_color_component > 1.0 {
+        u8::MAX as f32
+    } else {
+        1.0
+    };
+
+    for point in &mut points {
+        if let Some(color) = &mut point.color {
+            *color = Vec3::from_array(
+                (*color / color_scale)
+                    .clamp(Vec3::ZERO, Vec3::ONE)
+                    .to_array()
+                    .map(srgb_component_to_linear),
+            );
+        }
+    }
+
+
+    let mut point_cloud = PointCloud { points };
+    point_cloud.normalize_intensities();
+
+    Ok(point_cloud)
+}
+
+
+fn split_line(line: &str) -> Vec<&str> {
+    line.split(|character: char| character == ',' || character == ';' || character.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_point_cloud_from_str(file_name: &str, contents: &str) -> Result<PointCloud> {
+        let file_path = std::env::temp_dir().join(format!(
+            "nrg-{}-{}",
+            std::process::id(),
+            file_name
+        ));
+
+        fs::write(&file_path, contents).unwrap();
+        let point_cloud = load_xyz_point_cloud(&file_path);
+        fs::remove_file(&file_path).unwrap();
+
+        point_cloud
+    }
+
+    #[test]
+    fn csv_with_header_and_8_bit_colors() {
+        let point_cloud = load_point_cloud_from_str(
+            "header.csv",
+            "# exported points\n\
+            red,green,blue,X,Y,Z\n\
+            255,0,0,1.5,2,3\n\
+            \n\
+            // second point\n\
+            0;255;255;-1;0;0.25\n",
+        )
+        .unwrap();
+
+        assert_eq!(point_cloud.points.len(), 2);
+
+        let first_point = &point_cloud.points[0];
+        assert_eq!(first_point.position, Vec3::new(1.5, 2.0, 3.0));
+        assert_eq!(first_point.color, Some(Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(first_point.intensity, None);
+
+        assert_eq!(
+            point_cloud.points[1].position,
+            Vec3::new(-1.0, 0.0, 0.25)
+        );
+        assert_eq!(
+            point_cloud.points[1].color,
+            Some(Vec3::new(0.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn columns_are_inferred_without_header() {
+        let point_cloud = load_point_cloud_from_str(
+            "intensity.xyz",
+            "0 0 0 51\n\
+            1 2 3 255\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            point_cloud.points[1].position,
+            Vec3::new(1.0, 2.0, 3.0)
+        );
+        assert_eq!(point_cloud.points[1].color, None);
+        // Intensities up to 255 are treated as 8-bit values.
+        assert_eq!(point_cloud.points[0].intensity, Some(0.2));
+        assert_eq!(point_cloud.points[1].intensity, Some(1.0));
+
+        assert!(load_point_cloud_from_str("invalid.xyz", "0 0 0\n1 x 3\n").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Quantitative comparison of two voxel grids.
+
+use super::csg::align_to_lattice;
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+
+/// Labels of the diff volume produced by [`compare_grids`].
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiffLabel {
+    BothEmpty = 0,
+    BothOccupied = 1,
+    /// Occupied only in the reference grid.
+    Removed = 2,
+    /// Occupied only in the candidate grid.
+    Added = 3,
+}
+
+
+/// Summary statistics of absolute errors.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ErrorStatistics {
+    pub mean: f32,
+    pub root_mean_square: f32,
+    pub max: f32,
+}
+
+impl ErrorStatistics {
+    fn from_errors(errors: &[f32]) -> Self {
+        if errors.is_empty() {
+            return Self::default();
+        }
+
+        let count = errors.len() as f64;
+
+        Self {
+            mean: (errors.iter().map(|error| *error as f64).sum::<f64>() / count) as f32,
+            root_mean_square: (errors
+                .iter()
+                .map(|error| (*error as f64).powi(2))
+                .sum::<f64>()
+                / count)
+                .sqrt() as f32,
+            max: errors.iter().copied().fold(0.0, f32::max),
+        }
+    }
+}
+
+
+pub struct GridComparison {
+    pub reference_occupied_count: u64,
+    pub candidate_occupied_count: u64,
+
+    pub intersection_count: u64,
+    pub union_count: u64,
+
+    /// Voxels occupied in the candidate grid, but not in the reference grid.
+    pub added_count: u64,
+    /// Voxels occupied in the reference grid, but not in the candidate grid.
+    pub removed_count: u64,
+
+    /// Number of voxels that are edge voxels in both grids.
+    pub common_edge_count: u64,
+
+    /// Euclidean distance between linear RGB colors (components in `[0, 1]`) of common edge voxels.
+    pub color_error: ErrorStatistics,
+    pub metallic_error: ErrorStatistics,
+    pub roughness_error: ErrorStatistics,
+
+    /// Per-voxel [`DiffLabel`]s on the lattice of the reference grid (see [`Self::diff_lattice`]).
+    pub diff_labels: Vec<DiffLabel>,
+
+    /// The (empty) grid describing the lattice that [`Self::diff_labels`] are laid out on.
+    pub diff_lattice: VoxelGrid,
+}
+
+impl GridComparison {
+    /// Intersection over union of occupied voxels.
+    pub fn intersection_over_union(&self) -> f64 {
+        if self.union_count == 0 {
+            return 1.0;
+        }
+
+        self.intersection_count as f64 / self.union_count as f64
+    }
+
+    /// Dice coefficient of occupied voxels.
+    pub fn dice_coefficient(&self) -> f64 {
+        let total_count = self.reference_occupied_count + self.candidate_occupied_count;
+        if total_count == 0 {
+            return 1.0;
+        }
+
+        2.0 * self.intersection_count as f64 / total_count as f64
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "Occupied voxels in reference: {}",
+            self.reference_occupied_count
+        );
+        println!(
+            "Occupied voxels in candidate: {}",
+            self.candidate_occupied_count
+        );
+        println!(
+            "IoU: {:.6}, Dice: {:.6}",
+            self.intersection_over_union(),
+            self.dice_coefficient()
+        );
+        println!(
+            "Added voxels: {}, removed voxels: {}",
+            self.added_count, self.removed_count
+        );
+
+        println!("Common edge voxels: {}", self.common_edge_count);
+
+        for (name, statistics) in [
+            ("Color (RGB distance)", &self.color_error),
+            ("Metallic", &self.metallic_error),
+            ("Roughness", &self.roughness_error),
+        ] {
+            println!(
+                "  {} error: mean {:.5}, RMSE {:.5}, max {:.5}",
+                name, statistics.mean, statistics.root_mean_square, statistics.max
+            );
+        }
+    }
+}
+
+
+
+/// Compares two grids. The candidate grid is sampled onto the lattice of the reference grid
+/// (extended to cover both), so the grids may have different origins and voxel sizes.
+pub fn compare_grids(reference: &VoxelGrid, candidate: &VoxelGrid) -> GridComparison {
+    let common_bounds = reference
+        .world_bounds()
+        .compute_union(&candidate.world_bounds());
+
+    let aligned_reference = align_to_lattice(reference, reference, &common_bounds);
+    let aligned_candidate = align_to_lattice(candidate, reference, &common_bounds);
+
+
+    let mut comparison = GridComparison {
+        reference_occupied_count: 0,
+        candidate_occupied_count: 0,
+        intersection_count: 0,
+        union_count: 0,
+        added_count: 0,
+        removed_count: 0,
+        common_edge_count: 0,
+        color_error: ErrorStatistics::default(),
+        metallic_error: ErrorStatistics::default(),
+        roughness_error: ErrorStatistics::default(),
+        diff_labels: Vec::with_capacity(aligned_reference.voxels().len()),
+        diff_lattice: aligned_reference.with_replaced_voxels(vec![
+            VoxelData::Empty;
+            aligned_reference.voxels().len()
+        ]),
+    };
+
+    let mut color_errors: Vec<f32> = Vec::new();
+    let mut metallic_errors: Vec<f32> = Vec::new();
+    let mut roughness_errors: Vec<f32> = Vec::new();
+
+    for (reference_voxel, candidate_voxel) in aligned_reference
+        .voxels()
+        .iter()
+        .zip(aligned_candidate.voxels())
+    {
+        let diff_label = match (
+            reference_voxel.is_occupied(),
+            candidate_voxel.is_occupied(),
+        ) {
+            (false, false) => DiffLabel::BothEmpty,
+            (true, true) => DiffLabel::BothOccupied,
+            (true, false) => DiffLabel::Removed,
+            (false, true) => DiffLabel::Added,
+        };
+
+        match diff_label {
+            DiffLabel::BothEmpty => {}
+            DiffLabel::BothOccupied => {
+                comparison.reference_occupied_count += 1;
+                comparison.candidate_occupied_count += 1;
+                comparison.intersection_count += 1;
+                comparison.union_count += 1;
+            }
+            DiffLabel::Removed => {
+                comparison.reference_occupied_count += 1;
+                comparison.removed_count += 1;
+                comparison.union_count += 1;
+            }
+            DiffLabel::Added => {
+                comparison.candidate_occupied_count += 1;
+                comparison.added_count += 1;
+                comparison.union_count += 1;
+            }
+        }
+
+        comparison.diff_labels.push(diff_label);
+
+
+        if let (
+            VoxelData::Edge {
+                color: reference_color,
+                metallic_value: reference_metallic,
+                rougness_value: reference_roughness,
+                ..
+            },
+            VoxelData::Edge {
+                color: candidate_color,
+                metallic_value: candidate_metallic,
+                rougness_value: candidate_roughness,
+                ..
+            },
+        ) = (reference_voxel, candidate_voxel)
+        {
+            comparison.common_edge_count += 1;
+
+            color_errors.push(reference_color.distance(*candidate_color));
+            metallic_errors.push((reference_metallic - candidate_metallic).abs());
+            roughness_errors.push((reference_roughness - candidate_roughness).abs());
+        }
+    }
+
+    comparison.color_error = ErrorStatistics::from_errors(&color_errors);
+    comparison.metallic_error = ErrorStatistics::from_errors(&metallic_errors);
+    comparison.roughness_error = ErrorStatistics::from_errors(&roughness_errors);
+
+    comparison
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    /// Row of `length` unit edge voxels of the given color along x.
+    fn edge_row_grid(starting_point: Vec3, length: u64, color: Vec3) -> VoxelGrid {
+        let voxel = VoxelData::Edge {
+            color,
+            metallic_value: 0.0,
+            rougness_value: 1.0,
+            intensity: 0.0,
+        };
+
+        VoxelGrid::from_voxels(
+            starting_point,
+            0.5,
+            length,
+            1,
+            1,
+            vec![voxel; length as usize],
+        )
+    }
+
+    #[test]
+    fn overlap_metrics_of_shifted_grids() {
+        let reference = edge_row_grid(Vec3::ZERO, 4, Vec3::new(1.0, 0.0, 0.0));
+        let candidate = edge_row_grid(
+            Vec3::new(1.0, 0.0, 0.0),
+            4,
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        let comparison = compare_grids(&reference, &candidate);
+
+        assert_eq!(comparison.reference_occupied_count, 4);
+        assert_eq!(comparison.candidate_occupied_count, 4);
+        assert_eq!(comparison.intersection_count, 3);
+        assert_eq!(comparison.union_count, 5);
+        assert_eq!(comparison.added_count, 1);
+        assert_eq!(comparison.removed_count, 1);
+        assert_eq!(
+            comparison.diff_labels,
+            [
+                DiffLabel::Removed,
+                DiffLabel::BothOccupied,
+                DiffLabel::BothOccupied,
+                DiffLabel::BothOccupied,
+                DiffLabel::Added,
+            ]
+        );
+
+        assert!((comparison.intersection_over_union() - 0.6).abs() < 1e-12);
+        assert!((comparison.dice_coefficient() - 0.75).abs() < 1e-12);
+
+        assert_eq!(comparison.common_edge_count, 3);
+        assert!((comparison.color_error.max - 2.0_f32.sqrt()).abs() < 1e-6);
+        assert_eq!(comparison.roughness_error.max, 0.0);
+    }
+
+    #[test]
+    fn empty_grids_are_identical() {
+        let empty_grid = VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            2,
+            1,
+            1,
+            vec![VoxelData::Empty; 2],
+        );
+
+        let comparison = compare_grids(&empty_grid, &empty_grid);
+
+        assert_eq!(comparison.intersection_over_union(), 1.0);
+        assert_eq!(comparison.dice_coefficient(), 1.0);
+    }
+}
@@ -0,0 +1,308 @@
+//! Connected-component labeling of occupied (edge or inside) voxels.
+
+use glam::{U64Vec3, Vec3};
+
+use super::neighbourhood::Connectivity;
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+
+/// Label value used for empty voxels in [`ComponentLabeling::labels`].
+pub const EMPTY_VOXEL_LABEL: u32 = 0;
+
+
+pub struct ConnectedComponent {
+    /// Label of this component in [`ComponentLabeling::labels`] (starts at 1).
+    pub label: u32,
+
+    pub voxel_count: u64,
+
+    /// Minimum grid index (inclusive) of the component's bounding box.
+    pub min_grid_index: U64Vec3,
+
+    /// Maximum grid index (inclusive) of the component's bounding box.
+    pub max_grid_index: U64Vec3,
+}
+
+
+pub struct ComponentLabeling {
+    /// Component label for each voxel (flattened the same way as [`VoxelGrid::voxels`]).
+    /// Empty voxels are labeled with [`EMPTY_VOXEL_LABEL`].
+    pub labels: Vec<u32>,
+
+    /// Components, sorted by descending voxel count (the largest component has label 1).
+    pub components: Vec<ConnectedComponent>,
+}
+
+
+/// Labels all connected components of occupied voxels in the grid.
+///
+/// Labels are assigned by descending component size, i.e. the largest component
+/// is labeled 1, the second largest 2, and so on.
+pub fn label_connected_components(
+    grid: &VoxelGrid,
+    connectivity: Connectivity,
+) -> ComponentLabeling {
+    let offsets = connectivity.offsets();
+    let voxels = grid.voxels();
+
+    let mut labels = vec![EMPTY_VOXEL_LABEL; voxels.len()];
+    let mut components: Vec<ConnectedComponent> = Vec::new();
+
+    let mut flood_fill_stack: Vec<usize> = Vec::new();
+
+    for starting_flat_index in 0..voxels.len() {
+        if !voxels[starting_flat_index].is_occupied()
+            || labels[starting_flat_index] != EMPTY_VOXEL_LABEL
+        {
+            continue;
+        }
+
+        let temporary_label = components.len() as u32 + 1;
+
+        let starting_grid_index = grid.grid_index_from_flat_index(starting_flat_index);
+        let mut component = ConnectedComponent {
+            label: temporary_label,
+            voxel_count: 0,
+            min_grid_index: starting_grid_index,
+            max_grid_index: starting_grid_index,
+        };
+
+        labels[starting_flat_index] = temporary_label;
+        flood_fill_stack.push(starting_flat_index);
+
+        while let Some(flat_index) = flood_fill_stack.pop() {
+            let grid_index = grid.grid_index_from_flat_index(flat_index);
+
+            component.voxel_count += 1;
+            component.min_grid_index = component.min_grid_index.min(grid_index);
+            component.max_grid_index = component.max_grid_index.max(grid_index);
+
+            for offset in &offsets {
+                let neighbour_index = grid_index.as_i64vec3() + *offset;
+
+                let Some(neighbour) = grid.voxel_by_signed_xyz_index(neighbour_index) else {
+                    continue;
+                };
+
+                let neighbour_flat_index = grid.flat_index(
+                    neighbour_index.x as u64,
+                    neighbour_index.y as u64,
+                    neighbour_index.z as u64,
+                );
+
+                if neighbour.is_occupied() && labels[neighbour_flat_index] == EMPTY_VOXEL_LABEL {
+                    labels[neighbour_flat_index] = temporary_label;
+                    flood_fill_stack.push(neighbour_flat_index);
+                }
+            }
+        }
+
+        components.push(component);
+    }
+
+
+    // Relabel components so that labels are ordered by descending size.
+    components.sort_by_key(|component| std::cmp::Reverse(component.voxel_count));
+
+    let mut temporary_label_to_final_label = vec![EMPTY_VOXEL_LABEL; components.len() + 1];
+    for (component_index, component) in components.iter_mut().enumerate() {
+        let final_label = component_index as u32 + 1;
+
+        temporary_label_to_final_label[component.label as usize] = final_label;
+        component.label = final_label;
+    }
+
+    for label in labels.iter_mut() {
+        *label = temporary_label_to_final_label[*label as usize];
+    }
+
+
+    ComponentLabeling { labels, components }
+}
+
+
+/// Removes all but the `number_of_components_to_keep` largest connected components.
+pub fn keep_largest_components(
+    grid: &VoxelGrid,
+    connectivity: Connectivity,
+    number_of_components_to_keep: usize,
+) -> VoxelGrid {
+    let labeling = label_connected_components(grid, connectivity);
+
+    retain_labeled_voxels(grid, &labeling, |component| {
+        (component.label as usize) <= number_of_components_to_keep
+    })
+}
+
+
+/// Removes all connected components with fewer than `minimum_voxel_count` voxels.
+pub fn remove_small_components(
+    grid: &VoxelGrid,
+    connectivity: Connectivity,
+    minimum_voxel_count: u64,
+) -> VoxelGrid {
+    let labeling = label_connected_components(grid, connectivity);
+
+    retain_labeled_voxels(grid, &labeling, |component| {
+        component.voxel_count >= minimum_voxel_count
+    })
+}
+
+
+/// Prints the number of connected components along with their sizes and bounding boxes.
+pub fn print_component_report(grid: &VoxelGrid, connectivity: Connectivity) {
+    let labeling = label_connected_components(grid, connectivity);
+
+    println!(
+        "Found {} connected components ({}):",
+        labeling.components.len(),
+        connectivity
+    );
+
+    for component in &labeling.components {
+        let world_min = grid.starting_point + component.min_grid_index.as_vec3() * grid.voxel_size();
+        let world_max =
+            grid.starting_point + (component.max_grid_index + 1).as_vec3() * grid.voxel_size();
+
+        println!(
+            "  component {}: {} voxels, grid bounds {} to {}, world bounds {} to {}",
+            component.label,
+            component.voxel_count,
+            format_grid_index(component.min_grid_index),
+            format_grid_index(component.max_grid_index),
+            format_world_position(world_min),
+            format_world_position(world_max),
+        );
+    }
+}
+
+
+
+fn retain_labeled_voxels<F>(
+    grid: &VoxelGrid,
+    labeling: &ComponentLabeling,
+    mut predicate: F,
+) -> VoxelGrid
+where
+    F: FnMut(&ConnectedComponent) -> bool,
+{
+    let is_component_kept = labeling
+        .components
+        .iter()
+        .map(&mut predicate)
+        .collect::<Vec<_>>();
+
+    let retained_voxels = grid
+        .voxels()
+        .iter()
+        .zip(labeling.labels.iter())
+        .map(|(voxel, label)| {
+            if *label != EMPTY_VOXEL_LABEL && is_component_kept[*label as usize - 1] {
+                voxel.clone()
+            } else {
+                VoxelData::Empty
+            }
+        })
+        .collect::<Vec<_>>();
+
+    grid.with_replaced_voxels(retained_voxels)
+}
+
+
+fn format_grid_index(grid_index: U64Vec3) -> String {
+    format!(
+        "({}, {}, {})",
+        grid_index.x, grid_index.y, grid_index.z
+    )
+}
+
+fn format_world_position(position: Vec3) -> String {
+    format!(
+        "({:.3}, {:.3}, {:.3})",
+        position.x, position.y, position.z
+    )
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Single-layer grid built from rows of `#` (edge voxel) and `.` (empty voxel).
+    fn grid_from_rows(rows: &[&str]) -> VoxelGrid {
+        let voxels = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|character| match character {
+                '#' => VoxelData::new_edge_with_default_material(),
+                _ => VoxelData::Empty,
+            })
+            .collect::<Vec<_>>();
+
+        VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            rows[0].len() as u64,
+            rows.len() as u64,
+            1,
+            voxels,
+        )
+    }
+
+    fn occupied_voxel_count(grid: &VoxelGrid) -> usize {
+        grid.voxels()
+            .iter()
+            .filter(|voxel| voxel.is_occupied())
+            .count()
+    }
+
+    #[test]
+    fn components_are_labeled_by_descending_size() {
+        // The lone voxel in the middle only touches the pair along an edge.
+        let grid = grid_from_rows(&["##...", "..#..", "....#"]);
+
+        let face_labeling = label_connected_components(&grid, Connectivity::Six);
+        assert_eq!(face_labeling.components.len(), 3);
+        assert_eq!(
+            face_labeling.labels,
+            [1, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 3]
+        );
+        assert_eq!(face_labeling.components[0].voxel_count, 2);
+        assert_eq!(
+            face_labeling.components[0].min_grid_index,
+            U64Vec3::ZERO
+        );
+        assert_eq!(
+            face_labeling.components[0].max_grid_index,
+            U64Vec3::new(1, 0, 0)
+        );
+
+        let edge_labeling = label_connected_components(&grid, Connectivity::Eighteen);
+        assert_eq!(edge_labeling.components.len(), 2);
+        assert_eq!(edge_labeling.components[0].voxel_count, 3);
+        assert_eq!(edge_labeling.labels[7], 1);
+        assert_eq!(edge_labeling.labels[14], 2);
+        assert_eq!(edge_labeling.labels[2], EMPTY_VOXEL_LABEL);
+    }
+
+    #[test]
+    fn largest_and_small_components_are_filtered() {
+        let grid = grid_from_rows(&["##...", "..#..", "....#"]);
+
+        let largest_grid = keep_largest_components(&grid, Connectivity::Six, 1);
+        assert_eq!(occupied_voxel_count(&largest_grid), 2);
+        assert!(largest_grid
+            .voxel_by_xyz_index_unchecked(0, 0, 0)
+            .is_occupied());
+        assert!(!largest_grid
+            .voxel_by_xyz_index_unchecked(2, 1, 0)
+            .is_occupied());
+
+        let filtered_grid = remove_small_components(&grid, Connectivity::TwentySix, 2);
+        assert_eq!(occupied_voxel_count(&filtered_grid), 3);
+        assert!(!filtered_grid
+            .voxel_by_xyz_index_unchecked(4, 2, 0)
+            .is_occupied());
+    }
+}
@@ -0,0 +1,300 @@
+//! Boolean (constructive solid geometry) operations between voxel grids.
+//!
+//! Both operands are first aligned onto a common lattice: the voxel size and origin
+//! of the first grid, extended (for union and symmetric difference) to also cover
+//! the second grid. The second grid is sampled at the centers of the lattice voxels,
+//! so it may have a different origin and voxel size.
+//!
+//! Material attributes are resolved as follows:
+//! - voxels occupied in only one operand keep their state and material,
+//! - where both operands are occupied, an edge voxel is kept only if it is still
+//!   on the surface of the result (i.e. for union, only if both are edge voxels,
+//!   and for intersection, if either is), with the first operand's material taking
+//!   precedence,
+//! - inside voxels that become exposed by the operation (e.g. the cut faces of a
+//!   difference) are turned into edge voxels and take the material of the nearest edge
+//!   voxel of the second operand (the "cutter"), then of the first operand, and otherwise
+//!   the default material.
+
+use std::{fmt::Display, str::FromStr};
+
+use glam::{U64Vec3, Vec3};
+use miette::{miette, Report};
+
+use super::{
+    find_nearest_edge_voxel,
+    neighbourhood::StructuringElement,
+    promote_exposed_inside_voxels,
+};
+use crate::voxelizer::{aabb::Aabb, grid::VoxelGrid, voxel::VoxelData};
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// First operand minus the second.
+    Difference,
+    /// Symmetric difference (xor).
+    SymmetricDifference,
+}
+
+impl Display for CsgOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CsgOperation::Union => write!(f, "union"),
+            CsgOperation::Intersection => write!(f, "intersection"),
+            CsgOperation::Difference => write!(f, "difference"),
+            CsgOperation::SymmetricDifference => write!(f, "xor"),
+        }
+    }
+}
+
+impl FromStr for CsgOperation {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "union" => Ok(Self::Union),
+            "intersection" => Ok(Self::Intersection),
+            "difference" => Ok(Self::Difference),
+            "xor" => Ok(Self::SymmetricDifference),
+            _ => Err(miette!(
+                "Invalid CSG operation \"{}\", must be one of: union, intersection, difference, xor.",
+                s
+            )),
+        }
+    }
+}
+
+
+
+/// Combines two grids with the given boolean operation.
+/// The resulting grid uses the voxel size and lattice of the `first` grid.
+pub fn combine_grids(first: &VoxelGrid, second: &VoxelGrid, operation: CsgOperation) -> VoxelGrid {
+    let result_bounds = match operation {
+        CsgOperation::Union | CsgOperation::SymmetricDifference => {
+            first.world_bounds().compute_union(&second.world_bounds())
+        }
+        // The result is always a subset of the first operand.
+        CsgOperation::Intersection | CsgOperation::Difference => first.world_bounds(),
+    };
+
+    let aligned_first = align_to_lattice(first, first, &result_bounds);
+    let aligned_second = align_to_lattice(second, first, &result_bounds);
+
+
+    let combined_voxels = aligned_first
+        .voxels()
+        .iter()
+        .zip(aligned_second.voxels())
+        .map(|(first_voxel, second_voxel)| combine_voxels(first_voxel, second_voxel, operation))
+        .collect::<Vec<_>>();
+
+    let mut combined_grid = aligned_first.with_replaced_voxels(combined_voxels);
+
+
+    let material_search_offsets =
+        StructuringElement::Sphere { radius: 2 }.offsets_sorted_by_distance();
+
+    promote_exposed_inside_voxels(&mut combined_grid, |grid_index| {
+        find_nearest_edge_voxel(
+            &aligned_second,
+            grid_index,
+            &material_search_offsets,
+        )
+        .or_else(|| {
+            find_nearest_edge_voxel(
+                &aligned_first,
+                grid_index,
+                &material_search_offsets,
+            )
+        })
+    });
+
+    combined_grid
+}
+
+
+
+fn combine_voxels(first: &VoxelData, second: &VoxelData, operation: CsgOperation) -> VoxelData {
+    match operation {
+        CsgOperation::Union => match (first, second) {
+            (VoxelData::Empty, other) | (other, VoxelData::Empty) => other.clone(),
+            (VoxelData::Edge { .. }, VoxelData::Edge { .. }) => first.clone(),
+            // An edge that lies inside the other volume is no longer on the surface.
+            _ => VoxelData::InsideMesh,
+        },
+        CsgOperation::Intersection => match (first, second) {
+            (VoxelData::Empty, _) | (_, VoxelData::Empty) => VoxelData::Empty,
+            (VoxelData::Edge { .. }, _) => first.clone(),
+            (_, VoxelData::Edge { .. }) => second.clone(),
+            _ => VoxelData::InsideMesh,
+        },
+        CsgOperation::Difference => {
+            if second.is_occupied() {
+                VoxelData::Empty
+            } else {
+                first.clone()
+            }
+        }
+        CsgOperation::SymmetricDifference => match (first.is_occupied(), second.is_occupied()) {
+            (true, false) => first.clone(),
+            (false, true) => second.clone(),
+            _ => VoxelData::Empty,
+        },
+    }
+}
+
+
+/// Samples `grid` onto the lattice of `lattice_grid` (its voxel size and origin),
+/// covering at least `bounds`. Each target voxel takes the value of the source voxel
+/// that contains its center (nearest-neighbour sampling).
+pub fn align_to_lattice(grid: &VoxelGrid, lattice_grid: &VoxelGrid, bounds: &Aabb) -> VoxelGrid {
+    // Grids that already span exactly the requested bounds on the same lattice are kept as-is,
+    // which also avoids rounding the dimensions differently (e.g. for animation frames).
+    let grid_bounds = grid.world_bounds();
+    if grid.starting_point == lattice_grid.starting_point
+        && grid.voxel_half_extent == lattice_grid.voxel_half_extent
+        && grid_bounds.min == bounds.min
+        && grid_bounds.max == bounds.max
+    {
+        return grid.with_replaced_voxels(grid.voxels().to_vec());
+    }
+
+    let voxel_size = lattice_grid.voxel_size();
+
+    let first_voxel_offset = ((bounds.min - lattice_grid.starting_point) / voxel_size).floor();
+    let starting_point = lattice_grid.starting_point + first_voxel_offset * voxel_size;
+
+    let dimensions = ((bounds.max - starting_point) / voxel_size)
+        .ceil()
+        .max(Vec3::ONE)
+        .as_u64vec3();
+
+
+    let mut voxels = Vec::with_capacity((dimensions.x * dimensions.y * dimensions.z) as usize);
+
+    for z in 0..dimensions.z {
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let voxel_center = starting_point
+                    + lattice_grid.voxel_half_extent
+                    + U64Vec3::new(x, y, z).as_vec3() * voxel_size;
+
+                voxels.push(
+                    grid.voxel_at_world_position(voxel_center)
+                        .cloned()
+                        .unwrap_or(VoxelData::Empty),
+                );
+            }
+        }
+    }
+
+
+    VoxelGrid::from_voxels(
+        starting_point,
+        lattice_grid.voxel_half_extent,
+        dimensions.x,
+        dimensions.y,
+        dimensions.z,
+        voxels,
+    )
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    const BLUE: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    /// Solid 4x4x4 cube of unit voxels filling its whole grid, with edge voxels of the given color.
+    fn solid_cube_grid(starting_point: Vec3, color: Vec3) -> VoxelGrid {
+        let voxels = (0..4 * 4 * 4)
+            .map(|flat_index| {
+                let grid_index = [flat_index % 4, flat_index / 4 % 4, flat_index / 16];
+
+                if grid_index.iter().any(|index| *index == 0 || *index == 3) {
+                    VoxelData::Edge {
+                        color,
+                        metallic_value: 0.0,
+                        rougness_value: 1.0,
+                        intensity: 0.0,
+                    }
+                } else {
+                    VoxelData::InsideMesh
+                }
+            })
+            .collect::<Vec<_>>();
+
+        VoxelGrid::from_voxels(starting_point, 0.5, 4, 4, 4, voxels)
+    }
+
+    fn occupied_voxel_count(grid: &VoxelGrid) -> usize {
+        grid.voxels()
+            .iter()
+            .filter(|voxel| voxel.is_occupied())
+            .count()
+    }
+
+    fn edge_color(voxel: &VoxelData) -> Option<Vec3> {
+        match voxel {
+            VoxelData::Edge { color, .. } => Some(*color),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn operations_on_offset_lattices() {
+        // The second cube is shifted by two and a quarter voxels along x,
+        // so it is sampled at the voxel centers of the first lattice.
+        let first = solid_cube_grid(Vec3::ZERO, RED);
+        let second = solid_cube_grid(Vec3::new(2.25, 0.0, 0.0), BLUE);
+
+
+        let union = combine_grids(&first, &second, CsgOperation::Union);
+        assert_eq!(union.starting_point, Vec3::ZERO);
+        assert_eq!(union.dimensions(), U64Vec3::new(7, 4, 4));
+        assert_eq!(occupied_voxel_count(&union), 6 * 4 * 4);
+        // Overlapping edge voxels are no longer on the surface.
+        assert!(matches!(
+            union.voxel_by_xyz_index_unchecked(3, 1, 1),
+            VoxelData::InsideMesh
+        ));
+        assert_eq!(
+            edge_color(union.voxel_by_xyz_index_unchecked(5, 0, 0)),
+            Some(BLUE)
+        );
+
+
+        let intersection = combine_grids(&first, &second, CsgOperation::Intersection);
+        assert_eq!(intersection.dimensions(), U64Vec3::new(4, 4, 4));
+        assert_eq!(occupied_voxel_count(&intersection), 2 * 4 * 4);
+        // The first operand's material takes precedence.
+        assert_eq!(
+            edge_color(intersection.voxel_by_xyz_index_unchecked(3, 1, 1)),
+            Some(RED)
+        );
+        assert_eq!(
+            edge_color(intersection.voxel_by_xyz_index_unchecked(2, 1, 1)),
+            Some(BLUE)
+        );
+
+
+        let difference = combine_grids(&first, &second, CsgOperation::Difference);
+        assert_eq!(difference.dimensions(), U64Vec3::new(4, 4, 4));
+        assert_eq!(occupied_voxel_count(&difference), 2 * 4 * 4);
+        // The cut face takes the material of the cutter.
+        assert_eq!(
+            edge_color(difference.voxel_by_xyz_index_unchecked(1, 1, 1)),
+            Some(BLUE)
+        );
+        assert_eq!(
+            edge_color(difference.voxel_by_xyz_index_unchecked(0, 1, 1)),
+            Some(RED)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Euclidean distance transform over voxel grids.
+//!
+//! Implemented as three separable one-dimensional passes, following
+//! "Distance Transforms of Sampled Functions" by Felzenszwalb and Huttenlocher.
+
+use crate::voxelizer::grid::VoxelGrid;
+
+
+/// Computes, for each voxel, the squared Euclidean distance (in voxels) from its center
+/// to the center of the nearest voxel for which `is_target` returns `false`.
+///
+/// Voxels outside of the grid are considered to be non-target voxels, meaning that
+/// a target voxel on the boundary of the grid has a distance of 1.
+/// Non-target voxels have a distance of 0.
+pub fn squared_distance_to_nearest_non_target_voxel<F>(grid: &VoxelGrid, is_target: F) -> Vec<f32>
+where
+    F: Fn(usize) -> bool,
+{
+    let x_length = grid.x_length() as usize;
+    let y_length = grid.y_length() as usize;
+    let z_length = grid.z_length() as usize;
+
+    let mut distances = (0..grid.voxels().len())
+        .map(|flat_index| {
+            if is_target(flat_index) {
+                f32::INFINITY
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut line_buffer = Vec::new();
+    let mut output_buffer = Vec::new();
+    let mut workspace = DistanceTransformWorkspace::default();
+
+
+    // Pass along x.
+    for z in 0..z_length {
+        for y in 0..y_length {
+            let line_start = grid.flat_index(0, y as u64, z as u64);
+
+            transform_line(
+                &mut distances,
+                line_start,
+                1,
+                x_length,
+                &mut line_buffer,
+                &mut output_buffer,
+                &mut workspace,
+            );
+        }
+    }
+
+    // Pass along y.
+    for z in 0..z_length {
+        for x in 0..x_length {
+            let line_start = grid.flat_index(x as u64, 0, z as u64);
+
+            transform_line(
+                &mut distances,
+                line_start,
+                x_length,
+                y_length,
+                &mut line_buffer,
+                &mut output_buffer,
+                &mut workspace,
+            );
+        }
+    }
+
+    // Pass along z.
+    for y in 0..y_length {
+        for x in 0..x_length {
+            let line_start = grid.flat_index(x as u64, y as u64, 0);
+
+            transform_line(
+                &mut distances,
+                line_start,
+                x_length * y_length,
+                z_length,
+                &mut line_buffer,
+                &mut output_buffer,
+                &mut workspace,
+            );
+        }
+    }
+
+
+    distances
+}
+
+
+
+#[derive(Default)]
+struct DistanceTransformWorkspace {
+    parabola_locations: Vec<usize>,
+    parabola_boundaries: Vec<f32>,
+}
+
+
+/// Transforms a single line of the distance field in place. The line starts at
+/// `line_start` and its consecutive elements are `stride` apart.
+fn transform_line(
+    distances: &mut [f32],
+    line_start: usize,
+    stride: usize,
+    line_length: usize,
+    line_buffer: &mut Vec<f32>,
+    output_buffer: &mut Vec<f32>,
+    workspace: &mut DistanceTransformWorkspace,
+) {
+    line_buffer.clear();
+    line_buffer.extend((0..line_length).map(|i| distances[line_start + i * stride]));
+
+    distance_transform_1d(line_buffer, output_buffer, workspace);
+
+    for (i, distance) in output_buffer.iter().enumerate() {
+        // Everything outside the grid counts as a non-target voxel, so the distance
+        // is also bounded by the distance to the nearest grid boundary along this line.
+        let distance_to_lower_boundary = (i + 1) as f32;
+        let distance_to_upper_boundary = (line_length - i) as f32;
+        let boundary_distance = distance_to_lower_boundary.min(distance_to_upper_boundary);
+
+        distances[line_start + i * stride] = distance.min(boundary_distance * boundary_distance);
+    }
+}
+
+
+/// One-dimensional squared distance transform of the sampled function `f`.
+fn distance_transform_1d(
+    f: &[f32],
+    output: &mut Vec<f32>,
+    workspace: &mut DistanceTransformWorkspace,
+) {
+    output.clear();
+
+    let length = f.len();
+    if length == 0 {
+        return;
+    }
+
+    let locations = &mut workspace.parabola_locations;
+    let boundaries = &mut workspace.parabola_boundaries;
+
+    locations.clear();
+    boundaries.clear();
+
+
+    // Only finite samples define parabolas.
+    for q in 0..length {
+        if !f[q].is_finite() {
+            continue;
+        }
+
+        if locations.is_empty() {
+            locations.push(q);
+            boundaries.push(f32::NEG_INFINITY);
+            continue;
+        }
+
+        // The first parabola has a lower boundary of negative infinity,
+        // so this never removes all of them.
+        loop {
+            let v = *locations.last().unwrap();
+            let intersection = ((f[q] + (q * q) as f32) - (f[v] + (v * v) as f32))
+                / (2.0 * q as f32 - 2.0 * v as f32);
+
+            if intersection <= *boundaries.last().unwrap() {
+                locations.pop();
+                boundaries.pop();
+            } else {
+                locations.push(q);
+                boundaries.push(intersection);
+                break;
+            }
+        }
+    }
+
+
+    if locations.is_empty() {
+        output.resize(length, f32::INFINITY);
+        return;
+    }
+
+    let mut k = 0;
+    for q in 0..length {
+        while k + 1 < locations.len() && boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+
+        let v = locations[k];
+        let offset = q as f32 - v as f32;
+
+        output.push(offset * offset + f[v]);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::voxelizer::voxel::VoxelData;
+
+    #[test]
+    fn distances_match_brute_force() {
+        let (x_length, y_length, z_length) = (6_i64, 5_i64, 4_i64);
+
+        // Deterministic pseudo-random occupancy, mostly occupied.
+        let mut random_state: u32 = 12345;
+        let voxels = (0..x_length * y_length * z_length)
+            .map(|_| {
+                random_state = random_state.wrapping_mul(1103515245).wrapping_add(12345);
+
+                if (random_state >> 16).is_multiple_of(5) {
+                    VoxelData::Empty
+                } else {
+                    VoxelData::InsideMesh
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let grid = VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            x_length as u64,
+            y_length as u64,
+            z_length as u64,
+            voxels,
+        );
+
+        let distances = squared_distance_to_nearest_non_target_voxel(&grid, |flat_index| {
+            grid.voxels()[flat_index].is_occupied()
+        });
+
+
+        let grid_index_from_flat_index = |flat_index: i64| {
+            (
+                flat_index % x_length,
+                flat_index / x_length % y_length,
+                flat_index / (x_length * y_length),
+            )
+        };
+
+        for flat_index in 0..x_length * y_length * z_length {
+            let (x, y, z) = grid_index_from_flat_index(flat_index);
+
+            let expected_distance = if grid.voxels()[flat_index as usize].is_occupied() {
+                // The nearest voxel outside of the grid is straight along one of the axes.
+                let distance_to_outside = [
+                    (x + 1).min(x_length - x),
+                    (y + 1).min(y_length - y),
+                    (z + 1).min(z_length - z),
+                ]
+                .into_iter()
+                .min()
+                .unwrap();
+
+                (0..x_length * y_length * z_length)
+                    .filter(|other_flat_index| {
+                        !grid.voxels()[*other_flat_index as usize].is_occupied()
+                    })
+                    .map(|other_flat_index| {
+                        let (other_x, other_y, other_z) =
+                            grid_index_from_flat_index(other_flat_index);
+
+                        (x - other_x).pow(2) + (y - other_y).pow(2) + (z - other_z).pow(2)
+                    })
+                    .fold(distance_to_outside.pow(2), i64::min)
+            } else {
+                0
+            };
+
+            assert_eq!(
+                distances[flat_index as usize], expected_distance as f32,
+                "distance of voxel ({}, {}, {})",
+                x, y, z
+            );
+        }
+    }
+}
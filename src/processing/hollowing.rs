@@ -0,0 +1,512 @@
+//! Hollowing of solid voxel volumes (e.g. for 3D printing).
+//!
+//! Edge voxels are always kept, and inside voxels are kept only if they are within
+//! the requested wall thickness of the surface. The newly created cavity is left empty,
+//! and the inside voxels now exposed on its walls (and on the drain hole walls) are turned
+//! into edge voxels with the material of the nearest original surface voxel.
+
+use std::{fmt::Display, str::FromStr};
+
+use glam::{I64Vec3, U64Vec3};
+use miette::{miette, Report};
+
+use super::{
+    components::{label_connected_components, EMPTY_VOXEL_LABEL},
+    distance::squared_distance_to_nearest_non_target_voxel,
+    find_nearest_edge_voxel,
+    neighbourhood::{Connectivity, StructuringElement},
+    promote_exposed_inside_voxels,
+};
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WallThickness {
+    Voxels(f32),
+    WorldUnits(f32),
+}
+
+impl WallThickness {
+    pub fn in_voxels(&self, voxel_size: f32) -> f32 {
+        match self {
+            WallThickness::Voxels(thickness) => *thickness,
+            WallThickness::WorldUnits(thickness) => *thickness / voxel_size,
+        }
+    }
+}
+
+impl Display for WallThickness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WallThickness::Voxels(thickness) => write!(f, "{} voxels", thickness),
+            WallThickness::WorldUnits(thickness) => write!(f, "{} world units", thickness),
+        }
+    }
+}
+
+
+/// Signed grid axis direction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AxisDirection {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl AxisDirection {
+    pub fn as_grid_offset(&self) -> I64Vec3 {
+        match self {
+            AxisDirection::PositiveX => I64Vec3::X,
+            AxisDirection::NegativeX => I64Vec3::NEG_X,
+            AxisDirection::PositiveY => I64Vec3::Y,
+            AxisDirection::NegativeY => I64Vec3::NEG_Y,
+            AxisDirection::PositiveZ => I64Vec3::Z,
+            AxisDirection::NegativeZ => I64Vec3::NEG_Z,
+        }
+    }
+}
+
+impl Display for AxisDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AxisDirection::PositiveX => write!(f, "+x"),
+            AxisDirection::NegativeX => write!(f, "-x"),
+            AxisDirection::PositiveY => write!(f, "+y"),
+            AxisDirection::NegativeY => write!(f, "-y"),
+            AxisDirection::PositiveZ => write!(f, "+z"),
+            AxisDirection::NegativeZ => write!(f, "-z"),
+        }
+    }
+}
+
+impl FromStr for AxisDirection {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "+x" | "x" => Ok(Self::PositiveX),
+            "-x" => Ok(Self::NegativeX),
+            "+y" | "y" => Ok(Self::PositiveY),
+            "-y" => Ok(Self::NegativeY),
+            "+z" | "z" => Ok(Self::PositiveZ),
+            "-z" => Ok(Self::NegativeZ),
+            _ => Err(miette!(
+                "Invalid axis direction \"{}\", must be one of: +x, -x, +y, -y, +z, -z.",
+                s
+            )),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DrainHole {
+    /// Radius of the drain hole, in voxels.
+    pub radius: u32,
+
+    /// Direction in which the drain hole is drilled from the lowest point
+    /// of each cavity (e.g. `-y` drills downwards).
+    pub direction: AxisDirection,
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HollowingOptions {
+    pub wall_thickness: WallThickness,
+
+    pub drain_hole: Option<DrainHole>,
+}
+
+
+
+/// Hollows out the grid, keeping only a shell of the requested thickness.
+///
+/// Prints the saved volume and warns about wall regions that are already thinner
+/// than the requested thickness.
+pub fn hollow(grid: &VoxelGrid, options: HollowingOptions) -> VoxelGrid {
+    let wall_thickness_in_voxels = options.wall_thickness.in_voxels(grid.voxel_size());
+
+    let voxels = grid.voxels();
+    let squared_distances = squared_distance_to_nearest_non_target_voxel(grid, |flat_index| {
+        voxels[flat_index].is_occupied()
+    });
+
+    let mut hollowed_voxels = voxels.to_vec();
+    let mut removed_voxel_count: u64 = 0;
+
+    for (flat_index, voxel) in hollowed_voxels.iter_mut().enumerate() {
+        if matches!(voxel, VoxelData::InsideMesh)
+            && squared_distances[flat_index].sqrt() > wall_thickness_in_voxels
+        {
+            *voxel = VoxelData::Empty;
+            removed_voxel_count += 1;
+        }
+    }
+
+
+    warn_about_thin_walls(grid, &squared_distances, wall_thickness_in_voxels);
+
+
+    let mut hollowed_grid = grid.with_replaced_voxels(hollowed_voxels);
+
+    if let Some(drain_hole) = options.drain_hole {
+        let cavity_voxels = voxels
+            .iter()
+            .zip(hollowed_grid.voxels())
+            .map(|(original_voxel, hollowed_voxel)| {
+                if original_voxel.is_occupied() && !hollowed_voxel.is_occupied() {
+                    VoxelData::InsideMesh
+                } else {
+                    VoxelData::Empty
+                }
+            })
+            .collect::<Vec<_>>();
+
+        removed_voxel_count += drill_drain_holes(
+            &mut hollowed_grid,
+            &grid.with_replaced_voxels(cavity_voxels),
+            drain_hole,
+        );
+    }
+
+    // Every kept inside voxel lies within the wall thickness of the original surface.
+    let material_search_offsets = StructuringElement::Sphere {
+        radius: wall_thickness_in_voxels.ceil() as u32 + 1,
+    }
+    .offsets_sorted_by_distance();
+    promote_exposed_inside_voxels(&mut hollowed_grid, |grid_index| {
+        find_nearest_edge_voxel(grid, grid_index, &material_search_offsets)
+    });
+
+
+    let original_voxel_count = voxels.iter().filter(|voxel| voxel.is_occupied()).count() as u64;
+    let voxel_volume = grid.voxel_size().powi(3);
+
+    println!(
+        "Hollowing with wall thickness of {} removed {} voxels: \
+        saved volume is {:.4} cubic world units ({:.1} % of the original {:.4}).",
+        options.wall_thickness,
+        removed_voxel_count,
+        removed_voxel_count as f32 * voxel_volume,
+        if original_voxel_count > 0 {
+            removed_voxel_count as f32 / original_voxel_count as f32 * 100.0
+        } else {
+            0.0
+        },
+        original_voxel_count as f32 * voxel_volume,
+    );
+
+    hollowed_grid
+}
+
+
+
+/// Finds voxels on the medial axis of the volume (local maxima of the distance field)
+/// and warns if the wall thickness estimated from them is below the requested thickness.
+fn warn_about_thin_walls(
+    grid: &VoxelGrid,
+    squared_distances: &[f32],
+    wall_thickness_in_voxels: f32,
+) {
+    let neighbour_offsets = Connectivity::TwentySix.offsets();
+
+    let mut thin_voxel_count: u64 = 0;
+    let mut thinnest_wall = f32::INFINITY;
+    let mut thin_region_min = U64Vec3::MAX;
+    let mut thin_region_max = U64Vec3::MIN;
+
+    for (flat_index, voxel) in grid.voxels().iter().enumerate() {
+        if !voxel.is_occupied() {
+            continue;
+        }
+
+        let distance = squared_distances[flat_index].sqrt();
+
+        // Sampled at voxel centers, a wall that is `n` voxels thick has a maximum
+        // distance of `ceil(n / 2)`, so this is a lower estimate of the wall thickness.
+        let estimated_wall_thickness = 2.0 * distance - 1.0;
+        if estimated_wall_thickness >= wall_thickness_in_voxels {
+            continue;
+        }
+
+        let grid_index = grid.grid_index_from_flat_index(flat_index);
+
+        let is_local_maximum = neighbour_offsets.iter().all(|offset| {
+            let neighbour_index = grid_index.as_i64vec3() + *offset;
+
+            match grid.voxel_by_signed_xyz_index(neighbour_index) {
+                Some(neighbour) if neighbour.is_occupied() => {
+                    let neighbour_flat_index = grid.flat_index(
+                        neighbour_index.x as u64,
+                        neighbour_index.y as u64,
+                        neighbour_index.z as u64,
+                    );
+
+                    squared_distances[neighbour_flat_index] <= squared_distances[flat_index]
+                }
+                _ => true,
+            }
+        });
+
+        if !is_local_maximum {
+            continue;
+        }
+
+        thin_voxel_count += 1;
+        thinnest_wall = thinnest_wall.min(estimated_wall_thickness);
+        thin_region_min = thin_region_min.min(grid_index);
+        thin_region_max = thin_region_max.max(grid_index);
+    }
+
+
+    if thin_voxel_count > 0 {
+        println!(
+            "Warning: found {} voxels in wall regions thinner than the requested thickness of {:.2} voxels \
+            (thinnest estimated wall is {:.1} voxels), within grid indices \
+            ({}, {}, {}) to ({}, {}, {}).",
+            thin_voxel_count,
+            wall_thickness_in_voxels,
+            thinnest_wall,
+            thin_region_min.x,
+            thin_region_min.y,
+            thin_region_min.z,
+            thin_region_max.x,
+            thin_region_max.y,
+            thin_region_max.z,
+        );
+    }
+}
+
+
+/// Drills a drain hole from each separate cavity through the wall.
+/// Returns the number of removed voxels.
+fn drill_drain_holes(
+    hollowed_grid: &mut VoxelGrid,
+    cavity_grid: &VoxelGrid,
+    drain_hole: DrainHole,
+) -> u64 {
+    let labeling = label_connected_components(cavity_grid, Connectivity::TwentySix);
+
+    if labeling.components.is_empty() {
+        println!("Warning: hollowing produced no cavity, no drain holes will be drilled.");
+        return 0;
+    }
+
+
+    let direction = drain_hole.direction.as_grid_offset();
+    let radius = drain_hole.radius as i64;
+
+    // The drain hole of each cavity starts at its voxel that is furthest in the drain direction,
+    // preferring the one closest to the center of the cavity. The candidates of all cavities
+    // are found in a single pass over the labels (component `i` has label `i + 1`).
+    let component_centers = labeling
+        .components
+        .iter()
+        .map(|component| (component.min_grid_index + component.max_grid_index).as_dvec3() / 2.0)
+        .collect::<Vec<_>>();
+
+    let mut starting_grid_indices: Vec<Option<((i64, f64), I64Vec3)>> =
+        vec![None; labeling.components.len()];
+
+    for (flat_index, label) in labeling.labels.iter().enumerate() {
+        if *label == EMPTY_VOXEL_LABEL {
+            continue;
+        }
+
+        let component_index = *label as usize - 1;
+        let grid_index = cavity_grid
+            .grid_index_from_flat_index(flat_index)
+            .as_i64vec3();
+
+        let key = (
+            grid_index.dot(direction),
+            -(grid_index.as_dvec3() - component_centers[component_index]).length_squared(),
+        );
+
+        let starting_grid_index = &mut starting_grid_indices[component_index];
+        if starting_grid_index.is_none_or(|(best_key, _)| key >= best_key) {
+            *starting_grid_index = Some((key, grid_index));
+        }
+    }
+
+
+    let mut removed_voxel_count: u64 = 0;
+
+    for starting_grid_index in starting_grid_indices {
+        let (_, starting_grid_index) =
+            starting_grid_index.expect("expected component to contain at least one voxel");
+
+
+        // Drill until we have passed through the wall (or have left the grid).
+        let mut current_center = starting_grid_index;
+        let mut has_reached_wall = false;
+
+        loop {
+            let mut any_inside_grid = false;
+            let mut any_occupied = false;
+
+            for first in -radius..=radius {
+                for second in -radius..=radius {
+                    if first * first + second * second > radius * radius {
+                        continue;
+                    }
+
+                    let disc_offset = match drain_hole.direction {
+                        AxisDirection::PositiveX | AxisDirection::NegativeX => {
+                            I64Vec3::new(0, first, second)
+                        }
+                        AxisDirection::PositiveY | AxisDirection::NegativeY => {
+                            I64Vec3::new(first, 0, second)
+                        }
+                        AxisDirection::PositiveZ | AxisDirection::NegativeZ => {
+                            I64Vec3::new(first, second, 0)
+                        }
+                    };
+
+                    let target_index = current_center + disc_offset;
+
+                    if hollowed_grid
+                        .voxel_by_signed_xyz_index(target_index)
+                        .is_none()
+                    {
+                        continue;
+                    }
+
+                    any_inside_grid = true;
+
+                    let target_voxel = hollowed_grid.voxel_mut_by_xyz_index_unchecked(
+                        target_index.x as u64,
+                        target_index.y as u64,
+                        target_index.z as u64,
+                    );
+
+                    if target_voxel.is_occupied() {
+                        *target_voxel = VoxelData::Empty;
+                        removed_voxel_count += 1;
+                        any_occupied = true;
+                    }
+                }
+            }
+
+            if !any_inside_grid || (has_reached_wall && !any_occupied) {
+                break;
+            }
+
+            has_reached_wall |= any_occupied;
+
+            current_center += direction;
+        }
+    }
+
+
+    println!(
+        "Drilled {} drain hole(s) in direction {}.",
+        labeling.components.len(),
+        drain_hole.direction
+    );
+
+    removed_voxel_count
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    const RED: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+
+    /// Solid 9x9x9 cube filling the whole grid, with red edge voxels on its boundary.
+    fn solid_cube_grid() -> VoxelGrid {
+        let voxels = (0..9 * 9 * 9)
+            .map(|flat_index| {
+                let grid_index = [flat_index % 9, flat_index / 9 % 9, flat_index / 81];
+
+                if grid_index.iter().any(|index| *index == 0 || *index == 8) {
+                    VoxelData::Edge {
+                        color: RED,
+                        metallic_value: 0.0,
+                        rougness_value: 1.0,
+                        intensity: 0.0,
+                    }
+                } else {
+                    VoxelData::InsideMesh
+                }
+            })
+            .collect::<Vec<_>>();
+
+        VoxelGrid::from_voxels(Vec3::ZERO, 0.5, 9, 9, 9, voxels)
+    }
+
+    fn empty_voxel_count(grid: &VoxelGrid) -> usize {
+        grid.voxels()
+            .iter()
+            .filter(|voxel| !voxel.is_occupied())
+            .count()
+    }
+
+    fn is_red_edge_voxel(voxel: &VoxelData) -> bool {
+        matches!(voxel, VoxelData::Edge { color, .. } if *color == RED)
+    }
+
+    #[test]
+    fn hollowing_keeps_walls_of_the_requested_thickness() {
+        let grid = solid_cube_grid();
+
+        let hollowed_grid = hollow(
+            &grid,
+            HollowingOptions {
+                wall_thickness: WallThickness::Voxels(2.0),
+                drain_hole: None,
+            },
+        );
+
+        // The two outermost layers are kept, the 5x5x5 core is removed.
+        assert_eq!(empty_voxel_count(&hollowed_grid), 125);
+        assert!(!hollowed_grid
+            .voxel_by_xyz_index_unchecked(2, 4, 4)
+            .is_occupied());
+
+        // The inner wall is exposed to the cavity and takes the surface material.
+        assert!(is_red_edge_voxel(
+            hollowed_grid.voxel_by_xyz_index_unchecked(1, 4, 4)
+        ));
+        assert!(matches!(
+            hollowed_grid.voxel_by_xyz_index_unchecked(1, 1, 1),
+            VoxelData::InsideMesh
+        ));
+    }
+
+    #[test]
+    fn drain_hole_is_drilled_through_the_wall() {
+        let grid = solid_cube_grid();
+
+        let hollowed_grid = hollow(
+            &grid,
+            HollowingOptions {
+                wall_thickness: WallThickness::Voxels(2.0),
+                drain_hole: Some(DrainHole {
+                    radius: 0,
+                    direction: AxisDirection::NegativeY,
+                }),
+            },
+        );
+
+        // The hole starts below the center of the cavity's bottom layer.
+        assert_eq!(empty_voxel_count(&hollowed_grid), 125 + 2);
+        assert!(!hollowed_grid
+            .voxel_by_xyz_index_unchecked(4, 1, 4)
+            .is_occupied());
+        assert!(!hollowed_grid
+            .voxel_by_xyz_index_unchecked(4, 0, 4)
+            .is_occupied());
+        assert!(is_red_edge_voxel(
+            hollowed_grid.voxel_by_xyz_index_unchecked(3, 1, 4)
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+//! Post-processing operations that work on already-voxelized [`VoxelGrid`]s.
+
+use glam::{I64Vec3, U64Vec3};
+
+use self::neighbourhood::Connectivity;
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+pub mod compare;
+pub mod components;
+pub mod csg;
+pub mod distance;
+pub mod hollowing;
+pub mod morphology;
+pub mod neighbourhood;
+pub mod palette;
+pub mod pipeline;
+pub mod resample;
+
+
+
+/// Returns `true` if the voxel at `grid_index` shares a face with an empty voxel
+/// or with the boundary of the grid.
+pub fn is_voxel_exposed(grid: &VoxelGrid, grid_index: U64Vec3) -> bool {
+    Connectivity::Six.offsets().into_iter().any(|offset| {
+        grid.voxel_by_signed_xyz_index(grid_index.as_i64vec3() + offset)
+            .map(|neighbour| !neighbour.is_occupied())
+            .unwrap_or(true)
+    })
+}
+
+
+/// Finds the closest `Edge` voxel to `grid_index` in `grid`, looking only at the provided
+/// offsets (which should be sorted by ascending distance), and returns a copy of it.
+pub fn find_nearest_edge_voxel(
+    grid: &VoxelGrid,
+    grid_index: U64Vec3,
+    offsets_sorted_by_distance: &[I64Vec3],
+) -> Option<VoxelData> {
+    let grid_index = grid_index.as_i64vec3();
+
+    offsets_sorted_by_distance.iter().find_map(|offset| {
+        match grid.voxel_by_signed_xyz_index(grid_index + *offset) {
+            Some(voxel @ VoxelData::Edge { .. }) => Some(voxel.clone()),
+            _ => None,
+        }
+    })
+}
+
+
+/// Converts `InsideMesh` voxels that are no longer enclosed (i.e. that now share a face
+/// with an empty voxel or with the grid boundary) into `Edge` voxels.
+///
+/// The material of each promoted voxel is obtained from `material_source`
+/// (which receives the grid index of the promoted voxel). If it returns `None`,
+/// the default material is used instead.
+pub fn promote_exposed_inside_voxels<F>(grid: &mut VoxelGrid, mut material_source: F)
+where
+    F: FnMut(U64Vec3) -> Option<VoxelData>,
+{
+    let promoted_grid_indices = grid
+        .voxels()
+        .iter()
+        .enumerate()
+        .filter(|(_, voxel)| matches!(voxel, VoxelData::InsideMesh))
+        .map(|(flat_index, _)| grid.grid_index_from_flat_index(flat_index))
+        .filter(|grid_index| is_voxel_exposed(grid, *grid_index))
+        .collect::<Vec<_>>();
+
+    for grid_index in promoted_grid_indices {
+        let new_voxel = match material_source(grid_index) {
+            Some(edge_voxel @ VoxelData::Edge { .. }) => edge_voxel,
+            _ => VoxelData::new_edge_with_default_material(),
+        };
+
+        *grid.voxel_mut_by_xyz_index_unchecked(grid_index.x, grid_index.y, grid_index.z) = new_voxel;
+    }
+}
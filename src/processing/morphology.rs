@@ -0,0 +1,234 @@
+//! Morphological operations on the occupancy (edge or inside) of a voxel grid.
+//!
+//! Newly occupied voxels take their state and material from the nearest occupied
+//! voxel covered by the structuring element. Inside voxels that become exposed
+//! are turned into edge voxels, taking the material of the nearest edge voxel.
+
+use glam::I64Vec3;
+
+use super::{
+    find_nearest_edge_voxel,
+    neighbourhood::StructuringElement,
+    promote_exposed_inside_voxels,
+};
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+
+/// Grows the occupied region of the grid by the provided structuring element.
+pub fn dilate(grid: &VoxelGrid, structuring_element: StructuringElement) -> VoxelGrid {
+    let offsets = structuring_element.offsets_sorted_by_distance();
+
+    let dilated_voxels = grid
+        .voxels()
+        .iter()
+        .enumerate()
+        .map(|(flat_index, voxel)| {
+            if voxel.is_occupied() {
+                return voxel.clone();
+            }
+
+            let grid_index = grid.grid_index_from_flat_index(flat_index).as_i64vec3();
+
+            find_nearest_occupied_voxel(grid, grid_index, &offsets)
+                .cloned()
+                .unwrap_or(VoxelData::Empty)
+        })
+        .collect::<Vec<_>>();
+
+    grid.with_replaced_voxels(dilated_voxels)
+}
+
+
+/// Shrinks the occupied region of the grid by the provided structuring element.
+/// Voxels outside the grid are considered to be empty.
+pub fn erode(grid: &VoxelGrid, structuring_element: StructuringElement) -> VoxelGrid {
+    let offsets = structuring_element.offsets_sorted_by_distance();
+
+    let eroded_voxels = grid
+        .voxels()
+        .iter()
+        .enumerate()
+        .map(|(flat_index, voxel)| {
+            if !voxel.is_occupied() {
+                return VoxelData::Empty;
+            }
+
+            let grid_index = grid.grid_index_from_flat_index(flat_index).as_i64vec3();
+
+            let is_fully_covered = offsets.iter().all(|offset| {
+                grid.voxel_by_signed_xyz_index(grid_index + *offset)
+                    .map(VoxelData::is_occupied)
+                    .unwrap_or(false)
+            });
+
+            if is_fully_covered {
+                voxel.clone()
+            } else {
+                VoxelData::Empty
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut eroded_grid = grid.with_replaced_voxels(eroded_voxels);
+
+
+    // The surface has moved inwards, so some inside voxels are now on the surface.
+    // We also look one voxel further than the structuring element reaches, as the
+    // closest removed edge voxel may be just outside of it.
+    let material_search_offsets = StructuringElement::Sphere {
+        radius: structuring_element_reach(structuring_element) + 1,
+    }
+    .offsets_sorted_by_distance();
+
+    promote_exposed_inside_voxels(&mut eroded_grid, |grid_index| {
+        find_nearest_edge_voxel(grid, grid_index, &material_search_offsets)
+    });
+
+    eroded_grid
+}
+
+
+/// Erosion followed by dilation. Removes thin protrusions and small specks.
+pub fn open(grid: &VoxelGrid, structuring_element: StructuringElement) -> VoxelGrid {
+    dilate(
+        &erode(grid, structuring_element),
+        structuring_element,
+    )
+}
+
+
+/// Dilation followed by erosion. Closes small holes and gaps.
+pub fn close(grid: &VoxelGrid, structuring_element: StructuringElement) -> VoxelGrid {
+    erode(
+        &dilate(grid, structuring_element),
+        structuring_element,
+    )
+}
+
+
+
+/// Returns the nearest occupied voxel (preferring edge voxels over inside voxels
+/// at the same distance), looking only at the provided offsets.
+fn find_nearest_occupied_voxel<'g>(
+    grid: &'g VoxelGrid,
+    grid_index: I64Vec3,
+    offsets_sorted_by_distance: &[I64Vec3],
+) -> Option<&'g VoxelData> {
+    let mut nearest_inside_voxel: Option<(i64, &VoxelData)> = None;
+
+    for offset in offsets_sorted_by_distance {
+        let distance_squared = offset.length_squared();
+
+        if let Some((inside_voxel_distance_squared, inside_voxel)) = nearest_inside_voxel {
+            if distance_squared > inside_voxel_distance_squared {
+                return Some(inside_voxel);
+            }
+        }
+
+        match grid.voxel_by_signed_xyz_index(grid_index + *offset) {
+            Some(voxel @ VoxelData::Edge { .. }) => return Some(voxel),
+            Some(voxel @ VoxelData::InsideMesh) if nearest_inside_voxel.is_none() => {
+                nearest_inside_voxel = Some((distance_squared, voxel));
+            }
+            _ => {}
+        }
+    }
+
+    nearest_inside_voxel.map(|(_, voxel)| voxel)
+}
+
+
+/// Maximum distance (in voxels, along a single axis) covered by the structuring element.
+fn structuring_element_reach(structuring_element: StructuringElement) -> u32 {
+    match structuring_element {
+        StructuringElement::Neighbourhood(_) => 1,
+        StructuringElement::Sphere { radius } => radius,
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::processing::neighbourhood::Connectivity;
+
+    /// 5x5x5 grid with a red 3x3x3 cube in its middle (the center voxel is an inside voxel).
+    fn grid_with_small_cube() -> VoxelGrid {
+        let red_edge_voxel = VoxelData::Edge {
+            color: Vec3::new(1.0, 0.0, 0.0),
+            metallic_value: 0.0,
+            rougness_value: 1.0,
+            intensity: 0.0,
+        };
+
+        let mut voxels = Vec::with_capacity(125);
+        for z in 0..5_i64 {
+            for y in 0..5_i64 {
+                for x in 0..5_i64 {
+                    let distance_from_center = I64Vec3::new(x - 2, y - 2, z - 2).abs().max_element();
+
+                    voxels.push(match distance_from_center {
+                        0 => VoxelData::InsideMesh,
+                        1 => red_edge_voxel.clone(),
+                        _ => VoxelData::Empty,
+                    });
+                }
+            }
+        }
+
+        VoxelGrid::from_voxels(Vec3::ZERO, 0.5, 5, 5, 5, voxels)
+    }
+
+    fn occupied_voxel_count(grid: &VoxelGrid) -> usize {
+        grid.voxels()
+            .iter()
+            .filter(|voxel| voxel.is_occupied())
+            .count()
+    }
+
+    #[test]
+    fn dilation_and_erosion_grow_and_shrink_a_small_cube() {
+        let grid = grid_with_small_cube();
+
+        let six_neighbourhood = StructuringElement::Neighbourhood(Connectivity::Six);
+        let full_neighbourhood = StructuringElement::Neighbourhood(Connectivity::TwentySix);
+
+        // Face neighbours add a 3x3 slab on each of the six faces.
+        let dilated_grid = dilate(&grid, six_neighbourhood);
+        assert_eq!(occupied_voxel_count(&dilated_grid), 27 + 6 * 9);
+        assert!(matches!(
+            dilated_grid.voxel_by_xyz_index_unchecked(2, 2, 0),
+            VoxelData::Edge { .. }
+        ));
+        assert!(!dilated_grid
+            .voxel_by_xyz_index_unchecked(0, 0, 0)
+            .is_occupied());
+
+        // The full neighbourhood grows the cube to the whole grid.
+        assert_eq!(
+            occupied_voxel_count(&dilate(&grid, full_neighbourhood)),
+            125
+        );
+
+        // Only the center voxel is fully covered, and it is now exposed,
+        // so it becomes an edge voxel with the material of the removed surface.
+        let eroded_grid = erode(&grid, six_neighbourhood);
+        assert_eq!(occupied_voxel_count(&eroded_grid), 1);
+        assert!(matches!(
+            eroded_grid.voxel_by_xyz_index_unchecked(2, 2, 2),
+            VoxelData::Edge { color, .. } if *color == Vec3::new(1.0, 0.0, 0.0)
+        ));
+
+        // Opening with a larger element removes the cube completely.
+        assert_eq!(
+            occupied_voxel_count(&open(
+                &grid,
+                StructuringElement::Sphere { radius: 2 }
+            )),
+            0
+        );
+    }
+}
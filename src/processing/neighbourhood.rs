@@ -0,0 +1,148 @@
+use std::{fmt::Display, str::FromStr};
+
+use glam::I64Vec3;
+use miette::{miette, Context, IntoDiagnostic, Report, Result};
+
+
+/// Voxel connectivity, i.e. which voxels are considered to be neighbours of a given voxel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Connectivity {
+    /// Voxels sharing a face with the center voxel.
+    Six,
+    /// Voxels sharing a face or an edge with the center voxel.
+    Eighteen,
+    /// Voxels sharing a face, an edge or a corner with the center voxel.
+    TwentySix,
+}
+
+impl Connectivity {
+    /// Returns the grid index offsets of all neighbours (the center voxel is not included).
+    pub fn offsets(&self) -> Vec<I64Vec3> {
+        let mut offsets = Vec::with_capacity(26);
+
+        for z in -1..=1_i64 {
+            for y in -1..=1_i64 {
+                for x in -1..=1_i64 {
+                    let number_of_nonzero_components =
+                        (x != 0) as u8 + (y != 0) as u8 + (z != 0) as u8;
+
+                    let is_neighbour = match self {
+                        Connectivity::Six => number_of_nonzero_components == 1,
+                        Connectivity::Eighteen => (1..=2).contains(&number_of_nonzero_components),
+                        Connectivity::TwentySix => number_of_nonzero_components >= 1,
+                    };
+
+                    if is_neighbour {
+                        offsets.push(I64Vec3::new(x, y, z));
+                    }
+                }
+            }
+        }
+
+        offsets
+    }
+}
+
+impl Display for Connectivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Connectivity::Six => write!(f, "6-connectivity"),
+            Connectivity::Eighteen => write!(f, "18-connectivity"),
+            Connectivity::TwentySix => write!(f, "26-connectivity"),
+        }
+    }
+}
+
+impl FromStr for Connectivity {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "6" => Ok(Self::Six),
+            "18" => Ok(Self::Eighteen),
+            "26" => Ok(Self::TwentySix),
+            _ => Err(miette!(
+                "Invalid connectivity \"{}\", must be one of: 6, 18, 26.",
+                s
+            )),
+        }
+    }
+}
+
+
+
+/// Structuring element (i.e. the shape of the neighbourhood) used by morphological operations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StructuringElement {
+    Neighbourhood(Connectivity),
+    /// All voxels whose centers are at most `radius` voxels away from the center voxel.
+    Sphere {
+        radius: u32,
+    },
+}
+
+impl StructuringElement {
+    /// Returns the grid index offsets covered by this structuring element,
+    /// sorted by ascending distance from the center (the center itself is not included).
+    pub fn offsets_sorted_by_distance(&self) -> Vec<I64Vec3> {
+        let mut offsets = match self {
+            StructuringElement::Neighbourhood(connectivity) => connectivity.offsets(),
+            StructuringElement::Sphere { radius } => {
+                let radius = *radius as i64;
+                let mut offsets = Vec::new();
+
+                for z in -radius..=radius {
+                    for y in -radius..=radius {
+                        for x in -radius..=radius {
+                            let offset = I64Vec3::new(x, y, z);
+
+                            if offset != I64Vec3::ZERO && offset.length_squared() <= radius * radius
+                            {
+                                offsets.push(offset);
+                            }
+                        }
+                    }
+                }
+
+                offsets
+            }
+        };
+
+        offsets.sort_by_key(|offset| offset.length_squared());
+        offsets
+    }
+}
+
+impl Display for StructuringElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StructuringElement::Neighbourhood(connectivity) => write!(f, "{}", connectivity),
+            StructuringElement::Sphere { radius } => write!(f, "sphere with radius {}", radius),
+        }
+    }
+}
+
+impl FromStr for StructuringElement {
+    type Err = Report;
+
+    /// Parses one of `6`, `18`, `26` or `radius-<N>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(radius) = s.strip_prefix("radius-") {
+            let radius = radius.parse::<u32>().into_diagnostic().wrap_err_with(|| {
+                miette!(
+                    "Invalid structuring element radius: \"{}\".",
+                    radius
+                )
+            })?;
+
+            return Ok(Self::Sphere { radius });
+        }
+
+        Ok(Self::Neighbourhood(
+            Connectivity::from_str(s)
+                .wrap_err("Invalid structuring element, must be one of: 6, 18, 26, radius-<N>.")?,
+        ))
+    }
+}
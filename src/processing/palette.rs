@@ -0,0 +1,618 @@
+//! Palette quantization of `Edge` voxel colors, for formats that store a palette index
+//! per voxel instead of a full color.
+//!
+//! Colors are quantized in the Oklab color space, in which Euclidean distances roughly match
+//! perceived color differences.
+
+use std::{collections::HashMap, fmt::Display, fs, ops::Range, path::Path, str::FromStr};
+
+use glam::{Mat3, Vec3};
+use miette::{miette, Context, IntoDiagnostic, Report, Result};
+
+use crate::{
+    mesh::{linear_component_to_srgb, srgb_component_to_linear},
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+
+/// Palette index used for voxels without a color (empty and inside voxels).
+pub const EMPTY_PALETTE_INDEX: u8 = 0;
+
+/// Maximum number of colors in a palette (index 0 is reserved for [`EMPTY_PALETTE_INDEX`]).
+pub const MAX_PALETTE_COLORS: usize = u8::MAX as usize;
+
+/// Number of Lloyd iterations used to refine the initial median-cut palette with k-means.
+const K_MEANS_ITERATIONS: usize = 16;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteMethod {
+    /// Recursively splits the color box with the largest spread at its median.
+    MedianCut,
+    /// Median cut, followed by k-means refinement of the palette colors.
+    KMeans,
+}
+
+impl FromStr for PaletteMethod {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "median-cut" => Ok(Self::MedianCut),
+            "k-means" => Ok(Self::KMeans),
+            _ => Err(miette!(
+                "Invalid palette method \"{}\", expected one of: median-cut, k-means.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for PaletteMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaletteMethod::MedianCut => write!(f, "median-cut"),
+            PaletteMethod::KMeans => write!(f, "k-means"),
+        }
+    }
+}
+
+
+/// Where the palette comes from and how voxel colors are mapped onto it.
+#[derive(Clone, Debug)]
+pub struct PaletteOptions {
+    pub method: PaletteMethod,
+
+    /// Maximum number of colors to quantize to (at most [`MAX_PALETTE_COLORS`]).
+    pub max_colors: usize,
+
+    /// If set, this palette is used as-is instead of quantizing the voxel colors.
+    pub user_palette: Option<Palette>,
+
+    /// Whether to diffuse the quantization error to neighbouring edge voxels.
+    pub dithering: bool,
+}
+
+
+/// An ordered list of linear RGB colors.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Palette {
+    colors: Vec<Vec3>,
+}
+
+impl Palette {
+    /// Loads a palette from a text file containing one sRGB color per line, either as hex
+    /// (`#rrggbb`) or as three 0-255 components separated by spaces or commas.
+    /// Empty lines and lines starting with `//` are ignored.
+    pub fn load_from_file<P>(file_path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file_path = file_path.as_ref();
+
+        let contents = fs::read_to_string(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to read palette file {}.",
+                    file_path.display()
+                )
+            })?;
+
+        let mut colors = Vec::new();
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let color = parse_srgb_color(line).wrap_err_with(|| {
+                miette!(
+                    "Invalid color on line {} of palette file {}.",
+                    line_index + 1,
+                    file_path.display()
+                )
+            })?;
+
+            colors.push(color);
+        }
+
+        if colors.is_empty() {
+            return Err(miette!(
+                "Palette file {} contains no colors.",
+                file_path.display()
+            ));
+        }
+
+        if colors.len() > MAX_PALETTE_COLORS {
+            return Err(miette!(
+                "Palette file {} contains {} colors, but at most {} are supported.",
+                file_path.display(),
+                colors.len(),
+                MAX_PALETTE_COLORS
+            ));
+        }
+
+        Ok(Self { colors })
+    }
+
+    /// Creates a palette from linear RGB colors (at most [`MAX_PALETTE_COLORS`]).
+    pub fn from_colors(colors: Vec<Vec3>) -> Self {
+        debug_assert!(!colors.is_empty() && colors.len() <= MAX_PALETTE_COLORS);
+
+        Self { colors }
+    }
+
+    /// Palette colors (linear RGB). The color at position `i` has palette index `i + 1`.
+    pub fn colors(&self) -> &[Vec3] {
+        &self.colors
+    }
+
+    /// Palette colors, encoded as 8-bit sRGB.
+    pub fn srgb8_colors(&self) -> Vec<[u8; 3]> {
+        self.colors
+            .iter()
+            .map(|color| linear_color_to_srgb8(*color))
+            .collect()
+    }
+
+    /// Formats the palette in the format accepted by [`Self::load_from_file`] (hex sRGB).
+    pub fn to_text(&self) -> String {
+        self.srgb8_colors()
+            .into_iter()
+            .map(|[red, green, blue]| format!("#{:02x}{:02x}{:02x}\n", red, green, blue))
+            .collect()
+    }
+}
+
+
+/// Builds a palette for the `Edge` voxel colors of all given grids
+/// (or returns the user-supplied palette, if set).
+pub fn build_palette<'g, I>(grids: I, options: &PaletteOptions) -> Palette
+where
+    I: IntoIterator<Item = &'g VoxelGrid>,
+{
+    if let Some(user_palette) = &options.user_palette {
+        return user_palette.clone();
+    }
+
+    // Colors are deduplicated at 8-bit sRGB precision and weighted by their voxel counts,
+    // which keeps k-means fast for large grids.
+    let mut color_histogram: HashMap<[u8; 3], u64> = HashMap::new();
+
+    for grid in grids {
+        for voxel in grid.voxels() {
+            if let VoxelData::Edge { color, .. } = voxel {
+                *color_histogram
+                    .entry(linear_color_to_srgb8(*color))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    let weighted_colors = color_histogram
+        .into_iter()
+        .map(|(srgb_color, count)| {
+            (
+                linear_to_oklab(srgb8_to_linear_color(srgb_color)),
+                count as f32,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if weighted_colors.is_empty() {
+        return Palette {
+            colors: vec![Vec3::ZERO],
+        };
+    }
+
+    let max_colors = options.max_colors.clamp(1, MAX_PALETTE_COLORS);
+
+    let mut oklab_palette = median_cut(weighted_colors.clone(), max_colors);
+
+    if options.method == PaletteMethod::KMeans {
+        refine_with_k_means(&weighted_colors, &mut oklab_palette);
+    }
+
+    Palette {
+        colors: oklab_palette.into_iter().map(oklab_to_linear).collect(),
+    }
+}
+
+
+/// Maps each voxel of the grid to its palette index
+/// (flattened the same way as [`VoxelGrid::voxels`]).
+///
+/// Voxels without a color are mapped to [`EMPTY_PALETTE_INDEX`].
+/// With dithering, the quantization error of each voxel is spread evenly over its
+/// following (+x, +y and +z) edge voxel neighbours, similar to Floyd-Steinberg dithering.
+pub fn map_grid_to_palette(grid: &VoxelGrid, palette: &Palette, dithering: bool) -> Vec<u8> {
+    let oklab_palette = palette
+        .colors
+        .iter()
+        .map(|color| linear_to_oklab(*color))
+        .collect::<Vec<_>>();
+
+    let voxels = grid.voxels();
+    let mut indices = vec![EMPTY_PALETTE_INDEX; voxels.len()];
+
+    if !dithering {
+        let mut index_cache: HashMap<[u8; 3], u8> = HashMap::new();
+
+        for (voxel, index) in voxels.iter().zip(indices.iter_mut()) {
+            let VoxelData::Edge { color, .. } = voxel else {
+                continue;
+            };
+
+            *index = *index_cache
+                .entry(linear_color_to_srgb8(*color))
+                .or_insert_with(|| {
+                    nearest_palette_entry(&oklab_palette, linear_to_oklab(*color)) as u8 + 1
+                });
+        }
+
+        return indices;
+    }
+
+
+    let mut accumulated_errors = vec![Vec3::ZERO; voxels.len()];
+
+    for z in 0..grid.z_length() {
+        for y in 0..grid.y_length() {
+            for x in 0..grid.x_length() {
+                let flat_index = grid.flat_index(x, y, z);
+
+                let VoxelData::Edge { color, .. } = &voxels[flat_index] else {
+                    continue;
+                };
+
+                let target_color = linear_to_oklab(*color) + accumulated_errors[flat_index];
+                let palette_entry = nearest_palette_entry(&oklab_palette, target_color);
+
+                indices[flat_index] = palette_entry as u8 + 1;
+
+
+                let forward_neighbours = [(x + 1, y, z), (x, y + 1, z), (x, y, z + 1)]
+                    .into_iter()
+                    .filter(|(x, y, z)| {
+                        *x < grid.x_length() && *y < grid.y_length() && *z < grid.z_length()
+                    })
+                    .map(|(x, y, z)| grid.flat_index(x, y, z))
+                    .filter(|neighbour_index| {
+                        matches!(voxels[*neighbour_index], VoxelData::Edge { .. })
+                    })
+                    .collect::<Vec<_>>();
+
+                if forward_neighbours.is_empty() {
+                    continue;
+                }
+
+                let error_share =
+                    (target_color - oklab_palette[palette_entry]) / forward_neighbours.len() as f32;
+
+                for neighbour_index in forward_neighbours {
+                    accumulated_errors[neighbour_index] += error_share;
+                }
+            }
+        }
+    }
+
+    indices
+}
+
+
+
+/// Splits the colors into at most `max_colors` boxes and returns the weighted mean of each box.
+fn median_cut(mut weighted_colors: Vec<(Vec3, f32)>, max_colors: usize) -> Vec<Vec3> {
+    let mut boxes: Vec<Range<usize>> = Vec::with_capacity(max_colors);
+    boxes.push(0..weighted_colors.len());
+
+    while boxes.len() < max_colors {
+        // Split the box with the largest weighted spread along its longest axis.
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, color_box)| color_box.len() > 1)
+            .map(|(box_index, color_box)| {
+                let (min, max) = weighted_colors[color_box.clone()].iter().fold(
+                    (Vec3::MAX, Vec3::MIN),
+                    |(min, max), (color, _)| (min.min(*color), max.max(*color)),
+                );
+
+                let extent = max - min;
+                let weight = weighted_colors[color_box.clone()]
+                    .iter()
+                    .map(|(_, weight)| weight)
+                    .sum::<f32>();
+
+                (
+                    box_index,
+                    extent,
+                    extent.max_element() * weight.sqrt(),
+                )
+            })
+            .max_by(|(_, _, first_score), (_, _, second_score)| first_score.total_cmp(second_score));
+
+        let Some((box_index, extent, score)) = candidate else {
+            break;
+        };
+
+        if score <= 0.0 {
+            break;
+        }
+
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let color_box = boxes.swap_remove(box_index);
+        let box_colors = &mut weighted_colors[color_box.clone()];
+
+        box_colors.sort_by(|(first, _), (second, _)| first[axis].total_cmp(&second[axis]));
+
+        // Split at the weighted median, keeping at least one color on each side.
+        let half_weight = box_colors.iter().map(|(_, weight)| weight).sum::<f32>() / 2.0;
+
+        let mut accumulated_weight = 0.0;
+        let mut split_offset = box_colors.len() - 1;
+
+        for (offset, (_, weight)) in box_colors.iter().enumerate() {
+            accumulated_weight += weight;
+
+            if accumulated_weight >= half_weight {
+                split_offset = offset + 1;
+                break;
+            }
+        }
+
+        let split_index = color_box.start + split_offset.clamp(1, box_colors.len() - 1);
+
+        boxes.push(color_box.start..split_index);
+        boxes.push(split_index..color_box.end);
+    }
+
+    boxes
+        .into_iter()
+        .map(|color_box| weighted_mean(&weighted_colors[color_box]))
+        .collect()
+}
+
+
+/// Refines the palette with Lloyd's algorithm (k-means), starting from the given colors.
+fn refine_with_k_means(weighted_colors: &[(Vec3, f32)], palette: &mut [Vec3]) {
+    for _ in 0..K_MEANS_ITERATIONS {
+        let mut sums = vec![(Vec3::ZERO, 0.0f32); palette.len()];
+
+        for (color, weight) in weighted_colors {
+            let (sum, total_weight) = &mut sums[nearest_palette_entry(palette, *color)];
+
+            *sum += *color * *weight;
+            *total_weight += weight;
+        }
+
+        let mut largest_change: f32 = 0.0;
+
+        for (palette_color, (sum, total_weight)) in palette.iter_mut().zip(sums) {
+            // Entries without any assigned colors are left in place.
+            if total_weight <= 0.0 {
+                continue;
+            }
+
+            let new_color = sum / total_weight;
+            largest_change = largest_change.max(new_color.distance(*palette_color));
+
+            *palette_color = new_color;
+        }
+
+        if largest_change < 1e-4 {
+            break;
+        }
+    }
+}
+
+
+fn weighted_mean(weighted_colors: &[(Vec3, f32)]) -> Vec3 {
+    let (sum, total_weight) = weighted_colors.iter().fold(
+        (Vec3::ZERO, 0.0),
+        |(sum, total_weight), (color, weight)| (sum + *color * *weight, total_weight + weight),
+    );
+
+    sum / total_weight
+}
+
+
+fn nearest_palette_entry(palette: &[Vec3], color: Vec3) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by(|(_, first), (_, second)| {
+            first
+                .distance_squared(color)
+                .total_cmp(&second.distance_squared(color))
+        })
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+
+
+/// Parses an sRGB color, either as hex (`#rrggbb` or `rrggbb`) or as three 0-255 components
+/// separated by spaces or commas, and returns it in linear RGB.
+pub(crate) fn parse_srgb_color(color_str: &str) -> Result<Vec3> {
+    let is_bare_hex = color_str.len() == 6
+        && color_str
+            .chars()
+            .all(|character| character.is_ascii_hexdigit());
+
+    if let Some(hex) = color_str
+        .strip_prefix('#')
+        .or_else(|| is_bare_hex.then_some(color_str))
+    {
+        if hex.len() != 6 {
+            return Err(miette!(
+                "Invalid hex color \"{}\", expected #rrggbb.",
+                color_str
+            ));
+        }
+
+        let value = u32::from_str_radix(hex, 16)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Invalid hex color \"{}\".", color_str))?;
+
+        return Ok(srgb8_to_linear_color([
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+        ]));
+    }
+
+    let components = color_str
+        .split(|character: char| character == ',' || character.is_whitespace())
+        .filter(|component| !component.is_empty())
+        .map(|component| component.parse::<u8>().into_diagnostic())
+        .collect::<Result<Vec<_>>>()
+        .wrap_err_with(|| miette!("Invalid color \"{}\".", color_str))?;
+
+    let [red, green, blue] = components.as_slice() else {
+        return Err(miette!(
+            "Invalid color \"{}\", expected #rrggbb or three 0-255 components.",
+            color_str
+        ));
+    };
+
+    Ok(srgb8_to_linear_color([*red, *green, *blue]))
+}
+
+
+pub(crate) fn srgb8_to_linear_color(srgb_color: [u8; 3]) -> Vec3 {
+    Vec3::from_array(
+        srgb_color.map(|component| srgb_component_to_linear(component as f32 / u8::MAX as f32)),
+    )
+}
+
+pub(crate) fn linear_color_to_srgb8(color: Vec3) -> [u8; 3] {
+    color.to_array().map(|component| {
+        (linear_component_to_srgb(component.clamp(0.0, 1.0)) * u8::MAX as f32).round() as u8
+    })
+}
+
+
+
+// Conversion matrices from "A perceptual color space for image processing" by Björn Ottosson.
+// glam matrices are column-major, so the published row-major matrices are transposed.
+
+const LINEAR_RGB_TO_LMS: Mat3 = Mat3::from_cols_array(&[
+    0.412_221_46,
+    0.211_903_5,
+    0.088_302_46, //
+    0.536_332_55,
+    0.680_699_5,
+    0.281_718_85, //
+    0.051_445_995,
+    0.107_396_96,
+    0.629_978_7,
+]);
+
+const LMS_TO_OKLAB: Mat3 = Mat3::from_cols_array(&[
+    0.210_454_26,
+    1.977_998_5,
+    0.025_904_037, //
+    0.793_617_8,
+    -2.428_592_2,
+    0.782_771_77, //
+    -0.004_072_047,
+    0.450_593_7,
+    -0.808_675_77,
+]);
+
+const OKLAB_TO_LMS: Mat3 = Mat3::from_cols_array(&[
+    1.0,
+    1.0,
+    1.0, //
+    0.396_337_78,
+    -0.105_561_346,
+    -0.089_484_18, //
+    0.215_803_76,
+    -0.063_854_17,
+    -1.291_485_5,
+]);
+
+const LMS_TO_LINEAR_RGB: Mat3 = Mat3::from_cols_array(&[
+    4.076_741_7,
+    -1.268_438,
+    -0.004_196_086_3, //
+    -3.307_711_6,
+    2.609_757_4,
+    -0.703_418_6, //
+    0.230_969_94,
+    -0.341_319_4,
+    1.707_614_7,
+]);
+
+fn linear_to_oklab(color: Vec3) -> Vec3 {
+    let lms = LINEAR_RGB_TO_LMS * color.max(Vec3::ZERO);
+
+    LMS_TO_OKLAB * Vec3::new(lms.x.cbrt(), lms.y.cbrt(), lms.z.cbrt())
+}
+
+fn oklab_to_linear(oklab: Vec3) -> Vec3 {
+    let lms = OKLAB_TO_LMS * oklab;
+
+    (LMS_TO_LINEAR_RGB * (lms * lms * lms)).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn voxels_map_to_nearest_palette_index() {
+        let red = Vec3::new(1.0, 0.0, 0.0);
+        let green = Vec3::new(0.0, 1.0, 0.0);
+        let blue = Vec3::new(0.0, 0.0, 1.0);
+
+        let palette = Palette::from_colors(vec![red, green, blue]);
+
+        let edge_voxel = |color: Vec3| VoxelData::Edge {
+            color,
+            metallic_value: 0.0,
+            rougness_value: 1.0,
+            intensity: 0.0,
+        };
+
+        let voxels = vec![
+            VoxelData::Empty,
+            edge_voxel(red),
+            edge_voxel(green),
+            edge_voxel(blue),
+            VoxelData::InsideMesh,
+            edge_voxel(Vec3::new(0.1, 0.8, 0.1)),
+            edge_voxel(Vec3::new(0.05, 0.0, 0.9)),
+            edge_voxel(Vec3::new(0.9, 0.1, 0.0)),
+        ];
+
+        let grid = VoxelGrid::from_voxels(Vec3::ZERO, 0.5, 2, 2, 2, voxels);
+
+        // Without dithering, and with dithering (exact palette colors carry no error).
+        for dithering in [false, true] {
+            assert_eq!(
+                map_grid_to_palette(&grid, &palette, dithering)[..5],
+                [EMPTY_PALETTE_INDEX, 1, 2, 3, EMPTY_PALETTE_INDEX]
+            );
+        }
+
+        assert_eq!(
+            map_grid_to_palette(&grid, &palette, false),
+            [EMPTY_PALETTE_INDEX, 1, 2, 3, EMPTY_PALETTE_INDEX, 2, 3, 1]
+        );
+    }
+}
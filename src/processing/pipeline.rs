@@ -0,0 +1,382 @@
+//! Ordered post-processing pipeline, configurable from the command line.
+//!
+//! Each step is specified as `<operation>[:<key>=<value>[,<key>=<value>...]]`,
+//! for example `close:element=radius-2` or `dilate:element=26`.
+
+use std::{fmt::Display, str::FromStr};
+
+use miette::{miette, Context, IntoDiagnostic, Report, Result};
+
+use super::{
+    components,
+    hollowing::{self, AxisDirection, DrainHole, HollowingOptions, WallThickness},
+    morphology,
+    neighbourhood::{Connectivity, StructuringElement},
+};
+use crate::voxelizer::grid::VoxelGrid;
+
+
+pub const POST_PROCESSING_STEP_FORMAT_HELP: &str = "Each step has the format \
+    \"<operation>[:<key>=<value>[,<key>=<value>...]]\". Available operations: \
+    dilate, erode, open, close (parameter: element=6|18|26|radius-<N>, defaults to 6), \
+    keep-largest-components (parameters: count=<N>, defaults to 1; connectivity=6|18|26, defaults to 26), \
+    remove-small-components (parameters: min-voxels=<N>; connectivity=6|18|26, defaults to 26), \
+    report-components (parameter: connectivity=6|18|26, defaults to 26), \
+    hollow (parameters: thickness=<voxels> or thickness-world=<world units>; \
+    optional drain-hole-radius=<voxels> and drain-hole-direction=+x|-x|+y|-y|+z|-z, defaults to -y).";
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PostProcessingStep {
+    Dilate {
+        structuring_element: StructuringElement,
+    },
+    Erode {
+        structuring_element: StructuringElement,
+    },
+    Open {
+        structuring_element: StructuringElement,
+    },
+    Close {
+        structuring_element: StructuringElement,
+    },
+    KeepLargestComponents {
+        connectivity: Connectivity,
+        number_of_components_to_keep: usize,
+    },
+    RemoveSmallComponents {
+        connectivity: Connectivity,
+        minimum_voxel_count: u64,
+    },
+    /// Prints connected component sizes and bounding boxes without modifying the grid.
+    ReportComponents {
+        connectivity: Connectivity,
+    },
+    Hollow {
+        options: HollowingOptions,
+    },
+}
+
+impl PostProcessingStep {
+    pub fn apply(&self, grid: VoxelGrid) -> VoxelGrid {
+        match self {
+            PostProcessingStep::Dilate {
+                structuring_element,
+            } => morphology::dilate(&grid, *structuring_element),
+            PostProcessingStep::Erode {
+                structuring_element,
+            } => morphology::erode(&grid, *structuring_element),
+            PostProcessingStep::Open {
+                structuring_element,
+            } => morphology::open(&grid, *structuring_element),
+            PostProcessingStep::Close {
+                structuring_element,
+            } => morphology::close(&grid, *structuring_element),
+            PostProcessingStep::KeepLargestComponents {
+                connectivity,
+                number_of_components_to_keep,
+            } => components::keep_largest_components(
+                &grid,
+                *connectivity,
+                *number_of_components_to_keep,
+            ),
+            PostProcessingStep::RemoveSmallComponents {
+                connectivity,
+                minimum_voxel_count,
+            } => components::remove_small_components(&grid, *connectivity, *minimum_voxel_count),
+            PostProcessingStep::ReportComponents { connectivity } => {
+                components::print_component_report(&grid, *connectivity);
+                grid
+            }
+            PostProcessingStep::Hollow { options } => hollowing::hollow(&grid, *options),
+        }
+    }
+}
+
+impl Display for PostProcessingStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostProcessingStep::Dilate {
+                structuring_element,
+            } => write!(f, "dilate ({})", structuring_element),
+            PostProcessingStep::Erode {
+                structuring_element,
+            } => write!(f, "erode ({})", structuring_element),
+            PostProcessingStep::Open {
+                structuring_element,
+            } => write!(f, "open ({})", structuring_element),
+            PostProcessingStep::Close {
+                structuring_element,
+            } => write!(f, "close ({})", structuring_element),
+            PostProcessingStep::KeepLargestComponents {
+                connectivity,
+                number_of_components_to_keep,
+            } => write!(
+                f,
+                "keep-largest-components ({}, keeping {})",
+                connectivity, number_of_components_to_keep
+            ),
+            PostProcessingStep::RemoveSmallComponents {
+                connectivity,
+                minimum_voxel_count,
+            } => write!(
+                f,
+                "remove-small-components ({}, minimum {} voxels)",
+                connectivity, minimum_voxel_count
+            ),
+            PostProcessingStep::ReportComponents { connectivity } => {
+                write!(f, "report-components ({})", connectivity)
+            }
+            PostProcessingStep::Hollow { options } => {
+                write!(
+                    f,
+                    "hollow (wall thickness of {}",
+                    options.wall_thickness
+                )?;
+
+                if let Some(drain_hole) = options.drain_hole {
+                    write!(
+                        f,
+                        ", drain holes with radius {} towards {}",
+                        drain_hole.radius, drain_hole.direction
+                    )?;
+                }
+
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl FromStr for PostProcessingStep {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parameters = StepParameters::parse(s)?;
+
+        let step = match parameters.operation.as_str() {
+            "dilate" => PostProcessingStep::Dilate {
+                structuring_element: parameters.structuring_element()?,
+            },
+            "erode" => PostProcessingStep::Erode {
+                structuring_element: parameters.structuring_element()?,
+            },
+            "open" => PostProcessingStep::Open {
+                structuring_element: parameters.structuring_element()?,
+            },
+            "close" => PostProcessingStep::Close {
+                structuring_element: parameters.structuring_element()?,
+            },
+            "keep-largest-components" => PostProcessingStep::KeepLargestComponents {
+                connectivity: parameters.connectivity()?,
+                number_of_components_to_keep: parameters.parsed_or("count", 1)?,
+            },
+            "remove-small-components" => PostProcessingStep::RemoveSmallComponents {
+                connectivity: parameters.connectivity()?,
+                minimum_voxel_count: parameters.parsed_required("min-voxels")?,
+            },
+            "report-components" => PostProcessingStep::ReportComponents {
+                connectivity: parameters.connectivity()?,
+            },
+            "hollow" => PostProcessingStep::Hollow {
+                options: parameters.hollowing_options()?,
+            },
+            _ => {
+                return Err(miette!(
+                    "Unknown post-processing operation \"{}\". {}",
+                    parameters.operation,
+                    POST_PROCESSING_STEP_FORMAT_HELP
+                ))
+            }
+        };
+
+        parameters.ensure_all_consumed()?;
+
+        Ok(step)
+    }
+}
+
+
+
+/// Operation name and its `key=value` parameters, as parsed from a single step string.
+struct StepParameters {
+    operation: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl StepParameters {
+    fn parse(step_str: &str) -> Result<Self> {
+        let (operation, parameters_str) = step_str
+            .trim()
+            .split_once(':')
+            .unwrap_or((step_str.trim(), ""));
+
+        let mut parameters = Vec::new();
+
+        for parameter_str in parameters_str.split(',').filter(|p| !p.trim().is_empty()) {
+            let Some((key, value)) = parameter_str.split_once('=') else {
+                return Err(miette!(
+                    "Invalid parameter \"{}\" in post-processing step \"{}\": \
+                    expected \"<key>=<value>\".",
+                    parameter_str,
+                    step_str
+                ));
+            };
+
+            parameters.push((
+                key.trim().to_ascii_lowercase(),
+                value.trim().to_string(),
+            ));
+        }
+
+        Ok(Self {
+            operation: operation.to_ascii_lowercase(),
+            parameters,
+        })
+    }
+
+    /// Removes and returns the value of the given parameter, if present.
+    fn take(&mut self, key: &str) -> Option<String> {
+        let position = self.parameters.iter().position(|(k, _)| k == key)?;
+
+        Some(self.parameters.remove(position).1)
+    }
+
+    fn structuring_element(&mut self) -> Result<StructuringElement> {
+        let Some(element) = self.take("element") else {
+            return Ok(StructuringElement::Neighbourhood(
+                Connectivity::Six,
+            ));
+        };
+
+        StructuringElement::from_str(&element)
+            .wrap_err_with(|| miette!("Invalid parameters for \"{}\".", self.operation))
+    }
+
+    fn connectivity(&mut self) -> Result<Connectivity> {
+        let Some(connectivity) = self.take("connectivity") else {
+            return Ok(Connectivity::TwentySix);
+        };
+
+        Connectivity::from_str(&connectivity)
+            .wrap_err_with(|| miette!("Invalid parameters for \"{}\".", self.operation))
+    }
+
+    fn hollowing_options(&mut self) -> Result<HollowingOptions> {
+        let thickness_in_voxels: Option<f32> = self.parsed_optional("thickness")?;
+        let thickness_in_world_units: Option<f32> = self.parsed_optional("thickness-world")?;
+
+        let wall_thickness = match (thickness_in_voxels, thickness_in_world_units) {
+            (Some(thickness), None) => WallThickness::Voxels(thickness),
+            (None, Some(thickness)) => WallThickness::WorldUnits(thickness),
+            _ => {
+                return Err(miette!(
+                    "Exactly one of \"thickness\" or \"thickness-world\" must be provided \
+                    for post-processing operation \"{}\".",
+                    self.operation
+                ))
+            }
+        };
+
+
+        let drain_hole_radius: Option<u32> = self.parsed_optional("drain-hole-radius")?;
+        let drain_hole_direction = self.take("drain-hole-direction");
+
+        let drain_hole = match drain_hole_radius {
+            Some(radius) => Some(DrainHole {
+                radius,
+                direction: match drain_hole_direction {
+                    Some(direction) => direction.parse().wrap_err_with(|| {
+                        miette!("Invalid parameters for \"{}\".", self.operation)
+                    })?,
+                    None => AxisDirection::NegativeY,
+                },
+            }),
+            None => {
+                if drain_hole_direction.is_some() {
+                    return Err(miette!(
+                        "Parameter \"drain-hole-direction\" requires \"drain-hole-radius\" \
+                        for post-processing operation \"{}\".",
+                        self.operation
+                    ));
+                }
+
+                None
+            }
+        };
+
+
+        Ok(HollowingOptions {
+            wall_thickness,
+            drain_hole,
+        })
+    }
+
+    /// Parses the value of the given parameter, if present.
+    fn parsed_optional<T>(&mut self, key: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        self.take(key)
+            .map(|value| parse_parameter_value(&self.operation, key, &value))
+            .transpose()
+    }
+
+    /// Parses the value of the given parameter, or returns `default` if it is not present.
+    fn parsed_or<T>(&mut self, key: &str, default: T) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        match self.take(key) {
+            Some(value) => parse_parameter_value(&self.operation, key, &value),
+            None => Ok(default),
+        }
+    }
+
+    /// Parses the value of the given parameter, returning an error if it is not present.
+    fn parsed_required<T>(&mut self, key: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let Some(value) = self.take(key) else {
+            return Err(miette!(
+                "Missing required parameter \"{}\" for post-processing operation \"{}\".",
+                key,
+                self.operation
+            ));
+        };
+
+        parse_parameter_value(&self.operation, key, &value)
+    }
+
+    fn ensure_all_consumed(&self) -> Result<()> {
+        if let Some((key, _)) = self.parameters.first() {
+            return Err(miette!(
+                "Unknown parameter \"{}\" for post-processing operation \"{}\".",
+                key,
+                self.operation
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+
+fn parse_parameter_value<T>(operation: &str, key: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value.parse::<T>().into_diagnostic().wrap_err_with(|| {
+        miette!(
+            "Invalid value \"{}\" for parameter \"{}\" of post-processing operation \"{}\".",
+            value,
+            key,
+            operation
+        )
+    })
+}
@@ -0,0 +1,476 @@
+//! Resampling of voxel grids to a new voxel size, lattice origin or affine transform.
+
+use std::{fmt::Display, str::FromStr};
+
+use glam::{Affine3A, I64Vec3, U64Vec3, Vec3};
+use miette::{miette, Report};
+
+use super::{find_nearest_edge_voxel, neighbourhood::StructuringElement};
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+
+/// How the occupancy (and edge or inside state) of a resampled voxel is determined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OccupancyRule {
+    /// Takes the state of the source voxel that contains the resampled voxel's center.
+    Nearest,
+    /// Samples the source grid at several points inside the resampled voxel;
+    /// the voxel is occupied if more than half of the samples are occupied.
+    Majority,
+}
+
+impl Display for OccupancyRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OccupancyRule::Nearest => write!(f, "nearest"),
+            OccupancyRule::Majority => write!(f, "majority"),
+        }
+    }
+}
+
+impl FromStr for OccupancyRule {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "nearest" => Ok(Self::Nearest),
+            "majority" => Ok(Self::Majority),
+            _ => Err(miette!(
+                "Invalid occupancy rule \"{}\", must be one of: nearest, majority.",
+                s
+            )),
+        }
+    }
+}
+
+
+/// How color, metallic and roughness values of resampled edge voxels are filtered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttributeFilter {
+    /// Trilinear interpolation between the eight closest source edge voxels.
+    Trilinear,
+    /// Average of all source edge voxels covered by the resampled voxel.
+    Average,
+}
+
+impl Display for AttributeFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeFilter::Trilinear => write!(f, "trilinear"),
+            AttributeFilter::Average => write!(f, "average"),
+        }
+    }
+}
+
+impl FromStr for AttributeFilter {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trilinear" => Ok(Self::Trilinear),
+            "average" => Ok(Self::Average),
+            _ => Err(miette!(
+                "Invalid attribute filter \"{}\", must be one of: trilinear, average.",
+                s
+            )),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ResamplingOptions {
+    /// New full voxel size. Defaults to the voxel size of the source grid.
+    pub voxel_size: Option<f32>,
+
+    /// A world-space point that lies on a voxel corner of the new lattice.
+    /// Defaults to the minimum corner of the (transformed) source grid.
+    pub lattice_origin: Option<Vec3>,
+
+    /// Affine transform that maps source world space into the new world space.
+    pub transform: Affine3A,
+
+    pub occupancy_rule: OccupancyRule,
+
+    pub attribute_filter: AttributeFilter,
+}
+
+impl Display for ResamplingOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "occupancy rule {}, attribute filter {}",
+            self.occupancy_rule, self.attribute_filter
+        )?;
+
+        if let Some(voxel_size) = self.voxel_size {
+            write!(f, ", voxel size {}", voxel_size)?;
+        }
+
+        if let Some(lattice_origin) = self.lattice_origin {
+            write!(f, ", lattice origin {}", lattice_origin)?;
+        }
+
+        if self.transform != Affine3A::IDENTITY {
+            write!(f, ", transform {}", self.transform)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+
+/// Resamples the grid onto a new lattice, as described by `options`.
+pub fn resample(grid: &VoxelGrid, options: &ResamplingOptions) -> VoxelGrid {
+    let voxel_size = options.voxel_size.unwrap_or(grid.voxel_size());
+    let voxel_half_extent = voxel_size / 2.0;
+
+    let target_bounds = grid.world_bounds().transformed(&options.transform);
+
+    let starting_point = match options.lattice_origin {
+        Some(lattice_origin) => {
+            lattice_origin + ((target_bounds.min - lattice_origin) / voxel_size).floor() * voxel_size
+        }
+        None => target_bounds.min,
+    };
+
+    let dimensions = ((target_bounds.max - starting_point) / voxel_size)
+        .ceil()
+        .max(Vec3::ONE)
+        .as_u64vec3();
+
+
+    let target_to_source = options.transform.inverse();
+
+    // Number of sub-samples per axis for the majority rule and averaging filter:
+    // enough to hit every source voxel covered by a target voxel.
+    let samples_per_axis = (voxel_size / grid.voxel_size()).ceil().clamp(2.0, 8.0) as u32;
+
+    let material_search_offsets =
+        StructuringElement::Sphere { radius: 2 }.offsets_sorted_by_distance();
+
+
+    let mut voxels = Vec::with_capacity((dimensions.x * dimensions.y * dimensions.z) as usize);
+
+    for z in 0..dimensions.z {
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let target_voxel_min = starting_point + U64Vec3::new(x, y, z).as_vec3() * voxel_size;
+                let target_voxel_center = target_voxel_min + voxel_half_extent;
+
+                let source_center = target_to_source.transform_point3(target_voxel_center);
+
+                let needs_sub_samples = options.occupancy_rule == OccupancyRule::Majority
+                    || options.attribute_filter == AttributeFilter::Average;
+
+                let sub_samples = if needs_sub_samples {
+                    sample_sub_voxels(
+                        grid,
+                        &target_to_source,
+                        target_voxel_min,
+                        voxel_size,
+                        samples_per_axis,
+                    )
+                } else {
+                    Vec::new()
+                };
+
+
+                let is_edge = match options.occupancy_rule {
+                    OccupancyRule::Nearest => match grid.voxel_at_world_position(source_center) {
+                        Some(VoxelData::Edge { .. }) => Some(true),
+                        Some(VoxelData::InsideMesh) => Some(false),
+                        _ => None,
+                    },
+                    OccupancyRule::Majority => {
+                        let occupied_count = sub_samples
+                            .iter()
+                            .filter(|voxel| voxel.is_occupied())
+                            .count();
+
+                        if occupied_count * 2 > sub_samples.len() {
+                            Some(
+                                sub_samples
+                                    .iter()
+                                    .any(|voxel| matches!(voxel, VoxelData::Edge { .. })),
+                            )
+                        } else {
+                            None
+                        }
+                    }
+                };
+
+
+                let voxel = match is_edge {
+                    None => VoxelData::Empty,
+                    Some(false) => VoxelData::InsideMesh,
+                    Some(true) => {
+                        let filtered_voxel = match options.attribute_filter {
+                            AttributeFilter::Trilinear => {
+                                interpolate_edge_attributes_trilinearly(grid, source_center)
+                            }
+                            AttributeFilter::Average => average_edge_attributes(&sub_samples),
+                        };
+
+                        filtered_voxel
+                            .or_else(|| {
+                                let source_index = ((source_center - grid.starting_point)
+                                    / grid.voxel_size())
+                                .floor()
+                                .as_i64vec3()
+                                .clamp(I64Vec3::ZERO, grid.dimensions().as_i64vec3() - 1)
+                                .as_u64vec3();
+
+                                find_nearest_edge_voxel(grid, source_index, &material_search_offsets)
+                            })
+                            .unwrap_or_else(VoxelData::new_edge_with_default_material)
+                    }
+                };
+
+                voxels.push(voxel);
+            }
+        }
+    }
+
+
+    VoxelGrid::from_voxels(
+        starting_point,
+        voxel_half_extent,
+        dimensions.x,
+        dimensions.y,
+        dimensions.z,
+        voxels,
+    )
+}
+
+
+
+/// Samples the source grid at `samples_per_axis`^3 evenly spaced points inside the target voxel.
+fn sample_sub_voxels<'g>(
+    grid: &'g VoxelGrid,
+    target_to_source: &Affine3A,
+    target_voxel_min: Vec3,
+    target_voxel_size: f32,
+    samples_per_axis: u32,
+) -> Vec<&'g VoxelData> {
+    let sample_spacing = target_voxel_size / samples_per_axis as f32;
+
+    let mut samples = Vec::with_capacity(samples_per_axis.pow(3) as usize);
+
+    for z in 0..samples_per_axis {
+        for y in 0..samples_per_axis {
+            for x in 0..samples_per_axis {
+                let sample_position = target_voxel_min
+                    + (Vec3::new(x as f32, y as f32, z as f32) + 0.5) * sample_spacing;
+
+                let source_position = target_to_source.transform_point3(sample_position);
+
+                samples.push(
+                    grid.voxel_at_world_position(source_position)
+                        .unwrap_or(&VoxelData::Empty),
+                );
+            }
+        }
+    }
+
+    samples
+}
+
+
+/// Weighted average of edge voxel attributes. Returns `None` if there are no edge voxels.
+fn weighted_edge_attribute_average<'v, I>(weighted_voxels: I) -> Option<VoxelData>
+where
+    I: IntoIterator<Item = (&'v VoxelData, f32)>,
+{
+    let mut total_weight: f32 = 0.0;
+    let mut color_sum = Vec3::ZERO;
+    let mut metallic_sum: f32 = 0.0;
+    let mut roughness_sum: f32 = 0.0;
+    let mut intensity_sum: f32 = 0.0;
+
+    for (voxel, weight) in weighted_voxels {
+        let VoxelData::Edge {
+            color,
+            metallic_value,
+            rougness_value,
+            intensity,
+        } = voxel
+        else {
+            continue;
+        };
+
+        total_weight += weight;
+        color_sum += *color * weight;
+        metallic_sum += metallic_value * weight;
+        roughness_sum += rougness_value * weight;
+        intensity_sum += intensity * weight;
+    }
+
+    if total_weight <= 0.0 {
+        return None;
+    }
+
+    Some(VoxelData::Edge {
+        color: color_sum / total_weight,
+        metallic_value: metallic_sum / total_weight,
+        rougness_value: roughness_sum / total_weight,
+        intensity: intensity_sum / total_weight,
+    })
+}
+
+
+fn average_edge_attributes(samples: &[&VoxelData]) -> Option<VoxelData> {
+    weighted_edge_attribute_average(samples.iter().map(|voxel| (*voxel, 1.0)))
+}
+
+
+/// Trilinearly interpolates attributes of the eight source voxels surrounding `source_position`,
+/// considering only edge voxels (the weights of the remaining ones are redistributed).
+fn interpolate_edge_attributes_trilinearly(
+    grid: &VoxelGrid,
+    source_position: Vec3,
+) -> Option<VoxelData> {
+    let continuous_index =
+        (source_position - grid.starting_point) / grid.voxel_size() - Vec3::splat(0.5);
+
+    let base_index = continuous_index.floor();
+    let fraction = continuous_index - base_index;
+    let base_index = base_index.as_i64vec3();
+
+    let mut weighted_voxels = Vec::with_capacity(8);
+
+    for corner_index in 0..8_i64 {
+        let corner_offset = I64Vec3::new(
+            corner_index & 1,
+            (corner_index >> 1) & 1,
+            (corner_index >> 2) & 1,
+        );
+
+        let weight = (if corner_offset.x == 1 {
+            fraction.x
+        } else {
+            1.0 - fraction.x
+        }) * (if corner_offset.y == 1 {
+            fraction.y
+        } else {
+            1.0 - fraction.y
+        }) * (if corner_offset.z == 1 {
+            fraction.z
+        } else {
+            1.0 - fraction.z
+        });
+
+        if let Some(voxel) = grid.voxel_by_signed_xyz_index(base_index + corner_offset) {
+            weighted_voxels.push((voxel, weight));
+        }
+    }
+
+    weighted_edge_attribute_average(weighted_voxels)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    const BLUE: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    /// 4x4x2 grid of unit voxels built from rows of `r` (red edge voxel), `b` (blue edge voxel)
+    /// and `.` (empty voxel), with both layers identical.
+    fn two_layer_grid(rows: [&str; 4]) -> VoxelGrid {
+        let layer = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|character| {
+                let color = match character {
+                    'r' => RED,
+                    'b' => BLUE,
+                    _ => return VoxelData::Empty,
+                };
+
+                VoxelData::Edge {
+                    color,
+                    metallic_value: 0.0,
+                    rougness_value: 1.0,
+                    intensity: 0.0,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            4,
+            4,
+            2,
+            [layer.clone(), layer].concat(),
+        )
+    }
+
+    fn options(voxel_size: f32, occupancy_rule: OccupancyRule) -> ResamplingOptions {
+        ResamplingOptions {
+            voxel_size: Some(voxel_size),
+            lattice_origin: None,
+            transform: Affine3A::IDENTITY,
+            occupancy_rule,
+            attribute_filter: AttributeFilter::Average,
+        }
+    }
+
+    fn occupied_voxel_count(grid: &VoxelGrid) -> usize {
+        grid.voxels()
+            .iter()
+            .filter(|voxel| voxel.is_occupied())
+            .count()
+    }
+
+    #[test]
+    fn nearest_rule_upsamples_each_voxel() {
+        let grid = two_layer_grid(["rr..", "b...", "..rr", "..r."]);
+
+        let upsampled_grid = resample(&grid, &options(0.5, OccupancyRule::Nearest));
+
+        assert_eq!(upsampled_grid.dimensions(), U64Vec3::new(8, 8, 4));
+        assert_eq!(occupied_voxel_count(&upsampled_grid), 8 * 6 * 2);
+        assert!(matches!(
+            upsampled_grid.voxel_by_xyz_index_unchecked(1, 3, 3),
+            VoxelData::Edge { color, .. } if *color == BLUE
+        ));
+        assert!(!upsampled_grid
+            .voxel_by_xyz_index_unchecked(7, 0, 0)
+            .is_occupied());
+    }
+
+    #[test]
+    fn majority_rule_downsamples_mostly_occupied_voxels() {
+        let grid = two_layer_grid(["rr..", "b...", "..rr", "..r."]);
+
+        let downsampled_grid = resample(&grid, &options(2.0, OccupancyRule::Majority));
+
+        assert_eq!(
+            downsampled_grid.dimensions(),
+            U64Vec3::new(2, 2, 1)
+        );
+        assert!(!downsampled_grid
+            .voxel_by_xyz_index_unchecked(1, 0, 0)
+            .is_occupied());
+        assert!(!downsampled_grid
+            .voxel_by_xyz_index_unchecked(0, 1, 0)
+            .is_occupied());
+
+        // Three of the four covered voxels are occupied, and their colors are averaged.
+        let VoxelData::Edge { color, .. } = downsampled_grid.voxel_by_xyz_index_unchecked(0, 0, 0)
+        else {
+            panic!("expected an edge voxel");
+        };
+        assert!((*color - Vec3::new(2.0 / 3.0, 0.0, 1.0 / 3.0)).length() < 1e-5);
+
+        assert!(matches!(
+            downsampled_grid.voxel_by_xyz_index_unchecked(1, 1, 0),
+            VoxelData::Edge { color, .. } if *color == RED
+        ));
+    }
+}
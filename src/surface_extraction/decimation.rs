@@ -0,0 +1,395 @@
+//! Mesh decimation with quadric error metrics ("Surface Simplification Using Quadric Error
+//! Metrics", Garland and Heckbert 1997). Edges are collapsed in order of increasing error,
+//! each into the position that minimizes the summed squared distance to the planes of the
+//! original triangles around it. Collapses that would make the mesh non-manifold or fold
+//! a triangle over are skipped, so closed, manifold meshes stay closed and manifold.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use glam::{DMat3, DVec3, Vec3};
+
+use super::SurfaceMesh;
+
+
+/// Collapses are rejected if they turn a triangle's normal by more than roughly 78 degrees.
+const MIN_NORMAL_COSINE: f64 = 0.2;
+
+/// Determinants below this (with positions in voxel units) are treated as singular,
+/// e.g. for quadrics of flat regions, which have no unique optimal position.
+const MIN_QUADRIC_DETERMINANT: f64 = 1e-8;
+
+
+/// Symmetric 4x4 matrix `Q` measuring the squared distance `[p, 1] Q [p, 1]ᵀ` of a point `p`
+/// to a set of (weighted) planes. Stores the upper triangle, row by row.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the plane `normal · p + offset = 0` (with a unit normal).
+    fn from_plane(normal: DVec3, offset: f64, weight: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        let d = offset;
+
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|value| value * weight),
+        )
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut sum = self.0;
+        for (value, other_value) in sum.iter_mut().zip(other.0) {
+            *value += other_value;
+        }
+
+        Self(sum)
+    }
+
+    fn error(&self, point: DVec3) -> f64 {
+        let [a00, a01, a02, a03, a11, a12, a13, a22, a23, a33] = self.0;
+        let [x, y, z] = point.to_array();
+
+        a00 * x * x
+            + 2.0 * a01 * x * y
+            + 2.0 * a02 * x * z
+            + 2.0 * a03 * x
+            + a11 * y * y
+            + 2.0 * a12 * y * z
+            + 2.0 * a13 * y
+            + a22 * z * z
+            + 2.0 * a23 * z
+            + a33
+    }
+
+    /// Returns the point with the smallest error, if it is unique.
+    fn optimal_point(&self) -> Option<DVec3> {
+        let [a00, a01, a02, a03, a11, a12, a13, a22, a23, _] = self.0;
+
+        let matrix = DMat3::from_cols(
+            DVec3::new(a00, a01, a02),
+            DVec3::new(a01, a11, a12),
+            DVec3::new(a02, a12, a22),
+        );
+
+        if matrix.determinant().abs() < MIN_QUADRIC_DETERMINANT {
+            return None;
+        }
+
+        Some(matrix.inverse() * -DVec3::new(a03, a13, a23))
+    }
+}
+
+
+/// Candidate edge collapse in the queue, ordered by error. The vertex versions
+/// identify outdated entries (the quadric or position of a vertex has changed since).
+type QueuedCollapse = Reverse<(u64, u32, u32, u32, u32)>;
+
+
+struct Decimator {
+    /// Positions in voxel units, relative to the grid origin.
+    positions: Vec<DVec3>,
+    colors: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    quadrics: Vec<Quadric>,
+    vertex_versions: Vec<u32>,
+    is_vertex_alive: Vec<bool>,
+    /// Alive triangles around each vertex.
+    vertex_triangles: Vec<Vec<u32>>,
+
+    triangles: Vec<[u32; 3]>,
+    is_triangle_alive: Vec<bool>,
+    alive_triangle_count: usize,
+
+    queue: BinaryHeap<QueuedCollapse>,
+}
+
+impl Decimator {
+    fn new(mesh: &SurfaceMesh) -> Self {
+        let positions = mesh
+            .positions
+            .iter()
+            .map(|position| ((*position - mesh.grid_origin) / mesh.voxel_size).as_dvec3())
+            .collect::<Vec<_>>();
+
+        let triangles = mesh
+            .triangle_indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<_>>();
+
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+
+        for (triangle_index, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|vertex| positions[vertex as usize]);
+
+            // Planes are weighted by the triangle area.
+            let cross = (b - a).cross(c - a);
+            let area = cross.length() / 2.0;
+            let normal = cross.normalize_or_zero();
+
+            let plane_quadric = Quadric::from_plane(normal, -normal.dot(a), area);
+
+            for vertex in *triangle {
+                quadrics[vertex as usize] = quadrics[vertex as usize].add(&plane_quadric);
+                vertex_triangles[vertex as usize].push(triangle_index as u32);
+            }
+        }
+
+        let mut decimator = Self {
+            colors: mesh.colors.clone(),
+            normals: mesh.normals.clone(),
+            vertex_versions: vec![0; positions.len()],
+            is_vertex_alive: vec![true; positions.len()],
+            positions,
+            quadrics,
+            vertex_triangles,
+            is_triangle_alive: vec![true; triangles.len()],
+            alive_triangle_count: triangles.len(),
+            triangles,
+            queue: BinaryHeap::new(),
+        };
+
+        for triangle_index in 0..decimator.triangles.len() {
+            let triangle = decimator.triangles[triangle_index];
+
+            for k in 0..3 {
+                // In a closed mesh, each edge appears once in each direction.
+                let (vertex_a, vertex_b) = (triangle[k], triangle[(k + 1) % 3]);
+                if vertex_a < vertex_b {
+                    decimator.queue_collapse(vertex_a, vertex_b);
+                }
+            }
+        }
+
+        decimator
+    }
+
+    /// Returns the collapsed position of an edge and its error.
+    fn collapse_target(&self, vertex_a: u32, vertex_b: u32) -> (DVec3, f64) {
+        let quadric = self.quadrics[vertex_a as usize].add(&self.quadrics[vertex_b as usize]);
+
+        let position_a = self.positions[vertex_a as usize];
+        let position_b = self.positions[vertex_b as usize];
+        let midpoint = (position_a + position_b) / 2.0;
+
+        // Nearly singular quadrics can place the optimum far away from the edge.
+        if let Some(optimal_point) = quadric.optimal_point() {
+            if optimal_point.distance(midpoint) <= position_a.distance(position_b) {
+                return (optimal_point, quadric.error(optimal_point));
+            }
+        }
+
+        [position_a, position_b, midpoint]
+            .into_iter()
+            .map(|point| (point, quadric.error(point)))
+            .min_by(|(_, error_a), (_, error_b)| error_a.total_cmp(error_b))
+            .unwrap()
+    }
+
+    fn queue_collapse(&mut self, vertex_a: u32, vertex_b: u32) {
+        let (_, error) = self.collapse_target(vertex_a, vertex_b);
+
+        // The bits of non-negative floats sort in the same order as the floats.
+        self.queue.push(Reverse((
+            error.max(0.0).to_bits(),
+            vertex_a,
+            vertex_b,
+            self.vertex_versions[vertex_a as usize],
+            self.vertex_versions[vertex_b as usize],
+        )));
+    }
+
+    /// Returns the sorted, deduplicated neighbours of a vertex.
+    fn neighbours(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbours = self.vertex_triangles[vertex as usize]
+            .iter()
+            .flat_map(|triangle_index| self.triangles[*triangle_index as usize])
+            .filter(|neighbour| *neighbour != vertex)
+            .collect::<Vec<_>>();
+
+        neighbours.sort_unstable();
+        neighbours.dedup();
+
+        neighbours
+    }
+
+    /// Checks whether collapsing the edge into `target` keeps the mesh manifold
+    /// (the edge must have exactly two triangles, and the endpoints must have no common
+    /// neighbours besides the opposite vertices of those triangles) and no triangle flips.
+    fn can_collapse(&self, vertex_a: u32, vertex_b: u32, target: DVec3) -> bool {
+        let edge_triangle_count = self.vertex_triangles[vertex_a as usize]
+            .iter()
+            .filter(|triangle_index| self.triangles[**triangle_index as usize].contains(&vertex_b))
+            .count();
+
+        if edge_triangle_count != 2 {
+            return false;
+        }
+
+        let neighbours_b = self.neighbours(vertex_b);
+        let common_neighbour_count = self
+            .neighbours(vertex_a)
+            .iter()
+            .filter(|neighbour| neighbours_b.binary_search(neighbour).is_ok())
+            .count();
+
+        if common_neighbour_count != 2 {
+            return false;
+        }
+
+
+        for (moved_vertex, other_vertex) in [(vertex_a, vertex_b), (vertex_b, vertex_a)] {
+            for triangle_index in &self.vertex_triangles[moved_vertex as usize] {
+                let triangle = self.triangles[*triangle_index as usize];
+                if triangle.contains(&other_vertex) {
+                    continue;
+                }
+
+                let old_corners = triangle.map(|vertex| self.positions[vertex as usize]);
+                let new_corners = triangle.map(|vertex| {
+                    if vertex == moved_vertex {
+                        target
+                    } else {
+                        self.positions[vertex as usize]
+                    }
+                });
+
+                let old_normal = (old_corners[1] - old_corners[0])
+                    .cross(old_corners[2] - old_corners[0])
+                    .normalize_or_zero();
+                let new_normal = (new_corners[1] - new_corners[0])
+                    .cross(new_corners[2] - new_corners[0])
+                    .normalize_or_zero();
+
+                if old_normal.dot(new_normal) < MIN_NORMAL_COSINE {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Collapses `vertex_b` into `vertex_a`, which is moved to `target`.
+    fn collapse(&mut self, vertex_a: u32, vertex_b: u32, target: DVec3) {
+        let position_a = self.positions[vertex_a as usize];
+        let edge = self.positions[vertex_b as usize] - position_a;
+        let t = ((target - position_a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0) as f32;
+
+        self.positions[vertex_a as usize] = target;
+        self.colors[vertex_a as usize] =
+            self.colors[vertex_a as usize].lerp(self.colors[vertex_b as usize], t);
+        self.quadrics[vertex_a as usize] =
+            self.quadrics[vertex_a as usize].add(&self.quadrics[vertex_b as usize]);
+
+        for triangle_index in std::mem::take(&mut self.vertex_triangles[vertex_b as usize]) {
+            let triangle = &mut self.triangles[triangle_index as usize];
+
+            if triangle.contains(&vertex_a) {
+                // One of the two triangles of the collapsed edge.
+                self.is_triangle_alive[triangle_index as usize] = false;
+                self.alive_triangle_count -= 1;
+
+                for vertex in *triangle {
+                    self.vertex_triangles[vertex as usize]
+                        .retain(|other_index| *other_index != triangle_index);
+                }
+            } else {
+                for vertex in triangle.iter_mut() {
+                    if *vertex == vertex_b {
+                        *vertex = vertex_a;
+                    }
+                }
+
+                self.vertex_triangles[vertex_a as usize].push(triangle_index);
+            }
+        }
+
+        self.is_vertex_alive[vertex_b as usize] = false;
+        self.vertex_versions[vertex_a as usize] += 1;
+
+        for neighbour in self.neighbours(vertex_a) {
+            self.queue_collapse(vertex_a, neighbour);
+        }
+    }
+
+    fn run(&mut self, target_triangle_count: usize) {
+        while self.alive_triangle_count > target_triangle_count {
+            let Some(Reverse((_, vertex_a, vertex_b, version_a, version_b))) = self.queue.pop()
+            else {
+                break;
+            };
+
+            let is_outdated = !self.is_vertex_alive[vertex_a as usize]
+                || !self.is_vertex_alive[vertex_b as usize]
+                || self.vertex_versions[vertex_a as usize] != version_a
+                || self.vertex_versions[vertex_b as usize] != version_b;
+
+            if is_outdated {
+                continue;
+            }
+
+            let (target, _) = self.collapse_target(vertex_a, vertex_b);
+
+            if self.can_collapse(vertex_a, vertex_b, target) {
+                self.collapse(vertex_a, vertex_b, target);
+            }
+        }
+    }
+
+    /// Writes the remaining vertices and triangles back into the mesh.
+    fn write_into(self, mesh: &mut SurfaceMesh) {
+        let mut new_vertex_indices = vec![u32::MAX; self.positions.len()];
+
+        mesh.positions.clear();
+        mesh.normals.clear();
+        mesh.colors.clear();
+        mesh.triangle_indices.clear();
+
+        for (triangle, is_alive) in self.triangles.iter().zip(self.is_triangle_alive.iter()) {
+            if !is_alive {
+                continue;
+            }
+
+            for vertex in triangle {
+                let new_index = &mut new_vertex_indices[*vertex as usize];
+
+                if *new_index == u32::MAX {
+                    *new_index = mesh.push_vertex(
+                        mesh.grid_origin
+                            + self.positions[*vertex as usize].as_vec3() * mesh.voxel_size,
+                        self.normals[*vertex as usize],
+                        self.colors[*vertex as usize],
+                    );
+                }
+
+                mesh.triangle_indices.push(*new_index);
+            }
+        }
+    }
+}
+
+
+/// Decimates a closed, welded mesh in place to roughly `ratio` times its triangle count
+/// (fewer collapses happen if they would break the mesh). Vertex normals are not updated.
+pub fn decimate(mesh: &mut SurfaceMesh, ratio: f32) {
+    // A tetrahedron is the smallest closed mesh.
+    let target_triangle_count =
+        ((mesh.triangle_count() as f32 * ratio.clamp(0.0, 1.0)).round() as usize).max(4);
+
+    let mut decimator = Decimator::new(mesh);
+    decimator.run(target_triangle_count);
+    decimator.write_into(mesh);
+}
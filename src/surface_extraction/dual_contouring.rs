@@ -0,0 +1,356 @@
+//! Dual contouring ("Dual Contouring of Hermite Data", Ju et al. 2002) over the voxel centers,
+//! padded with one layer of empty samples so the surface is always closed.
+//!
+//! Every cell between eight neighbouring voxel centers that the surface passes through gets
+//! a single vertex, and every pair of neighbouring voxels with different occupancy produces
+//! a quad connecting the vertices of the four cells around them. Vertices are placed by
+//! minimizing a quadratic error function (QEF) built from the Hermite samples (surface points
+//! and normals) that were collected for the cell's edge voxels during voxelization: the vertex
+//! lands where the planes of the touching triangles meet, which keeps sharp edges and corners.
+//! Directions in which the planes do not constrain the vertex (e.g. along a flat face) are
+//! resolved towards the average of the cell's sign-changing edge midpoints, which is also
+//! where the vertex ends up if the cell has no Hermite samples (e.g. for point clouds).
+//!
+//! As with any method that places a single vertex per cell, cells containing two separate parts
+//! of the surface (e.g. two voxels touching only along an edge) produce non-manifold vertices.
+
+use std::collections::HashMap;
+
+use glam::{DVec3, Vec3};
+use parry3d::na::{Matrix3, Vector3};
+
+use super::{marching_cubes::color_weight, SurfaceMesh};
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+
+/// Eigenvalues of the QEF matrix below this fraction of the largest one are treated as zero,
+/// so that nearly parallel planes do not pull the vertex far away along poorly constrained
+/// directions.
+const RELATIVE_EIGENVALUE_THRESHOLD: f64 = 0.1;
+
+
+/// Occupancy of the voxel centers, padded with one layer of empty samples on each side
+/// (sample `s` corresponds to voxel `s - 1`).
+struct PaddedOccupancy {
+    dimensions: [usize; 3],
+    occupied: Vec<bool>,
+}
+
+impl PaddedOccupancy {
+    fn new_for_grid(grid: &VoxelGrid) -> Self {
+        let dimensions = grid
+            .dimensions()
+            .to_array()
+            .map(|length| length as usize + 2);
+
+        let mut occupied = vec![false; dimensions[0] * dimensions[1] * dimensions[2]];
+
+        for (flat_index, voxel) in grid.voxels().iter().enumerate() {
+            if voxel.is_occupied() {
+                let grid_index = grid.grid_index_from_flat_index(flat_index);
+
+                let sample_index = grid_index.x as usize
+                    + 1
+                    + (grid_index.y as usize + 1) * dimensions[0]
+                    + (grid_index.z as usize + 1) * dimensions[0] * dimensions[1];
+
+                occupied[sample_index] = true;
+            }
+        }
+
+        Self {
+            dimensions,
+            occupied,
+        }
+    }
+
+    #[inline]
+    fn index(&self, sample: [usize; 3]) -> usize {
+        sample[0]
+            + sample[1] * self.dimensions[0]
+            + sample[2] * self.dimensions[0] * self.dimensions[1]
+    }
+
+    #[inline]
+    fn is_occupied(&self, sample: [usize; 3]) -> bool {
+        self.occupied[self.index(sample)]
+    }
+}
+
+
+/// Returns the flat grid index of the voxel at the given (padded) sample,
+/// or `None` if the sample lies in the padding.
+fn voxel_flat_index_of_sample(grid: &VoxelGrid, sample: [usize; 3]) -> Option<usize> {
+    let dimensions = grid.dimensions().to_array();
+
+    if sample
+        .iter()
+        .zip(dimensions.iter())
+        .any(|(component, length)| *component == 0 || *component as u64 > *length)
+    {
+        return None;
+    }
+
+    Some(grid.flat_index(
+        sample[0] as u64 - 1,
+        sample[1] as u64 - 1,
+        sample[2] as u64 - 1,
+    ))
+}
+
+
+/// Minimizes `sum((n_i · (x - p_i))^2)` over the given planes (in sample coordinates),
+/// using a truncated pseudo-inverse around `mass_point`.
+fn solve_qef(planes: &[(DVec3, DVec3)], mass_point: DVec3) -> DVec3 {
+    let mut ata = Matrix3::<f64>::zeros();
+    let mut atb = Vector3::<f64>::zeros();
+
+    for (position, normal) in planes {
+        let normal = Vector3::new(normal.x, normal.y, normal.z);
+
+        ata += normal * normal.transpose();
+        atb += normal * normal.dot(&Vector3::new(position.x, position.y, position.z));
+    }
+
+    let mass_point_vector = Vector3::new(mass_point.x, mass_point.y, mass_point.z);
+    let residual = atb - ata * mass_point_vector;
+
+    let eigen = ata.symmetric_eigen();
+    let largest_eigenvalue = eigen.eigenvalues.max();
+    if largest_eigenvalue <= 0.0 {
+        return mass_point;
+    }
+
+    let mut offset = Vector3::<f64>::zeros();
+    for (eigenvalue, eigenvector) in eigen
+        .eigenvalues
+        .iter()
+        .zip(eigen.eigenvectors.column_iter())
+    {
+        if *eigenvalue < largest_eigenvalue * RELATIVE_EIGENVALUE_THRESHOLD {
+            continue;
+        }
+
+        offset += eigenvector * (eigenvector.dot(&residual) / eigenvalue);
+    }
+
+    mass_point + DVec3::new(offset.x, offset.y, offset.z)
+}
+
+
+/// Computes the vertex position (in sample coordinates) and color of the cell
+/// whose lowest corner is `cell`.
+fn compute_cell_vertex(
+    grid: &VoxelGrid,
+    occupancy: &PaddedOccupancy,
+    cell: [usize; 3],
+) -> (Vec3, Vec3) {
+    let corner_sample = |corner: usize| {
+        [
+            cell[0] + (corner & 1),
+            cell[1] + ((corner >> 1) & 1),
+            cell[2] + ((corner >> 2) & 1),
+        ]
+    };
+
+    // Midpoints of the cell edges whose ends differ in occupancy.
+    let mut midpoint_sum = DVec3::ZERO;
+    let mut midpoint_count = 0;
+
+    for corner in 0..8 {
+        for axis in 0..3 {
+            if corner & (1 << axis) != 0 {
+                continue;
+            }
+
+            let lower_sample = corner_sample(corner);
+            let upper_sample = corner_sample(corner | (1 << axis));
+
+            if occupancy.is_occupied(lower_sample) != occupancy.is_occupied(upper_sample) {
+                let mut midpoint = DVec3::from_array(lower_sample.map(|component| component as f64));
+                midpoint[axis] += 0.5;
+
+                midpoint_sum += midpoint;
+                midpoint_count += 1;
+            }
+        }
+    }
+
+    let mass_point = midpoint_sum / midpoint_count.max(1) as f64;
+
+
+    // Hermite samples of the cell's voxels, converted to sample coordinates
+    // (the center of voxel `i` is at sample `i + 1`).
+    let planes = (0..8)
+        .filter_map(|corner| voxel_flat_index_of_sample(grid, corner_sample(corner)))
+        .flat_map(|flat_index| grid.hermite_samples_by_flat_index(flat_index))
+        .map(|hermite_sample| {
+            let position = (hermite_sample.position - grid.starting_point) / grid.voxel_size() + 0.5;
+
+            (
+                position.as_dvec3(),
+                hermite_sample.normal.as_dvec3(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Edge voxels are occupied even if their center lies outside the mesh, so the surface
+    // can pass anywhere through the corner voxels' boxes, not just through the cell itself.
+    let cell_min = DVec3::from_array(cell.map(|component| component as f64));
+    let position = if planes.is_empty() {
+        mass_point
+    } else {
+        solve_qef(&planes, mass_point).clamp(cell_min - 0.5, cell_min + 1.5)
+    }
+    .as_vec3();
+
+
+    let mut color_sum = Vec3::ZERO;
+    let mut weight_sum = 0.0;
+
+    for corner in 0..8 {
+        let sample = corner_sample(corner);
+
+        let Some(VoxelData::Edge { color, .. }) =
+            voxel_flat_index_of_sample(grid, sample).map(|flat_index| &grid.voxels()[flat_index])
+        else {
+            continue;
+        };
+
+        let weight = color_weight(
+            Vec3::from_array(sample.map(|component| component as f32)).distance_squared(position),
+        );
+
+        color_sum += *color * weight;
+        weight_sum += weight;
+    }
+
+    let color = if weight_sum > 0.0 {
+        color_sum / weight_sum
+    } else {
+        Vec3::ONE
+    };
+
+    (position, color)
+}
+
+
+/// Appends the two triangles of a quad (counter-clockwise when viewed from the outside),
+/// splitting it along the diagonal that leaves the two triangles closest to coplanar.
+fn push_quad(mesh: &mut SurfaceMesh, quad: [u32; 4]) {
+    let triangle_normal = |a: u32, b: u32, c: u32| {
+        let [a, b, c] = [a, b, c].map(|vertex| mesh.positions[vertex as usize]);
+
+        (b - a).cross(c - a).normalize_or_zero()
+    };
+
+    let [v0, v1, v2, v3] = quad;
+
+    let first_diagonal_flatness = triangle_normal(v0, v1, v2).dot(triangle_normal(v0, v2, v3));
+    let second_diagonal_flatness = triangle_normal(v0, v1, v3).dot(triangle_normal(v1, v2, v3));
+
+    if first_diagonal_flatness >= second_diagonal_flatness {
+        mesh.triangle_indices
+            .extend_from_slice(&[v0, v1, v2, v0, v2, v3]);
+    } else {
+        mesh.triangle_indices
+            .extend_from_slice(&[v0, v1, v3, v1, v2, v3]);
+    }
+}
+
+
+/// Extracts the surface between occupied and empty voxels as a closed triangle mesh
+/// with shared vertices, placed on the original mesh surface using the grid's Hermite samples
+/// (see the module documentation).
+pub fn dual_contouring(grid: &VoxelGrid) -> SurfaceMesh {
+    let occupancy = PaddedOccupancy::new_for_grid(grid);
+
+    let mut mesh = SurfaceMesh::empty_for_grid(grid);
+
+    // Keyed by the index of the cell's lowest sample.
+    let mut cell_vertices: HashMap<usize, u32> = HashMap::new();
+
+    let sample_to_world =
+        |sample_position: Vec3| grid.starting_point + (sample_position - 0.5) * grid.voxel_size();
+
+    let [x_samples, y_samples, z_samples] = occupancy.dimensions;
+
+    for z in 0..z_samples {
+        for y in 0..y_samples {
+            for x in 0..x_samples {
+                let sample = [x, y, z];
+                let sample_is_occupied = occupancy.is_occupied(sample);
+
+                for axis in 0..3 {
+                    let mut upper_sample = sample;
+                    upper_sample[axis] += 1;
+
+                    if upper_sample[axis] >= occupancy.dimensions[axis]
+                        || occupancy.is_occupied(upper_sample) == sample_is_occupied
+                    {
+                        continue;
+                    }
+
+                    // One of the two samples is an actual voxel, so the sample is at least 1
+                    // along the other axes and all four cells around the edge exist.
+                    let axis_u = (axis + 1) % 3;
+                    let axis_v = (axis + 2) % 3;
+
+                    let mut quad = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(offset_u, offset_v)| {
+                        let mut cell = sample;
+                        cell[axis_u] -= offset_u;
+                        cell[axis_v] -= offset_v;
+
+                        *cell_vertices
+                            .entry(occupancy.index(cell))
+                            .or_insert_with(|| {
+                                let (position, color) = compute_cell_vertex(grid, &occupancy, cell);
+
+                                mesh.push_vertex(sample_to_world(position), Vec3::ZERO, color)
+                            })
+                    });
+
+                    // The quad is counter-clockwise around the positive axis direction,
+                    // which must point outwards (from the occupied to the empty sample).
+                    if !sample_is_occupied {
+                        quad.reverse();
+                    }
+
+                    push_quad(&mut mesh, quad);
+                }
+            }
+        }
+    }
+
+    mesh.recompute_vertex_normals();
+    mesh
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qef_finds_the_sharp_corner() {
+        let mass_point = DVec3::new(0.5, 0.6, 0.7);
+
+        // Three faces of a cube meeting at (1, 1, 1), sampled away from the corner.
+        let corner_planes = [
+            (DVec3::new(1.0, 0.2, 0.3), DVec3::X),
+            (DVec3::new(0.4, 1.0, 0.1), DVec3::Y),
+            (DVec3::new(0.6, 0.7, 1.0), DVec3::Z),
+        ];
+        assert!(solve_qef(&corner_planes, mass_point).abs_diff_eq(DVec3::ONE, 1e-9));
+
+        // Along an edge, the unconstrained direction keeps the mass point's coordinate.
+        let edge_planes = [
+            (DVec3::new(1.0, 0.2, 0.3), DVec3::X),
+            (DVec3::new(0.4, 1.0, 0.1), DVec3::Y),
+        ];
+        assert!(solve_qef(&edge_planes, mass_point).abs_diff_eq(DVec3::new(1.0, 1.0, 0.7), 1e-9));
+
+        assert_eq!(solve_qef(&[], mass_point), mass_point);
+    }
+}
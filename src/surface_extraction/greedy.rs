@@ -0,0 +1,273 @@
+//! Greedy meshing: the faces between occupied and empty voxels (including the grid boundary)
+//! are collected slice by slice along each axis, and adjacent coplanar faces with the same
+//! orientation and the same color (at 8-bit sRGB precision) are merged into larger rectangles.
+//! Faces between two occupied voxels are never emitted, so the interior is culled.
+//!
+//! Vertices are placed on voxel boundaries in world space, so the mesh exactly covers
+//! the occupied voxels of the grid.
+
+use glam::{I64Vec3, Vec3};
+
+use super::{voxel_surface_color, SurfaceMesh};
+use crate::{
+    processing::palette::{linear_color_to_srgb8, srgb8_to_linear_color},
+    voxelizer::grid::VoxelGrid,
+};
+
+
+/// A single voxel face in a slice, identified by its orientation and color.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct FaceKey {
+    /// Whether the face points towards the positive end of the slicing axis.
+    faces_positive: bool,
+    srgb_color: [u8; 3],
+}
+
+
+/// Looks up the precomputed face color of a voxel, treating voxels outside the grid as empty.
+fn voxel_face_color(
+    grid: &VoxelGrid,
+    face_colors: &[Option<[u8; 3]>],
+    grid_index: [i64; 3],
+) -> Option<[u8; 3]> {
+    let [x, y, z] = grid_index;
+    let dimensions = grid.dimensions().as_i64vec3();
+
+    if x < 0 || y < 0 || z < 0 || x >= dimensions.x || y >= dimensions.y || z >= dimensions.z {
+        return None;
+    }
+
+    face_colors[grid.flat_index(x as u64, y as u64, z as u64)]
+}
+
+
+/// Appends a rectangle lying in the plane `axis = plane`, spanning `[start_u, start_u + width]`
+/// and `[start_v, start_v + height]` along the other two axes (all in voxel units).
+#[allow(clippy::too_many_arguments)]
+fn push_rectangle(
+    mesh: &mut SurfaceMesh,
+    grid: &VoxelGrid,
+    axis: usize,
+    plane: i64,
+    start_u: i64,
+    start_v: i64,
+    width: i64,
+    height: i64,
+    face_key: FaceKey,
+) {
+    let axis_u = (axis + 1) % 3;
+    let axis_v = (axis + 2) % 3;
+
+    let mut corner = [0i64; 3];
+    corner[axis] = plane;
+    corner[axis_u] = start_u;
+    corner[axis_v] = start_v;
+
+    let mut edge_u = [0i64; 3];
+    edge_u[axis_u] = width;
+
+    let mut edge_v = [0i64; 3];
+    edge_v[axis_v] = height;
+
+    let corner = I64Vec3::from_array(corner);
+    let edge_u = I64Vec3::from_array(edge_u);
+    let edge_v = I64Vec3::from_array(edge_v);
+
+    let mut normal = Vec3::ZERO;
+    normal[axis] = if face_key.faces_positive { 1.0 } else { -1.0 };
+
+    let color = srgb8_to_linear_color(face_key.srgb_color);
+
+    let to_world =
+        |voxel_corner: I64Vec3| grid.starting_point + voxel_corner.as_vec3() * grid.voxel_size();
+
+    let vertex_indices = [
+        corner,
+        corner + edge_u,
+        corner + edge_u + edge_v,
+        corner + edge_v,
+    ]
+    .map(|voxel_corner| mesh.push_vertex(to_world(voxel_corner), normal, color));
+
+    // The (axis, u, v) axes are right-handed, so the corners above are
+    // counter-clockwise when viewed from the positive end of the slicing axis.
+    let [a, b, c, d] = vertex_indices;
+    if face_key.faces_positive {
+        mesh.triangle_indices.extend_from_slice(&[a, b, c, a, c, d]);
+    } else {
+        mesh.triangle_indices.extend_from_slice(&[a, c, b, a, d, c]);
+    }
+}
+
+
+/// Extracts the surface of all occupied voxels as a greedy-meshed set of rectangles
+/// (two triangles each, with flat normals and per-face colors).
+pub fn greedy_mesh(grid: &VoxelGrid) -> SurfaceMesh {
+    let mut mesh = SurfaceMesh::empty_for_grid(grid);
+
+    let dimensions = grid.dimensions().as_i64vec3().to_array();
+
+    // Each voxel is looked up by up to six faces, so its sRGB color is only computed once.
+    let face_colors = grid
+        .voxels()
+        .iter()
+        .map(|voxel| voxel_surface_color(voxel).map(linear_color_to_srgb8))
+        .collect::<Vec<_>>();
+
+    for axis in 0..3 {
+        let axis_u = (axis + 1) % 3;
+        let axis_v = (axis + 2) % 3;
+
+        let size_u = dimensions[axis_u];
+        let size_v = dimensions[axis_v];
+
+        let mut mask: Vec<Option<FaceKey>> = vec![None; (size_u * size_v) as usize];
+
+        // Each slice contains the faces between voxel layer `slice` and `slice + 1`.
+        for slice in -1..dimensions[axis] {
+            for v in 0..size_v {
+                for u in 0..size_u {
+                    let mut below = [0i64; 3];
+                    below[axis] = slice;
+                    below[axis_u] = u;
+                    below[axis_v] = v;
+
+                    let mut above = below;
+                    above[axis] += 1;
+
+                    mask[(u + v * size_u) as usize] = match (
+                        voxel_face_color(grid, &face_colors, below),
+                        voxel_face_color(grid, &face_colors, above),
+                    ) {
+                        (Some(srgb_color), None) => Some(FaceKey {
+                            faces_positive: true,
+                            srgb_color,
+                        }),
+                        (None, Some(srgb_color)) => Some(FaceKey {
+                            faces_positive: false,
+                            srgb_color,
+                        }),
+                        _ => None,
+                    };
+                }
+            }
+
+
+            // Greedily grow rectangles: first along u, then along v.
+            for v in 0..size_v {
+                let mut u = 0;
+
+                while u < size_u {
+                    let Some(face_key) = mask[(u + v * size_u) as usize] else {
+                        u += 1;
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while u + width < size_u
+                        && mask[(u + width + v * size_u) as usize] == Some(face_key)
+                    {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    'grow_height: while v + height < size_v {
+                        for offset_u in 0..width {
+                            if mask[(u + offset_u + (v + height) * size_u) as usize]
+                                != Some(face_key)
+                            {
+                                break 'grow_height;
+                            }
+                        }
+
+                        height += 1;
+                    }
+
+                    for offset_v in 0..height {
+                        for offset_u in 0..width {
+                            mask[(u + offset_u + (v + offset_v) * size_u) as usize] = None;
+                        }
+                    }
+
+                    push_rectangle(
+                        &mut mesh,
+                        grid,
+                        axis,
+                        slice + 1,
+                        u,
+                        v,
+                        width,
+                        height,
+                        face_key,
+                    );
+
+                    u += width;
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxelizer::voxel::VoxelData;
+
+    fn edge_voxel(color: Vec3) -> VoxelData {
+        VoxelData::Edge {
+            color,
+            metallic_value: 0.0,
+            rougness_value: 1.0,
+            intensity: 0.0,
+        }
+    }
+
+    #[test]
+    fn two_voxel_block_is_merged_into_six_quads() {
+        let red = Vec3::new(1.0, 0.0, 0.0);
+        let grid = VoxelGrid::from_voxels(
+            Vec3::new(1.0, 2.0, 3.0),
+            0.5,
+            2,
+            1,
+            1,
+            vec![edge_voxel(red), edge_voxel(red)],
+        );
+
+        let mesh = greedy_mesh(&grid);
+
+        assert_eq!(mesh.triangle_count(), 6 * 2);
+        assert_eq!(mesh.vertex_count(), 6 * 4);
+
+        let minimum = mesh.positions.iter().copied().fold(Vec3::MAX, Vec3::min);
+        let maximum = mesh.positions.iter().copied().fold(Vec3::MIN, Vec3::max);
+        assert_eq!(minimum, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(maximum, Vec3::new(3.0, 3.0, 4.0));
+        assert!(mesh.colors.iter().all(|color| *color == red));
+    }
+
+    #[test]
+    fn faces_of_different_colors_are_not_merged() {
+        let grid = VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            2,
+            1,
+            1,
+            vec![
+                edge_voxel(Vec3::new(1.0, 0.0, 0.0)),
+                edge_voxel(Vec3::new(0.0, 0.0, 1.0)),
+            ],
+        );
+
+        // The two end faces, and two faces on each of the four long sides.
+        assert_eq!(
+            greedy_mesh(&grid).triangle_count(),
+            (2 + 4 * 2) * 2
+        );
+    }
+}
@@ -0,0 +1,736 @@
+//! Marching cubes over a scalar field sampled at the voxel centers (negative inside, positive
+//! outside), padded with one layer of outside samples so the surface is always closed.
+//!
+//! Instead of the classic hand-written case table, the triangulation of each of the 256 cases
+//! is generated from the cube faces: every face contributes segments between its intersected
+//! edges, with ambiguous faces (inside corners on one diagonal, outside corners on the other)
+//! always separating the inside corners. As two neighbouring cubes make the same choice
+//! on their shared face, the segments chain into closed loops that fit together without cracks,
+//! and the resulting mesh is watertight and manifold.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::OnceLock};
+
+use glam::{I64Vec3, Vec3};
+use miette::{miette, Report, Result};
+
+use super::SurfaceMesh;
+use crate::{
+    processing::distance::squared_distance_to_nearest_non_target_voxel,
+    voxelizer::{grid::VoxelGrid, voxel::VoxelData},
+};
+
+
+/// Scalar field that marching cubes extracts the zero level set of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MarchingCubesField {
+    /// `-0.5` for occupied voxels and `0.5` for empty ones; the surface passes exactly halfway
+    /// between the centers of neighbouring occupied and empty voxels.
+    Occupancy,
+
+    /// Signed Euclidean distance (in voxels) to the boundary of the occupied voxels, lightly
+    /// smoothed so the surface follows the overall shape instead of each voxel step.
+    /// The sign of each sample is kept, so thin features don't disappear.
+    SignedDistance,
+}
+
+impl FromStr for MarchingCubesField {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "occupancy" => Ok(Self::Occupancy),
+            "signed-distance" => Ok(Self::SignedDistance),
+            _ => Err(miette!(
+                "Invalid marching cubes field \"{}\", must be one of: occupancy, signed-distance.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for MarchingCubesField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarchingCubesField::Occupancy => write!(f, "occupancy"),
+            MarchingCubesField::SignedDistance => write!(f, "signed-distance"),
+        }
+    }
+}
+
+
+/// Smallest magnitude (in voxels) of a smoothed signed distance sample. Keeping samples away
+/// from zero keeps vertices off the sample positions and prevents degenerate triangles.
+const MIN_SAMPLE_MAGNITUDE: f32 = 0.1;
+
+
+/// Scalar field sampled at the voxel centers, with one extra layer of samples on each side.
+/// The sample at `(x, y, z)` belongs to the voxel at `(x - 1, y - 1, z - 1)`.
+struct ScalarField {
+    dimensions: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl ScalarField {
+    /// Creates a field with all samples set to `0.5` (just outside the surface).
+    fn new_for_grid(grid: &VoxelGrid) -> Self {
+        let dimensions = grid
+            .dimensions()
+            .to_array()
+            .map(|length| length as usize + 2);
+
+        Self {
+            dimensions,
+            values: vec![0.5; dimensions.iter().product()],
+        }
+    }
+
+    #[inline]
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + y * self.dimensions[0] + z * self.dimensions[0] * self.dimensions[1]
+    }
+
+    #[inline]
+    fn stride(&self, axis: usize) -> usize {
+        match axis {
+            0 => 1,
+            1 => self.dimensions[0],
+            _ => self.dimensions[0] * self.dimensions[1],
+        }
+    }
+
+    /// Replaces the sample of each voxel with `update(flat grid index, current value)`.
+    fn update_voxel_samples<F>(&mut self, grid: &VoxelGrid, mut update: F)
+    where
+        F: FnMut(usize, f32) -> f32,
+    {
+        for z in 0..grid.z_length() {
+            for y in 0..grid.y_length() {
+                for x in 0..grid.x_length() {
+                    let sample_index = self.index(x as usize + 1, y as usize + 1, z as usize + 1);
+
+                    self.values[sample_index] = update(
+                        grid.flat_index(x, y, z),
+                        self.values[sample_index],
+                    );
+                }
+            }
+        }
+    }
+
+    /// Applies a `[1, 2, 1] / 4` filter along one axis (clamping at the borders).
+    fn blur_along_axis(&mut self, axis: usize) {
+        let stride = self.stride(axis);
+        let line_length = self.dimensions[axis];
+
+        let mut line = vec![0.0; line_length];
+
+        // Visit the first sample of each line along the axis.
+        let mut line_counts = self.dimensions;
+        line_counts[axis] = 1;
+
+        for z in 0..line_counts[2] {
+            for y in 0..line_counts[1] {
+                for x in 0..line_counts[0] {
+                    let line_start = self.index(x, y, z);
+
+                    for (i, value) in line.iter_mut().enumerate() {
+                        *value = self.values[line_start + i * stride];
+                    }
+
+                    for i in 0..line_length {
+                        let previous = line[i.saturating_sub(1)];
+                        let next = line[(i + 1).min(line_length - 1)];
+
+                        self.values[line_start + i * stride] =
+                            (previous + 2.0 * line[i] + next) / 4.0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+fn build_occupancy_field(grid: &VoxelGrid) -> ScalarField {
+    let mut field = ScalarField::new_for_grid(grid);
+
+    field.update_voxel_samples(grid, |flat_index, _| {
+        if grid.voxels()[flat_index].is_occupied() {
+            -0.5
+        } else {
+            0.5
+        }
+    });
+
+    field
+}
+
+fn build_signed_distance_field(grid: &VoxelGrid) -> ScalarField {
+    let voxels = grid.voxels();
+    let mut field = ScalarField::new_for_grid(grid);
+
+    // Distances are measured between voxel centers, while the surface lies halfway between
+    // the centers of neighbouring occupied and empty voxels, hence the half-voxel offsets.
+    let inside_squared_distances =
+        squared_distance_to_nearest_non_target_voxel(grid, |flat_index| {
+            voxels[flat_index].is_occupied()
+        });
+
+    field.update_voxel_samples(grid, |flat_index, value| {
+        if voxels[flat_index].is_occupied() {
+            0.5 - inside_squared_distances[flat_index].sqrt()
+        } else {
+            value
+        }
+    });
+
+    drop(inside_squared_distances);
+
+
+    // Note that voxels outside the grid count as occupied here, so empty voxels near
+    // the grid boundary get smaller (but still positive) distances.
+    let outside_squared_distances =
+        squared_distance_to_nearest_non_target_voxel(grid, |flat_index| {
+            !voxels[flat_index].is_occupied()
+        });
+
+    field.update_voxel_samples(grid, |flat_index, value| {
+        if voxels[flat_index].is_occupied() {
+            value
+        } else {
+            outside_squared_distances[flat_index].sqrt() - 0.5
+        }
+    });
+
+    drop(outside_squared_distances);
+
+
+    for axis in 0..3 {
+        field.blur_along_axis(axis);
+    }
+
+    // Smoothing must not move samples across the surface (or all the way onto it).
+    let occupancy = build_occupancy_field(grid);
+    for (value, occupancy_value) in field.values.iter_mut().zip(occupancy.values.iter()) {
+        *value = if *occupancy_value < 0.0 {
+            value.min(-MIN_SAMPLE_MAGNITUDE)
+        } else {
+            value.max(MIN_SAMPLE_MAGNITUDE)
+        };
+    }
+
+    field
+}
+
+
+
+/// Offset of a cube corner, with bit 0, 1 and 2 of the corner index selecting
+/// the far side of the cube along x, y and z.
+#[inline]
+fn corner_offset(corner: usize) -> [usize; 3] {
+    [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1]
+}
+
+/// The twelve cube edges as (lower corner, axis) pairs, four per axis.
+fn cube_edges() -> [(usize, usize); 12] {
+    let mut edges = [(0, 0); 12];
+    let mut edge_index = 0;
+
+    for axis in 0..3 {
+        for corner in 0..8 {
+            if corner & (1 << axis) == 0 {
+                edges[edge_index] = (corner, axis);
+                edge_index += 1;
+            }
+        }
+    }
+
+    edges
+}
+
+fn edge_between_corners(edges: &[(usize, usize); 12], corner_a: usize, corner_b: usize) -> usize {
+    let lower_corner = corner_a.min(corner_b);
+    let axis = (corner_a ^ corner_b).trailing_zeros() as usize;
+
+    edges
+        .iter()
+        .position(|edge| *edge == (lower_corner, axis))
+        .expect("expected corners to be adjacent")
+}
+
+
+/// A closed loop of intersected cube edges, ordered counter-clockwise when viewed
+/// from outside the surface.
+struct CaseLoop {
+    edges: Vec<usize>,
+
+    /// Index (into `edges`) of the vertex to triangulate the loop from as a fan, or `None`
+    /// if the loop must be triangulated around an extra vertex at its centroid.
+    fan_start: Option<usize>,
+}
+
+/// Generates the loops of all 256 cases (bit `i` of the case index is set if corner `i` is inside).
+fn build_case_table() -> Vec<Vec<CaseLoop>> {
+    let edges = cube_edges();
+
+    let corner_position = |corner: usize| Vec3::from_array(corner_offset(corner).map(|o| o as f32));
+    let edge_midpoint = |edge: usize| {
+        let (lower_corner, axis) = edges[edge];
+        corner_position(lower_corner) + corner_position(1 << axis) * 0.5
+    };
+
+    // Corners of each face in cyclic order, and its outward normal.
+    let faces = (0..3)
+        .flat_map(|axis| {
+            (0..2).map(move |side| {
+                let axis_u = (axis + 1) % 3;
+                let axis_v = (axis + 2) % 3;
+                let base = side << axis;
+
+                let mut normal = Vec3::ZERO;
+                normal[axis] = if side == 1 { 1.0 } else { -1.0 };
+
+                (
+                    [
+                        base,
+                        base | (1 << axis_u),
+                        base | (1 << axis_u) | (1 << axis_v),
+                        base | (1 << axis_v),
+                    ],
+                    normal,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let face_edges = faces
+        .iter()
+        .map(|(corners, _)| {
+            (0..4)
+                .map(|k| edge_between_corners(&edges, corners[k], corners[(k + 1) % 4]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let edges_share_face = |edge_a: usize, edge_b: usize| {
+        face_edges
+            .iter()
+            .any(|face| face.contains(&edge_a) && face.contains(&edge_b))
+    };
+
+
+    (0..256usize)
+        .map(|case_index| {
+            let is_inside = |corner: usize| case_index & (1 << corner) != 0;
+
+            // Segment from one intersected edge to the next, for each intersected edge.
+            let mut next_edge = [None; 12];
+
+            for ((corners, normal), face_edges) in faces.iter().zip(face_edges.iter()) {
+                let crossed_edges = (0..4)
+                    .filter(|&k| is_inside(corners[k]) != is_inside(corners[(k + 1) % 4]))
+                    .collect::<Vec<_>>();
+
+                // Each segment, along with an inside corner on the face that it cuts off.
+                let segments = match crossed_edges.len() {
+                    0 => Vec::new(),
+                    2 => {
+                        let inside_corner = *corners.iter().find(|c| is_inside(**c)).unwrap();
+                        vec![(
+                            face_edges[crossed_edges[0]],
+                            face_edges[crossed_edges[1]],
+                            inside_corner,
+                        )]
+                    }
+                    // Ambiguous face: cut off each of the two inside corners separately.
+                    _ => (0..4)
+                        .filter(|&k| is_inside(corners[k]))
+                        .map(|k| (face_edges[(k + 3) % 4], face_edges[k], corners[k]))
+                        .collect(),
+                };
+
+                for (edge_a, edge_b, inside_corner) in segments {
+                    let point_a = edge_midpoint(edge_a);
+                    let point_b = edge_midpoint(edge_b);
+
+                    // Viewed from outside the cube, the inside corner must lie to the right
+                    // of each segment for the loops to wind counter-clockwise around the outside.
+                    let inside_is_left = (point_b - point_a)
+                        .cross(corner_position(inside_corner) - point_a)
+                        .dot(*normal)
+                        > 0.0;
+
+                    let (from, to) = if inside_is_left {
+                        (edge_b, edge_a)
+                    } else {
+                        (edge_a, edge_b)
+                    };
+
+                    assert!(
+                        next_edge[from].replace(to).is_none(),
+                        "expected each intersected edge to start exactly one segment"
+                    );
+                }
+            }
+
+
+            let mut loops = Vec::new();
+            let mut is_visited = [false; 12];
+
+            for start_edge in 0..12 {
+                if next_edge[start_edge].is_none() || is_visited[start_edge] {
+                    continue;
+                }
+
+                let mut loop_edges = Vec::new();
+                let mut edge = start_edge;
+
+                while !is_visited[edge] {
+                    is_visited[edge] = true;
+                    loop_edges.push(edge);
+                    edge = next_edge[edge].expect("expected segments to form closed loops");
+                }
+
+                // Diagonals of a fan must not lie on a cube face, where the neighbouring cube
+                // could use the same diagonal (making the edge non-manifold).
+                let loop_length = loop_edges.len();
+                let fan_start = (0..loop_length).find(|&start| {
+                    (2..loop_length - 1).all(|offset| {
+                        !edges_share_face(
+                            loop_edges[start],
+                            loop_edges[(start + offset) % loop_length],
+                        )
+                    })
+                });
+
+                loops.push(CaseLoop {
+                    edges: loop_edges,
+                    fan_start,
+                });
+            }
+
+            loops
+        })
+        .collect()
+}
+
+static CASE_TABLE: OnceLock<Vec<Vec<CaseLoop>>> = OnceLock::new();
+
+
+
+/// Weight of an `Edge` voxel's color at a vertex `distance` voxels away from its center.
+#[inline]
+pub(super) fn color_weight(distance_squared: f32) -> f32 {
+    (-2.0 * distance_squared).exp()
+}
+
+/// Interpolates the colors of the `Edge` voxels around a vertex on the field edge
+/// starting at `sample` and running along `axis`. `position` is the vertex position
+/// in sample coordinates. Falls back to white if there are no `Edge` voxels nearby.
+fn interpolate_vertex_color(
+    grid: &VoxelGrid,
+    sample: [usize; 3],
+    axis: usize,
+    position: Vec3,
+) -> Vec3 {
+    let axis_u = (axis + 1) % 3;
+    let axis_v = (axis + 2) % 3;
+
+    let mut color_sum = Vec3::ZERO;
+    let mut weight_sum = 0.0;
+
+    for offset_axis in 0..=1i64 {
+        for offset_u in -1..=1i64 {
+            for offset_v in -1..=1i64 {
+                let mut neighbour_sample = sample.map(|component| component as i64);
+                neighbour_sample[axis] += offset_axis;
+                neighbour_sample[axis_u] += offset_u;
+                neighbour_sample[axis_v] += offset_v;
+
+                // Samples are offset by one from grid indices.
+                let Some(VoxelData::Edge { color, .. }) =
+                    grid.voxel_by_signed_xyz_index(I64Vec3::from_array(neighbour_sample) - 1)
+                else {
+                    continue;
+                };
+
+                let weight = color_weight(
+                    I64Vec3::from_array(neighbour_sample)
+                        .as_vec3()
+                        .distance_squared(position),
+                );
+
+                color_sum += *color * weight;
+                weight_sum += weight;
+            }
+        }
+    }
+
+    if weight_sum > 0.0 {
+        color_sum / weight_sum
+    } else {
+        Vec3::ONE
+    }
+}
+
+
+/// Extracts the zero level set of the selected scalar field as a closed triangle mesh
+/// with shared vertices, smooth normals and vertex colors interpolated from `Edge` voxels.
+pub fn marching_cubes(grid: &VoxelGrid, field_type: MarchingCubesField) -> SurfaceMesh {
+    let field = match field_type {
+        MarchingCubesField::Occupancy => build_occupancy_field(grid),
+        MarchingCubesField::SignedDistance => build_signed_distance_field(grid),
+    };
+
+    let case_table = CASE_TABLE.get_or_init(build_case_table);
+    let edges = cube_edges();
+
+    let mut mesh = SurfaceMesh::empty_for_grid(grid);
+
+    // Vertices are shared between the (up to four) cubes around each field edge,
+    // keyed by the index of the edge's lower sample and the edge axis.
+    let mut edge_vertices: HashMap<usize, u32> = HashMap::new();
+
+    let sample_to_world =
+        |sample_position: Vec3| grid.starting_point + (sample_position - 0.5) * grid.voxel_size();
+
+    let [x_samples, y_samples, z_samples] = field.dimensions;
+
+    for z in 0..z_samples - 1 {
+        for y in 0..y_samples - 1 {
+            for x in 0..x_samples - 1 {
+                let mut case_index = 0;
+                for corner in 0..8 {
+                    let [dx, dy, dz] = corner_offset(corner);
+
+                    if field.values[field.index(x + dx, y + dy, z + dz)] < 0.0 {
+                        case_index |= 1 << corner;
+                    }
+                }
+
+                if case_index == 0 || case_index == 255 {
+                    continue;
+                }
+
+
+                let mut cube_edge_vertices = [0u32; 12];
+
+                for case_loop in &case_table[case_index] {
+                    for &edge in &case_loop.edges {
+                        let (lower_corner, axis) = edges[edge];
+                        let [dx, dy, dz] = corner_offset(lower_corner);
+                        let sample = [x + dx, y + dy, z + dz];
+
+                        let lower_sample_index = field.index(sample[0], sample[1], sample[2]);
+
+                        cube_edge_vertices[edge] = *edge_vertices
+                            .entry(lower_sample_index * 3 + axis)
+                            .or_insert_with(|| {
+                                let lower_value = field.values[lower_sample_index];
+                                let upper_value =
+                                    field.values[lower_sample_index + field.stride(axis)];
+
+                                let t = lower_value / (lower_value - upper_value);
+
+                                let mut position =
+                                    Vec3::from_array(sample.map(|component| component as f32));
+                                position[axis] += t;
+
+                                mesh.push_vertex(
+                                    sample_to_world(position),
+                                    Vec3::ZERO,
+                                    interpolate_vertex_color(grid, sample, axis, position),
+                                )
+                            });
+                    }
+                }
+
+
+                for case_loop in &case_table[case_index] {
+                    let loop_vertices = case_loop
+                        .edges
+                        .iter()
+                        .map(|edge| cube_edge_vertices[*edge])
+                        .collect::<Vec<_>>();
+                    let loop_length = loop_vertices.len();
+
+                    match case_loop.fan_start {
+                        Some(fan_start) => {
+                            for offset in 1..loop_length - 1 {
+                                mesh.triangle_indices.extend_from_slice(&[
+                                    loop_vertices[fan_start],
+                                    loop_vertices[(fan_start + offset) % loop_length],
+                                    loop_vertices[(fan_start + offset + 1) % loop_length],
+                                ]);
+                            }
+                        }
+                        None => {
+                            let (position_sum, color_sum) = loop_vertices.iter().fold(
+                                (Vec3::ZERO, Vec3::ZERO),
+                                |(position_sum, color_sum), vertex| {
+                                    (
+                                        position_sum + mesh.positions[*vertex as usize],
+                                        color_sum + mesh.colors[*vertex as usize],
+                                    )
+                                },
+                            );
+
+                            let centroid = mesh.push_vertex(
+                                position_sum / loop_length as f32,
+                                Vec3::ZERO,
+                                color_sum / loop_length as f32,
+                            );
+
+                            for k in 0..loop_length {
+                                mesh.triangle_indices.extend_from_slice(&[
+                                    loop_vertices[k],
+                                    loop_vertices[(k + 1) % loop_length],
+                                    centroid,
+                                ]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    mesh.recompute_vertex_normals();
+    mesh
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use glam::Vec3;
+
+    use super::*;
+    use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+    /// Whether the two cube edges lie on a common cube face.
+    fn edges_share_face(edges: &[(usize, usize); 12], edge_a: usize, edge_b: usize) -> bool {
+        let corners = [edges[edge_a], edges[edge_b]]
+            .into_iter()
+            .flat_map(|(corner, axis)| [corner, corner | (1 << axis)])
+            .collect::<Vec<_>>();
+
+        (0..3).any(|axis| {
+            corners
+                .iter()
+                .all(|corner| corner & (1 << axis) == corners[0] & (1 << axis))
+        })
+    }
+
+    #[test]
+    fn case_table_loops_are_closed() {
+        let edges = cube_edges();
+        let case_table = build_case_table();
+
+        assert_eq!(case_table.len(), 256);
+
+        for (case_index, case_loops) in case_table.iter().enumerate() {
+            let is_inside = |corner: usize| case_index & (1 << corner) != 0;
+
+            let mut edge_uses = [0; 12];
+
+            for case_loop in case_loops {
+                let loop_length = case_loop.edges.len();
+                assert!(
+                    loop_length >= 3,
+                    "case {case_index} has a loop with {loop_length} edges"
+                );
+
+                for k in 0..loop_length {
+                    let edge = case_loop.edges[k];
+                    let next_edge = case_loop.edges[(k + 1) % loop_length];
+
+                    edge_uses[edge] += 1;
+                    assert!(
+                        edges_share_face(&edges, edge, next_edge),
+                        "case {case_index}: edges {edge} and {next_edge} are consecutive in a loop, \
+                        but do not share a face"
+                    );
+                }
+            }
+
+            for (edge, (corner, axis)) in edges.iter().enumerate() {
+                let is_crossed = is_inside(*corner) != is_inside(corner | (1 << axis));
+
+                assert_eq!(
+                    edge_uses[edge], is_crossed as usize,
+                    "case {case_index}: edge {edge} is used {} times",
+                    edge_uses[edge]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_surface_is_closed_and_manifold() {
+        let length = 16;
+        let center = Vec3::splat(length as f32 / 2.0);
+
+        let voxels = (0..length * length * length)
+            .map(|flat_index| {
+                let grid_index = Vec3::new(
+                    (flat_index % length) as f32,
+                    (flat_index / length % length) as f32,
+                    (flat_index / (length * length)) as f32,
+                );
+
+                if (grid_index + 0.5).distance(center) < 5.5 {
+                    VoxelData::new_edge_with_default_material()
+                } else {
+                    VoxelData::Empty
+                }
+            })
+            .collect();
+
+        let grid = VoxelGrid::from_voxels(
+            Vec3::ZERO,
+            0.5,
+            length as u64,
+            length as u64,
+            length as u64,
+            voxels,
+        );
+
+        for field_type in [
+            MarchingCubesField::Occupancy,
+            MarchingCubesField::SignedDistance,
+        ] {
+            let mesh = marching_cubes(&grid, field_type);
+            assert!(mesh.triangle_count() > 0);
+
+            // Each directed edge must appear exactly once, and its reverse must appear too:
+            // then every edge is shared by exactly two consistently oriented triangles.
+            let mut directed_edges: HashMap<(u32, u32), usize> = HashMap::new();
+
+            for triangle in mesh.triangle_indices.chunks_exact(3) {
+                for k in 0..3 {
+                    let edge = (triangle[k], triangle[(k + 1) % 3]);
+                    assert_ne!(
+                        edge.0, edge.1,
+                        "{field_type}: degenerate triangle"
+                    );
+
+                    *directed_edges.entry(edge).or_default() += 1;
+                }
+            }
+
+            for ((from, to), count) in &directed_edges {
+                assert_eq!(
+                    *count, 1,
+                    "{field_type}: edge {from}-{to} is used {count} times"
+                );
+                assert!(
+                    directed_edges.contains_key(&(*to, *from)),
+                    "{field_type}: edge {from}-{to} is a boundary edge"
+                );
+            }
+        }
+    }
+}
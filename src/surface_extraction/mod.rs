@@ -0,0 +1,193 @@
+//! Extraction of polygon surfaces from already-voxelized [`VoxelGrid`]s.
+
+use std::{fmt::Display, str::FromStr};
+
+use glam::Vec3;
+use miette::{miette, Report, Result};
+
+use self::marching_cubes::MarchingCubesField;
+use crate::voxelizer::{grid::VoxelGrid, voxel::VoxelData};
+
+pub mod decimation;
+pub mod dual_contouring;
+pub mod greedy;
+pub mod marching_cubes;
+pub mod smoothing;
+
+
+/// How the surface of a grid is turned into a mesh.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SurfaceExtractionMethod {
+    /// Voxel faces, with coplanar faces of equal color merged (see [`greedy`]).
+    Greedy,
+    /// A smooth, watertight surface through a scalar field sampled at the voxel centers
+    /// (see [`marching_cubes`]).
+    MarchingCubes,
+    /// A surface with one vertex per cell, placed using the Hermite data collected during
+    /// voxelization so that sharp edges and corners are kept (see [`dual_contouring`]).
+    DualContouring,
+}
+
+impl FromStr for SurfaceExtractionMethod {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "greedy" => Ok(Self::Greedy),
+            "marching-cubes" => Ok(Self::MarchingCubes),
+            "dual-contouring" => Ok(Self::DualContouring),
+            _ => Err(miette!(
+                "Invalid surface extraction method \"{}\", must be one of: \
+                greedy, marching-cubes, dual-contouring.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SurfaceExtractionMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SurfaceExtractionMethod::Greedy => write!(f, "greedy"),
+            SurfaceExtractionMethod::MarchingCubes => write!(f, "marching-cubes"),
+            SurfaceExtractionMethod::DualContouring => write!(f, "dual-contouring"),
+        }
+    }
+}
+
+
+impl SurfaceExtractionMethod {
+    /// Whether the method produces a welded mesh (with vertices shared between triangles),
+    /// which is required for smoothing and decimation.
+    pub fn produces_welded_mesh(&self) -> bool {
+        match self {
+            SurfaceExtractionMethod::Greedy => false,
+            SurfaceExtractionMethod::MarchingCubes => true,
+            SurfaceExtractionMethod::DualContouring => true,
+        }
+    }
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SurfaceExtractionOptions {
+    pub method: SurfaceExtractionMethod,
+
+    /// Scalar field used by marching cubes.
+    pub marching_cubes_field: MarchingCubesField,
+
+    /// Number of Taubin smoothing iterations applied to the extracted mesh.
+    pub smoothing_iterations: u32,
+
+    /// If set, the extracted mesh is decimated to roughly this fraction of its triangles.
+    pub decimation_ratio: Option<f32>,
+}
+
+
+/// An indexed triangle mesh in world space, with per-vertex normals and colors.
+#[derive(Clone, Debug)]
+pub struct SurfaceMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    /// Linear RGB color of each vertex.
+    pub colors: Vec<Vec3>,
+    /// Three vertex indices per triangle, counter-clockwise when viewed from the outside.
+    pub triangle_indices: Vec<u32>,
+
+    /// World-space minimum corner of the grid the mesh was extracted from.
+    pub grid_origin: Vec3,
+    /// Voxel size (full box width) of the grid the mesh was extracted from.
+    pub voxel_size: f32,
+}
+
+impl SurfaceMesh {
+    /// Creates an empty mesh for the given grid.
+    pub fn empty_for_grid(grid: &VoxelGrid) -> Self {
+        Self {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            triangle_indices: Vec::new(),
+            grid_origin: grid.starting_point,
+            voxel_size: grid.voxel_size(),
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_indices.len() / 3
+    }
+
+    /// Appends a vertex and returns its index.
+    pub fn push_vertex(&mut self, position: Vec3, normal: Vec3, color: Vec3) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.colors.push(color);
+
+        (self.positions.len() - 1) as u32
+    }
+
+    /// Replaces the vertex normals with the area-weighted average of the normals
+    /// of the triangles around each vertex.
+    pub fn recompute_vertex_normals(&mut self) {
+        let mut normals = vec![Vec3::ZERO; self.vertex_count()];
+
+        for triangle in self.triangle_indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+
+            // The length of the cross product is twice the triangle area.
+            let weighted_normal =
+                (self.positions[b] - self.positions[a]).cross(self.positions[c] - self.positions[a]);
+
+            normals[a] += weighted_normal;
+            normals[b] += weighted_normal;
+            normals[c] += weighted_normal;
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|normal| normal.try_normalize().unwrap_or(Vec3::Y))
+            .collect();
+    }
+}
+
+
+/// Color of a voxel's surface: the color of `Edge` voxels, or white (the default glTF base color)
+/// for `InsideMesh` voxels exposed by post-processing. Returns `None` for empty voxels.
+pub fn voxel_surface_color(voxel: &VoxelData) -> Option<Vec3> {
+    match voxel {
+        VoxelData::Empty => None,
+        VoxelData::Edge { color, .. } => Some(*color),
+        VoxelData::InsideMesh => Some(Vec3::ONE),
+    }
+}
+
+
+/// Runs the selected surface extraction method on a grid, followed by smoothing
+/// and decimation (if enabled).
+pub fn extract_surface(grid: &VoxelGrid, options: &SurfaceExtractionOptions) -> SurfaceMesh {
+    let mut mesh = match options.method {
+        SurfaceExtractionMethod::Greedy => greedy::greedy_mesh(grid),
+        SurfaceExtractionMethod::MarchingCubes => {
+            marching_cubes::marching_cubes(grid, options.marching_cubes_field)
+        }
+        SurfaceExtractionMethod::DualContouring => dual_contouring::dual_contouring(grid),
+    };
+
+    if options.smoothing_iterations > 0 {
+        smoothing::taubin_smooth(&mut mesh, options.smoothing_iterations);
+    }
+
+    if let Some(decimation_ratio) = options.decimation_ratio {
+        decimation::decimate(&mut mesh, decimation_ratio);
+    }
+
+    if options.smoothing_iterations > 0 || options.decimation_ratio.is_some() {
+        mesh.recompute_vertex_normals();
+    }
+
+    mesh
+}
@@ -0,0 +1,89 @@
+//! Taubin smoothing ("A Signal Processing Approach to Fair Surface Design", Taubin 1995):
+//! each iteration is a Laplacian smoothing step that shrinks the mesh, followed by
+//! a slightly larger negative step that inflates it back, so the mesh is smoothed
+//! without losing volume. Only vertex positions change; the topology stays the same.
+
+use glam::Vec3;
+
+use super::SurfaceMesh;
+
+
+const TAUBIN_LAMBDA: f32 = 0.5;
+const TAUBIN_MU: f32 = -0.53;
+
+
+/// Returns the indices of the neighbouring vertices of each vertex.
+fn vertex_neighbours(mesh: &SurfaceMesh) -> Vec<Vec<u32>> {
+    let mut neighbours = vec![Vec::new(); mesh.vertex_count()];
+
+    for triangle in mesh.triangle_indices.chunks_exact(3) {
+        for k in 0..3 {
+            let vertex = triangle[k];
+            let next_vertex = triangle[(k + 1) % 3];
+
+            neighbours[vertex as usize].push(next_vertex);
+            neighbours[next_vertex as usize].push(vertex);
+        }
+    }
+
+    for vertex_neighbours in &mut neighbours {
+        vertex_neighbours.sort_unstable();
+        vertex_neighbours.dedup();
+    }
+
+    neighbours
+}
+
+
+/// Moves each vertex by `factor` times the offset from it to the average of its neighbours.
+fn laplacian_step(
+    positions: &mut [Vec3],
+    neighbours: &[Vec<u32>],
+    factor: f32,
+    scratch: &mut Vec<Vec3>,
+) {
+    scratch.clear();
+    scratch.extend(
+        positions
+            .iter()
+            .zip(neighbours.iter())
+            .map(|(position, vertex_neighbours)| {
+                if vertex_neighbours.is_empty() {
+                    return *position;
+                }
+
+                let neighbour_average = vertex_neighbours
+                    .iter()
+                    .map(|neighbour| positions[*neighbour as usize])
+                    .sum::<Vec3>()
+                    / vertex_neighbours.len() as f32;
+
+                *position + (neighbour_average - *position) * factor
+            }),
+    );
+
+    positions.copy_from_slice(scratch);
+}
+
+
+/// Smooths a (welded) mesh in place with the given number of Taubin iterations.
+/// Normals are not updated.
+pub fn taubin_smooth(mesh: &mut SurfaceMesh, iterations: u32) {
+    let neighbours = vertex_neighbours(mesh);
+    let mut scratch = Vec::with_capacity(mesh.vertex_count());
+
+    for _ in 0..iterations {
+        laplacian_step(
+            &mut mesh.positions,
+            &neighbours,
+            TAUBIN_LAMBDA,
+            &mut scratch,
+        );
+        laplacian_step(
+            &mut mesh.positions,
+            &neighbours,
+            TAUBIN_MU,
+            &mut scratch,
+        );
+    }
+}
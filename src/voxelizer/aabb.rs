@@ -1,5 +1,6 @@
-use glam::Vec3;
+use glam::{Affine3A, Vec3};
 
+#[derive(Clone, Copy, Debug)]
 pub struct Aabb {
     pub min: Vec3,
     pub max: Vec3,
@@ -38,4 +39,42 @@ impl Aabb {
 
         Self::from_min_and_max(intersection_min, intersection_max)
     }
+
+    #[inline]
+    pub fn compute_union(&self, other: &Self) -> Self {
+        Self::from_min_and_max(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Axis-aligned bounding box of the transformed box.
+    pub fn transformed(&self, transform: &Affine3A) -> Self {
+        let mut transformed_min = Vec3::MAX;
+        let mut transformed_max = Vec3::MIN;
+
+        for corner_index in 0..8 {
+            let corner = Vec3::new(
+                if corner_index & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if corner_index & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if corner_index & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            );
+
+            let transformed_corner = transform.transform_point3(corner);
+
+            transformed_min = transformed_min.min(transformed_corner);
+            transformed_max = transformed_max.max(transformed_corner);
+        }
+
+        Self::from_min_and_max(transformed_min, transformed_max)
+    }
 }
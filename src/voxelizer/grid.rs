@@ -1,10 +1,16 @@
-use glam::{U64Vec3, Vec3};
-
-use super::voxel::{
-    ContextualNonFinalVoxelDataMut,
-    ContextualVoxelData,
-    NonFinalVoxelData,
-    VoxelData,
+use std::collections::HashMap;
+
+use glam::{I64Vec3, U64Vec3, Vec3};
+
+use super::{
+    aabb::Aabb,
+    voxel::{
+        ContextualNonFinalVoxelDataMut,
+        ContextualVoxelData,
+        HermiteSample,
+        NonFinalVoxelData,
+        VoxelData,
+    },
 };
 
 
@@ -73,10 +79,25 @@ impl NonFinalVoxelGrid {
     }
 
     pub fn into_final_grid(self) -> VoxelGrid {
+        let mut hermite_samples = HashMap::new();
+
         let final_grid = self
             .grid
             .into_iter()
-            .map(|non_final_voxel| non_final_voxel.into_final_voxel_data())
+            .enumerate()
+            .map(|(flat_index, mut non_final_voxel)| {
+                if let NonFinalVoxelData::Edge {
+                    hermite_samples: voxel_hermite_samples,
+                    ..
+                } = &mut non_final_voxel
+                {
+                    if !voxel_hermite_samples.is_empty() {
+                        hermite_samples.insert(flat_index, std::mem::take(voxel_hermite_samples));
+                    }
+                }
+
+                non_final_voxel.into_final_voxel_data()
+            })
             .collect::<Vec<_>>();
 
         VoxelGrid {
@@ -86,6 +107,7 @@ impl NonFinalVoxelGrid {
             y_length: self.y_length,
             z_length: self.z_length,
             grid: final_grid,
+            hermite_samples: (!hermite_samples.is_empty()).then_some(hermite_samples),
         }
     }
 
@@ -112,15 +134,192 @@ pub struct VoxelGrid {
 
     /// Indexed by x, y, and z, flattened out as a single Vec.
     grid: Vec<VoxelData>,
+
+    /// Hermite samples of edge voxels, keyed by flat index. Only present for grids voxelized
+    /// from meshes with `MeshVoxelizationOptions::collect_hermite_data` set.
+    hermite_samples: Option<HashMap<usize, Vec<HermiteSample>>>,
 }
 
 
 impl VoxelGrid {
+    /// Constructs a final voxel grid from an already-flattened list of voxels
+    /// (indexed by x, y, and z, see [`Self::flat_index`]).
+    ///
+    /// `starting_point` is the bounding box edge of the lowest-x lowest-y lowest-z voxel
+    /// (see [`NonFinalVoxelGrid::new`]).
+    pub fn from_voxels(
+        starting_point: Vec3,
+        voxel_half_extent: f32,
+        x_length: u64,
+        y_length: u64,
+        z_length: u64,
+        voxels: Vec<VoxelData>,
+    ) -> Self {
+        assert_eq!(
+            voxels.len() as u64,
+            x_length * y_length * z_length,
+            "voxel count does not match grid dimensions"
+        );
+
+        Self {
+            starting_point,
+            voxel_half_extent,
+            x_length,
+            y_length,
+            z_length,
+            grid: voxels,
+            hermite_samples: None,
+        }
+    }
+
+    /// Constructs a new grid with the same position, voxel size and dimensions as this one,
+    /// but with the provided voxels. Hermite samples are not carried over, as they
+    /// would no longer match the surface.
+    pub fn with_replaced_voxels(&self, voxels: Vec<VoxelData>) -> Self {
+        Self::from_voxels(
+            self.starting_point,
+            self.voxel_half_extent,
+            self.x_length,
+            self.y_length,
+            self.z_length,
+            voxels,
+        )
+    }
+
     pub fn voxels(&self) -> &[VoxelData] {
         &self.grid
     }
 
-    pub fn contextual_voxels(&self) -> VoxelGridContextualIterator {
+    /// Attaches Hermite samples (keyed by flat index) to the grid,
+    /// e.g. after assembling it from other grids that had them.
+    pub fn with_hermite_samples(
+        mut self,
+        hermite_samples: HashMap<usize, Vec<HermiteSample>>,
+    ) -> Self {
+        self.hermite_samples = (!hermite_samples.is_empty()).then_some(hermite_samples);
+        self
+    }
+
+    /// Returns `true` if Hermite samples were collected for this grid
+    /// (see [`Self::hermite_samples_by_flat_index`]).
+    #[inline]
+    pub fn has_hermite_data(&self) -> bool {
+        self.hermite_samples.is_some()
+    }
+
+    /// Returns the Hermite samples (surface points and normals) of the voxel at the given
+    /// flat index, or an empty slice if none were collected for it.
+    pub fn hermite_samples_by_flat_index(&self, flat_index: usize) -> &[HermiteSample] {
+        self.hermite_samples
+            .as_ref()
+            .and_then(|hermite_samples| hermite_samples.get(&flat_index))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    #[inline]
+    pub fn x_length(&self) -> u64 {
+        self.x_length
+    }
+
+    #[inline]
+    pub fn y_length(&self) -> u64 {
+        self.y_length
+    }
+
+    #[inline]
+    pub fn z_length(&self) -> u64 {
+        self.z_length
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> U64Vec3 {
+        U64Vec3::new(self.x_length, self.y_length, self.z_length)
+    }
+
+    /// Full voxel size (box width).
+    #[inline]
+    pub fn voxel_size(&self) -> f32 {
+        self.voxel_half_extent * 2.0
+    }
+
+    /// Returns the index into [`Self::voxels`] for the given grid position.
+    #[inline]
+    pub fn flat_index(&self, x: u64, y: u64, z: u64) -> usize {
+        (x + (y * self.x_length) + (z * self.y_length * self.x_length)) as usize
+    }
+
+    /// Inverse of [`Self::flat_index`].
+    #[inline]
+    pub fn grid_index_from_flat_index(&self, flat_index: usize) -> U64Vec3 {
+        let flat_index = flat_index as u64;
+
+        U64Vec3::new(
+            flat_index % self.x_length,
+            (flat_index / self.x_length) % self.y_length,
+            flat_index / (self.x_length * self.y_length),
+        )
+    }
+
+    pub fn voxel_by_xyz_index_unchecked(&self, x: u64, y: u64, z: u64) -> &VoxelData {
+        self.grid
+            .get(self.flat_index(x, y, z))
+            .unwrap_or_else(|| panic!("index ({}, {}, {}) is out of range", x, y, z))
+    }
+
+    pub fn voxel_mut_by_xyz_index_unchecked(&mut self, x: u64, y: u64, z: u64) -> &mut VoxelData {
+        let target_voxel_index = self.flat_index(x, y, z);
+
+        self.grid
+            .get_mut(target_voxel_index)
+            .unwrap_or_else(|| panic!("index ({}, {}, {}) is out of range", x, y, z))
+    }
+
+    /// Returns the voxel at the given (possibly negative or out-of-range) grid position,
+    /// or `None` if the position lies outside the grid.
+    pub fn voxel_by_signed_xyz_index(&self, index: I64Vec3) -> Option<&VoxelData> {
+        if index.x < 0
+            || index.y < 0
+            || index.z < 0
+            || index.x as u64 >= self.x_length
+            || index.y as u64 >= self.y_length
+            || index.z as u64 >= self.z_length
+        {
+            return None;
+        }
+
+        Some(self.voxel_by_xyz_index_unchecked(index.x as u64, index.y as u64, index.z as u64))
+    }
+
+    /// World-space bounding box of the entire grid.
+    pub fn world_bounds(&self) -> Aabb {
+        Aabb::from_min_and_max(
+            self.starting_point,
+            self.starting_point + self.dimensions().as_vec3() * self.voxel_size(),
+        )
+    }
+
+    /// Returns the grid index of the voxel that contains the given world-space position,
+    /// or `None` if the position lies outside the grid.
+    pub fn grid_index_at_world_position(&self, world_position: Vec3) -> Option<U64Vec3> {
+        let grid_index = ((world_position - self.starting_point) / self.voxel_size())
+            .floor()
+            .as_i64vec3();
+
+        self.voxel_by_signed_xyz_index(grid_index)?;
+
+        Some(grid_index.as_u64vec3())
+    }
+
+    /// Returns the voxel that contains the given world-space position,
+    /// or `None` if the position lies outside the grid.
+    pub fn voxel_at_world_position(&self, world_position: Vec3) -> Option<&VoxelData> {
+        let grid_index = self.grid_index_at_world_position(world_position)?;
+
+        Some(self.voxel_by_xyz_index_unchecked(grid_index.x, grid_index.y, grid_index.z))
+    }
+
+    pub fn contextual_voxels(&self) -> VoxelGridContextualIterator<'_> {
         VoxelGridContextualIterator::from_voxel_grid(self)
     }
 }
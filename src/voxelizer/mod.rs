@@ -1,11 +1,9 @@
-use std::ops::{Add, Div, Sub};
-
-use easy_gltf::{
-    model::{Triangle, Vertex},
-    Material,
-    Model,
+use std::{
+    collections::HashMap,
+    ops::{Add, Div, Sub},
 };
-use glam::Vec3;
+
+use glam::{Affine3A, I64Vec3, Mat3, U64Vec3, Vec3, Vec4};
 use parry3d::{
     bounding_volume::Aabb as Parry3dAabb,
     na::Point3 as Parry3dPoint3,
@@ -15,8 +13,19 @@ use parry3d::{
 
 use self::{
     aabb::Aabb,
-    grid::{ContextualVoxelGrid, NonFinalVoxelGrid},
-    voxel::NonFinalVoxelData,
+    grid::{ContextualVoxelGrid, NonFinalVoxelGrid, VoxelGrid},
+    voxel::{HermiteSample, NonFinalVoxelData, VoxelData},
+};
+use crate::{
+    mesh::{
+        InstancedMeshModel,
+        MeshMaterial,
+        MeshModel,
+        MeshTriangle,
+        SurfacePoint,
+        TEX_COORD_SET_COUNT,
+    },
+    point_cloud::PointCloud,
 };
 
 pub mod aabb;
@@ -113,7 +122,7 @@ fn initialize_voxel_work_grid(
 }
 
 
-fn compute_aabb_for_mesh_triangle(triangle: &[Vertex; 3]) -> Aabb {
+fn compute_aabb_for_mesh_triangle(triangle: &MeshTriangle) -> Aabb {
     let minimum_triangle_x = triangle[0]
         .position
         .x
@@ -167,7 +176,7 @@ fn compute_aabb_for_mesh_triangle(triangle: &[Vertex; 3]) -> Aabb {
 
 
 
-fn check_for_triangle_aabb_collision(voxel_aabb: &Aabb, triangle: &[Vertex; 3]) -> bool {
+fn check_for_triangle_aabb_collision(voxel_aabb: &Aabb, triangle: &MeshTriangle) -> bool {
     intersection_test_aabb_triangle(
         &Parry3dAabb::new(
             Parry3dPoint3::new(
@@ -203,20 +212,16 @@ fn check_for_triangle_aabb_collision(voxel_aabb: &Aabb, triangle: &[Vertex; 3])
 
 
 
-fn compute_minimum_aabb_for_mesh(mesh_triangles: &[Triangle], padding: f32) -> Aabb {
+fn compute_minimum_aabb_for_points<I>(points: I, padding: f32) -> Aabb
+where
+    I: IntoIterator<Item = Vec3>,
+{
     let mut current_minimum = Vec3::MAX;
     let mut current_maximum = Vec3::MIN;
 
-    for triangle in mesh_triangles {
-        for vertex in triangle {
-            current_minimum.x = current_minimum.x.min(vertex.position.x);
-            current_minimum.y = current_minimum.y.min(vertex.position.y);
-            current_minimum.z = current_minimum.z.min(vertex.position.z);
-
-            current_maximum.x = current_maximum.x.max(vertex.position.x);
-            current_maximum.y = current_maximum.y.max(vertex.position.y);
-            current_maximum.z = current_maximum.z.max(vertex.position.z);
-        }
+    for point in points {
+        current_minimum = current_minimum.min(point);
+        current_maximum = current_maximum.max(point);
     }
 
     Aabb::from_min_and_max(
@@ -226,171 +231,166 @@ fn compute_minimum_aabb_for_mesh(mesh_triangles: &[Triangle], padding: f32) -> A
 }
 
 
-fn get_closest_vertex(triangle: &[Vertex; 3], target_voxel_center: Vec3) -> &Vertex {
-    // Choose closest vertex and sample its color.
-    let vertex_one_distance = target_voxel_center.distance(Vec3::new(
-        triangle[0].position.x,
-        triangle[0].position.y,
-        triangle[0].position.z,
-    ));
-
-    let mut closest_vertex = &triangle[0];
-    let mut closest_vertex_distance = vertex_one_distance;
-
-
-    let vertex_two_distance = target_voxel_center.distance(Vec3::new(
-        triangle[1].position.x,
-        triangle[1].position.y,
-        triangle[1].position.z,
-    ));
-
-    if vertex_two_distance < closest_vertex_distance {
-        closest_vertex_distance = vertex_two_distance;
-        closest_vertex = &triangle[1];
-    }
-
-
-    let vertex_three_distance = target_voxel_center.distance(Vec3::new(
-        triangle[2].position.x,
-        triangle[2].position.y,
-        triangle[2].position.z,
-    ));
-
-    if vertex_three_distance < closest_vertex_distance {
-        // closest_vertex_distance = vertex_three_distance;
-        closest_vertex = &triangle[2];
-    }
-
-    closest_vertex
+fn compute_minimum_aabb_for_mesh(mesh_triangles: &[MeshTriangle], padding: f32) -> Aabb {
+    compute_minimum_aabb_for_points(
+        mesh_triangles
+            .iter()
+            .flatten()
+            .map(|vertex| vertex.position),
+        padding,
+    )
 }
 
 
 #[inline]
-fn sample_vertex_color(vertex: &Vertex, model_material: &Material) -> Vec3 {
-    let sampled_color = model_material.get_base_color(vertex.tex_coords);
-
-    Vec3::new(sampled_color.x, sampled_color.y, sampled_color.z)
+fn sample_vertex_color(
+    vertex_color: Vec4,
+    surface_point: &SurfacePoint,
+    model_material: &MeshMaterial,
+) -> Vec3 {
+    let sampled_color = model_material.base_color(surface_point) * vertex_color;
+
+    sampled_color.truncate()
 }
 
 
-fn voxelize_individual_model(
-    model: &Model,
-    max_voxelization_bounds: &Aabb,
-    voxel_size: f32,
-) -> ContextualVoxelGrid {
-    let model_triangles = model
-        .triangles()
-        .expect("expected the mesh to contain triangles");
-
+/// Barycentric coordinates of the point on the triangle that is closest to `point`
+/// (see Real-Time Collision Detection by Christer Ericson, section 5.1.5).
+fn closest_point_barycentric_coordinates(triangle: &MeshTriangle, point: Vec3) -> Vec3 {
+    let (a, b, c) = (
+        triangle[0].position,
+        triangle[1].position,
+        triangle[2].position,
+    );
 
-    let minimum_voxelization_bounds_to_cover_model =
-        compute_minimum_aabb_for_mesh(&model_triangles, voxel_size * 2.0);
+    let ab = b - a;
+    let ac = c - a;
 
-    // We don't want to waste memory on useless voxel space, so we reduce the user-provided
-    // maximum voxelization bound according to the intersection between the extend of the mesh
-    // and the maximum voxelization extent.
-    let actual_voxelization_bounds =
-        minimum_voxelization_bounds_to_cover_model.compute_intersection(max_voxelization_bounds);
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return Vec3::X;
+    }
 
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return Vec3::Y;
+    }
 
-    let mut voxel_grid = initialize_voxel_work_grid(&actual_voxelization_bounds, voxel_size);
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return Vec3::new(1.0 - v, v, 0.0);
+    }
 
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return Vec3::Z;
+    }
 
-    let model_material = model.material();
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return Vec3::new(1.0 - w, 0.0, w);
+    }
 
-    println!("Voxelizing {} triangles.", model_triangles.len());
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return Vec3::new(0.0, 1.0 - w, w);
+    }
 
-    for triangle in model_triangles {
-        let triangle_aabb = compute_aabb_for_mesh_triangle(&triangle);
+    let denominator = va + vb + vc;
+    if denominator.abs() <= f32::EPSILON {
+        // Degenerate triangle.
+        return Vec3::splat(1.0 / 3.0);
+    }
 
-        let (index_x_start, index_y_start, index_z_start) = {
-            let starting_x_index =
-                (triangle_aabb.min.x - voxel_grid.starting_point.x).div(voxel_size) as u64;
-            let starting_y_index =
-                (triangle_aabb.min.y - voxel_grid.starting_point.y).div(voxel_size) as u64;
-            let starting_z_index =
-                (triangle_aabb.min.z - voxel_grid.starting_point.z).div(voxel_size) as u64;
+    let v = vb / denominator;
+    let w = vc / denominator;
 
-            (
-                starting_x_index,
-                starting_y_index,
-                starting_z_index,
-            )
-        };
+    Vec3::new(1.0 - v - w, v, w)
+}
 
-        let (index_x_num, index_y_num, index_z_num) = {
-            let x_index_length =
-                (triangle_aabb.max.x - triangle_aabb.min.x).div(voxel_size) as u64 + 2;
-            let y_index_length =
-                (triangle_aabb.max.y - triangle_aabb.min.y).div(voxel_size) as u64 + 2;
-            let z_index_length =
-                (triangle_aabb.max.z - triangle_aabb.min.z).div(voxel_size) as u64 + 2;
 
-            (x_index_length, y_index_length, z_index_length)
-        };
+/// Interpolates the vertex positions of the triangle at the given barycentric coordinates.
+#[inline]
+fn interpolate_vertex_position(triangle: &MeshTriangle, barycentric_coordinates: Vec3) -> Vec3 {
+    triangle[0].position * barycentric_coordinates.x
+        + triangle[1].position * barycentric_coordinates.y
+        + triangle[2].position * barycentric_coordinates.z
+}
 
 
+/// Unit normal of the triangle (following its winding order),
+/// or `None` if the triangle is degenerate.
+fn compute_triangle_normal(triangle: &MeshTriangle) -> Option<Vec3> {
+    let normal = (triangle[1].position - triangle[0].position)
+        .cross(triangle[2].position - triangle[0].position);
 
-        for grid_index_x in index_x_start..(index_x_start + index_x_num) {
-            for grid_index_y in index_y_start..(index_y_start + index_y_num) {
-                for grid_index_z in index_z_start..(index_z_start + index_z_num) {
-                    let target_voxel = voxel_grid
-                        .contextual_non_final_voxel_mut_by_xyz_index_unchecked(
-                            grid_index_x,
-                            grid_index_y,
-                            grid_index_z,
-                        );
+    normal.try_normalize()
+}
 
-                    let target_voxel_center = target_voxel.center_coordinate_in_world_space();
-                    let target_voxel_aabb = target_voxel.aabb();
 
+/// Size of a voxel in texture coordinate units of each set, estimated from the ratio between
+/// the triangle's area in texture space and in world space.
+fn compute_tex_coord_footprints(
+    triangle: &MeshTriangle,
+    voxel_size: f32,
+) -> [f32; TEX_COORD_SET_COUNT] {
+    let world_area = (triangle[1].position - triangle[0].position)
+        .cross(triangle[2].position - triangle[0].position)
+        .length()
+        / 2.0;
+
+    if world_area <= f32::EPSILON {
+        return [0.0; TEX_COORD_SET_COUNT];
+    }
 
-                    // Checks whether the voxel "box" intersects the triangle.
-                    let triangle_intersects_with_voxel =
-                        check_for_triangle_aabb_collision(&target_voxel_aabb, &triangle);
+    std::array::from_fn(|tex_coord_set| {
+        let tex_coord_area = (triangle[1].tex_coords[tex_coord_set]
+            - triangle[0].tex_coords[tex_coord_set])
+            .perp_dot(triangle[2].tex_coords[tex_coord_set] - triangle[0].tex_coords[tex_coord_set])
+            .abs()
+            / 2.0;
 
-                    if triangle_intersects_with_voxel {
-                        // DEBUGONLY
-                        // println!(
-                        //     "Found intersecting voxel at ({}, {}, {})",
-                        //     target_voxel_center.x, target_voxel_center.y, target_voxel_center.z,
-                        // );
+        voxel_size * (tex_coord_area / world_area).sqrt()
+    })
+}
 
-                        let best_vertex = get_closest_vertex(&triangle, target_voxel_center);
 
-                        let sampled_color = sample_vertex_color(best_vertex, &model_material);
-                        let sampled_metallic_value =
-                            model_material.get_metallic(best_vertex.tex_coords);
-                        let sampled_roughness_value =
-                            model_material.get_roughness(best_vertex.tex_coords);
+/// Interpolates the vertex colors of the triangle at the given barycentric coordinates.
+fn interpolate_vertex_color(triangle: &MeshTriangle, barycentric_coordinates: Vec3) -> Vec4 {
+    triangle[0].color * barycentric_coordinates.x
+        + triangle[1].color * barycentric_coordinates.y
+        + triangle[2].color * barycentric_coordinates.z
+}
 
 
-                        match target_voxel.data {
-                            NonFinalVoxelData::Empty | NonFinalVoxelData::InsideMesh => {
-                                *target_voxel.data = NonFinalVoxelData::Edge {
-                                    color_samples: vec![sampled_color],
-                                    metallic_value_samples: vec![sampled_metallic_value],
-                                    roughness_value_samples: vec![sampled_roughness_value],
-                                }
-                            }
-                            NonFinalVoxelData::Edge {
-                                color_samples: base_color_or_texture_samples,
-                                metallic_value_samples,
-                                roughness_value_samples,
-                            } => {
-                                base_color_or_texture_samples.push(sampled_color);
-                                metallic_value_samples.push(sampled_metallic_value);
-                                roughness_value_samples.push(sampled_roughness_value);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+/// Interpolates the texture coordinates of the triangle at the given barycentric coordinates.
+fn surface_point_on_triangle(
+    triangle: &MeshTriangle,
+    barycentric_coordinates: Vec3,
+    tex_coord_footprints: [f32; TEX_COORD_SET_COUNT],
+) -> SurfacePoint {
+    SurfacePoint {
+        tex_coords: std::array::from_fn(|tex_coord_set| {
+            triangle[0].tex_coords[tex_coord_set] * barycentric_coordinates.x
+                + triangle[1].tex_coords[tex_coord_set] * barycentric_coordinates.y
+                + triangle[2].tex_coords[tex_coord_set] * barycentric_coordinates.z
+        }),
+        tex_coord_footprints,
     }
+}
 
 
-    // Compute binary fill state.
+/// Marks empty voxels enclosed by edge voxels as [`NonFinalVoxelData::InsideMesh`].
+fn fill_inside_voxels(voxel_grid: &mut NonFinalVoxelGrid) {
     #[derive(Clone, Copy, PartialEq, Eq, Debug)]
     enum BinaryState {
         OutsideMesh,
@@ -536,20 +536,320 @@ fn voxelize_individual_model(
             }
         }
     }
+}
 
 
+fn voxelize_individual_model(
+    model: &MeshModel,
+    max_voxelization_bounds: &Aabb,
+    voxel_size: f32,
+    options: &MeshVoxelizationOptions,
+) -> ContextualVoxelGrid {
+    let minimum_voxelization_bounds_to_cover_model =
+        compute_minimum_aabb_for_mesh(&model.triangles, voxel_size * 2.0);
+
+    // We don't want to waste memory on useless voxel space, so we reduce the user-provided
+    // maximum voxelization bound according to the intersection between the extend of the mesh
+    // and the maximum voxelization extent.
+    let actual_voxelization_bounds =
+        minimum_voxelization_bounds_to_cover_model.compute_intersection(max_voxelization_bounds);
+
+    voxelize_model_into_grid(
+        model,
+        &actual_voxelization_bounds,
+        voxel_size,
+        options,
+    )
+}
+
+
+/// Voxelizes a model into a grid that spans exactly `grid_bounds`
+/// (which must cover the model, see [`compute_voxelization_bounds_for_models`]).
+fn voxelize_model_into_grid(
+    model: &MeshModel,
+    grid_bounds: &Aabb,
+    voxel_size: f32,
+    options: &MeshVoxelizationOptions,
+) -> ContextualVoxelGrid {
+    let model_triangles = &model.triangles;
+
+    let mut voxel_grid = initialize_voxel_work_grid(grid_bounds, voxel_size);
+
+
+    let model_material = &model.material;
+
+    println!("Voxelizing {} triangles.", model_triangles.len());
+
+    for triangle in model_triangles {
+        let triangle_aabb = compute_aabb_for_mesh_triangle(triangle);
+        let triangle_normal = compute_triangle_normal(triangle);
+
+        let tex_coord_footprints = if options.select_texture_mip_levels {
+            compute_tex_coord_footprints(triangle, voxel_size)
+        } else {
+            [0.0; TEX_COORD_SET_COUNT]
+        };
+
+        let (index_x_start, index_y_start, index_z_start) = {
+            let starting_x_index =
+                (triangle_aabb.min.x - voxel_grid.starting_point.x).div(voxel_size) as u64;
+            let starting_y_index =
+                (triangle_aabb.min.y - voxel_grid.starting_point.y).div(voxel_size) as u64;
+            let starting_z_index =
+                (triangle_aabb.min.z - voxel_grid.starting_point.z).div(voxel_size) as u64;
+
+            (
+                starting_x_index,
+                starting_y_index,
+                starting_z_index,
+            )
+        };
+
+        let (index_x_num, index_y_num, index_z_num) = {
+            let x_index_length =
+                (triangle_aabb.max.x - triangle_aabb.min.x).div(voxel_size) as u64 + 2;
+            let y_index_length =
+                (triangle_aabb.max.y - triangle_aabb.min.y).div(voxel_size) as u64 + 2;
+            let z_index_length =
+                (triangle_aabb.max.z - triangle_aabb.min.z).div(voxel_size) as u64 + 2;
+
+            (x_index_length, y_index_length, z_index_length)
+        };
+
+
+
+        for grid_index_x in index_x_start..(index_x_start + index_x_num) {
+            for grid_index_y in index_y_start..(index_y_start + index_y_num) {
+                for grid_index_z in index_z_start..(index_z_start + index_z_num) {
+                    let target_voxel = voxel_grid
+                        .contextual_non_final_voxel_mut_by_xyz_index_unchecked(
+                            grid_index_x,
+                            grid_index_y,
+                            grid_index_z,
+                        );
+
+                    let target_voxel_center = target_voxel.center_coordinate_in_world_space();
+                    let target_voxel_aabb = target_voxel.aabb();
+
+
+                    // Checks whether the voxel "box" intersects the triangle.
+                    let triangle_intersects_with_voxel =
+                        check_for_triangle_aabb_collision(&target_voxel_aabb, triangle);
+
+                    if triangle_intersects_with_voxel {
+                        // DEBUGONLY
+                        // println!(
+                        //     "Found intersecting voxel at ({}, {}, {})",
+                        //     target_voxel_center.x, target_voxel_center.y, target_voxel_center.z,
+                        // );
+
+                        let barycentric_coordinates =
+                            closest_point_barycentric_coordinates(triangle, target_voxel_center);
+                        let surface_point = surface_point_on_triangle(
+                            triangle,
+                            barycentric_coordinates,
+                            tex_coord_footprints,
+                        );
+
+                        let sampled_color = sample_vertex_color(
+                            interpolate_vertex_color(triangle, barycentric_coordinates),
+                            &surface_point,
+                            model_material,
+                        );
+                        let sampled_metallic_value = model_material.metallic(&surface_point);
+                        let sampled_roughness_value = model_material.roughness(&surface_point);
+
+                        let hermite_sample = triangle_normal
+                            .filter(|_| options.collect_hermite_data)
+                            .map(|normal| HermiteSample {
+                                position: interpolate_vertex_position(
+                                    triangle,
+                                    barycentric_coordinates,
+                                ),
+                                normal,
+                            });
+
+
+                        match target_voxel.data {
+                            NonFinalVoxelData::Empty | NonFinalVoxelData::InsideMesh => {
+                                *target_voxel.data = NonFinalVoxelData::Edge {
+                                    color_samples: vec![sampled_color],
+                                    metallic_value_samples: vec![sampled_metallic_value],
+                                    roughness_value_samples: vec![sampled_roughness_value],
+                                    intensity_samples: Vec::new(),
+                                    hermite_samples: hermite_sample.into_iter().collect(),
+                                }
+                            }
+                            NonFinalVoxelData::Edge {
+                                color_samples: base_color_or_texture_samples,
+                                metallic_value_samples,
+                                roughness_value_samples,
+                                hermite_samples,
+                                ..
+                            } => {
+                                base_color_or_texture_samples.push(sampled_color);
+                                metallic_value_samples.push(sampled_metallic_value);
+                                roughness_value_samples.push(sampled_roughness_value);
+                                hermite_samples.extend(hermite_sample);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+
+    fill_inside_voxels(&mut voxel_grid);
+
 
     ContextualVoxelGrid {
-        gltf_model_primitive_index: model.primitive_index(),
+        gltf_model_primitive_index: model.primitive_index,
+        grid: voxel_grid.into_final_grid(),
+    }
+}
+
+
+/// Options for voxelizing meshes.
+pub struct MeshVoxelizationOptions {
+    /// If set, textures are sampled at the mip level that matches the voxel size
+    /// (instead of always at full resolution).
+    pub select_texture_mip_levels: bool,
+
+    /// If set, each edge voxel keeps the closest point on (and the normal of) every triangle
+    /// that touched it, which dual contouring needs to place vertices on sharp features.
+    pub collect_hermite_data: bool,
+}
+
+
+/// Options for [`voxelize_point_cloud`].
+pub struct PointCloudVoxelizationOptions {
+    /// Voxels containing fewer points than this are left empty.
+    pub minimum_points_per_voxel: u32,
+
+    /// If set, only voxels that contain points are occupied,
+    /// i.e. enclosed voxels are not filled in.
+    pub surface_only: bool,
+}
+
+
+/// Bins points into voxels. Each voxel that contains (enough) points becomes an edge voxel
+/// with the averaged color and intensity of its points. Points without a color are white.
+/// As point clouds have no materials, metallic and roughness values are taken from
+/// the default material.
+pub fn voxelize_point_cloud(
+    point_cloud: &PointCloud,
+    max_voxelization_bounds: Aabb,
+    voxel_size: f32,
+    options: &PointCloudVoxelizationOptions,
+) -> ContextualVoxelGrid {
+    let minimum_voxelization_bounds_to_cover_points = compute_minimum_aabb_for_points(
+        point_cloud.points.iter().map(|point| point.position),
+        voxel_size * 2.0,
+    );
+
+    let actual_voxelization_bounds =
+        minimum_voxelization_bounds_to_cover_points.compute_intersection(&max_voxelization_bounds);
+
+
+    let mut voxel_grid = initialize_voxel_work_grid(&actual_voxelization_bounds, voxel_size);
+
+    let default_material = MeshMaterial::default();
+
+    println!("Voxelizing {} points.", point_cloud.points.len());
+
+    for point in &point_cloud.points {
+        let grid_index = ((point.position - voxel_grid.starting_point) / voxel_size).floor();
+
+        if grid_index.cmplt(Vec3::ZERO).any()
+            || grid_index.x >= voxel_grid.x_length as f32
+            || grid_index.y >= voxel_grid.y_length as f32
+            || grid_index.z >= voxel_grid.z_length as f32
+        {
+            // The point is outside of the voxelization bounds.
+            continue;
+        }
+
+        let target_voxel = voxel_grid.non_final_voxel_mut_by_xyz_index_unchecked(
+            grid_index.x as u64,
+            grid_index.y as u64,
+            grid_index.z as u64,
+        );
+
+        let color = point.color.unwrap_or(Vec3::ONE);
+
+        match target_voxel {
+            NonFinalVoxelData::Empty | NonFinalVoxelData::InsideMesh => {
+                *target_voxel = NonFinalVoxelData::Edge {
+                    color_samples: vec![color],
+                    metallic_value_samples: vec![default_material.metallic_factor],
+                    roughness_value_samples: vec![default_material.roughness_factor],
+                    intensity_samples: point.intensity.into_iter().collect(),
+                    hermite_samples: Vec::new(),
+                }
+            }
+            NonFinalVoxelData::Edge {
+                color_samples,
+                metallic_value_samples,
+                roughness_value_samples,
+                intensity_samples,
+                ..
+            } => {
+                color_samples.push(color);
+                metallic_value_samples.push(default_material.metallic_factor);
+                roughness_value_samples.push(default_material.roughness_factor);
+                intensity_samples.extend(point.intensity);
+            }
+        }
+    }
+
+
+    if options.minimum_points_per_voxel > 1 {
+        let mut removed_voxel_count: u64 = 0;
+
+        for grid_index_z in 0..voxel_grid.z_length {
+            for grid_index_y in 0..voxel_grid.y_length {
+                for grid_index_x in 0..voxel_grid.x_length {
+                    let voxel = voxel_grid.non_final_voxel_mut_by_xyz_index_unchecked(
+                        grid_index_x,
+                        grid_index_y,
+                        grid_index_z,
+                    );
+
+                    if let NonFinalVoxelData::Edge { color_samples, .. } = voxel {
+                        if (color_samples.len() as u32) < options.minimum_points_per_voxel {
+                            *voxel = NonFinalVoxelData::Empty;
+                            removed_voxel_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        println!(
+            "Removed {} voxels with fewer than {} points.",
+            removed_voxel_count, options.minimum_points_per_voxel
+        );
+    }
+
+
+    if !options.surface_only {
+        fill_inside_voxels(&mut voxel_grid);
+    }
+
+
+    ContextualVoxelGrid {
+        gltf_model_primitive_index: 0,
         grid: voxel_grid.into_final_grid(),
     }
 }
 
 
 pub fn voxelize_models(
-    models: &[Model],
+    models: &[MeshModel],
     voxelization_bounds: Aabb,
     voxel_size: f32,
+    options: &MeshVoxelizationOptions,
 ) -> Vec<ContextualVoxelGrid> {
     // TODO
 
@@ -564,7 +864,7 @@ pub fn voxelize_models(
     //!       (see Fast 3D Triangle-Box Overlap Testing by Tomas Akenine-Möller)
     //!     if it does:
     //!       sample model's texture at provide texture coordinates
-    //!         (each triangle in GLTF provides a texture coordinate,
+    //!         (each triangle vertex provides a texture coordinate,
     //!          and its parent model provides the material from which
     //!          we can sample the texture)
     //!       set voxel to sampled texture value
@@ -584,7 +884,8 @@ pub fn voxelize_models(
     let mut voxelized_models: Vec<ContextualVoxelGrid> = Vec::with_capacity(models.len());
 
     for model in models {
-        let voxelized_model = voxelize_individual_model(model, &voxelization_bounds, voxel_size);
+        let voxelized_model =
+            voxelize_individual_model(model, &voxelization_bounds, voxel_size, options);
 
         voxelized_models.push(voxelized_model);
     }
@@ -592,3 +893,338 @@ pub fn voxelize_models(
 
     voxelized_models
 }
+
+
+/// Returns `true` if the transform maps the voxel lattice onto a (translated) voxel lattice,
+/// i.e. if its linear part only permutes and flips the axes, without scaling.
+fn preserves_voxel_lattice(transform: &Affine3A) -> bool {
+    const TOLERANCE: f32 = 1e-4;
+
+    transform.matrix3.to_cols_array_2d().iter().all(|column| {
+        let unit_components = column
+            .iter()
+            .filter(|component| (component.abs() - 1.0).abs() <= TOLERANCE)
+            .count();
+        let zero_components = column
+            .iter()
+            .filter(|component| component.abs() <= TOLERANCE)
+            .count();
+
+        unit_components == 1 && zero_components == 2
+    }) && (transform.matrix3.determinant().abs() - 1.0).abs() <= TOLERANCE
+}
+
+
+/// Copies the voxels (and Hermite samples) of `source_grid`, which lies on the same lattice
+/// as the target grid and starts at `source_offset` (in target voxels), into the target grid.
+/// Edge voxels take precedence over inside voxels, which in turn take precedence over empty ones.
+fn merge_grid_on_shared_lattice(
+    target_voxels: &mut [VoxelData],
+    target_hermite_samples: &mut HashMap<usize, Vec<HermiteSample>>,
+    target_dimensions: U64Vec3,
+    source_grid: &VoxelGrid,
+    source_offset: I64Vec3,
+) {
+    for (source_flat_index, source_voxel) in source_grid.voxels().iter().enumerate() {
+        if matches!(source_voxel, VoxelData::Empty) {
+            continue;
+        }
+
+        let target_index = source_grid
+            .grid_index_from_flat_index(source_flat_index)
+            .as_i64vec3()
+            + source_offset;
+
+        if target_index.cmplt(I64Vec3::ZERO).any()
+            || target_index.cmpge(target_dimensions.as_i64vec3()).any()
+        {
+            continue;
+        }
+
+        let target_index = target_index.as_u64vec3();
+        let target_flat_index = (target_index.x
+            + target_index.y * target_dimensions.x
+            + target_index.z * target_dimensions.x * target_dimensions.y)
+            as usize;
+
+        merge_voxel(
+            &mut target_voxels[target_flat_index],
+            source_voxel,
+        );
+
+        let source_hermite_samples = source_grid.hermite_samples_by_flat_index(source_flat_index);
+        if !source_hermite_samples.is_empty() {
+            target_hermite_samples
+                .entry(target_flat_index)
+                .or_default()
+                .extend_from_slice(source_hermite_samples);
+        }
+    }
+}
+
+
+/// Edge voxels take precedence over inside voxels, which in turn take precedence over empty ones.
+#[inline]
+fn merge_voxel(target_voxel: &mut VoxelData, source_voxel: &VoxelData) {
+    match (&target_voxel, source_voxel) {
+        (VoxelData::Edge { .. }, _) | (_, VoxelData::Empty) => {}
+        (VoxelData::InsideMesh, VoxelData::InsideMesh) => {}
+        _ => *target_voxel = source_voxel.clone(),
+    }
+}
+
+
+/// Voxelizes an instanced model into a single world grid (limited to `max_voxelization_bounds`).
+/// The world lattice is aligned with the first instance.
+///
+/// Instances whose transform preserves the voxel lattice (translations, combined with rotations
+/// by multiples of 90 degrees and mirroring) share a single voxelization of the model in local
+/// space, which is stamped into the world grid by sampling it at the center of each covered
+/// world voxel; instances translated by whole voxels relative to the first one are copied exactly.
+/// Other instances (rotated by arbitrary angles or scaled) are voxelized directly in world space,
+/// as nearest sampling of the local grid could leave gaps in their surface and break the inside fill.
+/// Hermite samples are transformed into world space and carried over in both cases.
+pub fn voxelize_instanced_model(
+    instanced_model: &InstancedMeshModel,
+    max_voxelization_bounds: &Aabb,
+    voxel_size: f32,
+    options: &MeshVoxelizationOptions,
+) -> ContextualVoxelGrid {
+    let model = &instanced_model.model;
+    let instance_transforms = &instanced_model.instance_transforms;
+
+    let local_bounds = compute_minimum_aabb_for_mesh(&model.triangles, voxel_size * 2.0);
+
+    let instance_bounds = instance_transforms
+        .iter()
+        .map(|instance_transform| local_bounds.transformed(instance_transform))
+        .collect::<Vec<_>>();
+
+    let world_bounds = instance_bounds
+        .iter()
+        .copied()
+        .reduce(|first, second| first.compute_union(&second))
+        .unwrap_or(local_bounds)
+        .compute_intersection(max_voxelization_bounds);
+
+    let lattice_origin = instance_transforms
+        .first()
+        .map_or(local_bounds.min, |instance_transform| {
+            instance_transform.transform_point3(local_bounds.min)
+        });
+
+    // As for non-instanced models, the grid lies within the bounds (which are padded
+    // by two voxels around the instances, so rounding inwards still covers them).
+    let starting_point =
+        lattice_origin + ((world_bounds.min - lattice_origin) / voxel_size).ceil() * voxel_size;
+
+    let dimensions = ((world_bounds.max - starting_point) / voxel_size)
+        .floor()
+        .max(Vec3::ZERO)
+        .as_u64vec3();
+
+
+    let stamped_instance_count = instance_transforms
+        .iter()
+        .filter(|instance_transform| preserves_voxel_lattice(instance_transform))
+        .count();
+
+    println!(
+        "Stamping voxelized model into {} instances, voxelizing {} other instances directly.",
+        stamped_instance_count,
+        instance_transforms.len() - stamped_instance_count
+    );
+
+    let local_grid = (stamped_instance_count > 0)
+        .then(|| voxelize_model_into_grid(model, &local_bounds, voxel_size, options).grid);
+
+
+    let mut voxels = vec![VoxelData::Empty; (dimensions.x * dimensions.y * dimensions.z) as usize];
+    let mut hermite_samples: HashMap<usize, Vec<HermiteSample>> = HashMap::new();
+
+    for (instance_transform, instance_bounds) in instance_transforms.iter().zip(instance_bounds) {
+        // Bounds of the instance, extended outwards to whole world voxels.
+        let instance_index_start = ((instance_bounds.min - starting_point) / voxel_size)
+            .floor()
+            .as_i64vec3();
+        let instance_index_end = ((instance_bounds.max - starting_point) / voxel_size)
+            .ceil()
+            .as_i64vec3();
+
+        let Some(local_grid) = local_grid
+            .as_ref()
+            .filter(|_| preserves_voxel_lattice(instance_transform))
+        else {
+            let instance_dimensions = instance_index_end - instance_index_start;
+            let instance_starting_point =
+                starting_point + instance_index_start.as_vec3() * voxel_size;
+
+            // Half a voxel of slack, so that rounding can't drop the last layer of voxels.
+            let instance_grid_bounds = Aabb::from_min_and_max(
+                instance_starting_point,
+                instance_starting_point + (instance_dimensions.as_vec3() + 0.5) * voxel_size,
+            );
+
+            let instance_grid = voxelize_model_into_grid(
+                &model.transformed(instance_transform),
+                &instance_grid_bounds,
+                voxel_size,
+                options,
+            )
+            .grid;
+
+            merge_grid_on_shared_lattice(
+                &mut voxels,
+                &mut hermite_samples,
+                dimensions,
+                &instance_grid,
+                instance_index_start,
+            );
+
+            continue;
+        };
+
+
+        let world_to_local = instance_transform.inverse();
+
+        // Normals are transformed with the inverse transpose, so they stay perpendicular
+        // to the surface under non-uniform scaling.
+        let normal_transform = Mat3::from(world_to_local.matrix3).transpose();
+
+        let index_start = instance_index_start
+            .clamp(I64Vec3::ZERO, dimensions.as_i64vec3())
+            .as_u64vec3();
+        let index_end = instance_index_end
+            .clamp(I64Vec3::ZERO, dimensions.as_i64vec3())
+            .as_u64vec3();
+
+        for z in index_start.z..index_end.z {
+            for y in index_start.y..index_end.y {
+                for x in index_start.x..index_end.x {
+                    let world_voxel_center =
+                        starting_point + (U64Vec3::new(x, y, z).as_vec3() + 0.5) * voxel_size;
+
+                    let Some(local_index) = local_grid.grid_index_at_world_position(
+                        world_to_local.transform_point3(world_voxel_center),
+                    ) else {
+                        continue;
+                    };
+
+                    let local_flat_index =
+                        local_grid.flat_index(local_index.x, local_index.y, local_index.z);
+
+                    let target_flat_index =
+                        (x + y * dimensions.x + z * dimensions.x * dimensions.y) as usize;
+
+                    merge_voxel(
+                        &mut voxels[target_flat_index],
+                        &local_grid.voxels()[local_flat_index],
+                    );
+
+                    let local_hermite_samples =
+                        local_grid.hermite_samples_by_flat_index(local_flat_index);
+
+                    if !local_hermite_samples.is_empty() {
+                        hermite_samples
+                            .entry(target_flat_index)
+                            .or_default()
+                            .extend(
+                                local_hermite_samples
+                                    .iter()
+                                    .map(|hermite_sample| HermiteSample {
+                                        position: instance_transform
+                                            .transform_point3(hermite_sample.position),
+                                        normal: (normal_transform * hermite_sample.normal)
+                                            .normalize_or_zero(),
+                                    }),
+                            );
+                    }
+                }
+            }
+        }
+    }
+
+
+    ContextualVoxelGrid {
+        gltf_model_primitive_index: model.primitive_index,
+        grid: VoxelGrid::from_voxels(
+            starting_point,
+            voxel_size / 2.0,
+            dimensions.x,
+            dimensions.y,
+            dimensions.z,
+            voxels,
+        )
+        .with_hermite_samples(hermite_samples),
+    }
+}
+
+
+/// Returns the bounds that [`voxelize_models`] would use to cover all of the given models
+/// (limited to `max_voxelization_bounds`), or `None` if there are no triangles.
+pub fn compute_voxelization_bounds_for_models(
+    models: &[MeshModel],
+    max_voxelization_bounds: &Aabb,
+    voxel_size: f32,
+) -> Option<Aabb> {
+    models
+        .iter()
+        .filter(|model| !model.triangles.is_empty())
+        .map(|model| compute_minimum_aabb_for_mesh(&model.triangles, voxel_size * 2.0))
+        .reduce(|first, second| first.compute_union(&second))
+        .map(|bounds| bounds.compute_intersection(max_voxelization_bounds))
+}
+
+
+/// Voxelizes each model into a grid spanning exactly `grid_bounds`, so that all resulting
+/// grids share the same lattice and dimensions (unlike [`voxelize_models`], which fits
+/// each grid to its model).
+pub fn voxelize_models_on_shared_grid(
+    models: &[MeshModel],
+    grid_bounds: Aabb,
+    voxel_size: f32,
+    options: &MeshVoxelizationOptions,
+) -> Vec<ContextualVoxelGrid> {
+    models
+        .iter()
+        .map(|model| voxelize_model_into_grid(model, &grid_bounds, voxel_size, options))
+        .collect()
+}
+
+
+/// Creates a grid of empty voxels spanning exactly `grid_bounds`.
+pub fn empty_grid_with_bounds(grid_bounds: Aabb, voxel_size: f32) -> VoxelGrid {
+    initialize_voxel_work_grid(&grid_bounds, voxel_size).into_final_grid()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn axis_permutations_preserve_the_lattice() {
+        let translation = Affine3A::from_translation(Vec3::new(0.5, -3.0, 7.25));
+        assert!(preserves_voxel_lattice(&translation));
+
+        let quarter_turn = Affine3A::from_rotation_y(FRAC_PI_2) * translation;
+        assert!(preserves_voxel_lattice(&quarter_turn));
+
+        let mirror = Affine3A::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+        assert!(preserves_voxel_lattice(&mirror));
+
+        assert!(!preserves_voxel_lattice(
+            &Affine3A::from_rotation_z(0.3)
+        ));
+        assert!(!preserves_voxel_lattice(&Affine3A::from_scale(
+            Vec3::splat(2.0)
+        )));
+
+        // Both x and y are mapped onto the x axis.
+        let collapsing_transform = Affine3A::from_mat3(Mat3::from_cols(Vec3::X, Vec3::X, Vec3::Z));
+        assert!(!preserves_voxel_lattice(&collapsing_transform));
+    }
+}
@@ -3,6 +3,7 @@ use std::ops::Add;
 use glam::{U64Vec3, Vec3};
 
 use super::aabb::Aabb;
+use crate::mesh::{DEFAULT_METALLIC, DEFAULT_ROUGHNESS};
 
 #[derive(Debug)]
 pub struct ContextualNonFinalVoxelDataMut<'d> {
@@ -100,6 +101,18 @@ fn combine_rgb_colors(colors_to_compose: &[Vec3]) -> Vec3 {
 }
 
 
+/// A point on the voxelized surface together with the surface normal at that point
+/// (Hermite data, as used by dual contouring).
+#[derive(Clone, Copy, Debug)]
+pub struct HermiteSample {
+    /// World-space position.
+    pub position: Vec3,
+
+    /// Unit normal of the triangle the position lies on.
+    pub normal: Vec3,
+}
+
+
 #[derive(Clone, Debug)]
 pub enum NonFinalVoxelData {
     Empty,
@@ -107,6 +120,11 @@ pub enum NonFinalVoxelData {
         color_samples: Vec<Vec3>,
         metallic_value_samples: Vec<f32>,
         roughness_value_samples: Vec<f32>,
+        /// Only present for point clouds (empty for meshes).
+        intensity_samples: Vec<f32>,
+        /// One sample per triangle that touched the voxel, only collected
+        /// if requested (see `MeshVoxelizationOptions::collect_hermite_data`).
+        hermite_samples: Vec<HermiteSample>,
     },
     InsideMesh,
 }
@@ -139,10 +157,17 @@ impl NonFinalVoxelData {
                 color_samples: base_color_or_texture_samples,
                 metallic_value_samples,
                 roughness_value_samples,
+                intensity_samples,
+                ..
             } => VoxelData::Edge {
                 color: combine_rgb_colors(&base_color_or_texture_samples),
                 metallic_value: average_f32_samples(&metallic_value_samples),
                 rougness_value: average_f32_samples(&roughness_value_samples),
+                intensity: if intensity_samples.is_empty() {
+                    0.0
+                } else {
+                    average_f32_samples(&intensity_samples)
+                },
             },
             NonFinalVoxelData::InsideMesh => VoxelData::InsideMesh,
         }
@@ -158,11 +183,32 @@ pub enum VoxelData {
         color: Vec3,
         metallic_value: f32,
         rougness_value: f32,
+        /// Average intensity (in `[0, 1]`) of the point cloud points in this voxel.
+        /// Always zero for voxelized meshes.
+        intensity: f32,
     },
     InsideMesh,
 }
 
 impl VoxelData {
+    /// Returns an edge voxel with the [default material](crate::mesh::MeshMaterial::default)
+    /// (white base color, non-metallic and fully rough).
+    #[inline]
+    pub fn new_edge_with_default_material() -> Self {
+        Self::Edge {
+            color: Vec3::ONE,
+            metallic_value: DEFAULT_METALLIC,
+            rougness_value: DEFAULT_ROUGHNESS,
+            intensity: 0.0,
+        }
+    }
+
+    /// Returns `true` if the voxel is part of the mesh volume (either an edge or an inside voxel).
+    #[inline]
+    pub fn is_occupied(&self) -> bool {
+        matches!(self, Self::Edge { .. } | Self::InsideMesh)
+    }
+
     #[inline]
     pub fn as_contextual(
         &self,